@@ -1,10 +1,12 @@
 use pest::Parser;
 use serde::{Deserialize, Serialize};
 use std::char::from_u32;
+use std::fmt;
 use std::num::NonZeroU32;
 use std::ops::Deref;
 use std::option::Option;
 use std::panic;
+use std::str::FromStr;
 
 use crate::coord;
 use crate::coordinate;
@@ -15,7 +17,7 @@ use crate::util::{coord_show, non_zero_u32_tuple};
 pub struct CoordinateParser;
 
 // Coordinate specifies the nested coordinate structure
-#[derive(Deserialize, PartialEq, Eq, Debug, Hash, Clone, Default)]
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Default)]
 pub struct Coordinate {
     pub row_cols: Vec<(NonZeroU32, NonZeroU32)>, // TEST: should never be empty list
 }
@@ -60,6 +62,24 @@ impl Coordinate {
         Some(truncated)
     }
 
+    // the deepest coordinate that both `self` and `other` are (or are nested in). Coordinates
+    // under different roots (e.g. root & meta) have no common ancestor
+    pub fn common_ancestor(&self, other: &Coordinate) -> Option<Coordinate> {
+        // walk both up to the same depth, and from there up to where their paths meet
+        let shared = self
+            .row_cols
+            .iter()
+            .zip(other.row_cols.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if shared == 0 {
+            return None;
+        }
+        Some(Coordinate {
+            row_cols: self.row_cols[..shared].to_vec(),
+        })
+    }
+
     pub fn row_col(&self) -> (NonZeroU32, NonZeroU32) {
         self.row_cols.last().unwrap().clone()
     }
@@ -134,10 +154,34 @@ impl Coordinate {
 
     pub fn col_to_string(&self) -> String {
         if let Some(parent) = self.parent() {
-            format! {"{}-{}", parent.to_string(), from_u32(self.col().get() + 64).unwrap()}
+            format! {"{}-{}", parent.to_string(), col_to_letters(self.col().get())}
         } else {
-            format! {"{}", from_u32(self.col().get() + 64).unwrap()}
+            col_to_letters(self.col().get())
+        }
+    }
+
+    // classic spreadsheet-style reference to the cell within its own grid, e.g. "B2" for
+    // "root-A1-B2". Root and meta are shown as they are.
+    pub fn to_a1_string(&self) -> String {
+        if self.parent().is_none() {
+            return self.to_string();
+        }
+        format! {"{}{}", col_to_letters(self.col().get()), self.row().get()}
+    }
+
+    // parses a spreadsheet-style reference (e.g. "B2") into a top-level cell, i.e. one
+    // whose parent is root
+    pub fn from_a1_str(a1_str: &str) -> Option<Coordinate> {
+        let digits_start = a1_str.find(|c: char| c.is_ascii_digit())?;
+        let (letters, digits) = a1_str.split_at(digits_start);
+        if digits.starts_with('0') || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
         }
+        let row = NonZeroU32::new(digits.parse::<u32>().ok()?)?;
+        let col = NonZeroU32::new(letters_to_col(letters)?)?;
+        Some(Coordinate {
+            row_cols: vec![non_zero_u32_tuple((1, 1)), (row, col)],
+        })
     }
 
     // if a cell is the parent, grandparent,..., (great xN)-grandparent of another
@@ -227,6 +271,119 @@ impl Coordinate {
     }
 }
 
+// letters that label a column: 1 -> "A", 26 -> "Z", 27 -> "AA", 28 -> "AB", ...
+pub fn col_to_letters(col: u32) -> String {
+    let mut letters = Vec::new();
+    let mut n = col;
+    while n > 0 {
+        let remainder = (n - 1) % 26;
+        letters.push(from_u32(remainder + 65).unwrap());
+        n = (n - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+// inverse of `col_to_letters`, which is None for anything but (uppercase) letters
+pub fn letters_to_col(letters: &str) -> Option<u32> {
+    if letters.is_empty() {
+        return None;
+    }
+    letters.chars().try_fold(0u32, |col, ch| {
+        if ch.is_ascii_uppercase() {
+            col.checked_mul(26)?.checked_add((ch as u32) - 64)
+        } else {
+            None
+        }
+    })
+}
+
+// `ParseError` is what parsing a badly written coordinate gives back, instead of panicking
+// like `coord!` does
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// parses a coordinate written the way `Coordinate::to_string` writes it (e.g. "root-A1-B2")
+impl FromStr for Coordinate {
+    type Err = ParseError;
+
+    fn from_str(coord_str: &str) -> Result<Coordinate, ParseError> {
+        let invalid = || ParseError(format! {"invalid coordinate: \"{}\"", coord_str});
+        let pairs = CoordinateParser::parse(Rule::coordinate, coord_str).map_err(|_| invalid())?;
+        let mut fragments: Vec<(NonZeroU32, NonZeroU32)> = Vec::new();
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::special if pair.as_str() == "root" => {
+                    fragments.push(non_zero_u32_tuple((1, 1)));
+                }
+                Rule::special if pair.as_str() == "meta" => {
+                    fragments.push(non_zero_u32_tuple((1, 2)));
+                }
+                Rule::fragment => {
+                    let mut fragment: (u32, u32) = (0, 0);
+                    for inner_pair in pair.into_inner() {
+                        match inner_pair.as_rule() {
+                            Rule::alpha => {
+                                fragment.1 = letters_to_col(inner_pair.as_str()).ok_or_else(|| {
+                                    ParseError(format! {
+                                        "column {} is out of range", inner_pair.as_str()
+                                    })
+                                })?;
+                            }
+                            Rule::digit => {
+                                fragment.0 = inner_pair.as_str().parse::<u32>().map_err(|_| {
+                                    ParseError(format! {
+                                        "row {} is out of range", inner_pair.as_str()
+                                    })
+                                })?;
+                            }
+                            _ => return Err(invalid()),
+                        }
+                    }
+                    // the grammar doesn't let a row start with 0 (so "root-A0" is already
+                    // invalid above), and both parts are always there
+                    match (NonZeroU32::new(fragment.0), NonZeroU32::new(fragment.1)) {
+                        (Some(row), Some(col)) => fragments.push((row, col)),
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        // coordinates always start at root or meta
+        match fragments.first().map(|(row, col)| (row.get(), col.get())) {
+            Some((1, 1)) | Some((1, 2)) => {}
+            _ => return Err(invalid()),
+        }
+        let coord = Coordinate {
+            row_cols: fragments,
+        };
+        // the parser stops at the first thing that isn't part of a coordinate, so make sure
+        // nothing was left over
+        if coord.to_string() != coord_str {
+            return Err(invalid());
+        }
+        Ok(coord)
+    }
+}
+
+// reads a cell name the way it's typed in (into "Go To", or at either end of a range): either
+// a full coordinate ("root-A1-B2") or a cell of the top-level grid written the short way ("A1")
+pub fn parse_cell_name(input: &str) -> Result<Coordinate, String> {
+    let input = input.trim();
+    let full = if input.starts_with("root") || input.starts_with("meta") {
+        input.to_string()
+    } else {
+        format! {"root-{}", input.to_uppercase()}
+    };
+    full.parse::<Coordinate>().map_err(|_| format! {"\"{}\" is not a cell", input})
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Hash)]
 pub struct Row(
     /* parent */ pub Coordinate,
@@ -279,11 +436,9 @@ macro_rules! coord {
                         match inner_pair.as_rule() {
                             // COLUMN
                             Rule::alpha => {
-                                let mut val: u32 = 0;
-                                for ch in inner_pair.as_str().to_string().chars() {
-                                    val += (ch as u32) - 64;
-                                }
-                                fragment.1 = val;
+                                fragment.1 =
+                                    $crate::coordinate::letters_to_col(inner_pair.as_str())
+                                        .expect("column letters should fit in a u32");
                             }
                             // ROW
                             Rule::digit => {
@@ -307,10 +462,8 @@ macro_rules! coord {
 #[macro_export]
 macro_rules! coord_col {
     ( $parent_str:tt, $col_str:tt ) => {{
-        let mut col: u32 = 0;
-        for ch in $col_str.to_string().chars() {
-            col += (ch as u32) - 64;
-        }
+        let col: u32 = $crate::coordinate::letters_to_col(&$col_str.to_string())
+            .expect("coord_col! expects column letters, like \"A\" or \"AA\"");
 
         Col(coord!($parent_str), NonZeroU32::new(col).unwrap())
     }};
@@ -336,6 +489,28 @@ mod tests {
         assert_ne!(coord!("root-A1-B2-B3").row().get(), 2);
     }
 
+    #[test]
+    fn test_common_ancestor() {
+        // siblings
+        assert_eq!(coord!("root-A1").common_ancestor(&coord!("root-B3")), Some(coord!("root")));
+        // cousins at different depths
+        assert_eq!(
+            coord!("root-A1-B2").common_ancestor(&coord!("root-A1-C1-A1")),
+            Some(coord!("root-A1"))
+        );
+        assert_eq!(
+            coord!("root-B2-A1-A1").common_ancestor(&coord!("root-C1")),
+            Some(coord!("root"))
+        );
+        // a cell and something nested inside it
+        assert_eq!(
+            coord!("root-A1").common_ancestor(&coord!("root-A1-B2")),
+            Some(coord!("root-A1"))
+        );
+        // different roots
+        assert_eq!(coord!("root-A1").common_ancestor(&coord!("meta-A1")), None);
+    }
+
     #[test]
     fn test_child_of() {
         assert_eq!(
@@ -417,6 +592,87 @@ mod tests {
         assert_eq!(coord!("meta").row_to_string(), "1");
     }
 
+    #[test]
+    fn test_from_str() {
+        assert_eq!("root-A1-B2".parse::<Coordinate>(), Ok(coord!("root-A1-B2")));
+        assert_eq!("meta".parse::<Coordinate>(), Ok(coord!("meta")));
+        assert_eq!(Coordinate::from_str("root-A10").map(|c| c.row().get()), Ok(10));
+        // malformed coordinates are errors rather than panics
+        assert!(Coordinate::from_str("root-A1 junk").is_err());
+        assert!(Coordinate::from_str("A1").is_err());
+        assert!(Coordinate::from_str("").is_err());
+        assert!(Coordinate::from_str("root-A").is_err());
+        assert!(Coordinate::from_str("root-1A").is_err());
+        assert!(Coordinate::from_str("root-a1").is_err());
+        assert!(Coordinate::from_str("root--A1").is_err());
+        assert_eq!(
+            Coordinate::from_str("root-A0"),
+            Err(ParseError("invalid coordinate: \"root-A0\"".to_string()))
+        );
+        assert!(Coordinate::from_str("root-A99999999999").is_err());
+    }
+
+    #[test]
+    fn test_parse_cell_name() {
+        assert_eq!(parse_cell_name("A1"), Ok(coord!("root-A1")));
+        assert_eq!(parse_cell_name(" b12 "), Ok(coord!("root-B12")));
+        assert_eq!(parse_cell_name("root-A1-B2"), Ok(coord!("root-A1-B2")));
+        assert_eq!(parse_cell_name("meta-A1"), Ok(coord!("meta-A1")));
+        assert!(parse_cell_name("").is_err());
+        assert!(parse_cell_name("12").is_err());
+        assert!(parse_cell_name("A0").is_err());
+        assert!(parse_cell_name("root-A1 B2").is_err());
+    }
+
+    #[test]
+    fn test_col_letters() {
+        let cases = vec![
+            (1, "A"),
+            (26, "Z"),
+            (27, "AA"),
+            (52, "AZ"),
+            (53, "BA"),
+            (702, "ZZ"),
+            (703, "AAA"),
+        ];
+        for (col, letters) in cases {
+            assert_eq!(col_to_letters(col), letters);
+            assert_eq!(letters_to_col(letters), Some(col));
+        }
+        assert_eq!(letters_to_col(""), None);
+        assert_eq!(letters_to_col("a"), None);
+    }
+
+    #[test]
+    fn test_col_to_string() {
+        let wide = Coordinate::child_of(&coord!("root"), non_zero_u32_tuple((1, 27)));
+        assert_eq!(wide.col_to_string(), "root-AA");
+        assert_eq!(wide.to_string(), "root-AA1");
+        assert_eq!(coord!("root-AA1"), wide);
+        assert_eq!(coord_col!("root", "AA"), wide.full_col());
+        assert_eq!(Coordinate::from_str("root-ZZ3-AAA1").map(|c| c.col().get()), Ok(703));
+    }
+
+    #[test]
+    fn test_a1_string() {
+        for a1 in vec!["A1", "Z9", "AA1", "AB12"] {
+            let coord = Coordinate::from_a1_str(a1).unwrap();
+            assert_eq!(coord.parent(), Some(coord!("root")));
+            assert_eq!(coord.to_a1_string(), a1);
+        }
+        assert_eq!(Coordinate::from_a1_str("B2"), Some(coord!("root-B2")));
+        assert_eq!(Coordinate::from_a1_str("AA1").unwrap().col().get(), 27);
+        // only the last part of a nested coordinate is shown
+        assert_eq!(coord!("root-A1-B2").to_a1_string(), "B2");
+        assert_eq!(coord!("root").to_a1_string(), "root");
+
+        assert_eq!(Coordinate::from_a1_str("A0"), None);
+        assert_eq!(Coordinate::from_a1_str("1A"), None);
+        assert_eq!(Coordinate::from_a1_str("a1"), None);
+        assert_eq!(Coordinate::from_a1_str("A"), None);
+        assert_eq!(Coordinate::from_a1_str("A1B"), None);
+    }
+
     #[test]
     fn test_neighbor_above() {
         assert_eq!(
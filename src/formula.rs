@@ -0,0 +1,436 @@
+// Formulas are input cells whose value starts with "=" (see `is_formula`), like "=A1+B2*2".
+// They're parsed into an `Expr` and worked out from the values of the cells they refer to
+// whenever they're shown, so they're never out of date. References are written the way
+// `formula_reference` inserts them: cells of the formula's own grid by their short A1 name,
+// anything else by its full coordinate.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::num::NonZeroU32;
+
+use crate::coordinate::{letters_to_col, Coordinate};
+use crate::grammar::{Grammar, Interactive, Kind};
+use crate::util::{formula_reference, is_formula};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Ref(Coordinate),
+    Neg(Box<Expr>),
+    BinOp(Box<Expr>, Op, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+// what a formula shows instead of a number when it can't be worked out
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormulaError {
+    // the formula isn't written right, e.g. "=1+" or "=(2"
+    Parse(String),
+    // it refers to a cell that doesn't exist
+    Ref(Coordinate),
+    // it refers to a cell that doesn't hold a number
+    Value(Coordinate),
+    DivByZero,
+    // it (eventually) refers back to itself
+    Cycle,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormulaError::Parse(_) => write!(f, "#ERROR!"),
+            FormulaError::Ref(_) => write!(f, "#REF!"),
+            FormulaError::Value(_) => write!(f, "#VALUE!"),
+            FormulaError::DivByZero => write!(f, "#DIV/0!"),
+            FormulaError::Cycle => write!(f, "#CYCLE!"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ref(Coordinate),
+    Op(char),
+    Open,
+    Close,
+}
+
+// parses the `formula` of the cell at `at` (with or without its leading "=")
+pub fn parse_formula(formula: &str, at: &Coordinate) -> Result<Expr, FormulaError> {
+    let formula = if is_formula(formula) { &formula[1..] } else { formula };
+    let tokens = tokenize(formula, at)?;
+    let mut parser = FormulaParser { tokens: &tokens, position: 0 };
+    let expr = parser.expr()?;
+    match parser.tokens.get(parser.position) {
+        None => Ok(expr),
+        Some(token) => Err(FormulaError::Parse(format! {"unexpected {:?}", token})),
+    }
+}
+
+fn tokenize(formula: &str, at: &Coordinate) -> Result<Vec<Token>, FormulaError> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        i += 1;
+        match chars[start] {
+            ' ' | '\t' => (),
+            ch @ '+' | ch @ '-' | ch @ '*' | ch @ '/' => tokens.push(Token::Op(ch)),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '0'..='9' | '.' => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                match number.parse::<f64>() {
+                    Ok(n) => tokens.push(Token::Number(n)),
+                    Err(_) => {
+                        return Err(FormulaError::Parse(format! {"\"{}\" isn't a number", number}))
+                    }
+                }
+            }
+            ch if ch.is_ascii_alphabetic() => {
+                i = reference_end(&chars, start);
+                let reference: String = chars[start..i].iter().collect();
+                tokens.push(Token::Ref(parse_reference(&reference, at)?));
+            }
+            ch => return Err(FormulaError::Parse(format! {"unexpected \"{}\"", ch})),
+        }
+    }
+    Ok(tokens)
+}
+
+// where the reference starting at `start` ends. A full coordinate takes in each "-" that's
+// directly followed by another cell, so "root-A1-B2" is a single cell while "A1-B2" and
+// "root-A1 - B2" are subtractions.
+fn reference_end(chars: &[char], start: usize) -> usize {
+    let word_end = |from: usize| {
+        (from..chars.len()).find(|&i| !chars[i].is_ascii_alphanumeric()).unwrap_or(chars.len())
+    };
+    let mut end = word_end(start);
+    let word: String = chars[start..end].iter().collect();
+    if word == "root" || word == "meta" {
+        while end + 1 < chars.len() && chars[end] == '-' && chars[end + 1].is_ascii_alphabetic() {
+            end = word_end(end + 1);
+        }
+    }
+    end
+}
+
+// a full coordinate ("root-A1-B2"), or a short one ("B2") for a cell in the same grid as `at`
+fn parse_reference(reference: &str, at: &Coordinate) -> Result<Coordinate, FormulaError> {
+    let invalid = || FormulaError::Parse(format! {"\"{}\" isn't a cell", reference});
+    if reference.starts_with("root") || reference.starts_with("meta") {
+        return reference.parse::<Coordinate>().map_err(|_| invalid());
+    }
+    let reference = reference.to_uppercase();
+    let digits_start = reference.find(|c: char| c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (letters, digits) = reference.split_at(digits_start);
+    if digits.starts_with('0') || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let row = digits.parse::<u32>().ok().and_then(NonZeroU32::new).ok_or_else(invalid)?;
+    let col = letters_to_col(letters).and_then(NonZeroU32::new).ok_or_else(invalid)?;
+    let parent = at.parent().ok_or_else(invalid)?;
+    Ok(Coordinate::child_of(&parent, (row, col)))
+}
+
+// recursive descent, where each level binds tighter than the one before it:
+//   expr   := term (("+" | "-") term)*
+//   term   := factor (("*" | "/") factor)*
+//   factor := ("-" | "+") factor | number | reference | "(" expr ")"
+struct FormulaParser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> FormulaParser<'a> {
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn next_op(&mut self, ops: &[char]) -> Option<Op> {
+        match self.tokens.get(self.position) {
+            Some(Token::Op(ch)) if ops.contains(ch) => {
+                self.position += 1;
+                Some(match ch {
+                    '+' => Op::Add,
+                    '-' => Op::Sub,
+                    '*' => Op::Mul,
+                    _ => Op::Div,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, FormulaError> {
+        let mut expr = self.term()?;
+        while let Some(op) = self.next_op(&['+', '-']) {
+            expr = Expr::BinOp(Box::new(expr), op, Box::new(self.term()?));
+        }
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        let mut expr = self.factor()?;
+        while let Some(op) = self.next_op(&['*', '/']) {
+            expr = Expr::BinOp(Box::new(expr), op, Box::new(self.factor()?));
+        }
+        Ok(expr)
+    }
+
+    fn factor(&mut self) -> Result<Expr, FormulaError> {
+        match self.next() {
+            Some(Token::Op('-')) => Ok(Expr::Neg(Box::new(self.factor()?))),
+            Some(Token::Op('+')) => self.factor(),
+            Some(Token::Number(n)) => Ok(Expr::Number(*n)),
+            Some(Token::Ref(coord)) => Ok(Expr::Ref(coord.clone())),
+            Some(Token::Open) => {
+                let expr = self.expr()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(FormulaError::Parse("missing \")\"".to_string())),
+                }
+            }
+            Some(token) => Err(FormulaError::Parse(format! {"unexpected {:?}", token})),
+            None => Err(FormulaError::Parse("unexpected end of formula".to_string())),
+        }
+    }
+}
+
+// `formula` (of the cell at `at`, which moves to `moved_to`) with its references pointing at
+// where `moved` says the cells they refer to end up. Everything else in it, including anything
+// that isn't a valid reference, is kept as it was written.
+pub fn rebase_formula<F>(formula: &str, at: &Coordinate, moved_to: &Coordinate, moved: F) -> String
+where
+    F: Fn(&Coordinate) -> Coordinate,
+{
+    let chars: Vec<char> = formula.chars().collect();
+    let mut rebased = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        i += 1;
+        if chars[start].is_ascii_alphabetic() {
+            i = reference_end(&chars, start);
+            let reference: String = chars[start..i].iter().collect();
+            if let Ok(coord) = parse_reference(&reference, at) {
+                let target = moved(&coord);
+                if target != coord || moved_to != at {
+                    rebased += &formula_reference(moved_to, &target);
+                    continue;
+                }
+            }
+        }
+        rebased.extend(&chars[start..i]);
+    }
+    rebased
+}
+
+// the cells that `expr` refers to, which it has to be recomputed for when they change
+pub fn references(expr: &Expr) -> Vec<Coordinate> {
+    match expr {
+        Expr::Number(_) => vec![],
+        Expr::Ref(coord) => vec![coord.clone()],
+        Expr::Neg(inner) => references(inner),
+        Expr::BinOp(left, _, right) => {
+            let mut refs = references(left);
+            for coord in references(right) {
+                if !refs.contains(&coord) {
+                    refs.push(coord);
+                }
+            }
+            refs
+        }
+    }
+}
+
+// the cells that the formula in the cell at `at` refers to. Formulas that don't parse don't
+// refer to anything.
+pub fn formula_sources(formula: &str, at: &Coordinate) -> Vec<Coordinate> {
+    parse_formula(formula, at).map(|expr| references(&expr)).unwrap_or_default()
+}
+
+pub fn evaluate(expr: &Expr, grammars: &HashMap<Coordinate, Grammar>) -> Result<f64, FormulaError> {
+    evaluate_visiting(expr, grammars, &mut HashSet::new())
+}
+
+fn evaluate_visiting(
+    expr: &Expr,
+    grammars: &HashMap<Coordinate, Grammar>,
+    visiting: &mut HashSet<Coordinate>,
+) -> Result<f64, FormulaError> {
+    match expr {
+        Expr::Number(n) => Ok(*n),
+        Expr::Ref(coord) => cell_number(grammars, coord, visiting),
+        Expr::Neg(inner) => Ok(-evaluate_visiting(inner, grammars, visiting)?),
+        Expr::BinOp(left, op, right) => {
+            let left = evaluate_visiting(left, grammars, visiting)?;
+            let right = evaluate_visiting(right, grammars, visiting)?;
+            match op {
+                Op::Add => Ok(left + right),
+                Op::Sub => Ok(left - right),
+                Op::Mul => Ok(left * right),
+                Op::Div if right == 0.0 => Err(FormulaError::DivByZero),
+                Op::Div => Ok(left / right),
+            }
+        }
+    }
+}
+
+// the number in the cell at `coord`, working out its formula if it has one. Empty cells count
+// as 0, like in other spreadsheets. `visiting` are the formulas being worked out already.
+fn cell_number(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    visiting: &mut HashSet<Coordinate>,
+) -> Result<f64, FormulaError> {
+    let not_a_number = || FormulaError::Value(coord.clone());
+    match grammars.get(coord).map(|g| &g.kind) {
+        Some(Kind::Input(value)) if is_formula(value) => {
+            if !visiting.insert(coord.clone()) {
+                return Err(FormulaError::Cycle);
+            }
+            let result = parse_formula(value, coord)
+                .and_then(|expr| evaluate_visiting(&expr, grammars, visiting));
+            visiting.remove(coord);
+            result
+        }
+        Some(Kind::Input(value)) | Some(Kind::Lookup(value, _)) => match value.trim() {
+            "" => Ok(0.0),
+            value => value.parse::<f64>().ok().filter(|n| n.is_finite()).ok_or_else(not_a_number),
+        },
+        Some(Kind::Number(n, _)) => Ok(*n),
+        Some(Kind::Interactive(_, Interactive::Slider(value, _, _))) => Ok(*value),
+        Some(_) => Err(not_a_number()),
+        None => Err(FormulaError::Ref(coord.clone())),
+    }
+}
+
+// what the cell at `coord` holding `value` shows: the result of its formula (or the error
+// code), or just the value if it isn't a formula
+pub fn formula_result(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    value: &str,
+) -> String {
+    if !is_formula(value) {
+        return value.to_string();
+    }
+    let mut visiting = HashSet::new();
+    visiting.insert(coord.clone());
+    match parse_formula(value, coord).and_then(|e| evaluate_visiting(&e, grammars, &mut visiting)) {
+        Ok(n) => n.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord;
+    use crate::grammar_map::*;
+    use crate::{g, grid};
+
+    fn eval(formula: &str) -> Result<f64, FormulaError> {
+        let expr = parse_formula(formula, &coord!("root-A1"))?;
+        evaluate(&expr, &HashMap::new())
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        assert_eq!(eval("=1+2*3"), Ok(7.0));
+        assert_eq!(eval("=(1+2)*3"), Ok(9.0));
+        assert_eq!(eval("=2*3+4*5"), Ok(26.0));
+        // operators of the same level go left to right
+        assert_eq!(eval("=10-4-3"), Ok(3.0));
+        assert_eq!(eval("=8/4/2"), Ok(1.0));
+        assert_eq!(eval("=-2*3"), Ok(-6.0));
+        assert_eq!(eval("=2*-3"), Ok(-6.0));
+        assert_eq!(eval("=-(1+2)"), Ok(-3.0));
+        assert_eq!(eval("= 1.5 * ( 2 + .5 )"), Ok(3.75));
+        assert_eq!(eval("=1/0"), Err(FormulaError::DivByZero));
+        for bad in vec!["=", "=1+", "=(2", "=1 2", "=2)", "=1..2", "=1%2", "=A0"] {
+            match eval(bad) {
+                Err(FormulaError::Parse(_)) => (),
+                result => panic!("expected {} not to parse, got {:?}", bad, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cell_references() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "3")), g!(Grammar::input("", "=A1+B2*2"))],
+                [g!(Grammar::input("", "text")), g!(Grammar::input("", " 4.5"))],
+                [g!(Grammar::input("", "")), g!(Grammar::input("", "=B1-root-A1"))]
+            ],
+        );
+        assert_eq!(
+            parse_formula("=A1+b2*2", &coord!("root-B1")),
+            Ok(Expr::BinOp(
+                Box::new(Expr::Ref(coord!("root-A1"))),
+                Op::Add,
+                Box::new(Expr::BinOp(
+                    Box::new(Expr::Ref(coord!("root-B2"))),
+                    Op::Mul,
+                    Box::new(Expr::Number(2.0))
+                ))
+            ))
+        );
+        assert_eq!(
+            formula_sources("=A1+B2*2-A1", &coord!("root-B1")),
+            vec![coord!("root-A1"), coord!("root-B2")]
+        );
+        assert_eq!(formula_result(&grammars, &coord!("root-B1"), "=A1+B2*2"), "12");
+        // formulas referring to other formulas, and to cells by their full coordinate
+        assert_eq!(formula_result(&grammars, &coord!("root-B3"), "=B1-root-A1"), "9");
+        // empty cells count as 0
+        assert_eq!(formula_result(&grammars, &coord!("root-B3"), "=A3+1"), "1");
+        assert_eq!(formula_result(&grammars, &coord!("root-B3"), "=A2+1"), "#VALUE!");
+        assert_eq!(formula_result(&grammars, &coord!("root-B3"), "=C9+1"), "#REF!");
+        assert_eq!(formula_result(&grammars, &coord!("root-B3"), "=B3+1"), "#CYCLE!");
+        assert_eq!(formula_result(&grammars, &coord!("root-B3"), "=1+"), "#ERROR!");
+        // anything else is shown as it is
+        assert_eq!(formula_result(&grammars, &coord!("root-A2"), "text"), "text");
+        // cells referring to each other
+        grammars.get_mut(&coord!("root-A1")).unwrap().kind = Kind::Input("=B3".to_string());
+        assert_eq!(formula_result(&grammars, &coord!("root-B1"), "=A1+B2*2"), "#CYCLE!");
+    }
+
+    #[test]
+    fn test_rebase_formula() {
+        // row 2 moves down to row 3
+        let moved = |c: &Coordinate| {
+            if c.parent() == Some(coord!("root")) && c.row().get() == 2 {
+                Coordinate::child_of(&coord!("root"), (NonZeroU32::new(3).unwrap(), c.col()))
+            } else {
+                c.clone()
+            }
+        };
+        let (a1, b2) = (coord!("root-A1"), coord!("root-B2"));
+        assert_eq!(rebase_formula("=A2 * (b1+2.5)", &a1, &a1, moved), "=A3 * (b1+2.5)");
+        assert_eq!(rebase_formula("=root-B2 - A2", &a1, &a1, moved), "=B3 - A3");
+        // the formula's own cell moving changes how references to other grids are written
+        assert_eq!(rebase_formula("=A1+1", &b2, &coord!("root-B2-A1"), moved), "=root-A1+1");
+        // invalid references stay as they are
+        assert_eq!(rebase_formula("=A0+A2+", &a1, &a1, moved), "=A0+A3+");
+    }
+}
@@ -27,6 +27,10 @@ pub struct Grammar {
     pub name: String,
     pub style: Style,
     pub kind: Kind,
+    // optional regex pattern that an input's value is expected to match. Values that don't
+    // match are still accepted, but the cell gets flagged (see `Model::invalid_cells`)
+    #[serde(default)]
+    pub validator: Option<String>,
 }
 js_serializable!(Grammar);
 js_deserializable!(Grammar);
@@ -34,7 +38,7 @@ js_deserializable!(Grammar);
 // Kinds of grammars in the system.
 // Since this is an Enum, a Grammar's kind field
 // can only be set to one these variants at a time
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Kind {
     // Read-only text grammar
     Text(String),
@@ -42,6 +46,9 @@ pub enum Kind {
     // Readable and writable text grammar
     Input(String),
 
+    // Numeric input grammar, which is shown formatted
+    Number(f64, NumberFormat),
+
     // Structural grammar that nests a grid of grammars
     Grid(Vec<(NonZeroU32, NonZeroU32)>),
 
@@ -62,6 +69,12 @@ pub enum Kind {
     ),
 
     Editor(/* content */ String),
+
+    // Rich text grammar, which is written in markdown and shown rendered
+    Markdown(/* source */ String),
+
+    // Image grammar, which shows the image at a URL (or an embedded data URI)
+    Image(/* url */ String),
 }
 js_serializable!(Kind);
 js_deserializable!(Kind);
@@ -79,8 +92,83 @@ pub enum Lookup {
     Col(Col),
 }
 
+impl Lookup {
+    // reads a range typed into a lookup cell, like "A1:B2" or "root-A1-A1:root-A1-C3". Both ends
+    // have to be in the same grid, and they can be given in any order
+    pub fn parse_range(value: &str) -> Result<Lookup, String> {
+        let mut ends = value.splitn(2, ':');
+        let (start, end) = match (ends.next(), ends.next()) {
+            (Some(start), Some(end)) => (parse_cell_name(start)?, parse_cell_name(end)?),
+            _ => return Err(format! {"\"{}\" is not a range", value}),
+        };
+        let parent = match (start.parent(), end.parent()) {
+            (Some(start_parent), Some(end_parent)) if start_parent == end_parent => start_parent,
+            _ => {
+                return Err(format! {
+                    "{} and {} are not in the same grid", start.to_string(), end.to_string()
+                })
+            }
+        };
+        let ((start_row, start_col), (end_row, end_col)) = (start.row_col(), end.row_col());
+        Ok(Lookup::Range {
+            parent,
+            start: (start_row.min(end_row), start_col.min(end_col)),
+            end: (start_row.max(end_row), start_col.max(end_col)),
+        })
+    }
+}
+
+// Ways of formatting a Kind::Number for display
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum NumberFormat {
+    Plain,
+    Currency,
+    Percent,
+    Fixed(/* decimal places */ u8),
+}
+
+impl NumberFormat {
+    pub fn format(&self, value: f64) -> String {
+        match self {
+            NumberFormat::Plain => value.to_string(),
+            NumberFormat::Currency => {
+                let fixed = format! {"{:.2}", value.abs()};
+                let (int_part, frac_part) = fixed.split_at(fixed.len() - 3);
+                // don't show "-$0.00" for tiny negative numbers
+                let sign = if value < 0.0 && fixed != "0.00" { "-" } else { "" };
+                format! {"{}${}{}", sign, with_thousands_separators(int_part), frac_part}
+            }
+            NumberFormat::Percent => format! {"{:.2}%", value * 100.0},
+            NumberFormat::Fixed(places) => format! {"{:.*}", *places as usize, value},
+        }
+    }
+}
+
+// "1234567" -> "1,234,567"
+fn with_thousands_separators(digits: &str) -> String {
+    let mut separated = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            separated.push(',');
+        }
+        separated.push(c);
+    }
+    separated
+}
+
+impl Kind {
+    // the kind that typing `value` into an input cell gives, which is a Kind::Number if the
+    // cell has a number format and `value` is a number
+    pub fn from_input(value: String, number_format: Option<NumberFormat>) -> Kind {
+        match (number_format, value.trim().parse::<f64>()) {
+            (Some(format), Ok(number)) if number.is_finite() => Kind::Number(number, format),
+            _ => Kind::Input(value),
+        }
+    }
+}
+
 // Kinds of interactive grammars
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Interactive {
     Button(),
     Slider(/*value*/ f64, /*min*/ f64, /*max*/ f64),
@@ -93,6 +181,7 @@ impl Default for Grammar {
             name: "".to_string(),
             style: Style::default(),
             kind: Kind::Input("".to_string()),
+            validator: None,
         }
     }
 }
@@ -102,8 +191,15 @@ impl Grammar {
         match &self.kind {
             Kind::Grid(sub_coords) => {
                 let mut grid_area_str = "\"".to_string();
-                let mut prev_row = 1;
-                let mut sub_coords = sub_coords.clone();
+                // hidden rows & columns collapse by leaving them out of the template
+                let mut sub_coords: Vec<_> = sub_coords
+                    .iter()
+                    .filter(|(row, col)| {
+                        !self.style.hidden_rows.contains(&row.get())
+                            && !self.style.hidden_cols.contains(&col.get())
+                    })
+                    .cloned()
+                    .collect();
                 sub_coords.sort_by(|(a_row, a_col), (b_row, b_col)| {
                     if a_row < b_row {
                         Ordering::Less
@@ -117,6 +213,7 @@ impl Grammar {
                         Ordering::Greater
                     }
                 });
+                let mut prev_row = sub_coords.first().map_or(1, |(row, _)| row.get());
                 for (row, col) in sub_coords {
                     if row.get() > prev_row {
                         grid_area_str.pop();
@@ -135,7 +232,7 @@ impl Grammar {
                 }
             }
             Kind::Lookup(_, _) => format! {
-                "{}display: inline-flex; grid-area: cell-{}; background: white;\n", self.style.to_string(), coord.to_string()
+                "{}display: inline-flex; grid-area: cell-{};\n", self.style.to_string(), coord.to_string()
             },
             _ => format! {"{}grid-area: cell-{};\n", self.style.to_string(), coord.to_string()},
         }
@@ -150,6 +247,7 @@ impl Grammar {
             name: name.into(),
             style: Style::default(),
             kind: Kind::Text(value.into()),
+            validator: None,
         }
     }
 
@@ -162,6 +260,7 @@ impl Grammar {
             name: name.into(),
             style: Style::default(),
             kind: Kind::Input(value.into()),
+            validator: None,
         }
     }
 
@@ -170,6 +269,7 @@ impl Grammar {
             name: "button".to_string(),
             style: Style::default(),
             kind: Kind::Interactive("".to_string(), Interactive::Button()),
+            validator: None,
         }
     }
 
@@ -178,6 +278,7 @@ impl Grammar {
             name: "slider".to_string(),
             style: Style::default(),
             kind: Kind::Interactive("".to_string(), Interactive::Slider(0.0, 0.0, 100.0)),
+            validator: None,
         }
     }
 
@@ -186,6 +287,7 @@ impl Grammar {
             name: "toggle".to_string(),
             style: Style::default(),
             kind: Kind::Interactive("".to_string(), Interactive::Toggle(false)),
+            validator: None,
         }
     }
 
@@ -201,6 +303,7 @@ impl Grammar {
             name: "".to_string(),
             style: Style::default(),
             kind: Kind::Grid(grid),
+            validator: None,
         }
     }
 }
@@ -251,7 +354,7 @@ mod tests {
     fn test_grammar_style() {
         assert_eq!(
             Grammar::default().style(&coord!("root-A1")),
-            format! {"/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root-A1;\n"}
+            format! {"/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\nfont-style: normal;\ntext-decoration: none;\nfont-family: sans-serif;\nfont-size: 14px;\ncolor: black;\nbackground: white;\ntext-align: left;\ndisplay: flex;\nalign-items: center;\nwhite-space: pre;\noverflow: hidden;\n\ngrid-area: cell-root-A1;\n"}
         );
         assert_ne!(
             Grammar::default().style(&coord!("root-A1")),
@@ -266,7 +369,84 @@ mod tests {
         assert_ne!(
             Grammar::as_grid(NonZeroU32::new(1).unwrap(), NonZeroU32::new(2).unwrap())
                 .style(&coord!("root-A1")),
-            format! {"/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root-A1;\n"}
+            format! {"/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\nfont-style: normal;\ntext-decoration: none;\nfont-family: sans-serif;\nfont-size: 14px;\ncolor: black;\nbackground: white;\ntext-align: left;\ndisplay: flex;\nalign-items: center;\nwhite-space: pre;\noverflow: hidden;\n\ngrid-area: cell-root-A1;\n"}
+        );
+    }
+
+    #[test]
+    fn test_grammar_style_hidden() {
+        let mut grid = Grammar::as_grid(NonZeroU32::new(2).unwrap(), NonZeroU32::new(2).unwrap());
+        grid.style.hidden_cols = vec![1];
+        assert_eq!(
+            grid.style(&coord!("root-A1")),
+            format! {"display: grid;\ngrid-area: cell-root-A1;\nheight: fit-content;\nwidth: fit-content !important;\ngrid-template-areas: \n\"cell-root-A1-B1\"\n\"cell-root-A1-B2\";\n"}
+        );
+        grid.style.hidden_rows = vec![1];
+        assert_eq!(
+            grid.style(&coord!("root-A1")),
+            format! {"display: grid;\ngrid-area: cell-root-A1;\nheight: fit-content;\nwidth: fit-content !important;\ngrid-template-areas: \n\"cell-root-A1-B2\";\n"}
+        );
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            Lookup::parse_range("A1:B2"),
+            Ok(Lookup::Range {
+                parent: coord!("root"),
+                start: non_zero_u32_tuple((1, 1)),
+                end: non_zero_u32_tuple((2, 2)),
+            })
+        );
+        // the ends get put in order
+        assert_eq!(
+            Lookup::parse_range("root-A1-C1:root-A1-A3"),
+            Ok(Lookup::Range {
+                parent: coord!("root-A1"),
+                start: non_zero_u32_tuple((1, 1)),
+                end: non_zero_u32_tuple((3, 3)),
+            })
+        );
+        assert!(Lookup::parse_range("A1").is_err());
+        assert!(Lookup::parse_range("A1:").is_err());
+        assert_eq!(
+            Lookup::parse_range("A1:root-B1-A1"),
+            Err("root-A1 and root-B1-A1 are not in the same grid".to_string())
+        );
+    }
+
+    #[test]
+    fn test_number_format() {
+        assert_eq!(NumberFormat::Plain.format(1234.5), "1234.5");
+        assert_eq!(NumberFormat::Plain.format(-2.0), "-2");
+        assert_eq!(NumberFormat::Currency.format(1234567.891), "$1,234,567.89");
+        assert_eq!(NumberFormat::Currency.format(-12.5), "-$12.50");
+        assert_eq!(NumberFormat::Currency.format(-0.001), "$0.00");
+        assert_eq!(NumberFormat::Currency.format(100.0), "$100.00");
+        assert_eq!(NumberFormat::Percent.format(0.125), "12.50%");
+        assert_eq!(NumberFormat::Percent.format(1.0), "100.00%");
+        assert_eq!(NumberFormat::Fixed(0).format(2.5001), "3");
+        assert_eq!(NumberFormat::Fixed(3).format(2.5), "2.500");
+    }
+
+    #[test]
+    fn test_kind_from_input() {
+        assert_eq!(
+            Kind::from_input(" 12.5 ".to_string(), Some(NumberFormat::Currency)),
+            Kind::Number(12.5, NumberFormat::Currency)
+        );
+        // numbers stay as plain input without a number format
+        assert_eq!(
+            Kind::from_input("12.5".to_string(), None),
+            Kind::Input("12.5".to_string())
+        );
+        assert_eq!(
+            Kind::from_input("twelve".to_string(), Some(NumberFormat::Plain)),
+            Kind::Input("twelve".to_string())
+        );
+        assert_eq!(
+            Kind::from_input("inf".to_string(), Some(NumberFormat::Plain)),
+            Kind::Input("inf".to_string())
         );
     }
 
@@ -34,14 +34,8 @@ pub fn build_grammar_map(
         }
         MapEntry::Grid(entry_table) => {
             let mut sub_coords = vec![];
-            let mut num_rows = 0;
-            let mut num_cols = 0;
             for (row_i, entry_row) in entry_table.iter().enumerate() {
-                num_rows = row_i + 1;
                 for (col_i, entry) in entry_row.iter().enumerate() {
-                    if col_i > num_cols {
-                        num_cols = col_i + 1
-                    }
                     let new_coord = Coordinate::child_of(
                         &root_coord,
                         non_zero_u32_tuple(((row_i + 1) as u32, (col_i + 1) as u32)),
@@ -50,23 +44,46 @@ pub fn build_grammar_map(
                     sub_coords.push(non_zero_u32_tuple(((row_i + 1) as u32, (col_i + 1) as u32)));
                 }
             }
+            // the grid is as wide as its first row and as tall as its first column, so it takes
+            // on whatever size its cells were built with
+            let cell_size = |row: u32, col: u32| {
+                map.get(&Coordinate::child_of(&root_coord, non_zero_u32_tuple((row, col))))
+                    .map_or((0.0, 0.0), |g| (g.style.width, g.style.height))
+            };
+            let first_row_len = entry_table.first().map_or(0, |row| row.len()) as u32;
+            let width: f64 = (1..=first_row_len).map(|col| cell_size(1, col).0).sum();
+            let height: f64 = (1..=entry_table.len() as u32).map(|row| cell_size(row, 1).1).sum();
             map.insert(
                 root_coord,
                 Grammar {
                     name: String::new(),
                     style: {
                         let mut s = Style::default();
-                        s.width = 90.0 * (num_cols as f64);
-                        s.height = 30.0 * (num_rows as f64);
+                        s.width = width;
+                        s.height = height;
                         s
                     },
                     kind: Kind::Grid(sub_coords),
+                    validator: None,
                 },
             );
         }
     }
 }
 
+// `grid_entry` is the runtime equivalent of the `grid!` macro, for when the size of the grid
+// isn't known until runtime. `cell` builds the entry at each (row, col), starting from (1, 1).
+pub fn grid_entry<F>(rows: u32, cols: u32, cell: F) -> MapEntry
+where
+    F: Fn(u32, u32) -> MapEntry,
+{
+    MapEntry::Grid(
+        (1..=rows)
+            .map(|row| (1..=cols).map(|col| Box::new(cell(row, col))).collect())
+            .collect(),
+    )
+}
+
 #[macro_export]
 macro_rules! g {
     ( $grammar:expr ) => {
@@ -116,3 +133,133 @@ macro_rules! grid {
 //         assert_eq!(map.keys().len(), 13);
 //     }
 // }
+
+// places where the sub-coordinates of the grids in a grammar map and the grammars actually in
+// the map have drifted apart
+#[derive(Debug, Default, PartialEq)]
+pub struct MapProblems {
+    // sub-coordinates listed by a grid that have no grammar in the map
+    pub missing: Vec<Coordinate>,
+    // grammars under a grid that doesn't list them (or under a parent that's gone)
+    pub orphans: Vec<Coordinate>,
+}
+
+impl MapProblems {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.orphans.is_empty()
+    }
+}
+
+// walks the grids from `root`, collecting the sub-coordinates that are missing from `map` and
+// the grammars that none of the grids list. Children of grammars that aren't grids (like
+// definitions) are left alone.
+pub fn check_grammar_map(map: &HashMap<Coordinate, Grammar>, root: &Coordinate) -> MapProblems {
+    let mut problems = MapProblems::default();
+    let mut to_visit = vec![root.clone()];
+    while let Some(coord) = to_visit.pop() {
+        if let Some(Grammar { kind: Kind::Grid(sub_coords), .. }) = map.get(&coord) {
+            for sub_coord in sub_coords {
+                let child = Coordinate::child_of(&coord, *sub_coord);
+                if map.contains_key(&child) {
+                    to_visit.push(child);
+                } else {
+                    problems.missing.push(child);
+                }
+            }
+        }
+    }
+    for coord in map.keys() {
+        if coord.row_cols.len() <= root.row_cols.len()
+            || !coord.row_cols.starts_with(&root.row_cols)
+        {
+            continue;
+        }
+        let listed = match coord.parent().and_then(|p| map.get(&p)) {
+            Some(Grammar { kind: Kind::Grid(sub_coords), .. }) => {
+                sub_coords.contains(coord.row_cols.last().unwrap())
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if !listed {
+            problems.orphans.push(coord.clone());
+        }
+    }
+    problems.missing.sort_by_key(|c| c.to_string());
+    problems.orphans.sort_by_key(|c| c.to_string());
+    problems
+}
+
+// fixes the problems found by `check_grammar_map`, by filling in missing cells with empty ones
+// and dropping orphaned grammars (along with anything nested in them)
+pub fn repair_grammar_map(map: &mut HashMap<Coordinate, Grammar>, problems: &MapProblems) {
+    for coord in problems.missing.iter() {
+        map.insert(coord.clone(), Grammar::default());
+    }
+    for orphan in problems.orphans.iter() {
+        map.retain(|c, _| !c.row_cols.starts_with(&orphan.row_cols));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::{DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT};
+
+    #[test]
+    fn test_grid_entry() {
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root"),
+            grid_entry(2, 4, |_, _| MapEntry::G(Grammar::default())),
+        );
+        // the grid itself and its 8 cells
+        assert_eq!(map.len(), 9);
+        assert!(map.contains_key(&coord!("root-D2")));
+        if let Kind::Grid(sub_coords) = &map[&coord!("root")].kind {
+            assert_eq!(sub_coords.len(), 8);
+        } else {
+            panic!("root should be a grid");
+        }
+        // the grid is the size of its cells
+        assert_eq!(map[&coord!("root")].style.width, 4.0 * DEFAULT_COL_WIDTH);
+        assert_eq!(map[&coord!("root")].style.height, 2.0 * DEFAULT_ROW_HEIGHT);
+
+        let mut sized = Grammar::default();
+        sized.style.width = 120.0;
+        sized.style.height = 40.0;
+        let entry = grid_entry(2, 3, |_, _| MapEntry::G(sized.clone()));
+        build_grammar_map(&mut map, coord!("root"), entry);
+        assert_eq!(map[&coord!("root")].style.width, 360.0);
+        assert_eq!(map[&coord!("root")].style.height, 80.0);
+    }
+
+    #[test]
+    fn test_check_and_repair_grammar_map() {
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root"),
+            grid_entry(2, 2, |_, _| MapEntry::G(Grammar::default())),
+        );
+        assert!(check_grammar_map(&map, &coord!("root")).is_empty());
+
+        // corrupt the map: B2 goes missing, and C1 (with a cell nested in it) and A1-A1 (whose
+        // parent isn't a grid) show up without being listed
+        map.remove(&coord!("root-B2"));
+        map.insert(coord!("root-C1"), Grammar::default());
+        map.insert(coord!("root-C1-A1"), Grammar::default());
+        map.insert(coord!("root-A1-A1"), Grammar::default());
+        let problems = check_grammar_map(&map, &coord!("root"));
+        assert_eq!(problems.missing, vec![coord!("root-B2")]);
+        assert_eq!(problems.orphans, vec![coord!("root-C1")]);
+
+        repair_grammar_map(&mut map, &problems);
+        assert!(check_grammar_map(&map, &coord!("root")).is_empty());
+        assert!(map.contains_key(&coord!("root-B2")));
+        assert!(!map.contains_key(&coord!("root-C1")));
+        assert!(!map.contains_key(&coord!("root-C1-A1")));
+        assert!(map.contains_key(&coord!("root-A1-A1")));
+    }
+}
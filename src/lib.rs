@@ -16,11 +16,14 @@ extern crate stdweb;
 extern crate pest_derive;
 
 pub mod coordinate;
+pub mod formula;
 pub mod grammar;
 pub mod grammar_map;
 pub mod model;
 pub mod session;
 pub mod style;
+pub mod suggestion;
+pub mod template;
 pub mod util;
 pub mod view;
 pub mod codemirror;
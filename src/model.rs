@@ -1,5 +1,9 @@
 use electron_sys::ipc_renderer;
+use js_sys::Array;
+use node_sys::Buffer;
 use pest::Parser;
+use regex::Regex;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 extern crate csv;
 use csv::Error;
@@ -10,20 +14,41 @@ use std::ops::Deref;
 use std::option::Option;
 use stdweb::traits::IEvent;
 use stdweb::unstable::{TryFrom, TryInto};
-use stdweb::web::{document, IElement, INode, IParentNode};
-use wasm_bindgen::JsValue;
+use stdweb::web::{document, window, Date, IElement, INode, IParentNode};
+use wasm_bindgen::prelude::*;
 use yew::events::{KeyDownEvent, KeyPressEvent, KeyUpEvent};
 use yew::prelude::*;
 use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
 use yew::services::ConsoleService;
 
-use crate::coordinate::{Col, Coordinate, Row};
-use crate::grammar::{Grammar, Kind, Lookup};
+use crate::coordinate::{parse_cell_name, Col, Coordinate, Row};
+use crate::formula::{formula_result, formula_sources};
+use crate::grammar::{Grammar, Interactive, Kind, Lookup, NumberFormat};
 use crate::grammar_map::*;
-use crate::session::Session;
-use crate::style::Style;
-use crate::util::{move_grammar, non_zero_u32_tuple, resize, resize_diff};
-use crate::view::{view_context_menu, view_grammar, view_menu_bar, view_side_nav, view_tab_bar};
+use crate::session::{Definitions, Session, SESSION_VERSION};
+use crate::style::{
+    ConditionalFormat, Style, StyleCache, Theme, DEFAULT_COL_WIDTH, DEFAULT_GRIDLINE_COLOR,
+    DEFAULT_ROW_HEIGHT, PREDICATE_NAMES,
+};
+use crate::template::{template_grammars, TemplateId};
+use crate::util::{
+    add_defn_rule, append_grid_rows, contains_locked, coordinate_color, copy_region, data_uri,
+    delimited_file_grid, delimiter_for_file_name, dom_content_size, dom_download, dom_focus_later,
+    dom_is_text_entry, dom_rect, dom_scroll_container, dom_scroll_offset, dom_scroll_to,
+    dom_viewport, fill_targets, find_case_insensitive, fit_line_size, formula_reference, grid_size,
+    grid_to_html, group_line_sizes, group_region, grouped_coord, grow_line_sizes, insert_at_caret,
+    is_formula, is_locked, is_merged, matches_validator, merge_lines, merge_region, move_grammar,
+    nested_cell_size, non_zero_u32_tuple, parse_json_grid, parse_xlsx, rekey, remove_definition,
+    remove_defn_rule, replace_case_insensitive, resize, resize_diff, resize_grid, scroll_into_view,
+    session_file_names, shift_cells, shift_conditional_formats, shift_line_sizes, shifted_coord,
+    sort_line_sizes, sort_region, sorted_coord, step_zoom, transpose_region, transposed_coord,
+    unmerge_region, zoom_to_fit, Sheet, ZOOM_STEP,
+};
+use crate::view::{
+    view_autosave_recovery, view_context_menu, view_goto, view_grammar, view_help_overlay,
+    view_loading_indicator, view_menu_bar, view_resize_preview, view_side_nav, view_status_bar,
+    view_tab_bar,
+};
 use crate::{coord, coord_col, coord_row, g, grid, row_col_vec};
 
 #[derive(Parser)]
@@ -46,6 +71,8 @@ pub struct Model {
     pub first_select_cell: Option<Coordinate>,
     pub last_select_cell: Option<Coordinate>,
 
+    // - `secondary_selections` are cells added to the selection one at a time with
+    //   Ctrl+click, on top of the range between `first_select_cell` and `last_select_cell`
     pub secondary_selections: HashSet<Coordinate>,
 
     // TODO: are `min_select_cell` and `max_select_cell` still useful
@@ -55,15 +82,54 @@ pub struct Model {
     // - `shift_key_pressed` is a simple indicator for when shift key is togridled
     pub shift_key_pressed: bool,
 
+    // - `tab_skips_locked` makes Tab navigation skip over locked cells, so it only
+    //   stops on editable ones (like in a form)
+    pub tab_skips_locked: bool,
+
     // - `zoom` is the value that corresponds to how "zoomed" the sheet is
     pub zoom: f32,
 
+    // - `scroll_offset` is how far (x, y) the sheet is scrolled inside `.main`
+    pub scroll_offset: (f64, f64),
+
     // - `meta_suggestions` contains a map of the name of suggestions to the
     //   suggested grammars stored in coord_col!("meta", "A")
     pub meta_suggestions: Vec<(String, Coordinate)>,
 
+    // - `lite_mode` is turned on when the sheet is too large to render suggestions and full
+    //   styling for every cell without slowing down (see `Model::estimate_render_cost`)
+    pub lite_mode: bool,
+
+    // - `forced_suggestions` is the cell whose suggestions were explicitly opened
+    //   with Ctrl-Space, which shows them even when auto-complete is turned off
+    pub forced_suggestions: Option<Coordinate>,
+
+    // - `dismissed_suggestions` is the cell whose suggestions were closed with Escape, which
+    //   stay closed until something is typed into it
+    pub dismissed_suggestions: Option<Coordinate>,
+
+    // - `observers` maps a cell to the lookups that show its value, so they can be
+    //   re-evaluated whenever the cell changes
+    pub observers: HashMap<Coordinate, Vec<Coordinate>>,
+
+    // - `invalid_cells` are the input cells whose value doesn't match their grammar's
+    //   validator, which get flagged in the view
+    pub invalid_cells: HashSet<Coordinate>,
+    // - `validator_regexes` are the validator patterns compiled so far (None if they don't compile)
+    validator_regexes: HashMap<String, Option<Regex>>,
+
+    // - `cell_history` has the last HISTORY_LENGTH numeric values of each cell, for the cells'
+    //   sparklines, which are shown for the cells in `sparklines`
+    pub cell_history: HashMap<Coordinate, Vec<f64>>,
+    pub sparklines: HashSet<Coordinate>,
+
+    // - `python_errors` are the output cells of RunPython whose code raised an exception,
+    //   which hold the error message instead of a result until they're run again or edited
+    pub python_errors: HashSet<Coordinate>,
+
     // - `lookups` represent an ordered list of coordinates that have lookups corresponding
-    // to them. the indexes are used to generate correspoding color coding for each lookup
+    // to them. the indexes are used to generate correspoding color coding for each lookup,
+    // so new sources get added at the end and the colors of existing ones don't change
     pub lookups: Vec<Coordinate>,
 
     // - `col_widths` & `row_heights` map coordinate to sizes based on column or row
@@ -76,6 +142,10 @@ pub struct Model {
     pub sessions: Vec<Session>,
     pub current_session_index: usize,
 
+    // - `saved_sessions` are the names of the .ise files in SESSIONS_DIRECTORY, which are
+    //   listed in the File Explorer menu
+    pub saved_sessions: Vec<String>,
+
     // - `side_menus` represent the state
     pub side_menus: Vec<SideMenu>,
     pub open_side_menu: Option<i32>,
@@ -88,6 +158,61 @@ pub struct Model {
     //    (which is None if no resizing is happening)
     pub resizing: Option<Coordinate>,
 
+    // - `resizing_line` is the row or column whose header border is being dragged, in which
+    //    case only its height (or width) changes
+    pub resizing_line: Option<GridLine>,
+
+    // - `resize_preview` is the prospective (width, height) of the cell being resized, which
+    //    only gets applied to the sheet once the drag ends. `resize_label_position` is where
+    //    the floating label showing it is drawn (near the mouse)
+    pub resize_preview: Option<(f64, f64)>,
+    pub resize_label_position: (f64, f64),
+
+    // - `style_cache` holds the CSS of each cell from the last render (see `keeps_cell_styles`)
+    pub style_cache: StyleCache,
+
+    // - `serialized_session` is the JSON dump of the current session that integration tests
+    //    read, which is only redone after the session might have changed (see `keeps_session`)
+    serialized_session: RefCell<Option<String>>,
+
+    // - `frozen_rows` & `frozen_cols` are the number of rows and columns at the start of the
+    //   top-level grid that stay pinned while it scrolls (mirrored in the session for saving)
+    pub frozen_rows: u32,
+    pub frozen_cols: u32,
+
+    // - `default_col_width` & `default_row_height` are the size new rows and columns get
+    //   (mirrored in the session for saving)
+    pub default_col_width: f64,
+    pub default_row_height: f64,
+
+    // - `filling` is the (source, target) of the fill handle being dragged
+    //    (which is None if no filling is happening)
+    pub filling: Option<(Coordinate, Coordinate)>,
+
+    // - `note_editor` is the cell whose note is being written
+    //    (which is None if no note is open for editing)
+    pub note_editor: Option<Coordinate>,
+
+    // - `goto_open` is whether the "Go To" box (for jumping to a cell by name) is showing
+    pub goto_open: bool,
+    // - `goto_node_ref` is the input of the "Go To" box, which gets focus when the box opens
+    pub goto_node_ref: NodeRef,
+
+    // - `help_open` is whether the list of keyboard shortcuts (Ctrl-/) is showing
+    pub help_open: bool,
+
+    // - `show_coords` is whether every cell is labelled with its coordinate, which helps when
+    //   working out how grids are nested
+    pub show_coords: bool,
+
+    // - `markdown_editor` is the markdown cell being edited, which shows its source instead of
+    //    the rendered markdown (which is None if no markdown is being edited)
+    pub markdown_editor: Option<Coordinate>,
+
+    // - `header_editor` is the column whose header is being renamed, which shows an input
+    //    instead of its label (None if no header is being renamed)
+    pub header_editor: Option<Col>,
+
     // - `link` is a function of the Yew framework for referring back to the current component
     //    so actions can be chained, for instance
     pub link: ComponentLink<Model>,
@@ -98,10 +223,34 @@ pub struct Model {
     //   by Ctrl+G the "Add Definition" button
     pub default_nested_row_cols: (NonZeroU32, NonZeroU32),
 
+    // - `insert_count` is how many rows/cols the "Insert Row" & "Insert Column" buttons add
+    pub insert_count: u32,
+
+    // - `recreate_row_cols` is the size of the fresh root grid made by the "Reset to" button
+    pub recreate_row_cols: (NonZeroU32, NonZeroU32),
+
+    // - `format_rule_draft` is the (predicate name, operand, background color) of the
+    //   conditional format being put together in the Settings menu
+    pub format_rule_draft: (String, String, String),
+
     pub context_menu_position: Option<(f64, f64)>,
 
     pub default_definition_name: String,
 
+    // - `search_query` & `search_replacement` are the values typed into the Search menu
+    pub search_query: String,
+    pub search_replacement: String,
+
+    // - `csv_orientation` is how CSV files dropped onto a cell get laid out
+    pub csv_orientation: CsvOrientation,
+
+    // - `clipboard` holds the grammars of the last copied selection
+    pub clipboard: Option<Clipboard>,
+
+    // - `current_cursor_position` is the caret offset (in characters) inside the active cell,
+    //   which is where references get inserted when pointing at cells while editing a formula
+    pub current_cursor_position: usize,
+
     // - `mouse_cursor` corresponds to the appearance of the mouse cursor
     pub mouse_cursor: CursorType,
 
@@ -112,6 +261,24 @@ pub struct Model {
 
     // - `tasks` are used to store asynchronous requests to read/load files
     pub tasks: Vec<ReaderTask>,
+
+    // - `loading_tasks` counts the file reads that haven't finished yet, for the busy indicator
+    pub loading_tasks: usize,
+
+    // - `autosave_interval` is how many changes to the session go by between auto-saves to
+    //   localStorage (0 turns auto-saving off), and `changes_since_autosave` counts them
+    pub autosave_interval: usize,
+    pub changes_since_autosave: usize,
+
+    // - `recovered_session` is an auto-saved session that was found on startup, which the
+    //   user gets offered to restore
+    pub recovered_session: Option<Session>,
+
+    // - `action_log` has the last ACTION_LOG_LENGTH actions (and the time they were dispatched
+    //   at, in ms) for debugging. They're only logged while `log_actions` is set, which is
+    //   whenever the Info menu that shows them is open
+    pub action_log: Vec<(f64, String)>,
+    pub log_actions: bool,
 }
 
 #[derive(Debug)]
@@ -120,15 +287,61 @@ pub struct SideMenu {
     pub icon_path: String,
 }
 
+// Clipboard holds a copy of a rectangular selection of grammars. Each grammar is keyed by its
+// path relative to the copied selection, where the first (row, col) is the position inside the
+// selection (starting at (1, 1)) and the rest is the path of any nested grammars.
+// `results` has what each copied formula cell showed, by its position in the selection, for
+// pasting values.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    pub rows: u32,
+    pub cols: u32,
+    pub grammars: Vec<(Vec<(NonZeroU32, NonZeroU32)>, Grammar)>,
+    pub results: HashMap<(NonZeroU32, NonZeroU32), String>,
+}
+
 // SUBACTIONS
 // Sub-actions for resize-related operations
+// (X & Y also carry the pointer position, which is where the size preview label is drawn)
+#[derive(Debug)]
 pub enum ResizeMsg {
     Start(Coordinate),
-    X(f64),
-    Y(f64),
+    // dragging the border of a row or column header resizes just that line
+    StartRow(Row),
+    StartCol(Col),
+    X(f64, (f64, f64)),
+    Y(f64, (f64, f64)),
     End,
 }
 
+// a whole row or column of a grid, e.g. the one whose header border is being dragged
+#[derive(Debug, Clone)]
+pub enum GridLine {
+    Row(Row),
+    Col(Col),
+}
+
+impl GridLine {
+    pub fn is_row(&self) -> bool {
+        match self {
+            GridLine::Row(_) => true,
+            GridLine::Col(_) => false,
+        }
+    }
+}
+
+// Sub-actions for jumping the active cell around its grid with Home/End/PageUp/PageDown
+// (see `navigation_target`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Navigation {
+    RowStart,
+    RowEnd,
+    GridStart,
+    GridEnd,
+    PageUp,
+    PageDown,
+}
+
 // Sub-actions for adjusting the current look of the cursor
 #[derive(Debug)]
 pub enum CursorType {
@@ -137,6 +350,15 @@ pub enum CursorType {
     Default,
 }
 
+// Orientation of imported CSV data: whether each CSV line becomes a row (RowMajor),
+// or a column (ColumnMajor) of the resulting grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvOrientation {
+    RowMajor,
+    ColumnMajor,
+}
+
+#[derive(Debug)]
 pub enum SelectMsg {
     Start(Coordinate),
     End(Coordinate),
@@ -144,16 +366,37 @@ pub enum SelectMsg {
 
 // ACTIONS
 // Trigridered in the view, sent to update function
+#[derive(Debug)]
 pub enum Action {
     // Do nothing
     Noop,
 
     // Change string value of Input grammar
     ChangeInput(Coordinate, /* new_value: */ String),
+    // the cell was left after editing it (on blur, which Enter causes too), so its value is
+    // added to the cell's history
+    CommitInput(Coordinate),
+    // Set (or clear) the regex pattern that an input's value is validated against
+    SetValidator(Coordinate, Option<String>),
+    SetNumberFormat(Coordinate, Option<NumberFormat>),
+    // how many decimal places a number typed into the cell is shown with (None shows it as typed)
+    SetPrecision(Coordinate, Option<u8>),
 
     SetActiveCell(Coordinate),
+    // moves the active cell from the cell at the coordinate, e.g. to the end of its row
+    Navigate(Coordinate, Navigation),
 
     NextSuggestion(Coordinate, /* index */ i32),
+    ForceSuggestions(Coordinate),
+    DismissSuggestions(Coordinate),
+    ToggleAutoComplete,
+    ToggleGridlines,
+    ToggleDarkMode,
+    SetGridlineColor(String),
+    // auto-saving to localStorage, and recovering what was auto-saved before a crash
+    SetAutosaveInterval(usize),
+    RestoreAutosave,
+    DiscardAutosave,
     DoCompletion(
         /* source: */ Coordinate,
         /* destination */ Coordinate,
@@ -161,52 +404,178 @@ pub enum Action {
 
     SetActiveMenu(Option<i32>),
 
-    ReadSession(/* filename: */ File),
+    ReadSession(/* filename: */ File, /* new_tab: */ bool),
 
-    LoadSession(FileData),
+    LoadSession(FileData, /* new_tab: */ bool),
 
-    SaveSession(),
+    ListSavedSessions,
+    OpenSavedSession(/* filename: */ String),
 
-    SetSessionTitle(String),
+    SaveSession(),
+    // downloads the definitions of the meta grid as a .isedef file, which can be imported into
+    // other sessions
+    ExportDefinitions(),
+    ReadDefinitions(File),
+    ImportDefinitions(FileData),
+
+    // renames the session at the given index in `sessions`
+    SetSessionTitle(/* session index */ usize, String),
+    RenameColumnHeader(Col, String),
+    // starts (or with None, stops) renaming a column header
+    EditColumnHeader(Option<Col>),
     ReadDriverFiles(Vec<File>),
     LoadDriverMainFile(FileData),
     UploadDriverMiscFile(FileData),
 
     // Grid Operations
     AddNestedGrid(Coordinate, (u32 /*rows*/, u32 /*cols*/)),
+    // replaces a cell with a fresh copy of one of the Home menu's templates
+    InsertTemplate(Coordinate, TemplateId),
 
     InsertRow,
     InsertCol,
+    // inserts that many rows (or columns) at once
+    InsertRows(u32),
+    InsertCols(u32),
+    SetInsertCount(u32),
+    DuplicateRow,
+    DuplicateColumn,
     DeleteRow,
     DeleteCol,
     Recreate,
+    RecreateWithSize(/* rows */ u32, /* cols */ u32),
     ZoomIn,
     ZoomOut,
     ZoomReset,
+    ZoomToFit,
+    ZoomToSelection,
+    Scroll(f64, f64),
+    ScrollToCell(Coordinate),
+    ShowGoTo(bool),
+    // jumps to the cell written as "A1" or "root-A1-B2"
+    GoTo(String),
+    ToggleHelpOverlay,
+    ToggleCoordinateOverlay,
+    // empties the log of actions shown in the Info menu
+    ClearActionLog,
 
     NewEditor,
+    // points the RunPython output of the editor at the first coordinate to the cell typed in,
+    // or back to the default output cell if it's left empty
+    SetEditorOutput(Coordinate, String),
 
     Resize(ResizeMsg),
+    FillStart(Coordinate),
+    FillTo(Coordinate),
+    FillEnd,
+    EditNote(Coordinate),
+    MakeMarkdown(Coordinate),
+    ToggleMarkdownEdit(Coordinate),
+    SetNote(Coordinate, String),
+    ClearNote(Coordinate),
+    SetFrozenRows(u32),
+    SetFrozenCols(u32),
+    SetDefaultColWidth(f64),
+    SetDefaultRowHeight(f64),
     SetCursorType(CursorType),
     Select(SelectMsg),
+    ReorderSession(/* from */ usize, /* to */ usize),
+    SelectRow(Row),
+    SelectColumn(Col),
+    // sizes a column (or row) to fit the widest (or tallest) cell content in it, or back to the
+    // default size if it's empty. Double-clicking a header border does this.
+    AutoFitColumn(Col),
+    AutoFitRow(Row),
+    // selects every cell of the active cell's grid, or of the grid around it if they're all
+    // selected already
+    SelectAll,
+    InsertReferenceToSelection(Coordinate),
+    InsertNewline(Coordinate),
     RangeDelete(),
+    TransposeRange(),
+    // moves the selected cells into a grid nested in the selection's top-left cell
+    GroupSelectionIntoGrid(),
+    // adds or removes trailing rows & columns of the grid at the coordinate
+    ResizeGrid(Coordinate, /* rows */ u32, /* cols */ u32),
+    // sorts the selected rows by the values in the first selected column
+    SortRange {
+        ascending: bool,
+    },
+    CopySelection,
+    PasteSelection,
+    // paste special: only the values of the clipboard's cells (keeping the destination's
+    // styles), or only their formatting (keeping the destination's values). The coordinate is
+    // where the paste starts, unless it's part of the selection, which then gets filled.
+    PasteValues(Coordinate),
+    PasteFormats(Coordinate),
 
     Lookup(
         /* source: */ Coordinate,
         /* lookup_type: */ Lookup,
     ),
     MergeCells(),
+    // merge each row (or column) of the selection into a cell of its own
+    MergeAcross,
+    MergeDown,
+    UnmergeCells(Coordinate),
+    HideRow(Row),
+    ShowRow(Row),
+    HideColumn(Col),
+    ShowColumn(Col),
 
     ChangeDefaultNestedGrid((NonZeroU32, NonZeroU32)),
+    ChangeRecreateSize((NonZeroU32, NonZeroU32)),
+    ValidateGrammarMap(/* repair */ bool),
+    ExportHTML(Coordinate),
+    ChangeFormatRuleDraft((String, String, String)),
+    AddConditionalFormat(ConditionalFormat),
+    RemoveConditionalFormat(usize),
+
+    // Cell Styling
+    SetFontFamily(Coordinate, String),
+    SetFontSize(Coordinate, f64),
+    SetBackgroundColor(Coordinate, /* CSS color */ String),
+    SetTextAlign(Coordinate, /* left, center or right */ String),
+    // resets the style of the selection (or the active cell), but keeps merges & sizes
+    ClearFormatting,
+    // these flip the property on the selection (or the active cell)
+    ToggleBold,
+    ToggleItalic,
+    ToggleUnderline,
+    // wraps (or unwraps) the text of the selection, if it includes the cell, or just the cell
+    ToggleWrapText(Coordinate),
 
     SetCurrentDefinitionName(String),
 
+    // Search
+    SetSearchQuery(String),
+    SetSearchReplacement(String),
+    // finds (or replaces) the Search menu's `search_query` with its `search_replacement`
+    FindReplace {
+        replace_all: bool,
+    },
+
     // SetCurrentParentGrammar(Coordinate),
     ToggleLookup(Coordinate),
 
     AddDefinition(Coordinate, /* name */ String),
+    // removes a definition from the meta grid, so it isn't suggested anymore
+    RemoveDefinition(Coordinate),
+    AddDefnRule(Coordinate),
+    RemoveDefnRule(Coordinate, /* rule index */ usize),
 
     TogridleShiftKey(bool),
+    ToggleCellLockNavigation,
+    ToggleLock(Coordinate),
+    // shows (or hides) a chart of the values that a cell has had
+    ToggleSparkline(Coordinate),
+    // moves a slider (or flips a toggle, where any non-zero value means "on")
+    SetInteractiveValue(Coordinate, f64),
+    // Ctrl+click adds a cell to (or takes it out of) the selection, Escape clears those cells
+    ToggleSecondarySelection(Coordinate),
+    ClearSecondarySelections,
+    // folds a nested grid into a single summary cell, or unfolds it again
+    ToggleCollapse(Coordinate),
 
     // Alerts and stuff
     Alert(String),
@@ -214,8 +583,25 @@ pub enum Action {
     ShowContextMenu((f64, f64)),
     HideContextMenu,
 
-    ReadCSVFile(File, Coordinate),
-    LoadCSVFile(FileData, Coordinate),
+    ReadCSVFile(File, Coordinate, CsvOrientation),
+    // the u8 is the field delimiter, e.g. b'\t' for tab-separated files
+    ReadDelimitedFile(File, Coordinate, u8),
+    LoadCSVFile(FileData, Coordinate, CsvOrientation, u8),
+    // adds the rows of a CSV file below the grid at the coordinate, instead of replacing it
+    AppendCSVRows(File, Coordinate),
+    LoadAppendedCSVRows(FileData, Coordinate),
+    SetCsvOrientation(CsvOrientation),
+    ReadJSONFile(File, Coordinate),
+    LoadJSONFile(FileData, Coordinate),
+    // images are read in as data URIs, which need the file's MIME type
+    ReadImageFile(File, Coordinate, /* mime type */ String),
+    LoadImageFile(FileData, Coordinate, /* mime type */ String),
+    InsertImage(Coordinate, /* url */ String),
+    // turns an image cell back into an empty input
+    RemoveImage(Coordinate),
+    // every worksheet of an .xlsx file gets opened in a new tab
+    ReadXLSXFile(File),
+    LoadXLSXFile(FileData),
 
     RunPython(
         String,     /* TODO: pass in sheet as well */
@@ -232,16 +618,384 @@ impl Model {
         &mut self.sessions[self.current_session_index]
     }
 
-    // only use this if you need a COPY of the current session
-    // i.e. not changing its values
+    // the cell that RunPython writes the output of the code editor at `editor` into
+    pub fn editor_output(&self, editor: &Coordinate) -> Coordinate {
+        self.get_session()
+            .editor_outputs
+            .get(editor)
+            .cloned()
+            .unwrap_or(coord!("root-A1"))
+    }
+
+    // height of a row, which is the default row height if it hasn't been resized
+    pub fn row_height(&self, row: &Row) -> f64 {
+        *self.row_heights.get(row).unwrap_or(&self.default_row_height)
+    }
+
+    // starts reading `file` in the background, which counts as loading until `callback`'s
+    // action comes back (see `finishes_loading`)
+    fn read_file(&mut self, file: File, callback: Callback<FileData>) {
+        let task = self.reader.read_file(file, callback);
+        self.tasks.push(task);
+        self.loading_tasks += 1;
+    }
+
+    // adds the current value of the cell at `coord` to its history, if it's a number
+    fn record_history(&mut self, coord: &Coordinate) {
+        let value = self.get_session().grammars.get(coord).and_then(|g| numeric_value(&g.kind));
+        if let Some(value) = value {
+            push_history(&mut self.cell_history, coord, value);
+        }
+    }
+
+    // writes the current session to localStorage, so it can be recovered after a crash
+    fn autosave(&mut self) {
+        self.changes_since_autosave = 0;
+        let bytes = self.to_session().to_ise_bytes();
+        if bytes.len() > MAX_AUTOSAVE_BYTES {
+            self.update(Action::Alert(format! {
+                "session is too large to auto-save ({} KB, the limit is {} KB)",
+                bytes.len() / 1000, MAX_AUTOSAVE_BYTES / 1000
+            }));
+            return;
+        }
+        let key = match self.get_session().autosave_key.clone() {
+            Some(key) => key,
+            None => {
+                let storage = window().local_storage();
+                let keys: Vec<String> = (0..storage.len()).filter_map(|i| storage.key(i)).collect();
+                let key = unused_autosave_key(&keys);
+                self.get_session_mut().autosave_key = Some(key.clone());
+                key
+            }
+        };
+        let payload = String::from_utf8(bytes).expect("a serialized session should be UTF-8");
+        if window().local_storage().insert(&key, &payload).is_err() {
+            self.update(Action::Alert(
+                "could not auto-save the session, localStorage is full".to_string(),
+            ));
+        }
+    }
+
+    // JSON dump of the current session (for integration tests), cached between renders
+    pub fn serialized_session(&self) -> String {
+        self.serialized_session
+            .borrow_mut()
+            .get_or_insert_with(|| serde_json::to_string(self.get_session()).unwrap())
+            .clone()
+    }
+
+    pub fn theme(&self) -> Theme {
+        if self.get_session().dark_mode {
+            Theme::Dark
+        } else {
+            Theme::Light
+        }
+    }
+
+    // CSS for the cell at `coord`, reusing the one from the last render if the cell hasn't changed
+    pub fn cell_style(&self, coord: &Coordinate) -> String {
+        let session = self.get_session();
+        let style = self.style_cache.get(
+            session.grammars.get(coord).expect("no grammar with this coordinate"),
+            &self.col_widths,
+            &self.row_heights,
+            (self.default_col_width, self.default_row_height),
+            &session.conditional_formats,
+            self.theme(),
+            coord,
+        );
+        // the lookup marker isn't cached, since adding a lookup doesn't touch the source cell
+        match self.source_color(coord) {
+            Some(color) => format! {"{}box-shadow: inset 0 0 0 2px {};\n", style, color},
+            None => style,
+        }
+    }
+
+    // the color that the cell at `coord` is marked with, if a lookup shows its value
+    pub fn source_color(&self, coord: &Coordinate) -> Option<&'static str> {
+        if self.observers.get(coord).map_or(true, |lookups| lookups.is_empty()) {
+            return None;
+        }
+        lookup_color(&self.lookups, coord)
+    }
+
+    // the color of the lookup at `coord`, which is the color of the (first) cell it shows.
+    // Lookups that don't show a cell yet get a color of their own
+    pub fn lookup_marker_color(&self, coord: &Coordinate) -> String {
+        let grammars = &self.get_session().grammars;
+        let source_color = match grammars.get(coord).map(|g| &g.kind) {
+            Some(Kind::Lookup(_, Some(lookup))) => lookup_sources(grammars, lookup)
+                .first()
+                .and_then(|source| lookup_color(&self.lookups, source)),
+            _ => None,
+        };
+        source_color.map_or_else(|| coordinate_color(coord), |color| color.to_string())
+    }
+
+    // flags `coord` in `invalid_cells` if its value doesn't match its grammar's validator (and
+    // unflags it otherwise), returning whether the flag changed
+    fn validate_cell(&mut self, coord: Coordinate) -> bool {
+        let is_valid = match self.sessions[self.current_session_index].grammars.get(&coord) {
+            Some(Grammar {
+                kind: Kind::Input(value),
+                validator,
+                ..
+            }) => matches_validator(&mut self.validator_regexes, validator, value),
+            _ => true,
+        };
+        if is_valid {
+            self.invalid_cells.remove(&coord)
+        } else {
+            self.invalid_cells.insert(coord)
+        }
+    }
+
+    // recomputes `invalid_cells` for the whole session, after its cells were replaced
+    fn validate_all(&mut self) {
+        self.invalid_cells.clear();
+        let grammars = &self.sessions[self.current_session_index].grammars;
+        for (coord, g) in grammars {
+            if let Kind::Input(value) = &g.kind {
+                if !matches_validator(&mut self.validator_regexes, &g.validator, value) {
+                    self.invalid_cells.insert(coord.clone());
+                }
+            }
+        }
+    }
+
+    // rebuilds the observers & lookup colors from scratch, for when the cells were replaced
+    fn rebuild_observers(&mut self) {
+        let grammars = &self.get_session().grammars;
+        let (observers, lookups) = (build_observers(grammars), build_lookups(grammars));
+        self.observers = observers;
+        self.lookups = lookups;
+    }
+
+    // width of a column, which is the default column width if it hasn't been resized
+    pub fn col_width(&self, col: &Col) -> f64 {
+        *self.col_widths.get(col).unwrap_or(&self.default_col_width)
+    }
+
     pub fn to_session(&self) -> Session {
-        self.get_session().clone()
+        let mut session = self.get_session().clone();
+        session.active_cell = self.active_cell.clone();
+        session.first_select_cell = self.first_select_cell.clone();
+        session.last_select_cell = self.last_select_cell.clone();
+        session
+    }
+
+    // puts the active cell & selection back to where they were when the current session was
+    // saved, and focuses the active cell
+    fn restore_cursor(&mut self) {
+        let (active, first, last) = saved_cursor(self.get_session());
+        self.active_cell = Some(active.clone());
+        self.first_select_cell = first;
+        self.last_select_cell = last;
+        focus_on_cell(&active);
+    }
+
+    // pastes the values (or only the formats) of the clipboard into the selection if it includes
+    // `coord`, or from `coord` on otherwise
+    fn paste_special(&mut self, coord: Coordinate, values: bool) -> ShouldRender {
+        let clipboard = match self.clipboard.clone() {
+            Some(clipboard) => clipboard,
+            None => return false,
+        };
+        let bounds = match self.selection_bounds() {
+            Some(bounds) if self.selected_cells().contains(&coord) => bounds,
+            _ => match coord.parent() {
+                Some(parent) => {
+                    let (row, col) = coord.row_col();
+                    (parent, (row.get(), col.get()), (row.get(), col.get()))
+                }
+                None => return false,
+            },
+        };
+        let (parent, (top, left), (bottom, right)) = bounds;
+        let fill = Model::longest_common_grid_shape(
+            (clipboard.rows, clipboard.cols),
+            (bottom - top + 1, right - left + 1),
+        );
+        for row in top..(top + fill.0) {
+            for col in left..(left + fill.1) {
+                let cell = Coordinate::child_of(&parent, non_zero_u32_tuple((row, col)));
+                if is_locked(&self.get_session().grammars, &cell) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", cell.to_string()
+                    }));
+                }
+            }
+        }
+        paste_special(
+            &mut self.get_session_mut().grammars,
+            &clipboard,
+            &parent,
+            (top, left),
+            fill,
+            values,
+        );
+        // pasted values don't look anything up anymore
+        self.rebuild_observers();
+        true
+    }
+
+    // merges each row of the selection (or each column if `!across`) into a cell of its own
+    fn merge_selected_lines(&mut self, across: bool) -> ShouldRender {
+        let bounds = if self.last_select_cell.is_some() {
+            self.selection_bounds()
+        } else {
+            None
+        };
+        let (parent, (top, left), (bottom, right)) = match bounds {
+            Some(bounds) => bounds,
+            None => {
+                return self.update(Action::Alert(
+                    "Merging needs a selection of cells".to_string(),
+                ))
+            }
+        };
+        for row in top..=bottom {
+            for col in left..=right {
+                let cell = Coordinate::child_of(&parent, non_zero_u32_tuple((row, col)));
+                if is_locked(&self.get_session().grammars, &cell) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", cell.to_string()
+                    }));
+                }
+            }
+        }
+        merge_lines(
+            &mut self.get_session_mut().grammars,
+            &parent,
+            (top, left),
+            (bottom, right),
+            across,
+        );
+        true
     }
 
     fn load_session(&mut self, session: Session) {
-        self.get_session_mut().root = session.root;
-        self.get_session_mut().meta = session.meta;
-        self.get_session_mut().grammars = session.grammars;
+        replace_session_contents(self.get_session_mut(), session);
+        self.show_loaded_session();
+    }
+
+    // catches the model up with the current session after it was loaded
+    fn show_loaded_session(&mut self) {
+        self.frozen_rows = self.get_session().frozen_rows;
+        self.frozen_cols = self.get_session().frozen_cols;
+        self.default_col_width = self.get_session().default_col_width;
+        self.default_row_height = self.get_session().default_row_height;
+        self.rebuild_observers();
+        self.validate_all();
+        self.restore_cursor();
+    }
+
+    // rough estimate of how expensive a render is, which is the number of
+    // cells that get rendered from `view_root`
+    pub fn estimate_render_cost(&self) -> usize {
+        let session = self.get_session();
+        render_cost(&session.grammars, &session.collapsed, &self.view_root)
+    }
+
+    // returns the parent, top-left (row, col) and bottom-right (row, col) of the current
+    // selection, falling back to the active cell if no range is selected
+    pub fn selection_bounds(&self) -> Option<(Coordinate, (u32, u32), (u32, u32))> {
+        let (first, last) = match (self.first_select_cell.clone(), self.last_select_cell.clone()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => {
+                let active = self.active_cell.clone()?;
+                (active.clone(), active)
+            }
+        };
+        let parent = first.parent()?;
+        if last.parent() != Some(parent.clone()) {
+            return None;
+        }
+        let (first_row, first_col) = first.row_col();
+        let (last_row, last_col) = last.row_col();
+        Some((
+            parent,
+            (
+                first_row.get().min(last_row.get()),
+                first_col.get().min(last_col.get()),
+            ),
+            (
+                first_row.get().max(last_row.get()),
+                first_col.get().max(last_col.get()),
+            ),
+        ))
+    }
+
+    // returns the coordinates of every cell in the current selection (or just the
+    // active cell if no range is selected)
+    pub fn selected_cells(&self) -> Vec<Coordinate> {
+        let (parent, (top, left), (bottom, right)) = match self.selection_bounds() {
+            Some(bounds) => bounds,
+            None => return vec![],
+        };
+        let mut cells = Vec::new();
+        for row in top..=bottom {
+            for col in left..=right {
+                let cell = Coordinate::child_of(&parent, non_zero_u32_tuple((row, col)));
+                if self.get_session().grammars.contains_key(&cell) {
+                    cells.push(cell);
+                }
+            }
+        }
+        cells
+    }
+
+    // the active cell if it's a grid, or otherwise the grid that it's in
+    pub fn active_grid(&self) -> Option<Coordinate> {
+        let active = self.active_cell.clone()?;
+        if grid_size(&self.get_session().grammars, &active).is_some() {
+            Some(active)
+        } else {
+            active.parent()
+        }
+    }
+
+    // the selected range along with the Ctrl+clicked cells
+    pub fn all_selected_cells(&self) -> Vec<Coordinate> {
+        merge_selections(self.selected_cells(), &self.secondary_selections)
+    }
+
+    // the cells that formatting applies to: the selection, or the active cell if nothing's selected
+    pub fn format_targets(&self) -> Vec<Coordinate> {
+        let selected_cells = self.all_selected_cells();
+        if selected_cells.is_empty() {
+            self.active_cell.iter().cloned().collect()
+        } else {
+            selected_cells
+        }
+    }
+
+    // the cells a style set on `coord` applies to: the whole selection if `coord` is part of it,
+    // or just `coord` otherwise
+    fn style_targets(&self, coord: Coordinate) -> Vec<Coordinate> {
+        let selected_cells = self.all_selected_cells();
+        if selected_cells.contains(&coord) {
+            selected_cells
+        } else {
+            vec![coord]
+        }
+    }
+
+    // when pasting into a selection that's an integer multiple of the clipboard's shape,
+    // the clipboard gets tiled to fill the selection. Otherwise it's pasted once.
+    // Returns the (rows, cols) that the paste will fill.
+    pub fn longest_common_grid_shape(clipboard: (u32, u32), selection: (u32, u32)) -> (u32, u32) {
+        let (clip_rows, clip_cols) = clipboard;
+        let (sel_rows, sel_cols) = selection;
+        if clip_rows == 0 || clip_cols == 0 {
+            return clipboard;
+        }
+        if sel_rows % clip_rows == 0 && sel_cols % clip_cols == 0 {
+            selection
+        } else {
+            clipboard
+        }
     }
 
     fn query_parent(&self, coord_parent: Coordinate) -> Vec<Coordinate> {
@@ -260,125 +1014,727 @@ impl Model {
     }
 
     fn query_col(&self, coord_col: Col) -> Vec<Coordinate> {
-        self.get_session()
-            .grammars
-            .keys()
-            .clone()
-            .filter_map(|k| {
-                if k.row_cols.len() == 1
-                /* ignore root & meta */
-                {
-                    None
-                } else if k.full_col() == coord_col {
-                    Some(k.clone())
-                } else {
-                    None
-                }
-            })
-            .collect()
+        cells_in_col(&self.get_session().grammars, &coord_col)
     }
 
     // Gotta move
 
     fn query_row(&self, coord_row: Row) -> Vec<Coordinate> {
-        self.get_session()
-            .grammars
-            .keys()
-            .clone()
-            .filter_map(|k| {
-                if k.row_cols.len() == 1
-                /* ignore root & meta */
-                {
-                    None
-                } else if k.full_row() == coord_row {
-                    Some(k.clone())
-                } else {
-                    None
-                }
-            })
-            .collect()
+        cells_in_row(&self.get_session().grammars, &coord_row)
     }
 }
 
-impl Component for Model {
-    type Message = Action;
-    type Properties = ();
+// the (active cell, first selected cell, last selected cell) saved in a session. Saved cells that
+// are no longer in the session are dropped, with the active cell falling back to root-A1.
+fn saved_cursor(session: &Session) -> (Coordinate, Option<Coordinate>, Option<Coordinate>) {
+    let existing = |c: &Option<Coordinate>| c.clone().filter(|c| session.grammars.contains_key(c));
+    let active = existing(&session.active_cell).unwrap_or(coord!("root-A1"));
+    // a selection is only kept if both of its ends are
+    match (existing(&session.first_select_cell), existing(&session.last_select_cell)) {
+        (Some(first), Some(last)) => (active, Some(first), Some(last)),
+        _ => (active, None, None),
+    }
+}
 
-    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let root_grammar = Grammar {
-            name: "root".to_string(),
-            style: Style::default(),
-            kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),
-        };
-        let meta_grammar = Grammar {
-            name: "meta".to_string(),
-            style: Style::default(),
-            kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1)]),
-        };
-        let mut m = Model {
-            view_root: coord!("root"),
-            col_widths: hashmap! {
-               coord_col!("root","A") => 90.0,
-               coord_col!("root","B") => 90.0,
-               coord_col!("meta","A") => 180.0,
-               coord_col!("meta-A3","A") => 90.0,
-               coord_col!("meta-A3","B") => 180.0,
-            },
-            row_heights: hashmap! {
-               coord_row!("root","1") => 30.0,
-               coord_row!("root","2") => 30.0,
-               coord_row!("root","3") => 30.0,
-               coord_row!("meta","1") => 180.0,
-            },
-            active_cell: Some(coord!("root-A1")),
-            meta_suggestions: vec![
-                ("js_grammar".to_string(), coord!("meta-A1")),
-                ("java_grammar".to_string(), coord!("meta-A2")),
-                ("defn".to_string(), coord!("meta-A3")),
-            ],
+// moves the notes kept for cells, and which grids are collapsed, along with their cells when
+// rows or columns are moved around, dropping the ones `moved` returns None for because their
+// cell is gone
+fn move_cell_state<F>(session: &mut Session, moved: F)
+where
+    F: Fn(&Coordinate) -> Option<Coordinate>,
+{
+    rekey(&mut session.notes, &moved);
+    session.collapsed = session.collapsed.drain().filter_map(|c| moved(&c)).collect();
+}
 
-            console: ConsoleService::new(),
-            reader: ReaderService::new(),
+// where the item at `index` of a list ends up after the item at `from` is moved to `to`
+fn moved_index(index: usize, from: usize, to: usize) -> usize {
+    if index == from {
+        to
+    } else if from < index && index <= to {
+        index - 1
+    } else if to <= index && index < from {
+        index + 1
+    } else {
+        index
+    }
+}
 
-            first_select_cell: None,
-            last_select_cell: None,
+// the (top-left, bottom-right) cells of the grid at `grid`
+fn grid_corners(
+    grammars: &HashMap<Coordinate, Grammar>,
+    grid: &Coordinate,
+) -> Option<(Coordinate, Coordinate)> {
+    let (rows, cols) = grid_size(grammars, grid).filter(|(rows, cols)| *rows > 0 && *cols > 0)?;
+    Some((
+        Coordinate::child_of(grid, non_zero_u32_tuple((1, 1))),
+        Coordinate::child_of(grid, non_zero_u32_tuple((rows, cols))),
+    ))
+}
 
-            secondary_selections: HashSet::new(),
+// the selection that select-all makes from the active cell `cell`: the whole grid that the cell
+// is in, or if one of the grids it's in is already the `selection`, the whole grid around that one
+pub fn select_all_bounds(
+    grammars: &HashMap<Coordinate, Grammar>,
+    cell: &Coordinate,
+    selection: Option<(Coordinate, Coordinate)>,
+) -> Option<(Coordinate, Coordinate)> {
+    // the corners of the grids around the cell, from the innermost one out
+    let mut grids = vec![];
+    let mut grid = cell.parent();
+    while let Some(corners) = grid.as_ref().and_then(|g| grid_corners(grammars, g)) {
+        grids.push(corners);
+        grid = grid.and_then(|g| g.parent());
+    }
+    let level = match grids.iter().position(|corners| Some(corners) == selection.as_ref()) {
+        Some(selected) => (selected + 1).min(grids.len() - 1),
+        None => 0,
+    };
+    grids.into_iter().nth(level)
+}
 
-            min_select_cell: None,
-            max_select_cell: None,
-            zoom: 1.0,
+// where the active cell ends up when moving from `coord` with `navigation`. Rows & columns
+// are those of the grid that `coord` is in, and a page is `page_rows` rows.
+pub fn navigation_target(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    navigation: Navigation,
+    page_rows: u32,
+) -> Option<Coordinate> {
+    let parent = coord.parent()?;
+    let row = coord.row().get();
+    match navigation {
+        Navigation::RowStart => cells_in_row(grammars, &coord.full_row()).first().cloned(),
+        Navigation::RowEnd => cells_in_row(grammars, &coord.full_row()).last().cloned(),
+        Navigation::GridStart => {
+            Some(Coordinate::child_of(&parent, non_zero_u32_tuple((1, 1))))
+                .filter(|c| grammars.contains_key(c))
+        }
+        Navigation::GridEnd => {
+            let sub_coords = match grammars.get(&parent).map(|g| &g.kind) {
+                Some(Kind::Grid(sub_coords)) => sub_coords,
+                _ => return None,
+            };
+            let last_row = sub_coords.iter().map(|(r, _)| *r).max()?;
+            let last_col = sub_coords.iter().map(|(_, c)| *c).max()?;
+            let corner = Coordinate::child_of(&parent, (last_row, last_col));
+            if grammars.contains_key(&corner) {
+                return Some(corner);
+            }
+            // the corner can be missing (e.g. merged away), so go to the end of the last row
+            cells_in_row(grammars, &Row(parent, last_row)).last().cloned()
+        }
+        Navigation::PageUp | Navigation::PageDown => {
+            let cells = cells_in_col(grammars, &coord.full_col());
+            if navigation == Navigation::PageDown {
+                // the furthest cell down the column that's at most a page away
+                let target = row.saturating_add(page_rows);
+                cells.into_iter().filter(|c| c.row().get() <= target).last()
+            } else {
+                let target = row.saturating_sub(page_rows);
+                cells.into_iter().find(|c| c.row().get() >= target)
+            }
+        }
+    }
+}
 
-            sessions: vec![Session {
-                title: "my session".to_string(),
-                root: root_grammar.clone(),
-                meta: meta_grammar.clone(),
-                grammars: {
-                    let mut map = HashMap::new();
-                    build_grammar_map(
-                        &mut map,
-                        coord!("root"),
-                        grid![
-                            [
-                                g!(Grammar::input("", "A1")),
-                                g!(Grammar::input("", "B1")),
-                                g!(Grammar::input("", "C1"))
-                            ],
-                            [
-                                g!(Grammar::input("", "A2")),
-                                g!(Grammar::input("", "B2")),
-                                g!(Grammar::input("", "C2"))
-                            ],
-                            [
-                                g!(Grammar::input("", "A3")),
-                                g!(Grammar::input("", "B3")),
-                                g!(Grammar::input("", "C3")) // grid![
-                                                             //     [
-                                                             //         g!(Grammar::input("", "C3-A1")),
-                                                             //         g!(Grammar::input("", "C3-B1"))
-                                                             //     ],
-                                                             //     [
-                                                             //         g!(Grammar::input("", "C3-A2")),
+// coordinates of the cells in a column, sorted from top to bottom
+fn cells_in_col(grammars: &HashMap<Coordinate, Grammar>, coord_col: &Col) -> Vec<Coordinate> {
+    let mut cells: Vec<Coordinate> = grammars
+        .keys()
+        .filter(|k| {
+            k.row_cols.len() != 1 /* ignore root & meta */ && k.full_col() == *coord_col
+        })
+        .cloned()
+        .collect();
+    cells.sort_by_key(|c| c.row());
+    cells
+}
+
+// coordinates of the cells in a row, sorted from left to right
+fn cells_in_row(grammars: &HashMap<Coordinate, Grammar>, coord_row: &Row) -> Vec<Coordinate> {
+    let mut cells: Vec<Coordinate> = grammars
+        .keys()
+        .filter(|k| {
+            k.row_cols.len() != 1 /* ignore root & meta */ && k.full_row() == *coord_row
+        })
+        .cloned()
+        .collect();
+    cells.sort_by_key(|c| c.col());
+    cells
+}
+
+// hides (or shows) the row (or column if `!rows`) `index` of the grid at `parent`, where `cells`
+// are the cells in it. The grid remembers its hidden rows and columns so they collapse in its
+// template, and showing a line keeps the cells that are still covered by a merge or that are
+// in a hidden line the other way hidden.
+fn set_line_hidden(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    cells: &[Coordinate],
+    index: u32,
+    rows: bool,
+    hidden: bool,
+) {
+    let (hidden_rows, hidden_cols) = match grammars.get_mut(parent) {
+        Some(grid) => {
+            let lines = if rows {
+                &mut grid.style.hidden_rows
+            } else {
+                &mut grid.style.hidden_cols
+            };
+            lines.retain(|line| *line != index);
+            if hidden {
+                lines.push(index);
+            }
+            (grid.style.hidden_rows.clone(), grid.style.hidden_cols.clone())
+        }
+        None => return,
+    };
+    for cell in cells {
+        if let Some(g) = grammars.get_mut(cell) {
+            g.style.display = !hidden
+                && !covered_by_merge(cell, g)
+                && !hidden_rows.contains(&cell.row().get())
+                && !hidden_cols.contains(&cell.col().get());
+        }
+    }
+}
+
+// merging hides every cell of the merged region except the bottom-right one, which spans it
+fn covered_by_merge(coord: &Coordinate, grammar: &Grammar) -> bool {
+    let (row_span, col_span) = (grammar.style.row_span, grammar.style.col_span);
+    is_merged(grammar)
+        && ((row_span != (0, 0) && coord.row().get() != row_span.1)
+            || (col_span != (0, 0) && coord.col().get() != col_span.1))
+}
+
+// the kind an Input or Lookup cell becomes when it's toggled between the two. The text typed
+// into the cell carries over (without the "$" that's typed to start a lookup), so toggling
+// back and forth doesn't lose it.
+fn toggled_lookup_kind(kind: &Kind) -> Option<Kind> {
+    match kind {
+        Kind::Input(value) => Some(Kind::Lookup(
+            value.trim().trim_start_matches('$').trim_start().to_string(),
+            None,
+        )),
+        Kind::Lookup(value, _) => Some(Kind::Input(value.clone())),
+        _ => None,
+    }
+}
+
+// separator between the values collected by a row or column lookup
+const LOOKUP_SEPARATOR: &str = ", ";
+
+// number of values kept in a cell's history
+pub const HISTORY_LENGTH: usize = 50;
+
+// the value of an input or number cell, if it's a number
+fn numeric_value(kind: &Kind) -> Option<f64> {
+    match kind {
+        Kind::Input(value) => value.trim().parse::<f64>().ok().filter(|n| n.is_finite()),
+        Kind::Number(number, _) => Some(*number),
+        Kind::Interactive(_, Interactive::Slider(value, _, _)) => Some(*value),
+        _ => None,
+    }
+}
+
+// the interactive grammar with its value set to `value`, clamped to the slider's range
+fn with_interactive_value(interactive: &Interactive, value: f64) -> Interactive {
+    match interactive {
+        Interactive::Slider(_, min, max) => {
+            Interactive::Slider(value.max(*min).min(*max), *min, *max)
+        }
+        Interactive::Toggle(_) => Interactive::Toggle(value != 0.0),
+        Interactive::Button() => Interactive::Button(),
+    }
+}
+
+// steps a resize drag along with `msg`, for either a cell's corner (`cell`) or a row or column
+// header's border (`line`). The prospective (width, height) is kept in `preview`, and only gets
+// applied to `row_heights`/`col_widths` once the drag ends, with the grids the cell or line is in
+// growing along with it. Returns whether the preview moved.
+fn drag_resize(
+    cell: &mut Option<Coordinate>,
+    line: &mut Option<GridLine>,
+    preview: &mut Option<(f64, f64)>,
+    row_heights: &mut HashMap<Row, f64>,
+    col_widths: &mut HashMap<Col, f64>,
+    (default_width, default_height): (f64, f64),
+    msg: &ResizeMsg,
+) -> bool {
+    let height_of = |row_heights: &HashMap<Row, f64>, row: &Row| {
+        *row_heights.get(row).unwrap_or(&default_height)
+    };
+    let width_of = |col_widths: &HashMap<Col, f64>, col: &Col| {
+        *col_widths.get(col).unwrap_or(&default_width)
+    };
+    match msg {
+        ResizeMsg::Start(coord) => {
+            let width = width_of(col_widths, &coord.full_col());
+            let height = height_of(row_heights, &coord.full_row());
+            *preview = Some((width, height));
+            *cell = Some(coord.clone());
+            false
+        }
+        ResizeMsg::StartRow(row) => {
+            *preview = Some((0.0, height_of(row_heights, row)));
+            *line = Some(GridLine::Row(row.clone()));
+            false
+        }
+        ResizeMsg::StartCol(col) => {
+            *preview = Some((width_of(col_widths, col), 0.0));
+            *line = Some(GridLine::Col(col.clone()));
+            false
+        }
+        // a row's height doesn't change by dragging sideways, nor a column's width by dragging
+        // up or down
+        ResizeMsg::X(_, _) if line.as_ref().map_or(false, GridLine::is_row) => false,
+        ResizeMsg::Y(_, _) if line.as_ref().map_or(false, |l| !l.is_row()) => false,
+        ResizeMsg::X(offset_x, _) => match preview {
+            Some((width, _)) => {
+                *width += offset_x;
+                true
+            }
+            None => false,
+        },
+        ResizeMsg::Y(offset_y, _) => match preview {
+            Some((_, height)) => {
+                *height += offset_y;
+                true
+            }
+            None => false,
+        },
+        ResizeMsg::End => {
+            // only now does the previewed size get applied to the sheet
+            match (cell.clone(), line.take(), preview.take()) {
+                (Some(coord), _, Some((width, height))) => {
+                    let row_height_diff = height - height_of(row_heights, &coord.full_row());
+                    let col_width_diff = width - width_of(col_widths, &coord.full_col());
+                    grow_line_sizes(
+                        row_heights,
+                        col_widths,
+                        coord,
+                        row_height_diff,
+                        col_width_diff,
+                        true,
+                    );
+                }
+                (None, Some(GridLine::Row(row)), Some((_, height))) => {
+                    let diff = height - height_of(row_heights, &row);
+                    let grid = row.0.clone();
+                    row_heights.insert(row, height);
+                    // the grid (and the grids it's nested in) grow along with it
+                    grow_line_sizes(row_heights, col_widths, grid, diff, 0.0, false);
+                }
+                (None, Some(GridLine::Col(col)), Some((width, _))) => {
+                    let diff = width - width_of(col_widths, &col);
+                    let grid = col.0.clone();
+                    col_widths.insert(col, width);
+                    grow_line_sizes(row_heights, col_widths, grid, 0.0, diff, false);
+                }
+                _ => (),
+            }
+            *cell = None;
+            false
+        }
+    }
+}
+
+// a new session holding the cells of an imported worksheet. It keeps the meta grammars of
+// the `current` session, but none of its notes, labels or formats
+fn sheet_session(current: &Session, sheet: Sheet) -> Session {
+    let meta = coord!("meta");
+    let mut grammars: HashMap<Coordinate, Grammar> = current
+        .grammars
+        .iter()
+        .filter(|(c, _)| c.row_cols.starts_with(&meta.row_cols))
+        .map(|(c, g)| (c.clone(), g.clone()))
+        .collect();
+    let cells = &sheet.cells;
+    let (rows, cols) = (cells.len() as u32, cells[0].len() as u32);
+    build_grammar_map(
+        &mut grammars,
+        coord!("root"),
+        grid_entry(rows, cols, |row, col| {
+            g!(Grammar::input("", cells[row as usize - 1][col as usize - 1].as_str()))
+        }),
+    );
+    for (top_left, bottom_right) in sheet.merges.iter() {
+        merge_region(&mut grammars, &coord!("root"), *top_left, *bottom_right);
+    }
+    Session {
+        title: sheet.name.clone(),
+        root: grammars[&coord!("root")].clone(),
+        grammars,
+        column_labels: HashMap::new(),
+        frozen_rows: 0,
+        frozen_cols: 0,
+        notes: HashMap::new(),
+        conditional_formats: vec![],
+        active_cell: None,
+        first_select_cell: None,
+        last_select_cell: None,
+        collapsed: HashSet::new(),
+        editor_outputs: HashMap::new(),
+        autosave_key: None,
+        ..current.clone()
+    }
+}
+
+// copies what a loaded `session` holds over `target`, which keeps its own title and tab state
+fn replace_session_contents(target: &mut Session, session: Session) {
+    target.root = session.root;
+    target.meta = session.meta;
+    target.grammars = session.grammars;
+    target.frozen_rows = session.frozen_rows;
+    target.frozen_cols = session.frozen_cols;
+    target.default_col_width = session.default_col_width;
+    target.default_row_height = session.default_row_height;
+    target.notes = session.notes;
+    target.conditional_formats = session.conditional_formats;
+    target.collapsed = session.collapsed;
+    target.show_gridlines = session.show_gridlines;
+    target.gridline_color = session.gridline_color;
+    target.dark_mode = session.dark_mode;
+    target.editor_outputs = session.editor_outputs;
+    target.active_cell = session.active_cell;
+    target.first_select_cell = session.first_select_cell;
+    target.last_select_cell = session.last_select_cell;
+}
+
+// reads the .ise file in `bytes` into a new tab, or over the session at `index`, and returns the
+// index of the loaded session. A file that can't be read leaves `sessions` as they were.
+fn load_session_bytes(
+    sessions: &mut Vec<Session>,
+    index: usize,
+    bytes: &[u8],
+    new_tab: bool,
+) -> Result<usize, String> {
+    let session = Session::from_ise_bytes(bytes)?;
+    if new_tab {
+        sessions.push(session);
+        Ok(sessions.len() - 1)
+    } else {
+        replace_session_contents(&mut sessions[index], session);
+        Ok(index)
+    }
+}
+
+// appends `value` to the history of `coord`, dropping the oldest values past HISTORY_LENGTH.
+// A cell that's left without changing it doesn't add the same value again.
+fn push_history(history: &mut HashMap<Coordinate, Vec<f64>>, coord: &Coordinate, value: f64) {
+    let values = history.entry(coord.clone()).or_insert_with(Vec::new);
+    if values.last() == Some(&value) {
+        return;
+    }
+    values.push(value);
+    if values.len() > HISTORY_LENGTH {
+        values.drain(..values.len() - HISTORY_LENGTH);
+    }
+}
+
+pub const ACTION_LOG_LENGTH: usize = 500;
+// logged actions are cut off after this many characters, since some of them carry whole files
+pub const MAX_LOGGED_ACTION_LENGTH: usize = 200;
+
+// appends `entry` to the action log, dropping the oldest entries past ACTION_LOG_LENGTH
+fn push_log(log: &mut Vec<(f64, String)>, timestamp: f64, entry: String) {
+    let entry = if entry.chars().count() > MAX_LOGGED_ACTION_LENGTH {
+        format! {"{}...", entry.chars().take(MAX_LOGGED_ACTION_LENGTH).collect::<String>()}
+    } else {
+        entry
+    };
+    log.push((timestamp, entry));
+    if log.len() > ACTION_LOG_LENGTH {
+        log.remove(0);
+    }
+}
+
+// forgets the history of cells that aren't input or number cells anymore
+fn retain_history(
+    history: &mut HashMap<Coordinate, Vec<f64>>,
+    grammars: &HashMap<Coordinate, Grammar>,
+) {
+    history.retain(|coord, _| match grammars.get(coord).map(|g| &g.kind) {
+        Some(Kind::Input(_)) | Some(Kind::Number(_, _)) => true,
+        _ => false,
+    });
+}
+
+// joins the values of the input cells among `cells`, which is what a row or column lookup shows
+fn join_input_values(grammars: &HashMap<Coordinate, Grammar>, cells: &[Coordinate]) -> String {
+    cells
+        .iter()
+        .filter_map(|c| match grammars.get(c).map(|g| &g.kind) {
+            Some(Kind::Input(value)) => Some(formula_result(grammars, c, value)),
+            Some(Kind::Number(n, _)) => Some(n.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<String>>()
+        .join(LOOKUP_SEPARATOR)
+}
+
+// coordinates of the cells whose values a lookup displays, i.e. the cells it observes
+fn lookup_sources(grammars: &HashMap<Coordinate, Grammar>, lookup: &Lookup) -> Vec<Coordinate> {
+    match lookup {
+        Lookup::Cell(source) => vec![source.clone()],
+        Lookup::Row(row) => cells_in_row(grammars, row),
+        Lookup::Col(col) => cells_in_col(grammars, col),
+        Lookup::Range { parent, start, end } => {
+            let mut cells: Vec<Coordinate> = grammars
+                .keys()
+                .filter(|c| c.parent().as_ref() == Some(parent))
+                .filter(|c| {
+                    let (row, col) = c.row_col();
+                    start.0 <= row && row <= end.0 && start.1 <= col && col <= end.1
+                })
+                .cloned()
+                .collect();
+            // row by row, left to right
+            cells.sort_by_key(|c| c.row_col());
+            cells
+        }
+    }
+}
+
+// the value a lookup displays. Cell lookups also show the value of other lookups, so that
+// lookups can be chained.
+fn evaluate_lookup(grammars: &HashMap<Coordinate, Grammar>, lookup: &Lookup) -> String {
+    match lookup {
+        Lookup::Cell(source) => match grammars.get(source).map(|g| &g.kind) {
+            // formulas are looked up as their result
+            Some(Kind::Input(value)) => formula_result(grammars, source, value),
+            Some(Kind::Lookup(value, _)) => value.clone(),
+            // formatted numbers are looked up as the plain number, so they can be calculated with
+            Some(Kind::Number(n, _)) => n.to_string(),
+            // lookups of sliders and toggles act as their bound output cells
+            Some(Kind::Interactive(_, Interactive::Slider(value, _, _))) => value.to_string(),
+            Some(Kind::Interactive(_, Interactive::Toggle(checked))) => checked.to_string(),
+            _ => String::new(),
+        },
+        lookup => join_input_values(grammars, &lookup_sources(grammars, lookup)),
+    }
+}
+
+// registers `observer` as a dependent of each of the `sources`, replacing whatever it was
+// observing before
+fn observe(
+    observers: &mut HashMap<Coordinate, Vec<Coordinate>>,
+    observer: &Coordinate,
+    sources: Vec<Coordinate>,
+) {
+    for dependents in observers.values_mut() {
+        dependents.retain(|c| c != observer);
+    }
+    observers.retain(|_, dependents| !dependents.is_empty());
+    for source in sources {
+        observers.entry(source).or_insert_with(Vec::new).push(observer.clone());
+    }
+}
+
+// colors that lookups and the cells they show get marked with, in the order their sources were
+// looked up. Past the end of the list colors get reused
+pub const LOOKUP_COLORS: [&str; 8] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#9a6324",
+];
+
+fn lookup_color(lookups: &[Coordinate], source: &Coordinate) -> Option<&'static str> {
+    let index = lookups.iter().position(|c| c == source)?;
+    Some(LOOKUP_COLORS[index % LOOKUP_COLORS.len()])
+}
+
+// adds the `sources` that aren't in `lookups` yet to the end of it
+fn register_lookup_sources(lookups: &mut Vec<Coordinate>, sources: &[Coordinate]) {
+    for source in sources {
+        if !lookups.contains(source) {
+            lookups.push(source.clone());
+        }
+    }
+}
+
+// lists the sources of every lookup in `grammars`, for when a session gets loaded. Lookups are
+// gone through top to bottom, so a session's colors are the same every time it's loaded
+fn build_lookups(grammars: &HashMap<Coordinate, Grammar>) -> Vec<Coordinate> {
+    let mut lookup_cells: Vec<(&Coordinate, &Lookup)> = grammars
+        .iter()
+        .filter_map(|(c, g)| match &g.kind {
+            Kind::Lookup(_, Some(lookup)) => Some((c, lookup)),
+            _ => None,
+        })
+        .collect();
+    lookup_cells.sort_by(|(a, _), (b, _)| a.row_cols.cmp(&b.row_cols));
+    let mut lookups = Vec::new();
+    for (_, lookup) in lookup_cells {
+        register_lookup_sources(&mut lookups, &lookup_sources(grammars, lookup));
+    }
+    lookups
+}
+
+// builds the observers of every lookup and formula in `grammars`, for when a session gets loaded
+fn build_observers(grammars: &HashMap<Coordinate, Grammar>) -> HashMap<Coordinate, Vec<Coordinate>> {
+    let mut observers = HashMap::new();
+    for (coord, grammar) in grammars.iter() {
+        match &grammar.kind {
+            Kind::Lookup(_, Some(lookup)) => {
+                observe(&mut observers, coord, lookup_sources(grammars, lookup));
+            }
+            Kind::Input(value) if is_formula(value) => {
+                observe(&mut observers, coord, formula_sources(value, coord));
+            }
+            _ => (),
+        }
+    }
+    observers
+}
+
+// the cells outside of the definition at `defn` that refer to something inside of it, through a
+// definition binding, a lookup or a formula. Removing the definition would leave them dangling.
+fn definition_dependents(
+    grammars: &HashMap<Coordinate, Grammar>,
+    defn: &Coordinate,
+) -> Vec<Coordinate> {
+    let inside = |c: &Coordinate| c.row_cols.starts_with(&defn.row_cols);
+    let mut dependents: Vec<Coordinate> = grammars
+        .iter()
+        .filter(|(coord, grammar)| {
+            let references = match &grammar.kind {
+                Kind::Defn(_, defn_coord, rules) => std::iter::once(defn_coord.clone())
+                    .chain(rules.iter().map(|(_, rule_coord)| rule_coord.clone()))
+                    .collect(),
+                Kind::Lookup(_, Some(lookup)) => lookup_sources(grammars, lookup),
+                Kind::Input(value) => formula_sources(value, coord),
+                _ => vec![],
+            };
+            !inside(coord) && references.iter().any(inside)
+        })
+        .map(|(coord, _)| coord.clone())
+        .collect();
+    dependents.sort_by(|a, b| a.row_cols.cmp(&b.row_cols));
+    dependents
+}
+
+// re-evaluates the lookups and formulas that depend on `changed` (and the ones that depend on
+// those), returning the coordinates of the cells that were updated. `visited` guards against
+// lookups that refer to each other in a cycle.
+fn propagate_change(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    observers: &HashMap<Coordinate, Vec<Coordinate>>,
+    changed: &Coordinate,
+) -> Vec<Coordinate> {
+    let mut visited: HashSet<Coordinate> = HashSet::new();
+    visited.insert(changed.clone());
+    let mut to_visit = vec![changed.clone()];
+    let mut updated = Vec::new();
+    while let Some(source) = to_visit.pop() {
+        for observer in observers.get(&source).cloned().unwrap_or_default() {
+            if !visited.insert(observer.clone()) {
+                continue;
+            }
+            let lookup = match grammars.get(&observer).map(|g| &g.kind) {
+                Some(Kind::Lookup(_, Some(lookup))) => Some(lookup.clone()),
+                // formulas are worked out whenever they're shown, so they only need re-rendering
+                Some(Kind::Input(value)) if is_formula(value) => None,
+                _ => continue,
+            };
+            if let Some(lookup) = lookup {
+                let value = evaluate_lookup(grammars, &lookup);
+                if let Some(g) = grammars.get_mut(&observer) {
+                    g.kind = Kind::Lookup(value, Some(lookup));
+                }
+            }
+            updated.push(observer.clone());
+            to_visit.push(observer);
+        }
+    }
+    updated
+}
+
+impl Component for Model {
+    type Message = Action;
+    type Properties = ();
+
+    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let root_grammar = Grammar {
+            name: "root".to_string(),
+            style: Style::default(),
+            kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),
+            validator: None,
+        };
+        let meta_grammar = Grammar {
+            name: "meta".to_string(),
+            style: Style::default(),
+            kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1)]),
+            validator: None,
+        };
+        let mut m = Model {
+            view_root: coord!("root"),
+            col_widths: hashmap! {
+               coord_col!("root","A") => DEFAULT_COL_WIDTH,
+               coord_col!("root","B") => DEFAULT_COL_WIDTH,
+               coord_col!("meta","A") => 180.0,
+               coord_col!("meta-A3","A") => DEFAULT_COL_WIDTH,
+               coord_col!("meta-A3","B") => 180.0,
+            },
+            row_heights: hashmap! {
+               coord_row!("root","1") => DEFAULT_ROW_HEIGHT,
+               coord_row!("root","2") => DEFAULT_ROW_HEIGHT,
+               coord_row!("root","3") => DEFAULT_ROW_HEIGHT,
+               coord_row!("meta","1") => 180.0,
+            },
+            active_cell: Some(coord!("root-A1")),
+            meta_suggestions: vec![
+                ("js_grammar".to_string(), coord!("meta-A1")),
+                ("java_grammar".to_string(), coord!("meta-A2")),
+                ("defn".to_string(), coord!("meta-A3")),
+            ],
+            forced_suggestions: None,
+            dismissed_suggestions: None,
+            lite_mode: false,
+
+            console: ConsoleService::new(),
+            reader: ReaderService::new(),
+
+            first_select_cell: None,
+            last_select_cell: None,
+
+            secondary_selections: HashSet::new(),
+
+            min_select_cell: None,
+            max_select_cell: None,
+            zoom: 1.0,
+            scroll_offset: (0.0, 0.0),
+
+            sessions: vec![Session {
+                title: "my session".to_string(),
+                root: root_grammar.clone(),
+                meta: meta_grammar.clone(),
+                grammars: {
+                    let mut map = HashMap::new();
+                    build_grammar_map(
+                        &mut map,
+                        coord!("root"),
+                        grid![
+                            [
+                                g!(Grammar::input("", "A1")),
+                                g!(Grammar::input("", "B1")),
+                                g!(Grammar::input("", "C1"))
+                            ],
+                            [
+                                g!(Grammar::input("", "A2")),
+                                g!(Grammar::input("", "B2")),
+                                g!(Grammar::input("", "C2"))
+                            ],
+                            [
+                                g!(Grammar::input("", "A3")),
+                                g!(Grammar::input("", "B3")),
+                                g!(Grammar::input("", "C3")) // grid![
+                                                             //     [
+                                                             //         g!(Grammar::input("", "C3-A1")),
+                                                             //         g!(Grammar::input("", "C3-B1"))
+                                                             //     ],
+                                                             //     [
+                                                             //         g!(Grammar::input("", "C3-A2")),
                                                              //         g!(Grammar::input("", "C3-B2"))
                                                              //     ]
                                                              // ]
@@ -387,10 +1743,30 @@ impl Component for Model {
                     );
                     map
                 },
+                auto_complete: true,
+                column_labels: HashMap::new(),
+                version: SESSION_VERSION,
+                frozen_rows: 0,
+                frozen_cols: 0,
+                default_col_width: DEFAULT_COL_WIDTH,
+                default_row_height: DEFAULT_ROW_HEIGHT,
+                notes: HashMap::new(),
+                conditional_formats: vec![],
+                active_cell: None,
+                first_select_cell: None,
+                last_select_cell: None,
+                collapsed: HashSet::new(),
+                show_gridlines: true,
+                gridline_color: DEFAULT_GRIDLINE_COLOR.to_string(),
+                dark_mode: false,
+                editor_outputs: HashMap::new(),
+                autosave_key: None,
             }],
 
             current_session_index: 0,
 
+            saved_sessions: session_file_names(read_sessions_directory()),
+
             side_menus: vec![
                 SideMenu {
                     name: "Home".to_string(),
@@ -404,6 +1780,10 @@ impl Component for Model {
                     name: "Settings".to_string(),
                     icon_path: "assets/settings_icon.png".to_string(),
                 },
+                SideMenu {
+                    name: "Search".to_string(),
+                    icon_path: "assets/search_icon.svg".to_string(),
+                },
                 SideMenu {
                     name: "Info".to_string(),
                     icon_path: "assets/info_icon.png".to_string(),
@@ -412,24 +1792,74 @@ impl Component for Model {
             open_side_menu: None,
 
             resizing: None,
+            resizing_line: None,
+            resize_preview: None,
+            resize_label_position: (0.0, 0.0),
+            style_cache: StyleCache::default(),
+            serialized_session: RefCell::new(None),
+            filling: None,
+            note_editor: None,
+            goto_open: false,
+            goto_node_ref: NodeRef::default(),
+            help_open: false,
+            show_coords: false,
+            markdown_editor: None,
+            header_editor: None,
+            frozen_rows: 0,
+            frozen_cols: 0,
+            default_col_width: DEFAULT_COL_WIDTH,
+            default_row_height: DEFAULT_ROW_HEIGHT,
 
             link,
             tasks: vec![],
+            loading_tasks: 0,
+
+            autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+            changes_since_autosave: 0,
+            recovered_session: read_autosave(),
+
+            action_log: vec![],
+            log_actions: false,
 
             focus_node_ref: NodeRef::default(),
             next_focus_node_ref: NodeRef::default(),
 
             shift_key_pressed: false,
+            tab_skips_locked: false,
 
             default_nested_row_cols: non_zero_u32_tuple((3, 3)),
+            insert_count: 1,
+            recreate_row_cols: non_zero_u32_tuple((3, 3)),
+            format_rule_draft: (
+                PREDICATE_NAMES[0].to_string(),
+                String::new(),
+                "#ffcccc".to_string(),
+            ),
 
             context_menu_position: None,
 
             default_definition_name: "".to_string(),
 
+            search_query: "".to_string(),
+            search_replacement: "".to_string(),
+
+            csv_orientation: CsvOrientation::RowMajor,
+
+            clipboard: None,
+
+            current_cursor_position: 0,
+
             mouse_cursor: CursorType::Default,
 
             lookups: vec![],
+
+            invalid_cells: HashSet::new(),
+            validator_regexes: HashMap::new(),
+            python_errors: HashSet::new(),
+            cell_history: HashMap::new(),
+            sparklines: HashSet::new(),
+
+            observers: HashMap::new(),
         };
         // load suggestions from
         m.meta_suggestions = m
@@ -449,6 +1879,23 @@ impl Component for Model {
     // The update function is split into sub-update functions that
     // are specifc to each EventType
     fn update(&mut self, event_type: Self::Message) -> ShouldRender {
+        if !keeps_cell_styles(&event_type) {
+            self.style_cache.clear();
+        }
+        let changes_session = !keeps_session(&event_type);
+        if changes_session {
+            *self.serialized_session.get_mut() = None;
+        }
+        if finishes_loading(&event_type) {
+            self.loading_tasks = self.loading_tasks.saturating_sub(1);
+        }
+        if self.log_actions {
+            match event_type {
+                // every keypress dispatches a Noop, which would drown out everything else
+                Action::Noop => (),
+                _ => push_log(&mut self.action_log, Date::now(), format! {"{:?}", event_type}),
+            }
+        }
         let should_render = match event_type {
             Action::Noop => false,
 
@@ -459,13 +1906,29 @@ impl Component for Model {
             }
 
             Action::ChangeInput(coord, new_value) => {
+                if is_locked(&self.get_session().grammars, &coord) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coord.to_string()
+                    }));
+                }
+                self.current_cursor_position = caret_position();
+                self.python_errors.remove(&coord);
+                // only this cell (and the lookups showing it) need their style recomputed
+                self.style_cache.invalidate(&coord);
+                if self.dismissed_suggestions.as_ref() == Some(&coord) {
+                    self.dismissed_suggestions = None;
+                }
                 if let Some(g) = self.get_session_mut().grammars.get_mut(&coord) {
                     match g {
                         Grammar {
                             kind: Kind::Input(_),
                             ..
+                        }
+                        | Grammar {
+                            kind: Kind::Number(_, _),
+                            ..
                         } => {
-                            g.kind = Kind::Input(new_value);
+                            g.kind = Kind::from_input(new_value, g.style.number_format);
                         }
                         Grammar {
                             kind: Kind::Lookup(_, lookup_type),
@@ -473,18 +1936,120 @@ impl Component for Model {
                         } => {
                             g.kind = Kind::Lookup(new_value, lookup_type.clone());
                         }
+                        Grammar {
+                            kind: Kind::Markdown(_),
+                            ..
+                        } => {
+                            g.kind = Kind::Markdown(new_value);
+                        }
                         _ => (),
                     }
                 }
+                // a formula depends on the cells it refers to (and other inputs on nothing), so
+                // it gets refreshed along with them
+                let sources = match self.get_session().grammars.get(&coord).map(|g| &g.kind) {
+                    Some(Kind::Input(value)) => Some(formula_sources(value, &coord)),
+                    _ => None,
+                };
+                if let Some(sources) = sources {
+                    observe(&mut self.observers, &coord, sources);
+                }
+                // refresh the lookups and formulas that show this cell's value
+                let observers = &self.observers;
+                let updated = propagate_change(
+                    &mut self.sessions[self.current_session_index].grammars,
+                    observers,
+                    &coord,
+                );
+                for updated_coord in &updated {
+                    self.style_cache.invalidate(updated_coord);
+                }
+                // only re-render when something else changes, so typing isn't interrupted
+                self.validate_cell(coord) || !updated.is_empty()
+            }
+
+            Action::CommitInput(coord) => {
+                self.record_history(&coord);
+                self.sparklines.contains(&coord)
+            }
+
+            Action::SetValidator(coord, validator) => {
+                match self.get_session_mut().grammars.get_mut(&coord) {
+                    Some(g) => g.validator = validator,
+                    None => return false,
+                }
+                self.validate_cell(coord);
+                true
+            }
+
+            Action::SetNumberFormat(coord, number_format) => {
+                if let Some(g) = self.get_session_mut().grammars.get_mut(&coord) {
+                    g.style.number_format = number_format;
+                    // re-read the cell's value with the new format
+                    match g.kind.clone() {
+                        Kind::Input(value) => g.kind = Kind::from_input(value, number_format),
+                        Kind::Number(value, _) => {
+                            g.kind = Kind::from_input(value.to_string(), number_format)
+                        }
+                        _ => (),
+                    }
+                    return true;
+                }
                 false
             }
 
+            Action::SetPrecision(coord, precision) => {
+                match self.get_session_mut().grammars.get_mut(&coord) {
+                    Some(g) => {
+                        g.style.display_precision = precision;
+                        true
+                    }
+                    None => false,
+                }
+            }
+
+            Action::Navigate(coord, navigation) => {
+                // a page is as many rows (of the default height) as fit in the window
+                let page_rows = (window().inner_height() as f64 / self.default_row_height) as u32;
+                let target = navigation_target(
+                    &self.get_session().grammars,
+                    &coord,
+                    navigation,
+                    page_rows.max(1),
+                );
+                match target {
+                    Some(target) if target != coord => self.update(Action::SetActiveCell(target)),
+                    _ => false,
+                }
+            }
+
             Action::SetActiveCell(coord) => {
+                if self.forced_suggestions.as_ref() != Some(&coord) {
+                    self.forced_suggestions = None;
+                }
                 self.active_cell = Some(coord.clone());
                 focus_on_cell(&coord);
+                self.update(Action::ScrollToCell(coord));
                 true
             }
 
+            Action::Scroll(x, y) => {
+                // scrolling doesn't change what's rendered
+                self.scroll_offset = (x, y);
+                false
+            }
+
+            Action::ScrollToCell(coord) => {
+                if let (Some(cell), Some(view)) = (dom_rect(&coord), dom_scroll_container()) {
+                    let offset = scroll_into_view(cell, view, self.scroll_offset);
+                    if offset != self.scroll_offset {
+                        dom_scroll_to(offset);
+                        self.scroll_offset = offset;
+                    }
+                }
+                false
+            }
+
             Action::NextSuggestion(coord, index) => {
                 let next_suggestion_id =
                     format! {"cell-{}-suggestion-{}", coord.to_string(), index};
@@ -499,39 +2064,102 @@ impl Component for Model {
                 true
             }
 
-            Action::ReadCSVFile(file, coord) => {
-                let upload_callback = self.link.callback(move |file_data: FileData| {
-                    Action::LoadCSVFile(file_data.clone(), coord.clone())
-                });
-                let task = self.reader.read_file(file, upload_callback.clone());
-                self.tasks.push(task);
-                false
+            Action::ForceSuggestions(coord) => {
+                if self.dismissed_suggestions.as_ref() == Some(&coord) {
+                    self.dismissed_suggestions = None;
+                }
+                self.forced_suggestions = Some(coord);
+                true
+            }
+
+            Action::DismissSuggestions(coord) => {
+                if self.forced_suggestions.as_ref() == Some(&coord) {
+                    self.forced_suggestions = None;
+                }
+                self.dismissed_suggestions = Some(coord.clone());
+                // focus goes back from the dropdown to the cell
+                focus_on_cell(&coord);
+                true
+            }
+
+            Action::ToggleAutoComplete => {
+                let session = self.get_session_mut();
+                session.auto_complete = !session.auto_complete;
+                true
+            }
+
+            Action::ToggleGridlines => {
+                let session = self.get_session_mut();
+                session.show_gridlines = !session.show_gridlines;
+                true
+            }
+
+            Action::ToggleDarkMode => {
+                let session = self.get_session_mut();
+                session.dark_mode = !session.dark_mode;
+                true
+            }
+
+            Action::SetGridlineColor(color) => {
+                self.get_session_mut().gridline_color = color;
+                true
             }
 
-            Action::LoadCSVFile(file_data, coordinate) => {
-                let csv = std::str::from_utf8(&file_data.content).unwrap().to_string();
-                let mut reader = csv::Reader::from_reader(csv.as_bytes());
-                let mut grid: Vec<Vec<String>> = Vec::new();
-                let headers_csv = reader.headers().unwrap();
-                let mut header_row: Vec<String> = Vec::new();
-                let len_header = headers_csv.len() as i32;
+            Action::SetAutosaveInterval(interval) => {
+                self.autosave_interval = interval;
+                true
+            }
 
-                for header in 0..len_header {
-                    let header_usize = header as usize;
-                    header_row.push(headers_csv.get(header_usize).unwrap().to_string());
+            Action::RestoreAutosave => {
+                if let Some(session) = self.recovered_session.take() {
+                    // the restored session keeps being auto-saved where it was recovered from
+                    self.get_session_mut().title = session.title.clone();
+                    self.get_session_mut().autosave_key = session.autosave_key.clone();
+                    self.load_session(session);
                 }
-                grid.push(header_row);
+                true
+            }
 
-                for row in reader.records() {
-                    let mut grid_row = Vec::new();
-                    let row = row.unwrap();
-                    let lenght_r = row.len() as i32;
-                    for cell in 0..lenght_r {
-                        let cell_usize = cell as usize;
-                        grid_row.push(row.get(cell_usize).unwrap().to_string());
-                    }
-                    grid.push(grid_row);
+            Action::DiscardAutosave => {
+                let key = self.recovered_session.take().and_then(|s| s.autosave_key);
+                if let Some(key) = key {
+                    window().local_storage().remove(&key);
                 }
+                true
+            }
+
+            Action::ReadCSVFile(file, coord, orientation) => {
+                let upload_callback = self.link.callback(move |file_data: FileData| {
+                    Action::LoadCSVFile(file_data.clone(), coord.clone(), orientation, b',')
+                });
+                self.read_file(file, upload_callback);
+                true
+            }
+
+            Action::ReadDelimitedFile(file, coord, delimiter) => {
+                let orientation = self.csv_orientation;
+                let upload_callback = self.link.callback(move |file_data: FileData| {
+                    Action::LoadCSVFile(file_data.clone(), coord.clone(), orientation, delimiter)
+                });
+                self.read_file(file, upload_callback);
+                true
+            }
+
+            Action::LoadCSVFile(file_data, coordinate, orientation, delimiter) => {
+                if contains_locked(&self.get_session().grammars, &coordinate) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coordinate.to_string()
+                    }));
+                }
+                let grid = match delimited_file_grid(
+                    &file_data.name,
+                    &file_data.content,
+                    delimiter,
+                    orientation,
+                ) {
+                    Ok(grid) => grid,
+                    Err(message) => return self.update(Action::Alert(message)),
+                };
                 let num_rows = grid.len();
                 let num_cols = grid[0].len();
 
@@ -545,6 +2173,7 @@ impl Component for Model {
                     kind: Kind::Grid(sub_coords),
                     name,
                     style,
+                    validator,
                 }) = self.get_session().grammars.get(&parent)
                 {
                     let mut grammar = self.get_session().grammars.clone();
@@ -556,39 +2185,260 @@ impl Component for Model {
                         grammar.remove(&c);
                         grammar.insert(c, Grammar::input("", grid_));
                     }
-                    self.get_session_mut().grammars = grammar;
+                    self.get_session_mut().grammars = grammar;
+                }
+
+                true
+            }
+
+            Action::AppendCSVRows(file, coord) => {
+                let callback = self.link.callback(move |file_data: FileData| {
+                    Action::LoadAppendedCSVRows(file_data, coord.clone())
+                });
+                self.read_file(file, callback);
+                true
+            }
+
+            Action::LoadAppendedCSVRows(file_data, coord) => {
+                if contains_locked(&self.get_session().grammars, &coord) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coord.to_string()
+                    }));
+                }
+                let rows = delimited_file_grid(
+                    &file_data.name,
+                    &file_data.content,
+                    delimiter_for_file_name(&file_data.name),
+                    self.csv_orientation,
+                )
+                .and_then(|rows| {
+                    append_grid_rows(&mut self.get_session_mut().grammars, &coord, &rows)
+                });
+                match rows {
+                    Ok(_) => {
+                        self.rebuild_observers();
+                        true
+                    }
+                    Err(message) => self.update(Action::Alert(message)),
+                }
+            }
+
+            Action::InsertTemplate(coord, template) => {
+                if contains_locked(&self.get_session().grammars, &coord) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coord.to_string()
+                    }));
+                }
+                // whatever was nested in the cell before gets replaced by the template
+                self.get_session_mut()
+                    .grammars
+                    .retain(|c, _| *c == coord || !c.row_cols.starts_with(&coord.row_cols));
+                self.active_cell = Some(coord.clone());
+                self.update(Action::AddNestedGrid(coord.clone(), template.size()));
+
+                // the nested cells keep the sizes AddNestedGrid gave them
+                let grammars = &mut self.get_session_mut().grammars;
+                for (c, mut grammar) in template_grammars(template, &coord) {
+                    if c == coord {
+                        continue;
+                    }
+                    if let Some(sized) = grammars.get(&c) {
+                        grammar.style.width = sized.style.width;
+                        grammar.style.height = sized.style.height;
+                    }
+                    grammars.insert(c, grammar);
+                }
+                true
+            }
+
+            Action::SetCsvOrientation(orientation) => {
+                self.csv_orientation = orientation;
+                true
+            }
+
+            Action::ReadJSONFile(file, coord) => {
+                let upload_callback = self.link.callback(move |file_data: FileData| {
+                    Action::LoadJSONFile(file_data.clone(), coord.clone())
+                });
+                self.read_file(file, upload_callback);
+                true
+            }
+
+            Action::ReadImageFile(file, coord, mime) => {
+                let upload_callback = self.link.callback(move |file_data: FileData| {
+                    Action::LoadImageFile(file_data, coord.clone(), mime.clone())
+                });
+                self.read_file(file, upload_callback);
+                true
+            }
+
+            Action::LoadImageFile(file_data, coord, mime) => {
+                self.update(Action::InsertImage(coord, data_uri(&mime, &file_data.content)))
+            }
+
+            Action::ReadXLSXFile(file) => {
+                let callback = self.link.callback(Action::LoadXLSXFile);
+                self.read_file(file, callback);
+                true
+            }
+
+            Action::LoadXLSXFile(file_data) => {
+                let sheets = match parse_xlsx(&file_data.content) {
+                    Ok(sheets) => sheets,
+                    Err(message) => return self.update(Action::Alert(message)),
+                };
+                for sheet in sheets {
+                    let session = sheet_session(&self.to_session(), sheet);
+                    self.sessions.push(session);
+                }
+                self.current_session_index = self.sessions.len() - 1;
+                self.show_loaded_session();
+                true
+            }
+
+            Action::InsertImage(coord, url) => {
+                if is_locked(&self.get_session().grammars, &coord) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coord.to_string()
+                    }));
+                }
+                match self.get_session_mut().grammars.get_mut(&coord) {
+                    Some(g) => g.kind = Kind::Image(url),
+                    None => return false,
+                }
+                true
+            }
+
+            Action::RemoveImage(coord) => {
+                if is_locked(&self.get_session().grammars, &coord) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coord.to_string()
+                    }));
+                }
+                match self.get_session_mut().grammars.get_mut(&coord) {
+                    Some(g @ Grammar { kind: Kind::Image(_), .. }) => {
+                        g.kind = Kind::Input(String::new())
+                    }
+                    _ => return false,
+                }
+                true
+            }
+
+            Action::LoadJSONFile(file_data, coordinate) => {
+                if contains_locked(&self.get_session().grammars, &coordinate) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coordinate.to_string()
+                    }));
+                }
+                let grid = match std::str::from_utf8(&file_data.content)
+                    .map_err(|e| e.to_string())
+                    .and_then(parse_json_grid)
+                {
+                    Ok(grid) => grid,
+                    Err(message) => {
+                        return self.update(Action::Alert(format! {
+                            "could not import {}: {}", file_data.name, message
+                        }));
+                    }
+                };
+                let num_rows = grid.len();
+                let num_cols = grid[0].len();
+
+                self.update(Action::AddNestedGrid(
+                    coordinate.clone(),
+                    (num_rows as u32, num_cols as u32),
+                ));
+
+                let grammars = &mut self.get_session_mut().grammars;
+                for (row_, values) in grid.iter().enumerate() {
+                    for (col_, value) in values.iter().enumerate() {
+                        let c = Coordinate::child_of(
+                            &coordinate,
+                            non_zero_u32_tuple(((row_ + 1) as u32, (col_ + 1) as u32)),
+                        );
+                        grammars.insert(c, Grammar::input("", value.as_str()));
+                    }
+                }
+
+                true
+            }
+
+            Action::Select(SelectMsg::Start(coord)) => {
+                self.first_select_cell = Some(coord.clone());
+                self.last_select_cell = None;
+                self.secondary_selections.clear();
+                true
+            }
+            Action::ToggleSecondarySelection(coord) => {
+                if !self.secondary_selections.remove(&coord) {
+                    self.secondary_selections.insert(coord);
+                }
+                true
+            }
+            Action::ClearSecondarySelections => {
+                self.secondary_selections.clear();
+                true
+            }
+            Action::SelectAll => {
+                let active = match self.active_cell.clone() {
+                    Some(active) => active,
+                    None => return false,
+                };
+                let selection = match (&self.first_select_cell, &self.last_select_cell) {
+                    (Some(first), Some(last)) => Some((first.clone(), last.clone())),
+                    _ => None,
+                };
+                match select_all_bounds(&self.get_session().grammars, &active, selection) {
+                    Some((first, last)) => {
+                        self.update(Action::Select(SelectMsg::Start(first)));
+                        self.update(Action::Select(SelectMsg::End(last)))
+                    }
+                    None => false,
                 }
-
-                true
             }
-
-            Action::Select(SelectMsg::Start(coord)) => {
-                self.first_select_cell = Some(coord.clone());
-                self.last_select_cell = None;
-                true
+            Action::SelectRow(row) => {
+                let cells = self.query_row(row);
+                match (cells.first(), cells.last()) {
+                    (Some(first), Some(last)) => {
+                        self.update(Action::Select(SelectMsg::Start(first.clone())));
+                        self.update(Action::Select(SelectMsg::End(last.clone())))
+                    }
+                    _ => false,
+                }
+            }
+            Action::SelectColumn(col) => {
+                let cells = self.query_col(col);
+                match (cells.first(), cells.last()) {
+                    (Some(first), Some(last)) => {
+                        self.update(Action::Select(SelectMsg::Start(first.clone())));
+                        self.update(Action::Select(SelectMsg::End(last.clone())))
+                    }
+                    _ => false,
+                }
             }
             Action::Select(SelectMsg::End(coord)) => {
-                if let Some(mut selection_start) = self.first_select_cell.clone() {
-                    // ensure that selection_start and selection_end have common parent
-                    let mut common_parent = selection_start.parent();
-                    let mut selection_end = Some(coord.clone());
-                    let depth_start = selection_start.row_cols.len();
-                    let depth_end = selection_end.clone().unwrap().row_cols.len();
-                    // depend on which select coord has higher depth, find their common parent
-                    if depth_start < depth_end {
-                        while selection_end.clone().and_then(|c| c.parent()) != common_parent {
-                            selection_end = selection_end.and_then(|c| c.parent());
-                        }
-                    } else {
-                        common_parent = selection_end.clone().unwrap().parent();
-                        while selection_start.parent() != common_parent {
-                            selection_start = selection_start.parent().unwrap();
+                if let Some(first) = self.first_select_cell.clone() {
+                    // both ends of the selection get lifted to cells of the grid they share
+                    let common_parent = match (first.parent(), coord.parent()) {
+                        (Some(first_parent), Some(last_parent)) => {
+                            first_parent.common_ancestor(&last_parent)
                         }
-                    }
+                        _ => None,
+                    };
+                    let depth = match common_parent {
+                        Some(parent) => parent.row_cols.len() + 1,
+                        None => return false,
+                    };
+                    let mut selection_start = Coordinate {
+                        row_cols: first.row_cols[..depth].to_vec(),
+                    };
+                    let mut selection_end = Coordinate {
+                        row_cols: coord.row_cols[..depth].to_vec(),
+                    };
                     // find the min of row,col and max of row,col in selected region
                     // which may contain a span coord that has smaller or larger row,col
                     let (mut start_row, mut start_col) = selection_start.clone().row_col();
-                    let (mut end_row, mut end_col) = selection_end.clone().unwrap().row_col();
+                    let (mut end_row, mut end_col) = selection_end.row_col();
                     if start_row > end_row {
                         let tmp = start_row.clone();
                         start_row = end_row;
@@ -640,29 +2490,85 @@ impl Component for Model {
                     }
 
                     selection_start.row_cols[depth_check - 1] = (start_row, start_col);
-                    selection_end.as_mut().unwrap().row_cols[depth_check - 1] = (end_row, end_col);
+                    selection_end.row_cols[depth_check - 1] = (end_row, end_col);
                     self.first_select_cell = Some(selection_start.clone());
-                    self.last_select_cell = selection_end.clone();
+                    self.last_select_cell = Some(selection_end);
                 }
                 true
             }
 
-            Action::RangeDelete() => {
-                let (first_row, first_col) = self.first_select_cell.clone().unwrap().row_col();
-                let (last_row, last_col) = self.last_select_cell.clone().unwrap().row_col();
+            Action::InsertReferenceToSelection(target) => {
+                // "point mode": while a formula is being edited, clicking another cell inserts
+                // a reference to it at the caret instead of moving the active cell
+                let active = match self.active_cell.clone() {
+                    Some(active) if active != target => active,
+                    _ => return false,
+                };
+                let editing_formula = match self.get_session().grammars.get(&active) {
+                    Some(Grammar {
+                        kind: Kind::Input(value),
+                        ..
+                    }) => is_formula(value),
+                    _ => false,
+                };
+                if !editing_formula {
+                    return false;
+                }
+                let reference = formula_reference(&active, &target);
+                let caret = self.current_cursor_position;
+                if let Some(g) = self.get_session_mut().grammars.get_mut(&active) {
+                    if let Kind::Input(value) = &g.kind {
+                        g.kind = Kind::Input(insert_at_caret(value, caret, &reference));
+                    }
+                }
+                self.current_cursor_position = caret + reference.chars().count();
+                focus_on_cell(&active);
+                true
+            }
 
-                let row_range = first_row.get()..=last_row.get();
-                let col_range = first_col.get()..=last_col.get();
+            Action::InsertNewline(coord) => {
+                // Alt+Enter breaks the line inside the cell, at the caret
+                let caret = caret_position();
+                match self.get_session_mut().grammars.get_mut(&coord) {
+                    Some(Grammar {
+                        kind: Kind::Input(value),
+                        ..
+                    }) => *value = insert_at_caret(value, caret, "\n"),
+                    _ => return false,
+                }
+                self.current_cursor_position = caret + 1;
+                place_caret(&coord, caret + 1);
+                true
+            }
 
-                let parent_check = self.last_select_cell.clone().unwrap().parent();
-                let depth_check = self.last_select_cell.clone().unwrap().row_cols.len();
+            Action::RangeDelete() => {
+                // the Ctrl+clicked cells get cleared along with the range
+                let mut targets: Vec<Coordinate> =
+                    self.secondary_selections.iter().cloned().collect();
+                if let (Some(first), Some(last)) =
+                    (self.first_select_cell.clone(), self.last_select_cell.clone())
+                {
+                    let (first_row, first_col) = first.row_col();
+                    let (last_row, last_col) = last.row_col();
+                    let row_range = first_row.get()..=last_row.get();
+                    let col_range = first_col.get()..=last_col.get();
+                    let parent_check = last.parent();
+                    let in_range = self.get_session().grammars.keys().filter(|coord| {
+                        row_range.contains(&coord.row().get())
+                            && col_range.contains(&coord.col().get())
+                            && coord.parent() == parent_check
+                    });
+                    targets.extend(in_range.cloned());
+                }
 
                 let mut ref_grammars = self.get_session_mut().grammars.clone();
+                let mut skipped_locked = false;
                 for (coord, grammar) in ref_grammars.clone().iter_mut() {
-                    if row_range.contains(&coord.row().get())
-                        && col_range.contains(&coord.col().get())
-                        && coord.parent() == parent_check
-                    {
+                    if targets.contains(coord) {
+                        if is_locked(&ref_grammars, coord) {
+                            skipped_locked = true;
+                            continue;
+                        }
                         let get_kind = grammar.kind.clone();
                         match get_kind {
                             Kind::Input(value) => {
@@ -673,7 +2579,9 @@ impl Component for Model {
                             }
                             Kind::Grid(sub_coords) => {
                                 for (c, g) in ref_grammars.clone().iter_mut() {
-                                    if c.parent().is_some() && c.parent().unwrap() == coord.clone()
+                                    if c.parent().is_some()
+                                        && c.parent().unwrap() == coord.clone()
+                                        && !is_locked(&ref_grammars, c)
                                     {
                                         g.kind = Kind::Input("".to_string());
                                         self.get_session_mut()
@@ -686,6 +2594,289 @@ impl Component for Model {
                         }
                     }
                 }
+                if skipped_locked {
+                    self.update(Action::Alert("locked cells were left as they are".to_string()));
+                }
+                true
+            }
+
+            Action::CopySelection => {
+                if let Some((parent, top_left, bottom_right)) = self.selection_bounds() {
+                    let grammars = &self.get_session().grammars;
+                    let copied = copy_region(grammars, &parent, top_left, bottom_right);
+                    let results = copied
+                        .iter()
+                        .filter_map(|(path, grammar)| match (&path[..], &grammar.kind) {
+                            ([(row, col)], Kind::Input(value)) if is_formula(value) => {
+                                let source = Coordinate::child_of(
+                                    &parent,
+                                    non_zero_u32_tuple((
+                                        top_left.0 + row.get() - 1,
+                                        top_left.1 + col.get() - 1,
+                                    )),
+                                );
+                                Some(((*row, *col), formula_result(grammars, &source, value)))
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    self.clipboard = Some(Clipboard {
+                        rows: bottom_right.0 - top_left.0 + 1,
+                        cols: bottom_right.1 - top_left.1 + 1,
+                        grammars: copied,
+                        results,
+                    });
+                }
+                false
+            }
+
+            Action::PasteSelection => {
+                let clipboard = match self.clipboard.clone() {
+                    Some(clipboard) => clipboard,
+                    None => return false,
+                };
+                if let Some((parent, (top, left), (bottom, right))) = self.selection_bounds() {
+                    let (fill_rows, fill_cols) = Model::longest_common_grid_shape(
+                        (clipboard.rows, clipboard.cols),
+                        (bottom - top + 1, right - left + 1),
+                    );
+                    let existing = self.get_session().grammars.clone();
+                    let mut grammars = existing.clone();
+                    for tile_row in 0..(fill_rows / clipboard.rows) {
+                        for tile_col in 0..(fill_cols / clipboard.cols) {
+                            let row_offset = top + tile_row * clipboard.rows - 1;
+                            let col_offset = left + tile_col * clipboard.cols - 1;
+                            for (path, grammar) in clipboard.grammars.iter() {
+                                let (row, col) = path[0];
+                                let dest_cell = Coordinate::child_of(
+                                    &parent,
+                                    non_zero_u32_tuple((row_offset + row.get(), col_offset + col.get())),
+                                );
+                                // only paste into cells that exist in the grid
+                                if !existing.contains_key(&dest_cell) {
+                                    continue;
+                                }
+                                // nothing is pasted unless all of it can be
+                                if path.len() == 1 && contains_locked(&existing, &dest_cell) {
+                                    return self.update(Action::Alert(format! {
+                                        "{} is locked", dest_cell.to_string()
+                                    }));
+                                }
+                                if path.len() == 1 {
+                                    // clear out whatever was nested in the destination cell
+                                    grammars.retain(|c, _| {
+                                        !(c.row_cols.len() > dest_cell.row_cols.len()
+                                            && c.row_cols.starts_with(&dest_cell.row_cols))
+                                    });
+                                }
+                                let mut dest = dest_cell.clone();
+                                dest.row_cols.extend_from_slice(&path[1..]);
+                                grammars.insert(dest, grammar.clone());
+                            }
+                        }
+                    }
+                    self.get_session_mut().grammars = grammars;
+                }
+                true
+            }
+
+            Action::PasteValues(coord) => self.paste_special(coord, true),
+
+            Action::PasteFormats(coord) => self.paste_special(coord, false),
+
+            Action::TransposeRange() => {
+                let bounds = if self.first_select_cell.is_some() && self.last_select_cell.is_some() {
+                    self.selection_bounds()
+                } else {
+                    None
+                };
+                if let Some((parent, top_left, bottom_right)) = bounds {
+                    transpose_region(
+                        &mut self.get_session_mut().grammars,
+                        &parent,
+                        top_left,
+                        bottom_right,
+                    );
+                    move_cell_state(self.get_session_mut(), |c| {
+                        transposed_coord(c, &parent, top_left, bottom_right)
+                    });
+                    // the selection now covers the transposed rectangle
+                    let (rows, cols) = (
+                        bottom_right.0 - top_left.0 + 1,
+                        bottom_right.1 - top_left.1 + 1,
+                    );
+                    self.last_select_cell = Some(Coordinate::child_of(
+                        &parent,
+                        non_zero_u32_tuple((top_left.0 + cols - 1, top_left.1 + rows - 1)),
+                    ));
+                    self.first_select_cell =
+                        Some(Coordinate::child_of(&parent, non_zero_u32_tuple(top_left)));
+                    self.rebuild_observers();
+                    true
+                } else {
+                    self.update(Action::Alert(
+                        "Transpose needs a rectangular selection of cells".to_string(),
+                    ))
+                }
+            }
+
+            Action::ResizeGrid(coord, rows, cols) => {
+                if rows == 0 || cols == 0 {
+                    return self.update(Action::Alert(
+                        "a grid needs at least one row and one column".to_string(),
+                    ));
+                }
+                if grid_size(&self.get_session().grammars, &coord).is_none() {
+                    return self.update(Action::Alert(format! {
+                        "{} isn't a grid", coord.to_string()
+                    }));
+                }
+                if contains_locked(&self.get_session().grammars, &coord) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coord.to_string()
+                    }));
+                }
+                resize_grid(&mut self.get_session_mut().grammars, &coord, rows, cols);
+                // the active cell can be one of the cells that were removed
+                let active_removed = self
+                    .active_cell
+                    .as_ref()
+                    .map_or(false, |c| !self.get_session().grammars.contains_key(c));
+                if active_removed {
+                    self.active_cell =
+                        Some(Coordinate::child_of(&coord, non_zero_u32_tuple((1, 1))));
+                    self.first_select_cell = None;
+                    self.last_select_cell = None;
+                }
+                self.rebuild_observers();
+                true
+            }
+
+            Action::GroupSelectionIntoGrid() => {
+                let bounds = if self.last_select_cell.is_some() {
+                    self.selection_bounds()
+                } else {
+                    None
+                };
+                let (parent, (top, left), (bottom, right)) = match bounds {
+                    Some(bounds) => bounds,
+                    None => {
+                        return self.update(Action::Alert(
+                            "Grouping needs a rectangular selection of cells".to_string(),
+                        ))
+                    }
+                };
+                for row in top..=bottom {
+                    for col in left..=right {
+                        let cell = Coordinate::child_of(&parent, non_zero_u32_tuple((row, col)));
+                        if contains_locked(&self.get_session().grammars, &cell) {
+                            return self.update(Action::Alert(format! {
+                                "{} is locked", cell.to_string()
+                            }));
+                        }
+                        // merges span rows & columns of the parent grid, not of the new one
+                        if self.get_session().grammars.get(&cell).map_or(false, is_merged) {
+                            return self.update(Action::Alert(format! {
+                                "{} is merged, unmerge it before grouping", cell.to_string()
+                            }));
+                        }
+                    }
+                }
+                // the grid's rows & columns keep the sizes they had in the parent grid
+                let row_heights: Vec<f64> = (top..=bottom)
+                    .map(|row| self.row_height(&Row(parent.clone(), NonZeroU32::new(row).unwrap())))
+                    .collect();
+                let col_widths: Vec<f64> = (left..=right)
+                    .map(|col| self.col_width(&Col(parent.clone(), NonZeroU32::new(col).unwrap())))
+                    .collect();
+
+                group_region(
+                    &mut self.get_session_mut().grammars,
+                    &parent,
+                    (top, left),
+                    (bottom, right),
+                );
+                move_cell_state(self.get_session_mut(), |c| {
+                    let grouped = grouped_coord(c, &parent, (top, left), (bottom, right));
+                    grouped.or_else(|| Some(c.clone()))
+                });
+                group_line_sizes(
+                    &mut self.row_heights,
+                    &mut self.col_widths,
+                    &parent,
+                    (top, left),
+                    (bottom, right),
+                );
+                let grid = Coordinate::child_of(&parent, non_zero_u32_tuple((top, left)));
+                for (i, height) in row_heights.iter().enumerate() {
+                    let row = Row(grid.clone(), NonZeroU32::new(i as u32 + 1).unwrap());
+                    self.row_heights.insert(row, *height);
+                }
+                for (i, width) in col_widths.iter().enumerate() {
+                    let col = Col(grid.clone(), NonZeroU32::new(i as u32 + 1).unwrap());
+                    self.col_widths.insert(col, *width);
+                }
+                resize(self, grid.clone(), row_heights.iter().sum(), col_widths.iter().sum());
+                self.rebuild_observers();
+                self.first_select_cell = None;
+                self.last_select_cell = None;
+                self.active_cell = Some(Coordinate::child_of(&grid, non_zero_u32_tuple((1, 1))));
+                true
+            }
+
+            Action::SortRange { ascending } => {
+                let bounds = if self.last_select_cell.is_some() {
+                    self.selection_bounds()
+                } else {
+                    None
+                };
+                let (parent, (top, left), (bottom, right)) = match bounds {
+                    Some(bounds) if (bounds.1).0 < (bounds.2).0 => bounds,
+                    _ => {
+                        return self.update(Action::Alert(
+                            "Sorting needs a selection of more than one row".to_string(),
+                        ))
+                    }
+                };
+                for row in top..=bottom {
+                    for col in left..=right {
+                        let cell = Coordinate::child_of(&parent, non_zero_u32_tuple((row, col)));
+                        if contains_locked(&self.get_session().grammars, &cell) {
+                            return self.update(Action::Alert(format! {
+                                "{} is locked", cell.to_string()
+                            }));
+                        }
+                        // merged cells span rows that would be sorted apart
+                        if self.get_session().grammars.get(&cell).map_or(false, is_merged) {
+                            return self.update(Action::Alert(format! {
+                                "{} is merged, unmerge it before sorting", cell.to_string()
+                            }));
+                        }
+                    }
+                }
+                let whole_rows = grid_size(&self.get_session().grammars, &parent)
+                    .map_or(false, |(_, cols)| left == 1 && right == cols);
+                let order = sort_region(
+                    &mut self.get_session_mut().grammars,
+                    &parent,
+                    (top, left),
+                    (bottom, right),
+                    ascending,
+                );
+                sort_line_sizes(
+                    &mut self.row_heights,
+                    &mut self.col_widths,
+                    &parent,
+                    (left, right),
+                    top,
+                    &order,
+                    whole_rows,
+                );
+                move_cell_state(self.get_session_mut(), |c| {
+                    sorted_coord(c, &parent, (left, right), top, &order).or_else(|| Some(c.clone()))
+                });
+                // lookups that moved observe the same cells from their new place
+                self.rebuild_observers();
                 true
             }
 
@@ -755,29 +2946,156 @@ impl Component for Model {
                 true
             }
 
-            Action::DoCompletion(source_coord, dest_coord) => {
-                move_grammar(self, source_coord, dest_coord.clone());
+            Action::HideRow(row) => {
+                let cells = self.query_row(row.clone());
+                let Row(parent, index) = row;
+                set_line_hidden(
+                    &mut self.get_session_mut().grammars,
+                    &parent,
+                    &cells,
+                    index.get(),
+                    true,
+                    true,
+                );
+                true
+            }
+
+            Action::ShowRow(row) => {
+                let cells = self.query_row(row.clone());
+                let Row(parent, index) = row;
+                set_line_hidden(
+                    &mut self.get_session_mut().grammars,
+                    &parent,
+                    &cells,
+                    index.get(),
+                    true,
+                    false,
+                );
+                true
+            }
+
+            Action::HideColumn(col) => {
+                let cells = self.query_col(col.clone());
+                let Col(parent, index) = col;
+                set_line_hidden(
+                    &mut self.get_session_mut().grammars,
+                    &parent,
+                    &cells,
+                    index.get(),
+                    false,
+                    true,
+                );
+                true
+            }
+
+            Action::ShowColumn(col) => {
+                let cells = self.query_col(col.clone());
+                let Col(parent, index) = col;
+                set_line_hidden(
+                    &mut self.get_session_mut().grammars,
+                    &parent,
+                    &cells,
+                    index.get(),
+                    false,
+                    false,
+                );
+                true
+            }
+
+            Action::MergeAcross => self.merge_selected_lines(true),
+            Action::MergeDown => self.merge_selected_lines(false),
+
+            Action::UnmergeCells(coord) => {
+                let cells = unmerge_region(&mut self.get_session_mut().grammars, &coord);
+                if cells.is_empty() {
+                    info!("Expect a merged cell to unmerge");
+                    return false;
+                }
+                // merging only changed the size of the cells' grammars, so restore them
+                // to the size of their row & column
+                for cell in cells.iter() {
+                    let row_height = self.row_height(&cell.full_row());
+                    let col_width = self.col_width(&cell.full_col());
+                    if let Some(g) = self.get_session_mut().grammars.get_mut(cell) {
+                        g.style.height = row_height;
+                        g.style.width = col_width;
+                    }
+                }
+                if let Some(anchor) = cells.last() {
+                    let row_height = self.row_height(&anchor.full_row());
+                    let col_width = self.col_width(&anchor.full_col());
+                    resize(self, anchor.clone(), row_height, col_width);
+                }
                 true
             }
 
+            Action::DoCompletion(source_coord, dest_coord) => {
+                self.forced_suggestions = None;
+                match move_grammar(self, source_coord, dest_coord.clone()) {
+                    Ok(()) => {
+                        // the completed grammar brings its own validators and values along
+                        self.validate_all();
+                        true
+                    }
+                    Err(message) => self.update(Action::Alert(message)),
+                }
+            }
+
             Action::SetActiveMenu(active_menu) => {
                 self.open_side_menu = active_menu;
+                // actions are only worth logging while someone can see them
+                self.log_actions = active_menu
+                    .and_then(|index| self.side_menus.get(index as usize))
+                    .map_or(false, |side_menu| side_menu.name == "Info");
                 true
             }
 
-            Action::ReadSession(file) => {
-                let callback = self.link.callback(Action::LoadSession);
-                let task = self.reader.read_file(file, callback);
-                self.tasks.push(task);
-                false
+            Action::ClearActionLog => {
+                self.action_log.clear();
+                true
+            }
+
+            Action::ReadSession(file, new_tab) => {
+                let callback = self
+                    .link
+                    .callback(move |file_data| Action::LoadSession(file_data, new_tab));
+                self.read_file(file, callback);
+                true
+            }
+
+            Action::LoadSession(file_data, new_tab) => {
+                let index = self.current_session_index;
+                match load_session_bytes(&mut self.sessions, index, &file_data.content, new_tab) {
+                    Ok(index) => {
+                        self.current_session_index = index;
+                        self.show_loaded_session();
+                        true
+                    }
+                    Err(message) => self.update(Action::Alert(message)),
+                }
             }
 
-            Action::LoadSession(file_data) => {
-                let session: Session =
-                    serde_json::from_str(format! {"{:?}", file_data}.deref()).unwrap();
-                self.load_session(session);
+            Action::ListSavedSessions => {
+                self.saved_sessions = session_file_names(read_sessions_directory());
                 true
             }
+
+            Action::OpenSavedSession(file_name) => match read_saved_session(&file_name) {
+                Some(bytes) => {
+                    let index = self.current_session_index;
+                    match load_session_bytes(&mut self.sessions, index, &bytes, true) {
+                        Ok(index) => {
+                            self.current_session_index = index;
+                            self.show_loaded_session();
+                            true
+                        }
+                        Err(message) => self.update(Action::Alert(message)),
+                    }
+                }
+                None => self.update(Action::Alert(format! {
+                    "could not open {}/{}", SESSIONS_DIRECTORY, file_name
+                })),
+            },
             Action::SaveSession() => {
                 /* TODO: uncomment when this is working
                 use node_sys::fs as node_fs;
@@ -787,18 +3105,86 @@ impl Component for Model {
                     Function
                 };
                 let session = self.to_session();
-                let j = serde_json::to_string(&session.clone());
+                let j = String::from_utf8(session.to_ise_bytes()).unwrap();
                 let filename = session.title.to_string();
                 let jsfilename = JsString::from(filename);
-                let jsbuffer = Buffer::from_string(&JsString::from(j.unwrap()), None);
+                let jsbuffer = Buffer::from_string(&JsString::from(j), None);
                 let jscallback = Function::new_no_args("{}");
                 node_fs::append_file(&jsfilename, &jsbuffer, None, &jscallback);
                 */
                 false
             }
 
-            Action::SetSessionTitle(name) => {
-                self.get_session_mut().title = name;
+            Action::ExportDefinitions() => {
+                let definitions = Definitions::of_session(self.get_session());
+                if definitions.grammars.is_empty() {
+                    return self.update(Action::Alert(
+                        "there are no definitions to export".to_string(),
+                    ));
+                }
+                let contents = String::from_utf8(definitions.to_isedef_bytes())
+                    .expect("definitions are serialized as JSON");
+                let file_name = format! {"{}.isedef", self.get_session().title};
+                dom_download(&file_name, &contents, "application/json");
+                false
+            }
+
+            Action::ReadDefinitions(file) => {
+                let callback = self.link.callback(Action::ImportDefinitions);
+                self.read_file(file, callback);
+                true
+            }
+
+            Action::ImportDefinitions(file_data) => {
+                let definitions = match Definitions::from_isedef_bytes(&file_data.content) {
+                    Ok(definitions) => definitions,
+                    Err(message) => return self.update(Action::Alert(message)),
+                };
+                let skipped = definitions.merge_into(self.get_session_mut());
+                self.rebuild_observers();
+                // `meta_suggestions` catch up at the end of `update`
+                if !skipped.is_empty() {
+                    self.update(Action::Alert(format! {
+                        "these definitions weren't imported, because there are definitions with \
+                         the same name already: {}",
+                        skipped.join(", ")
+                    }));
+                }
+                true
+            }
+
+            Action::ReorderSession(from, to) => {
+                if from == to || from >= self.sessions.len() || to >= self.sessions.len() {
+                    return false;
+                }
+                let session = self.sessions.remove(from);
+                self.sessions.insert(to, session);
+                // the active session stays active, wherever it ends up
+                self.current_session_index = moved_index(self.current_session_index, from, to);
+                true
+            }
+
+            Action::SetSessionTitle(index, name) => match self.sessions.get_mut(index) {
+                Some(session) => {
+                    session.title = name;
+                    true
+                }
+                None => self.update(Action::Alert(format! {"there's no session {}", index})),
+            },
+
+            Action::RenameColumnHeader(col, label) => {
+                self.header_editor = None;
+                if label.trim() == "" {
+                    // clearing a label goes back to showing the column letter
+                    self.get_session_mut().column_labels.remove(&col);
+                } else {
+                    self.get_session_mut().column_labels.insert(col, label);
+                }
+                true
+            }
+
+            Action::EditColumnHeader(col) => {
+                self.header_editor = col;
                 true
             }
 
@@ -914,18 +3300,15 @@ impl Component for Model {
                     .link
                     .callback(|file_data| Action::UploadDriverMiscFile(file_data));
                 for file in misc_files {
-                    let task = self.reader.read_file(file, upload_callback.clone());
-                    self.tasks.push(task);
+                    self.read_file(file, upload_callback.clone());
                 }
 
                 // Load main driver file. After this task has been scheduled and executed, the
                 // driver is ready for use.
-                self.tasks.push(
-                    self.reader
-                        .read_file(main_file, self.link.callback(Action::LoadDriverMainFile)),
-                );
+                let main_callback = self.link.callback(Action::LoadDriverMainFile);
+                self.read_file(main_file, main_callback);
 
-                false
+                true
             }
 
             Action::UploadDriverMiscFile(file_data) => {
@@ -937,9 +3320,17 @@ impl Component for Model {
                 // And here, for the documentation for the electon_sys Rust bindings for electron.ipcRenderer:
                 //   https://docs.rs/electron-sys/0.4.0/electron_sys/struct.IpcRenderer.html
 
+                let contents = match std::str::from_utf8(&file_data.content) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        return self.update(Action::Alert(format! {
+                            "could not upload {}: {}", file_data.name, e
+                        }));
+                    }
+                };
                 let args: [JsValue; 2] = [
                     JsValue::from_str(file_data.name.deref()),
-                    JsValue::from_str(std::str::from_utf8(&file_data.content).unwrap()),
+                    JsValue::from_str(contents),
                 ];
                 ipc_renderer.send_sync("upload-driver-misc-file", Box::new(args));
                 false
@@ -947,7 +3338,14 @@ impl Component for Model {
 
             Action::LoadDriverMainFile(main_file_data) => {
                 info! {"Loading Driver: {}", &main_file_data.name};
-                let file_contents = std::str::from_utf8(&main_file_data.content).unwrap();
+                let file_contents = match std::str::from_utf8(&main_file_data.content) {
+                    Ok(file_contents) => file_contents,
+                    Err(e) => {
+                        return self.update(Action::Alert(format! {
+                            "could not load driver {}: {}", main_file_data.name, e
+                        }));
+                    }
+                };
                 // dump file contents into script tag and attach to the DOM
                 let script = document().create_element("script").unwrap();
                 script.set_text_content(file_contents);
@@ -964,13 +3362,17 @@ impl Component for Model {
                     info!("Expect a cell is active");
                     return false;
                 }
-                // height and width initial value
-                let mut tmp_heigth = 30.0;
-                let mut tmp_width = 90.0;
-
-                let current_cell = self.active_cell.clone();
                 let ref_grammas = self.get_session().grammars.clone();
-                let current_grammar = ref_grammas.get(&current_cell.clone().unwrap()).unwrap();
+                let current_grammar = ref_grammas
+                    .get(&coord)
+                    .cloned()
+                    .unwrap_or(Grammar::default());
+
+                // the nested cells evenly split the size of the cell they're nested in
+                let (cell_width, cell_height) = nested_cell_size(
+                    (current_grammar.style.width, current_grammar.style.height),
+                    (rows, cols),
+                );
 
                 let (r, c) = non_zero_u32_tuple((rows, cols));
                 let mut grammar = Grammar::as_grid(r, c);
@@ -978,27 +3380,15 @@ impl Component for Model {
                     // set active cell to first cell inside the new nested grammar
                     self.active_cell = sub_coords.first().map(|c| Coordinate::child_of(&coord, *c));
 
-                    let current_width = current_grammar.style.width;
-                    let current_height = current_grammar.style.height;
-
-                    // check if active cell row height and width is greater than default value
-                    if current_width > tmp_width {
-                        // set height argument to active cell height if greater
-                        // TODO: use the actual number of columns instead of hard coded "3" .
-                        tmp_width = current_width / 3.0;
-                    }
-                    if current_height > tmp_heigth {
-                        // set width argument to active cell width if greater
-                        // TODO: use the actual number of rows instead of hard coded "3" .
-                        tmp_heigth = current_height / 3.0;
-                    }
-
                     for sub_coord in sub_coords {
                         let new_coord = Coordinate::child_of(&coord, sub_coord);
 
+                        let mut child = Grammar::default();
+                        child.style.width = cell_width;
+                        child.style.height = cell_height;
                         self.get_session_mut()
                             .grammars
-                            .insert(new_coord.clone(), Grammar::default());
+                            .insert(new_coord.clone(), child);
                         if current_grammar.style.col_span.0 == 0
                             && current_grammar.style.row_span.0 == 0
                         {
@@ -1006,12 +3396,12 @@ impl Component for Model {
                             if !&self.row_heights.contains_key(&new_coord.clone().full_row()) {
                                 &self
                                     .row_heights
-                                    .insert(new_coord.clone().full_row(), tmp_heigth);
+                                    .insert(new_coord.clone().full_row(), cell_height);
                             }
                             if !&self.col_widths.contains_key(&new_coord.clone().full_col()) {
                                 &self
                                     .col_widths
-                                    .insert(new_coord.clone().full_col(), tmp_width);
+                                    .insert(new_coord.clone().full_col(), cell_width);
                             }
                         }
                     }
@@ -1033,15 +3423,15 @@ impl Component for Model {
                 resize(
                     self,
                     coord.clone(),
-                    (rows as f64) * (/* default row height */tmp_heigth),
-                    (cols as f64) * (/* default col width */tmp_width),
+                    (rows as f64) * cell_height,
+                    (cols as f64) * cell_width,
                 );
 
                 true
             }
 
             Action::ZoomIn => {
-                self.zoom += 0.1;
+                self.zoom = step_zoom(self.zoom, ZOOM_STEP);
                 true
             }
             Action::ZoomReset => {
@@ -1050,11 +3440,37 @@ impl Component for Model {
             }
 
             Action::ZoomOut => {
-                self.zoom -= 0.1;
+                self.zoom = step_zoom(self.zoom, -ZOOM_STEP);
+                true
+            }
+
+            Action::ZoomToFit => {
+                // the rendered size already has the current zoom applied, so undo it
+                if let Some((left, top, right, bottom)) = dom_rect(&self.view_root) {
+                    let zoom = self.zoom as f64;
+                    let size = ((right - left) / zoom, (bottom - top) / zoom);
+                    self.zoom = zoom_to_fit(size, dom_viewport());
+                }
+                true
+            }
+
+            Action::ZoomToSelection => {
+                let (first, last) = match (&self.first_select_cell, &self.last_select_cell) {
+                    (Some(first), Some(last)) => (first.clone(), last.clone()),
+                    _ => return false,
+                };
+                if let (Some(first_rect), Some(last_rect)) = (dom_rect(&first), dom_rect(&last)) {
+                    let zoom = self.zoom as f64;
+                    let width = first_rect.2.max(last_rect.2) - first_rect.0.min(last_rect.0);
+                    let height = first_rect.3.max(last_rect.3) - first_rect.1.min(last_rect.1);
+                    self.zoom = zoom_to_fit((width / zoom, height / zoom), dom_viewport());
+                    focus_on_cell(&first);
+                }
                 true
             }
 
-            Action::InsertCol => {
+            Action::InsertCol => self.update(Action::InsertCols(1)),
+            Action::InsertCols(count) => {
                 if let Some(coord) = self.active_cell.clone() {
                     // find the bottom-most coord
                     let mut right_most_coord = coord.clone();
@@ -1067,25 +3483,38 @@ impl Component for Model {
                     }
 
                     let right_most_col_coords = self.query_col(right_most_coord.full_col());
-                    let new_col_coords = right_most_col_coords
+                    let new_col_coords: Vec<(NonZeroU32, NonZeroU32)> = right_most_col_coords
                         .iter()
-                        .map(|c| (c.row(), NonZeroU32::new(c.col().get() + 1).unwrap()));
+                        .map(|c| (c.row(), NonZeroU32::new(c.col().get() + 1).unwrap()))
+                        .collect();
 
                     let parent = coord.parent().unwrap();
                     if let Some(Grammar {
                         kind: Kind::Grid(sub_coords),
                         name,
                         style,
+                        validator,
                     }) = self.to_session().grammars.get(&parent)
                     {
                         let mut new_sub_coords = sub_coords.clone();
+                        // all of the columns go into one copy of the map, which is swapped in once
                         let mut grammars = self.get_session_mut().grammars.clone();
-                        for c in new_col_coords {
-                            grammars.insert(
-                                Coordinate::child_of(&parent.clone(), c),
-                                Grammar::default(),
+                        for offset in 0..count {
+                            for &(row, col) in &new_col_coords {
+                                // new cells start out at the default column width
+                                let c = (row, NonZeroU32::new(col.get() + offset).unwrap());
+                                let new_coord = Coordinate::child_of(&parent.clone(), c);
+                                let mut grammar = Grammar::default();
+                                grammar.style.width = self.default_col_width;
+                                grammar.style.height = self.row_height(&new_coord.full_row());
+                                grammars.insert(new_coord, grammar);
+                                new_sub_coords.push(c);
+                            }
+                            let new_col = right_most_coord.col().get() + 1 + offset;
+                            self.col_widths.insert(
+                                Col(parent.clone(), NonZeroU32::new(new_col).unwrap()),
+                                self.default_col_width,
                             );
-                            new_sub_coords.push(c);
                         }
                         grammars.insert(
                             parent,
@@ -1093,14 +3522,17 @@ impl Component for Model {
                                 kind: Kind::Grid(new_sub_coords.clone()),
                                 name: name.clone(),
                                 style: style.clone(),
+                                validator: validator.clone(),
                             },
                         );
                         self.get_session_mut().grammars = grammars;
                     }
                 }
+                self.rebuild_observers();
                 true
             }
-            Action::InsertRow => {
+            Action::InsertRow => self.update(Action::InsertRows(1)),
+            Action::InsertRows(count) => {
                 if let Some(coord) = self.active_cell.clone() {
                     // find the bottom-most coord
                     let mut bottom_most_coord = coord.clone();
@@ -1112,47 +3544,186 @@ impl Component for Model {
                         }
                     }
                     let bottom_most_row_coords = self.query_row(bottom_most_coord.full_row());
-                    let new_row_coords = bottom_most_row_coords
+                    let new_row_coords: Vec<(NonZeroU32, NonZeroU32)> = bottom_most_row_coords
                         .iter()
-                        .map(|c| (NonZeroU32::new(c.row().get() + 1).unwrap(), c.col()));
+                        .map(|c| (NonZeroU32::new(c.row().get() + 1).unwrap(), c.col()))
+                        .collect();
                     let parent = coord.parent().unwrap();
                     if let Some(Grammar {
                         kind: Kind::Grid(sub_coords),
                         name,
                         style,
+                        validator,
                     }) = self.to_session().grammars.get(&parent)
                     {
                         let mut new_sub_coords = sub_coords.clone();
 
-                        let mut grammars = self.get_session_mut().grammars.clone();
-                        for c in new_row_coords {
-                            grammars.insert(
-                                Coordinate::child_of(&parent.clone(), c),
-                                Grammar::default(),
-                            );
-                            new_sub_coords.push(c);
+                        // all of the rows go into one copy of the map, which is swapped in once
+                        let mut grammars = self.get_session_mut().grammars.clone();
+                        for offset in 0..count {
+                            for &(row, col) in &new_row_coords {
+                                // new cells start out at the default row height
+                                let c = (NonZeroU32::new(row.get() + offset).unwrap(), col);
+                                let new_coord = Coordinate::child_of(&parent.clone(), c);
+                                let mut grammar = Grammar::default();
+                                grammar.style.width = self.col_width(&new_coord.full_col());
+                                grammar.style.height = self.default_row_height;
+                                grammars.insert(new_coord, grammar);
+                                new_sub_coords.push(c);
+                            }
+                            let new_row = bottom_most_coord.row().get() + 1 + offset;
+                            self.row_heights.insert(
+                                Row(parent.clone(), NonZeroU32::new(new_row).unwrap()),
+                                self.default_row_height,
+                            );
+                        }
+                        grammars.insert(
+                            parent,
+                            Grammar {
+                                kind: Kind::Grid(new_sub_coords.clone()),
+                                name: name.clone(),
+                                style: style.clone(),
+                                validator: validator.clone(),
+                            },
+                        );
+                        self.get_session_mut().grammars = grammars;
+                    }
+                }
+                self.rebuild_observers();
+                true
+            }
+            Action::DuplicateRow => {
+                if let Some((coord, parent)) = self
+                    .active_cell
+                    .clone()
+                    .and_then(|c| c.parent().map(|p| (c, p)))
+                {
+                    // make room for the new row right below the active cell's row
+                    let new_row = coord.row().get() + 1;
+                    shift_cells(&mut self.get_session_mut().grammars, &parent, new_row, true);
+                    move_cell_state(self.get_session_mut(), |c| {
+                        shifted_coord(c, &parent, new_row, 1, true)
+                    });
+                    shift_line_sizes(
+                        &mut self.row_heights,
+                        &mut self.col_widths,
+                        &parent,
+                        new_row,
+                        1,
+                        true,
+                    );
+                    shift_conditional_formats(
+                        &mut self.get_session_mut().conditional_formats,
+                        &parent,
+                        new_row,
+                        1,
+                        true,
+                    );
+
+                    let source_row = coord.full_row();
+                    let row_height = self.row_heights.get(&source_row).cloned();
+                    let new_full_row = Row(parent.clone(), NonZeroU32::new(new_row).unwrap());
+                    if let Some(height) = row_height {
+                        self.row_heights.insert(new_full_row.clone(), height);
+                    }
+                    for source in self.query_row(source_row) {
+                        let sub_coord = non_zero_u32_tuple((new_row, source.col().get()));
+                        if let Some(Grammar {
+                            kind: Kind::Grid(sub_coords),
+                            ..
+                        }) = self.get_session_mut().grammars.get_mut(&parent)
+                        {
+                            sub_coords.push(sub_coord);
+                        }
+                        let dest = Coordinate::child_of(&parent, sub_coord);
+                        if let Err(message) = move_grammar(self, source, dest) {
+                            return self.update(Action::Alert(message));
+                        }
+                    }
+                    // `move_grammar` resizes the destination, so match the source row again
+                    if let Some(height) = row_height {
+                        self.row_heights.insert(new_full_row, height);
+                    }
+                }
+                self.rebuild_observers();
+                true
+            }
+
+            Action::DuplicateColumn => {
+                if let Some((coord, parent)) = self
+                    .active_cell
+                    .clone()
+                    .and_then(|c| c.parent().map(|p| (c, p)))
+                {
+                    // make room for the new column right after the active cell's column
+                    let new_col = coord.col().get() + 1;
+                    shift_cells(&mut self.get_session_mut().grammars, &parent, new_col, false);
+                    move_cell_state(self.get_session_mut(), |c| {
+                        shifted_coord(c, &parent, new_col, 1, false)
+                    });
+                    shift_line_sizes(
+                        &mut self.row_heights,
+                        &mut self.col_widths,
+                        &parent,
+                        new_col,
+                        1,
+                        false,
+                    );
+                    shift_conditional_formats(
+                        &mut self.get_session_mut().conditional_formats,
+                        &parent,
+                        new_col,
+                        1,
+                        false,
+                    );
+
+                    let source_col = coord.full_col();
+                    let col_width = self.col_widths.get(&source_col).cloned();
+                    let new_full_col = Col(parent.clone(), NonZeroU32::new(new_col).unwrap());
+                    if let Some(width) = col_width {
+                        self.col_widths.insert(new_full_col.clone(), width);
+                    }
+                    for source in self.query_col(source_col) {
+                        let sub_coord = non_zero_u32_tuple((source.row().get(), new_col));
+                        if let Some(Grammar {
+                            kind: Kind::Grid(sub_coords),
+                            ..
+                        }) = self.get_session_mut().grammars.get_mut(&parent)
+                        {
+                            sub_coords.push(sub_coord);
+                        }
+                        let dest = Coordinate::child_of(&parent, sub_coord);
+                        if let Err(message) = move_grammar(self, source, dest) {
+                            return self.update(Action::Alert(message));
                         }
-                        grammars.insert(
-                            parent,
-                            Grammar {
-                                kind: Kind::Grid(new_sub_coords.clone()),
-                                name: name.clone(),
-                                style: style.clone(),
-                            },
-                        );
-                        self.get_session_mut().grammars = grammars;
+                    }
+                    // `move_grammar` resizes the destination, so match the source column again
+                    if let Some(width) = col_width {
+                        self.col_widths.insert(new_full_col, width);
                     }
                 }
+                self.rebuild_observers();
                 true
             }
+
             Action::DeleteRow => {
                 //Taking Active cell
                 if let Some(coord) = self.active_cell.clone() {
+                    let grammars = &self.get_session().grammars;
+                    if self
+                        .query_row(coord.full_row())
+                        .iter()
+                        .any(|c| contains_locked(grammars, c))
+                    {
+                        return self.update(Action::Alert(
+                            "can't delete a row with locked cells".to_string(),
+                        ));
+                    }
                     //Have to initialize many things for them to work in loop
                     let mut next_row = coord.clone();
                     let mut grammars = self.get_session_mut().grammars.clone();
                     let mut row_coords1 = self.query_row(next_row.full_row());
-                    let _parent = coord.parent().unwrap();
+                    let parent = coord.parent().unwrap();
 
                     let mut temp: Vec<Grammar> = vec![];
                     let mut u = 0;
@@ -1186,6 +3757,7 @@ impl Component for Model {
                                 kind: Kind::Grid(sub_coords),
                                 name,
                                 style,
+                                validator,
                             }) = self.to_session().grammars.get(&parent)
                             {
                                 new_row_coords = sub_coords.clone();
@@ -1209,6 +3781,7 @@ impl Component for Model {
                                         kind: Kind::Grid(new_row_coords.clone()),
                                         name: name.clone(),
                                         style: style.clone(),
+                                        validator: validator.clone(),
                                     },
                                 );
                                 break;
@@ -1225,12 +3798,42 @@ impl Component for Model {
                         next_row = below_coord;
                     }
                     self.get_session_mut().grammars = grammars;
+                    move_cell_state(self.get_session_mut(), |c| {
+                        shifted_coord(c, &parent, coord.row().get(), -1, true)
+                    });
+                    // the sizes of the rows below move up along with their cells
+                    shift_line_sizes(
+                        &mut self.row_heights,
+                        &mut self.col_widths,
+                        &parent,
+                        coord.row().get(),
+                        -1,
+                        true,
+                    );
+                    shift_conditional_formats(
+                        &mut self.get_session_mut().conditional_formats,
+                        &parent,
+                        coord.row().get(),
+                        -1,
+                        true,
+                    );
                 }
+                self.rebuild_observers();
                 true
             }
             Action::DeleteCol => {
                 //Taking Active cell
                 if let Some(coord) = self.active_cell.clone() {
+                    let grammars = &self.get_session().grammars;
+                    if self
+                        .query_col(coord.full_col())
+                        .iter()
+                        .any(|c| contains_locked(grammars, c))
+                    {
+                        return self.update(Action::Alert(
+                            "can't delete a column with locked cells".to_string(),
+                        ));
+                    }
                     //Have to initialize many things for them to work in loop
                     let mut next_col = coord.clone();
                     let mut grammars = self.get_session_mut().grammars.clone();
@@ -1248,6 +3851,7 @@ impl Component for Model {
                         kind: Kind::Grid(sub_coords),
                         name: _,
                         style: _,
+                        validator: _,
                     }) = self.get_session_mut().grammars.get(&parent)
                     {
                         let _new_col_coords = sub_coords.clone();
@@ -1277,6 +3881,7 @@ impl Component for Model {
                                 kind: Kind::Grid(sub_coords),
                                 name,
                                 style,
+                                validator,
                             }) = self.to_session().grammars.get(&parent)
                             {
                                 new_col_coords = sub_coords.clone();
@@ -1300,6 +3905,7 @@ impl Component for Model {
                                         kind: Kind::Grid(new_col_coords.clone()),
                                         name: name.clone(),
                                         style: style.clone(),
+                                        validator: validator.clone(),
                                     },
                                 );
                                 break;
@@ -1316,7 +3922,27 @@ impl Component for Model {
                         next_col = right_coord;
                     }
                     self.get_session_mut().grammars = grammars;
+                    move_cell_state(self.get_session_mut(), |c| {
+                        shifted_coord(c, &parent, coord.col().get(), -1, false)
+                    });
+                    // the sizes of the columns to the right move left along with their cells
+                    shift_line_sizes(
+                        &mut self.row_heights,
+                        &mut self.col_widths,
+                        &parent,
+                        coord.col().get(),
+                        -1,
+                        false,
+                    );
+                    shift_conditional_formats(
+                        &mut self.get_session_mut().conditional_formats,
+                        &parent,
+                        coord.col().get(),
+                        -1,
+                        false,
+                    );
                 }
+                self.rebuild_observers();
                 true
             }
 
@@ -1358,6 +3984,46 @@ impl Component for Model {
             //     };
             //     true
             // }
+            Action::RecreateWithSize(rows, cols) => {
+                if rows == 0 || cols == 0 {
+                    return false;
+                }
+                // replace everything under root with an empty grid, leaving the meta table as-is
+                let root = coord!("root");
+                let (width, height) = (self.default_col_width, self.default_row_height);
+                let session = self.get_session_mut();
+                session.notes.retain(|c, _| !c.row_cols.starts_with(&root.row_cols));
+                let grammars = &mut session.grammars;
+                grammars.retain(|c, _| !c.row_cols.starts_with(&root.row_cols));
+                build_grammar_map(
+                    grammars,
+                    root.clone(),
+                    grid_entry(rows, cols, |_, _| {
+                        let mut cell = Grammar::default();
+                        cell.style.width = width;
+                        cell.style.height = height;
+                        g!(cell)
+                    }),
+                );
+                let root_grammar = grammars[&root].clone();
+                self.get_session_mut().root = root_grammar;
+                // the new rows & columns start out at the default size
+                for row in 1..=rows {
+                    self.row_heights
+                        .insert(Row(root.clone(), NonZeroU32::new(row).unwrap()), height);
+                }
+                for col in 1..=cols {
+                    self.col_widths
+                        .insert(Col(root.clone(), NonZeroU32::new(col).unwrap()), width);
+                }
+                self.rebuild_observers();
+                self.invalid_cells.clear();
+                self.active_cell = Some(coord!("root-A1"));
+                self.first_select_cell = None;
+                self.last_select_cell = None;
+                true
+            }
+
             Action::Recreate => {
                 self.get_session_mut().grammars = {
                     info! {"~rec is being fired"}
@@ -1407,11 +4073,13 @@ impl Component for Model {
                                         s
                                     },
                                     kind: Kind::Text("Define Grammar".to_string()),
+                                    validator: None,
                                 }),
                                 g!(Grammar {
                                     name: "defn_name".to_string(),
                                     style: Style::default(),
                                     kind: Kind::Input(String::new()),
+                                    validator: None,
                                 })
                             ],
                             [grid![
@@ -1433,69 +4101,290 @@ impl Component for Model {
             }
 
             Action::Resize(msg) => {
+                let default_size = (self.default_col_width, self.default_row_height);
+                let moved = drag_resize(
+                    &mut self.resizing,
+                    &mut self.resizing_line,
+                    &mut self.resize_preview,
+                    &mut self.row_heights,
+                    &mut self.col_widths,
+                    default_size,
+                    &msg,
+                );
                 match msg {
-                    ResizeMsg::Start(coord) => {
-                        self.resizing = Some(coord);
+                    ResizeMsg::X(_, position) if moved => {
+                        self.resize_label_position = position;
+                        self.mouse_cursor = CursorType::EW;
                     }
-                    ResizeMsg::X(offset_x) => {
-                        if let Some(coord) = self.resizing.clone() {
-                            resize_diff(self, coord, 0.0, offset_x);
-                            self.mouse_cursor = CursorType::EW;
-                        }
+                    ResizeMsg::Y(_, position) if moved => {
+                        self.resize_label_position = position;
+                        self.mouse_cursor = CursorType::NS;
+                    }
+                    ResizeMsg::End => self.mouse_cursor = CursorType::Default,
+                    _ => (),
+                }
+                true
+            }
+
+            Action::AutoFitColumn(col) => {
+                // the measured size has the current zoom applied, so undo it
+                let zoom = self.zoom as f64;
+                let width = fit_line_size(
+                    &self.get_session().grammars,
+                    &self.query_col(col.clone()),
+                    |c| dom_content_size(c).map(|(width, _)| width / zoom),
+                    self.default_col_width,
+                );
+                let diff = width - self.col_width(&col);
+                let grid = col.0.clone();
+                self.col_widths.insert(col, width);
+                // the grid (and the grids it's nested in) grow or shrink along with it
+                resize_diff(self, grid, 0.0, diff);
+                true
+            }
+
+            Action::AutoFitRow(row) => {
+                let zoom = self.zoom as f64;
+                let height = fit_line_size(
+                    &self.get_session().grammars,
+                    &self.query_row(row.clone()),
+                    |c| dom_content_size(c).map(|(_, height)| height / zoom),
+                    self.default_row_height,
+                );
+                let diff = height - self.row_height(&row);
+                let grid = row.0.clone();
+                self.row_heights.insert(row, height);
+                resize_diff(self, grid, diff, 0.0);
+                true
+            }
+
+            Action::SetFrozenRows(rows) => {
+                self.frozen_rows = rows;
+                self.get_session_mut().frozen_rows = rows;
+                true
+            }
+
+            Action::SetFrozenCols(cols) => {
+                self.frozen_cols = cols;
+                self.get_session_mut().frozen_cols = cols;
+                true
+            }
+
+            Action::SetDefaultColWidth(width) => {
+                if width <= 0.0 {
+                    return false;
+                }
+                self.default_col_width = width;
+                self.get_session_mut().default_col_width = width;
+                true
+            }
+
+            Action::SetDefaultRowHeight(height) => {
+                if height <= 0.0 {
+                    return false;
+                }
+                self.default_row_height = height;
+                self.get_session_mut().default_row_height = height;
+                true
+            }
+
+            Action::FillStart(coord) => {
+                self.filling = Some((coord.clone(), coord));
+                true
+            }
+
+            Action::FillTo(coord) => {
+                match self.filling.clone() {
+                    // only cells in the same grid as the source can be filled
+                    Some((source, target))
+                        if target != coord && source.parent() == coord.parent() =>
+                    {
+                        self.filling = Some((source, coord));
+                        true
                     }
-                    ResizeMsg::Y(offset_y) => {
-                        if let Some(coord) = self.resizing.clone() {
-                            resize_diff(self, coord, offset_y, 0.0);
-                            self.mouse_cursor = CursorType::NS;
+                    _ => false,
+                }
+            }
+
+            Action::FillEnd => {
+                if let Some((source, target)) = self.filling.take() {
+                    let targets = fill_targets(&source, &target);
+                    let grammars = &self.get_session().grammars;
+                    if let Some(locked) = targets.iter().find(|c| contains_locked(grammars, c)) {
+                        return self.update(Action::Alert(format! {
+                            "{} is locked", locked.to_string()
+                        }));
+                    }
+                    for dest in targets {
+                        if let Err(message) = move_grammar(self, source.clone(), dest) {
+                            return self.update(Action::Alert(message));
                         }
                     }
-                    ResizeMsg::End => {
-                        self.resizing = None;
-                        self.mouse_cursor = CursorType::Default;
+                }
+                true
+            }
+
+            Action::MakeMarkdown(coord) => {
+                // the text of an input cell becomes the markdown source
+                if let Some(g) = self.get_session_mut().grammars.get_mut(&coord) {
+                    if let Kind::Input(value) = g.kind.clone() {
+                        g.kind = Kind::Markdown(value);
+                        self.markdown_editor = Some(coord);
+                        return true;
+                    }
+                }
+                false
+            }
+
+            Action::ToggleMarkdownEdit(coord) => {
+                if self.markdown_editor.as_ref() == Some(&coord) {
+                    self.markdown_editor = None;
+                } else {
+                    self.markdown_editor = Some(coord);
+                }
+                true
+            }
+
+            Action::ShowGoTo(open) => {
+                self.goto_open = open;
+                if open {
+                    dom_focus_later(&self.goto_node_ref);
+                }
+                true
+            }
+
+            Action::GoTo(target) => {
+                self.goto_open = false;
+                match parse_cell_name(&target) {
+                    Ok(coord) if self.get_session().grammars.contains_key(&coord) => {
+                        self.update(Action::SetActiveCell(coord))
+                    }
+                    Ok(coord) => self.update(Action::Alert(format! {
+                        "there is no cell at {}", coord.to_string()
+                    })),
+                    Err(message) => self.update(Action::Alert(message)),
+                };
+                true
+            }
+
+            Action::EditNote(coord) => {
+                self.note_editor = Some(coord);
+                true
+            }
+
+            Action::SetNote(coord, note) => {
+                // saving an empty note removes it
+                if note.trim() == "" {
+                    return self.update(Action::ClearNote(coord));
+                }
+                self.get_session_mut().notes.insert(coord, note);
+                self.note_editor = None;
+                true
+            }
+
+            Action::SetInteractiveValue(coord, value) => {
+                if is_locked(&self.get_session().grammars, &coord) {
+                    return self.update(Action::Alert(format! {
+                        "{} is locked", coord.to_string()
+                    }));
+                }
+                if let Some(g) = self.get_session_mut().grammars.get_mut(&coord) {
+                    if let Kind::Interactive(name, interactive) = &g.kind {
+                        g.kind = Kind::Interactive(
+                            name.clone(),
+                            with_interactive_value(interactive, value),
+                        );
+                    }
+                }
+                self.style_cache.invalidate(&coord);
+                self.record_history(&coord);
+                let observers = &self.observers;
+                let updated = propagate_change(
+                    &mut self.sessions[self.current_session_index].grammars,
+                    observers,
+                    &coord,
+                );
+                for updated_coord in &updated {
+                    self.style_cache.invalidate(updated_coord);
+                }
+                true
+            }
+
+            Action::ToggleSparkline(coord) => {
+                if !self.sparklines.remove(&coord) {
+                    self.sparklines.insert(coord);
+                }
+                true
+            }
+
+            Action::ToggleLock(coord) => {
+                if let Some(g) = self.get_session_mut().grammars.get_mut(&coord) {
+                    g.style.locked = !g.style.locked;
+                }
+                true
+            }
+
+            Action::ToggleCollapse(coord) => {
+                match self.get_session().grammars.get(&coord) {
+                    Some(Grammar { kind: Kind::Grid(_), .. }) => (),
+                    _ => return false,
+                }
+                let collapsed = &mut self.get_session_mut().collapsed;
+                if !collapsed.remove(&coord) {
+                    collapsed.insert(coord.clone());
+                    // the cells inside a collapsed grid aren't shown, so the grid itself
+                    // becomes the active cell instead
+                    if self
+                        .active_cell
+                        .as_ref()
+                        .map_or(false, |c| c.common_ancestor(&coord) == Some(coord.clone()))
+                    {
+                        self.active_cell = Some(coord.clone());
+                        self.first_select_cell = None;
+                        self.last_select_cell = None;
                     }
                 }
                 true
             }
 
+            Action::ClearNote(coord) => {
+                self.get_session_mut().notes.remove(&coord);
+                self.note_editor = None;
+                true
+            }
+
             Action::SetCursorType(cursor_type) => {
                 self.mouse_cursor = cursor_type;
                 true
             }
 
             Action::Lookup(source_coord, lookup_type) => {
-                match lookup_type {
-                    Lookup::Cell(dest_coord) => {
-                        move_grammar(self, source_coord, dest_coord.clone());
-                    }
-                    _ => (),
+                let grammars = &self.get_session().grammars;
+                let value = evaluate_lookup(grammars, &lookup_type);
+                let sources = lookup_sources(grammars, &lookup_type);
+                register_lookup_sources(&mut self.lookups, &sources);
+                observe(&mut self.observers, &source_coord, sources);
+                if let Some(g) = self.get_session_mut().grammars.get_mut(&source_coord) {
+                    g.kind = Kind::Lookup(value, Some(lookup_type));
                 }
-                false
+                // lookups that show this lookup's value need to catch up too
+                let observers = &self.observers;
+                propagate_change(
+                    &mut self.sessions[self.current_session_index].grammars,
+                    observers,
+                    &source_coord,
+                );
+                true
             }
             Action::ToggleLookup(coord) => {
                 match self.get_session_mut().grammars.get_mut(&coord) {
-                    Some(
-                        g
-                        @
-                        Grammar {
-                            kind: Kind::Input(_),
-                            ..
-                        },
-                    ) => {
-                        g.kind = Kind::Lookup("".to_string(), None);
-                    }
-                    Some(
-                        g
-                        @
-                        Grammar {
-                            kind: Kind::Lookup(_, _),
-                            ..
-                        },
-                    ) => {
-                        g.kind = Kind::Input("".to_string());
-                    }
-                    _ => {
-                        info! { "[Action::ToggleLookup] cannot togridle non-Input/Lookup kind of grammar" }
-                    }
+                    Some(g) => match toggled_lookup_kind(&g.kind) {
+                        Some(kind) => g.kind = kind,
+                        None => {
+                            info! { "[Action::ToggleLookup] cannot togridle non-Input/Lookup kind of grammar" }
+                        }
+                    },
+                    None => (),
                 };
                 true
             }
@@ -1508,6 +4397,14 @@ impl Component for Model {
              * 3) Defining how grammars connect with respective drivers and have values evaluated
              *    and passed back to the interface.
              */
+            Action::AddDefnRule(coord) => {
+                add_defn_rule(&mut self.get_session_mut().grammars, &coord)
+            }
+
+            Action::RemoveDefnRule(coord, index) => {
+                remove_defn_rule(&mut self.get_session_mut().grammars, &coord, index)
+            }
+
             Action::AddDefinition(coord, defn_name) => {
                 // adds a new grammar or sub-grammar to the meta
                 let max_a_row =
@@ -1528,22 +4425,245 @@ impl Component for Model {
                 let defn_coord = Coordinate::child_of(&(coord!("meta")), defn_meta_sub_coord);
                 info! {"Adding Definition: {} to {}", coord.to_string(), defn_coord.to_string()};
 
-                move_grammar(self, coord, defn_coord.clone());
-                // give moved grammar name {defn_name} as specified in "Add Definition" button
-                if let Some(g) = self.get_session_mut().grammars.get_mut(&defn_coord) {
-                    g.name = defn_name;
-                }
+                if let Err(message) = move_grammar(self, coord, defn_coord.clone()) {
+                    return self.update(Action::Alert(message));
+                }
+                // give moved grammar name {defn_name} as specified in "Add Definition" button
+                if let Some(g) = self.get_session_mut().grammars.get_mut(&defn_coord) {
+                    g.name = defn_name;
+                }
+                true
+            }
+
+            Action::RemoveDefinition(defn) => {
+                let dependents = definition_dependents(&self.get_session().grammars, &defn);
+                if !dependents.is_empty() {
+                    let dependents: Vec<String> =
+                        dependents.iter().map(|c| c.to_string()).collect();
+                    return self.update(Action::Alert(format! {
+                        "{} can't be removed, it's still referred to by {}",
+                        defn.to_string(),
+                        dependents.join(", ")
+                    }));
+                }
+                let session = self.get_session_mut();
+                if !remove_definition(&mut session.grammars, &mut session.meta, &defn) {
+                    return false;
+                }
+                shift_line_sizes(
+                    &mut self.row_heights,
+                    &mut self.col_widths,
+                    &coord!("meta"),
+                    defn.row().get(),
+                    -1,
+                    true,
+                );
+                self.forced_suggestions = None;
+                self.rebuild_observers();
+                // `meta_suggestions` catch up at the end of `update`
+                true
+            }
+
+            Action::TogridleShiftKey(togridle) => {
+                self.shift_key_pressed = togridle;
+                false
+            }
+
+            Action::ToggleHelpOverlay => {
+                self.help_open = !self.help_open;
+                true
+            }
+
+            Action::ToggleCoordinateOverlay => {
+                self.show_coords = !self.show_coords;
+                true
+            }
+
+            Action::ToggleCellLockNavigation => {
+                self.tab_skips_locked = !self.tab_skips_locked;
+                true
+            }
+
+            Action::ChangeRecreateSize(row_col) => {
+                self.recreate_row_cols = row_col;
+                // the "Reset to" button and the other size input read the size from the view
+                true
+            }
+
+            Action::ExportHTML(coord) => {
+                let session = self.get_session();
+                if let Some((_, cols)) = grid_size(&session.grammars, &coord) {
+                    // the columns are only headed if some of them were given a label
+                    let columns =
+                        (1..=cols).map(|col| Col(coord.clone(), NonZeroU32::new(col).unwrap()));
+                    let headers: Vec<String> =
+                        if columns.clone().any(|col| session.column_labels.contains_key(&col)) {
+                            columns.map(|col| session.column_label(&col)).collect()
+                        } else {
+                            vec![]
+                        };
+                    let html = grid_to_html(&session.grammars, &coord, &headers);
+                    dom_download(&format! {"{}.html", session.title}, &html, "text/html");
+                    return false;
+                }
+                self.update(Action::Alert(format! {
+                    "{} is not a grid, so it can't be exported",
+                    coord.to_string()
+                }))
+            }
+
+            Action::ValidateGrammarMap(repair) => {
+                let problems = check_grammar_map(&self.get_session().grammars, &self.view_root);
+                if problems.is_empty() {
+                    return self.update(Action::Alert("the grammar map is consistent".to_string()));
+                }
+                let message = format! {
+                    "the grammar map has {} missing cells and {} orphaned grammars{}",
+                    problems.missing.len(),
+                    problems.orphans.len(),
+                    if repair { " (repaired)" } else { "" },
+                };
+                if repair {
+                    repair_grammar_map(&mut self.get_session_mut().grammars, &problems);
+                    self.rebuild_observers();
+                }
+                self.update(Action::Alert(message));
+                repair
+            }
+
+            Action::ChangeFormatRuleDraft(draft) => {
+                // re-rendered so the rule builder's callbacks see the new draft
+                self.format_rule_draft = draft;
+                true
+            }
+
+            Action::AddConditionalFormat(format) => {
+                self.get_session_mut().conditional_formats.push(format);
+                true
+            }
+
+            Action::RemoveConditionalFormat(index) => {
+                let formats = &mut self.get_session_mut().conditional_formats;
+                if index >= formats.len() {
+                    return false;
+                }
+                formats.remove(index);
+                true
+            }
+
+            Action::SetInsertCount(count) => {
+                self.insert_count = count.max(1);
+                true
+            }
+
+            Action::ChangeDefaultNestedGrid(row_col) => {
+                self.default_nested_row_cols = row_col;
+                false
+            }
+
+            Action::SetFontFamily(coord, font_family) => {
+                for cell in self.style_targets(coord) {
+                    if let Some(g) = self.get_session_mut().grammars.get_mut(&cell) {
+                        g.style.font_family = font_family.clone();
+                    }
+                }
+                true
+            }
+
+            Action::SetFontSize(coord, font_size) => {
+                for cell in self.style_targets(coord) {
+                    if let Some(g) = self.get_session_mut().grammars.get_mut(&cell) {
+                        g.style.font_size = font_size;
+                    }
+                }
+                true
+            }
+
+            Action::SetBackgroundColor(coord, color) => {
+                // if the cell is part of the current selection, color the whole selection
+                for cell in self.style_targets(coord) {
+                    if let Some(g) = self.get_session_mut().grammars.get_mut(&cell) {
+                        g.style.background_color = color.clone();
+                    }
+                }
+                true
+            }
+
+            Action::ClearFormatting => {
+                let mut skipped_locked = false;
+                for cell in self.format_targets() {
+                    if is_locked(&self.get_session().grammars, &cell) {
+                        skipped_locked = true;
+                        continue;
+                    }
+                    if let Some(g) = self.get_session_mut().grammars.get_mut(&cell) {
+                        g.style = g.style.cleared();
+                    }
+                }
+                if skipped_locked {
+                    self.update(Action::Alert("locked cells were left as they are".to_string()));
+                }
+                true
+            }
+
+            Action::ToggleBold => {
+                let cells = self.format_targets();
+                toggle_style(
+                    &mut self.get_session_mut().grammars,
+                    &cells,
+                    |style| style.font_weight >= 600,
+                    |style, bold| style.font_weight = if bold { 700 } else { 400 },
+                );
+                true
+            }
+
+            Action::ToggleItalic => {
+                let cells = self.format_targets();
+                toggle_style(
+                    &mut self.get_session_mut().grammars,
+                    &cells,
+                    |style| style.font_style == "italic",
+                    |style, italic| {
+                        style.font_style = if italic { "italic" } else { "normal" }.to_string()
+                    },
+                );
                 true
             }
 
-            Action::TogridleShiftKey(togridle) => {
-                self.shift_key_pressed = togridle;
-                false
+            Action::ToggleUnderline => {
+                let cells = self.format_targets();
+                toggle_style(
+                    &mut self.get_session_mut().grammars,
+                    &cells,
+                    |style| style.text_decoration == "underline",
+                    |style, underline| {
+                        style.text_decoration =
+                            if underline { "underline" } else { "none" }.to_string()
+                    },
+                );
+                true
             }
 
-            Action::ChangeDefaultNestedGrid(row_col) => {
-                self.default_nested_row_cols = row_col;
-                false
+            Action::ToggleWrapText(coord) => {
+                let cells = self.style_targets(coord);
+                toggle_style(
+                    &mut self.get_session_mut().grammars,
+                    &cells,
+                    |style| style.wrap_text,
+                    |style, wrap_text| style.wrap_text = wrap_text,
+                );
+                true
+            }
+
+            Action::SetTextAlign(coord, text_align) => {
+                // same as background colors, alignment applies to the whole selection
+                let cells = self.style_targets(coord);
+                for cell in cells {
+                    if let Some(g) = self.get_session_mut().grammars.get_mut(&cell) {
+                        g.style.text_align = text_align.clone();
+                    }
+                }
+                true
             }
 
             Action::ShowContextMenu(pos) => {
@@ -1562,6 +4682,102 @@ impl Component for Model {
                 false
             }
 
+            Action::SetSearchQuery(query) => {
+                self.search_query = query;
+                false
+            }
+
+            Action::SetSearchReplacement(replacement) => {
+                self.search_replacement = replacement;
+                false
+            }
+
+            Action::FindReplace { replace_all } => {
+                let query = self.search_query.clone();
+                let replacement = self.search_replacement.clone();
+                // matching is case-insensitive, and only looks at Input & Text grammars
+                let mut matches: Vec<Coordinate> = self
+                    .get_session()
+                    .grammars
+                    .iter()
+                    .filter_map(|(c, g)| match &g.kind {
+                        Kind::Input(value) | Kind::Text(value)
+                            if find_case_insensitive(value, &query).is_some() =>
+                        {
+                            Some(c.clone())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                matches.sort_by(|a, b| a.row_cols.cmp(&b.row_cols));
+
+                if replace_all {
+                    let grammars = &self.get_session().grammars;
+                    let locked = matches.iter().filter(|c| is_locked(grammars, c)).count();
+                    matches.retain(|c| !is_locked(grammars, c));
+                    for c in matches.iter() {
+                        if let Some(g) = self.get_session_mut().grammars.get_mut(c) {
+                            g.kind = match &g.kind {
+                                Kind::Input(value) => Kind::Input(
+                                    replace_case_insensitive(value, &query, &replacement)
+                                        .unwrap_or(value.clone()),
+                                ),
+                                Kind::Text(value) => Kind::Text(
+                                    replace_case_insensitive(value, &query, &replacement)
+                                        .unwrap_or(value.clone()),
+                                ),
+                                kind => kind.clone(),
+                            };
+                        }
+                    }
+                    let skipped = if locked > 0 {
+                        format! {", {} locked cell(s) were left as they are", locked}
+                    } else {
+                        String::new()
+                    };
+                    self.update(Action::Alert(format! {
+                        "Replaced \"{}\" in {} cell(s){}", query, matches.len(), skipped
+                    }));
+                    true
+                } else {
+                    // highlight the next match after the active cell, wrapping around to the first
+                    let next_match = matches
+                        .iter()
+                        .find(|c| {
+                            self.active_cell
+                                .as_ref()
+                                .map_or(true, |active| c.row_cols > active.row_cols)
+                        })
+                        .or(matches.first())
+                        .cloned();
+                    match next_match {
+                        Some(c) => self.update(Action::SetActiveCell(c)),
+                        None => self.update(Action::Alert(format! {
+                            "No cells match \"{}\"", query
+                        })),
+                    }
+                }
+            }
+
+            Action::SetEditorOutput(editor, output_str) => {
+                if output_str.trim().is_empty() {
+                    self.get_session_mut().editor_outputs.remove(&editor);
+                    return true;
+                }
+                match output_str.trim().parse::<Coordinate>() {
+                    Ok(output) if output == editor => self.update(Action::Alert(
+                        "[Action:SetEditorOutput] an editor can't output into itself".to_string(),
+                    )),
+                    Ok(output) => {
+                        self.get_session_mut().editor_outputs.insert(editor, output);
+                        true
+                    }
+                    Err(e) => self.update(Action::Alert(format! {
+                        "[Action:SetEditorOutput] {}", e
+                    })),
+                }
+            }
+
             Action::NewEditor => {
                 match self
                     .active_cell
@@ -1586,9 +4802,13 @@ impl Component for Model {
             }
 
             Action::RunPython(__code, output_coord) => {
-                let editor_id = format! {
-                    "codemirror-{}",
-                    self.active_cell.clone().map(|c| c.to_string()).unwrap_or(String::new()),
+                let editor_id = match &self.active_cell {
+                    Some(active_cell) => format! {"codemirror-{}", active_cell.to_string()},
+                    None => {
+                        return self.update(Action::Alert(
+                            "[Action:RunPython] no active cell to run code from".to_string(),
+                        ))
+                    }
                 };
                 // TODO: later, find a way to parse the grammar values into valid python
                 // expressions if that's what the grammars represent.
@@ -1604,30 +4824,70 @@ impl Component for Model {
                 let grammars = stdweb::Object::try_from(string_map).expect(
                     "[Action:RunPython] Grammar Map can be serialized into Javascript Object",
                 );
-                let return_value: String = js! {
+                // plain cell values for scripts that just want `values["A1"]`, passed as JSON so
+                // that numbers stay numbers
+                let values = serde_json::Value::Object(python_values(&self.get_session().grammars))
+                    .to_string();
+                // exceptions (from python, or a missing editor) are caught and passed back as
+                // `error`, so a bad script doesn't take the whole app down with it
+                let result = js! {
                     let editorEl = document.getElementById(@{editor_id.clone()});
-                    let code = editorEl.value;
-                    pyodide.globals.grammars = @{grammars};
-                    return pyodide.runPython(code);
-                }
-                .try_into()
-                .unwrap();
-                if let Some(
-                    g
-                    @
-                    Grammar {
-                        kind: Kind::Input(_),
-                        ..
-                    },
-                ) = self.get_session_mut().grammars.get_mut(&output_coord)
-                {
-                    g.kind = Kind::Input(return_value);
+                    if (editorEl === null) {
+                        return { error: "no code editor found for " + @{editor_id} };
+                    }
+                    try {
+                        pyodide.globals.grammars = @{grammars};
+                        pyodide.globals.values = JSON.parse(@{values});
+                        let output = pyodide.runPython(editorEl.value);
+                        return { output: output === undefined ? "" : String(output) };
+                    } catch (e) {
+                        return { error: String(e) };
+                    }
+                };
+                let error = js! { return @{&result}.error; }.into_string();
+                let output = js! { return @{&result}.output; }.into_string();
+                let is_error = error.is_some();
+                let value = error.or(output).unwrap_or_default();
+                match self.get_session_mut().grammars.get_mut(&output_coord) {
+                    Some(g @ Grammar { kind: Kind::Input(_), .. }) => g.kind = Kind::Input(value),
+                    _ => {
+                        return self.update(Action::Alert(format! {
+                            "[Action:RunPython] {} isn't an input cell, python output: {}",
+                            output_coord.to_string(), value
+                        }))
+                    }
+                }
+                if is_error {
+                    self.python_errors.insert(output_coord);
+                } else {
+                    self.record_history(&output_coord);
+                    self.python_errors.remove(&output_coord);
                 }
 
-                false
+                true
             }
         };
 
+        self.lite_mode = use_lite_mode(self.estimate_render_cost());
+
+        // a cell that's now something other than a number starts its history over
+        if !self.cell_history.is_empty() {
+            let grammars = &self.sessions[self.current_session_index].grammars;
+            retain_history(&mut self.cell_history, grammars);
+        }
+
+        if changes_session {
+            self.changes_since_autosave += 1;
+            // an auto-save could overwrite the one that's being offered for recovery, so there
+            // aren't any until the user has restored or discarded it
+            if self.autosave_interval > 0
+                && self.changes_since_autosave >= self.autosave_interval
+                && self.recovered_session.is_none()
+            {
+                self.autosave();
+            }
+        }
+
         self.meta_suggestions = self
             .query_col(coord_col!("meta", "A"))
             .iter()
@@ -1649,10 +4909,20 @@ impl Component for Model {
     }
 
     fn view(&self) -> Html {
-        let is_resizing = self.resizing.is_some();
+        let is_resizing = self.resizing.is_some() || self.resizing_line.is_some();
+        let has_secondary_selections = !self.secondary_selections.is_empty();
+        let help_open = self.help_open;
+        let is_filling = self.filling.is_some();
         // for integration tests
-        let serialized_model = serde_json::to_string(&self.get_session()).unwrap();
-        let zoom = format! { "zoom: {};", &self.zoom };
+        let serialized_model = self.serialized_session();
+        // the gridline color is a CSS variable, so cells pick it up without restyling each one
+        let wrapper_style = format! {
+            "zoom: {}; --gridline-color: {};",
+            &self.zoom, self.theme().gridline_color(&self.get_session().gridline_color)
+        };
+        let app_class = if self.get_session().dark_mode { "app dark-mode" } else { "app" };
+        let grid_wrapper_class =
+            grid_wrapper_class(self.lite_mode, self.get_session().show_gridlines);
         let cursor = format! { "cursor: {};", match self.mouse_cursor {
             CursorType::NS => "ns-resize",
             CursorType::EW => "ew-resize",
@@ -1663,8 +4933,13 @@ impl Component for Model {
             (r.get(), c.get())
         };
         let active_cell = self.active_cell.clone().expect("active_cell should be set");
+        // merging needs a selection of more than one cell
+        let can_merge = match (&self.first_select_cell, &self.last_select_cell) {
+            (Some(first), Some(last)) => first != last,
+            _ => false,
+        };
         html! {
-            <div
+            <div class=app_class
             onclick=self.link.callback(move |e: ClickEvent| {
                 Action::HideContextMenu
             })>
@@ -1674,17 +4949,64 @@ impl Component for Model {
                 { view_menu_bar(&self) }
 
                 { view_tab_bar(&self) }
-                <div class="main">
+                <div class="main"
+                    onscroll=self.link.callback(|_: ScrollEvent| {
+                        let (x, y) = dom_scroll_offset();
+                        Action::Scroll(x, y)
+                    })>
 
-                    <div id="grammars" class="grid-wrapper" style={zoom}
+                    <div id="grammars" class=grid_wrapper_class style={wrapper_style}
                         // Global Keyboard shortcuts
                         onkeypress=self.link.callback(move |e : KeyPressEvent| {
-                            let keys = key_combination(&e);
-                            match keys.deref() {
+                            // only the combinations listed in SHORTCUTS do anything here
+                            let shortcut = match find_shortcut(&key_combination(&e)) {
+                                Some(shortcut) => shortcut,
+                                None => return Action::Noop,
+                            };
+                            let typing = e.target().map_or(false, |t| dom_is_text_entry(&t));
+                            match shortcut {
                                 // Tab (navigation) is handled in onkeydown
                                 "Ctrl-g" => {
                                     Action::AddNestedGrid(active_cell.clone(), (default_row, default_col))
                                 }
+                                "Ctrl-b" => {
+                                    e.prevent_default();
+                                    Action::ToggleBold
+                                }
+                                "Ctrl-i" => {
+                                    e.prevent_default();
+                                    Action::ToggleItalic
+                                }
+                                "Ctrl-u" => {
+                                    e.prevent_default();
+                                    Action::ToggleUnderline
+                                }
+                                "Ctrl-j" => {
+                                    e.prevent_default();
+                                    Action::ShowGoTo(true)
+                                }
+                                "Ctrl-l" => {
+                                    e.prevent_default();
+                                    Action::ToggleLookup(active_cell.clone())
+                                }
+                                "Ctrl-m" if can_merge => {
+                                    e.prevent_default();
+                                    Action::MergeCells()
+                                }
+                                "Ctrl-Shift-M" => {
+                                    e.prevent_default();
+                                    Action::UnmergeCells(active_cell.clone())
+                                }
+                                "Ctrl-/" => {
+                                    e.prevent_default();
+                                    Action::ToggleHelpOverlay
+                                }
+                                // text that's being typed keeps the browser's select-all
+                                "Ctrl-a" if !typing => {
+                                    // instead of the browser selecting all the text on the page
+                                    e.prevent_default();
+                                    Action::SelectAll
+                                }
                                 _ => Action::Noop
                             }
                         })
@@ -1697,6 +5019,10 @@ impl Component for Model {
                         onkeydown=self.link.callback(move |e: KeyDownEvent| {
                             if e.key() == "Shift" {
                                 Action::TogridleShiftKey(true)
+                            } else if e.key() == "Escape" && help_open {
+                                Action::ToggleHelpOverlay
+                            } else if e.key() == "Escape" && has_secondary_selections {
+                                Action::ClearSecondarySelections
                             } else {
                                 Action::Noop
 
@@ -1713,16 +5039,19 @@ impl Component for Model {
                         onmouseup=self.link.callback(move |e: MouseUpEvent| {
                             if is_resizing.clone() {
                                 Action::Resize(ResizeMsg::End)
+                            } else if is_filling {
+                                Action::FillEnd
                             } else {
                                 Action::Noop
                             }
                         })
                         onmousemove=self.link.callback(move |e: MouseMoveEvent| {
                             if is_resizing.clone() {
+                                let position = (e.client_x() as f64, e.client_y() as f64);
                                 if e.movement_x().abs() > e.movement_y().abs() {
-                                    Action::Resize(ResizeMsg::X(e.movement_x() as f64))
+                                    Action::Resize(ResizeMsg::X(e.movement_x() as f64, position))
                                 } else {
-                                    Action::Resize(ResizeMsg::Y(e.movement_y() as f64))
+                                    Action::Resize(ResizeMsg::Y(e.movement_y() as f64, position))
                                 }
                             } else {
                                 Action::Noop
@@ -1733,8 +5062,15 @@ impl Component for Model {
                         })*/>
                         { view_grammar(&self, coord!{"root"}) }
                         { view_context_menu(&self) }
+                        { view_resize_preview(&self) }
+                        { view_loading_indicator(&self) }
+                        { view_goto(&self) }
+                        { view_autosave_recovery(&self) }
+                        { view_help_overlay(&self) }
                     </div>
                 </div>
+
+                { view_status_bar(&self) }
                 <input id="integration-test-model-dump" style="width: 0;height: 0;">{serialized_model}</input>
 
             </div>
@@ -1742,6 +5078,303 @@ impl Component for Model {
     }
 }
 
+// the values of the top-level cells that python code gets as `values`, keyed by their
+// coordinate without the "root-" prefix (e.g. "A1", or "A1-B2" for nested cells)
+fn python_values(
+    grammars: &HashMap<Coordinate, Grammar>,
+) -> serde_json::Map<String, serde_json::Value> {
+    grammars
+        .iter()
+        .filter_map(|(coord, grammar)| {
+            let key = coord.to_string().strip_prefix("root-")?.to_string();
+            let value = match &grammar.kind {
+                Kind::Input(value) | Kind::Text(value) => serde_json::Value::from(value.clone()),
+                Kind::Number(number, _) => serde_json::Value::from(*number),
+                _ => return None,
+            };
+            Some((key, value))
+        })
+        .collect()
+}
+
+// number of changes to the session between auto-saves, unless it's changed in the settings
+pub const DEFAULT_AUTOSAVE_INTERVAL: usize = 20;
+
+// localStorage only holds around 5MB per origin, so larger sessions aren't auto-saved
+pub const MAX_AUTOSAVE_BYTES: usize = 4_000_000;
+
+const AUTOSAVE_KEY_PREFIX: &str = "ise-autosave:";
+
+// auto-saves are stored per session, under a number that's picked when it's first auto-saved.
+// Sessions can share a title, so the title can't be used.
+fn autosave_key(id: u32) -> String {
+    format! {"{}{}", AUTOSAVE_KEY_PREFIX, id}
+}
+
+fn is_autosave_key(key: &str) -> bool {
+    key.starts_with(AUTOSAVE_KEY_PREFIX)
+}
+
+// an auto-save key that none of `keys` (the keys already in localStorage) has
+fn unused_autosave_key(keys: &[String]) -> String {
+    let last_id = keys
+        .iter()
+        .filter_map(|key| key.strip_prefix(AUTOSAVE_KEY_PREFIX)?.parse::<u32>().ok())
+        .max();
+    autosave_key(last_id.map_or(1, |id| id + 1))
+}
+
+// the first auto-saved session in localStorage that can still be loaded, if there is one
+fn read_autosave() -> Option<Session> {
+    let storage = window().local_storage();
+    (0..storage.len())
+        .filter_map(|index| storage.key(index))
+        .filter(|key| is_autosave_key(key))
+        .find_map(|key| {
+            let mut session = Session::from_ise_bytes(storage.get(&key)?.as_bytes()).ok()?;
+            session.autosave_key = Some(key);
+            Some(session)
+        })
+}
+
+// classes of the element that wraps the sheet
+fn grid_wrapper_class(lite_mode: bool, show_gridlines: bool) -> String {
+    let mut classes = vec!["grid-wrapper"];
+    if lite_mode {
+        classes.push("lite");
+    }
+    if !show_gridlines {
+        classes.push("no-gridlines");
+    }
+    classes.join(" ")
+}
+
+// actions that leave the current session as it is, so its serialized dump (see
+// `Model::serialized_session`) is kept across them
+fn keeps_session(action: &Action) -> bool {
+    match action {
+        Action::Noop
+        | Action::Alert(_)
+        | Action::SetActiveCell(_)
+        | Action::CommitInput(_)
+        | Action::Navigate(_, _)
+        | Action::SetCursorType(_)
+        | Action::ShowContextMenu(_)
+        | Action::HideContextMenu
+        | Action::Select(_)
+        | Action::SelectAll
+        | Action::ToggleSecondarySelection(_)
+        | Action::ClearSecondarySelections
+        | Action::ClearActionLog
+        | Action::ToggleCoordinateOverlay
+        | Action::EditColumnHeader(_)
+        | Action::TogridleShiftKey(_)
+        | Action::Scroll(_, _)
+        | Action::ScrollToCell(_)
+        | Action::Resize(ResizeMsg::X(_, _))
+        | Action::Resize(ResizeMsg::Y(_, _)) => true,
+        _ => false,
+    }
+}
+
+// the cells of the selected range followed by the Ctrl+clicked ones that aren't in it
+fn merge_selections(
+    mut cells: Vec<Coordinate>,
+    secondary: &HashSet<Coordinate>,
+) -> Vec<Coordinate> {
+    let mut extra: Vec<Coordinate> =
+        secondary.iter().filter(|c| !cells.contains(c)).cloned().collect();
+    extra.sort_by(|a, b| a.row_cols.cmp(&b.row_cols));
+    cells.extend(extra);
+    cells
+}
+
+// pastes `clipboard` (tiled to `fill_rows` x `fill_cols`) into the cells of `parent` starting
+// at (top, left), like PasteSelection does. Only the kinds of the cells are pasted if `values`,
+// and only their styles otherwise. Grids are structure rather than values, so a grid never gets
+// pasted over a cell (or a value over a grid), and a grid's nested cells are left out.
+pub fn paste_special(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    clipboard: &Clipboard,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (fill_rows, fill_cols): (u32, u32),
+    values: bool,
+) {
+    for tile_row in 0..(fill_rows / clipboard.rows) {
+        for tile_col in 0..(fill_cols / clipboard.cols) {
+            let row_offset = top + tile_row * clipboard.rows - 1;
+            let col_offset = left + tile_col * clipboard.cols - 1;
+            for (path, source) in clipboard.grammars.iter().filter(|(path, _)| path.len() == 1) {
+                let (row, col) = path[0];
+                let dest_cell = Coordinate::child_of(
+                    parent,
+                    non_zero_u32_tuple((row_offset + row.get(), col_offset + col.get())),
+                );
+                let dest = match grammars.get_mut(&dest_cell) {
+                    Some(dest) => dest,
+                    None => continue,
+                };
+                if !values {
+                    dest.style = source.style.with_structure_of(&dest.style);
+                    continue;
+                }
+                // lookups and formulas are pasted as what they showed, not what they're made of
+                match (&source.kind, &dest.kind) {
+                    (Kind::Grid(_), _) | (_, Kind::Grid(_)) => {}
+                    (Kind::Lookup(value, _), _) => dest.kind = Kind::Input(value.clone()),
+                    (Kind::Input(value), _) if is_formula(value) => {
+                        let result = clipboard.results.get(&path[0]).cloned();
+                        dest.kind = Kind::Input(result.unwrap_or_default());
+                    }
+                    (kind, _) => dest.kind = kind.clone(),
+                }
+            }
+        }
+    }
+}
+
+// flips a style property (read with `is_set`, written with `set`) on `cells`. If any of them
+// doesn't have it yet they all get it, otherwise it's taken off all of them
+fn toggle_style<F, G>(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    cells: &[Coordinate],
+    is_set: F,
+    set: G,
+) where
+    F: Fn(&Style) -> bool,
+    G: Fn(&mut Style, bool),
+{
+    let all_set = cells
+        .iter()
+        .filter_map(|c| grammars.get(c))
+        .all(|g| is_set(&g.style));
+    for cell in cells {
+        if let Some(g) = grammars.get_mut(cell) {
+            set(&mut g.style, !all_set);
+        }
+    }
+}
+
+// actions that a file read comes back with. They count as finishing the read whether or not
+// the file turns out to be valid, so a bad file doesn't leave the busy indicator up
+fn finishes_loading(action: &Action) -> bool {
+    match action {
+        Action::LoadCSVFile(..)
+        | Action::LoadAppendedCSVRows(..)
+        | Action::LoadJSONFile(..)
+        | Action::LoadImageFile(..)
+        | Action::LoadXLSXFile(_)
+        | Action::LoadSession(..)
+        | Action::ImportDefinitions(_)
+        | Action::LoadDriverMainFile(_)
+        | Action::UploadDriverMiscFile(_) => true,
+        _ => false,
+    }
+}
+
+// actions that can't change how any cell is styled, so the style cache survives them. Anything
+// else might (sizes, merges, formats, switching sessions...) and clears it
+fn keeps_cell_styles(action: &Action) -> bool {
+    match action {
+        // typing invalidates just the cells it changes
+        Action::ChangeInput(_, _) => true,
+        _ => keeps_session(action),
+    }
+}
+
+// above this many rendered cells, the view switches to "lite" mode
+const LITE_MODE_CELL_THRESHOLD: usize = 2500;
+
+// counts the cells that get rendered for the grammar at `root`
+fn render_cost(
+    grammars: &HashMap<Coordinate, Grammar>,
+    collapsed: &HashSet<Coordinate>,
+    root: &Coordinate,
+) -> usize {
+    grammars
+        .keys()
+        .filter(|c| {
+            c.row_cols.starts_with(&root.row_cols) && is_rendered(grammars, collapsed, root, c)
+        })
+        .count()
+}
+
+// whether the cell at `coord` is rendered from `root`: it and every grid it's nested in (up to
+// `root`) have to be displayed and not in a hidden row or column, and none of those grids can be
+// collapsed
+fn is_rendered(
+    grammars: &HashMap<Coordinate, Grammar>,
+    collapsed: &HashSet<Coordinate>,
+    root: &Coordinate,
+    coord: &Coordinate,
+) -> bool {
+    let mut cell = coord.clone();
+    loop {
+        if !grammars.get(&cell).map_or(false, |g| g.style.display) {
+            return false;
+        }
+        let parent = match cell.parent() {
+            Some(parent) if cell != *root => parent,
+            _ => return true,
+        };
+        if collapsed.contains(&parent) {
+            return false;
+        }
+        if let Some(Grammar { style, .. }) = grammars.get(&parent) {
+            if style.hidden_rows.contains(&cell.row().get())
+                || style.hidden_cols.contains(&cell.col().get())
+            {
+                return false;
+            }
+        }
+        cell = parent;
+    }
+}
+
+fn use_lite_mode(render_cost: usize) -> bool {
+    render_cost > LITE_MODE_CELL_THRESHOLD
+}
+
+// the global keyboard shortcuts (see `onkeypress` in `Model::view`), which are also what the
+// help overlay (Ctrl-/) lists
+pub const SHORTCUTS: &[(&str, &str)] = &[
+    ("Ctrl-g", "Nest a grid in the active cell"),
+    ("Ctrl-b", "Bold"),
+    ("Ctrl-i", "Italic"),
+    ("Ctrl-u", "Underline"),
+    ("Ctrl-j", "Go to a cell by name"),
+    ("Ctrl-l", "Turn the active cell into a lookup (or back)"),
+    ("Ctrl-m", "Merge the selected cells"),
+    ("Ctrl-Shift-M", "Unmerge the active cell"),
+    ("Ctrl-a", "Select the grid of the active cell (again for the grid around it)"),
+    ("Ctrl-/", "Show these shortcuts"),
+];
+
+// shortcuts that are handled by the cells themselves, listed in the help overlay as well
+pub const CELL_SHORTCUTS: &[(&str, &str)] = &[
+    ("Tab / Shift-Tab", "Move to the next / previous cell"),
+    ("Enter", "Move to the cell below"),
+    ("Home / End", "Move to the start / end of the row"),
+    ("Ctrl-Home / Ctrl-End", "Move to the first / last cell of the grid"),
+    ("PageUp / PageDown", "Move up / down a page of rows"),
+    ("Alt-Enter", "New line inside the cell"),
+    ("Ctrl-Space", "Show suggestions"),
+    ("Backspace / Delete", "Clear the selected cells"),
+    ("Shift-click", "Select up to the clicked cell"),
+    ("Ctrl-click", "Add a cell to the selection"),
+    ("Escape", "Close suggestions, clear Ctrl-clicked cells"),
+];
+
+// the entry of SHORTCUTS for a combination from `key_combination`. Letters are matched
+// regardless of case, since Shift (or caps lock) changes the key that's reported
+fn find_shortcut(keys: &str) -> Option<&'static str> {
+    SHORTCUTS
+        .iter()
+        .map(|(shortcut, _)| *shortcut)
+        .find(|shortcut| shortcut.eq_ignore_ascii_case(keys))
+}
+
 fn key_combination<K>(e: &K) -> String
 where
     K: IKeyboardEvent,
@@ -1766,3 +5399,901 @@ fn focus_on_cell(c: &Coordinate) {
         }
     };
 }
+
+// puts the caret at `offset` (in characters) inside the cell at `c`, once the view has re-rendered
+// the cell's new value
+fn place_caret(c: &Coordinate, offset: usize) {
+    let cell_id = format! {"cell-{}", c.to_string()};
+    let offset = offset as u32;
+    js! {
+        setTimeout(function() {
+            try {
+                let text = document.getElementById(@{cell_id}).firstChild.firstChild;
+                let range = document.createRange();
+                range.setStart(text, Math.min(@{offset}, text.length));
+                range.collapse(true);
+                let selection = window.getSelection();
+                selection.removeAllRanges();
+                selection.addRange(range);
+            } catch (e) {}
+        }, 0);
+    };
+}
+
+// directory (relative to where the app is launched) that saved sessions are listed from
+pub const SESSIONS_DIRECTORY: &str = "sessions";
+
+// node_sys::fs doesn't bind the synchronous reads, so they're bound here the same way
+#[wasm_bindgen(module = "fs")]
+extern "C" {
+    #[wasm_bindgen(js_name = "readdirSync", catch)]
+    fn readdir_sync(path: &str) -> Result<Array, JsValue>;
+
+    #[wasm_bindgen(js_name = "readFileSync", catch)]
+    fn read_file_sync(path: &str) -> Result<Buffer, JsValue>;
+}
+
+// names of the entries in SESSIONS_DIRECTORY (through electron's node integration),
+// which is empty if the directory doesn't exist
+fn read_sessions_directory() -> Vec<String> {
+    match readdir_sync(SESSIONS_DIRECTORY) {
+        Ok(entries) => entries.iter().filter_map(|entry| entry.as_string()).collect(),
+        Err(e) => {
+            info! {"cannot read sessions directory: {:?}", e};
+            vec![]
+        }
+    }
+}
+
+// the contents of a file in SESSIONS_DIRECTORY
+fn read_saved_session(file_name: &str) -> Option<Vec<u8>> {
+    let path = format! {"{}/{}", SESSIONS_DIRECTORY, file_name};
+    match read_file_sync(&path) {
+        Ok(buffer) => Some(buffer.to_vec()),
+        Err(e) => {
+            info! {"cannot read session file: {:?}", e};
+            None
+        }
+    }
+}
+
+// offset of the caret inside the currently focused element, counted in characters
+fn caret_position() -> usize {
+    let offset: u32 = js! {
+        let selection = window.getSelection();
+        return selection ? selection.focusOffset : 0;
+    }
+    .try_into()
+    .unwrap_or(0);
+    offset as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionBuilder;
+
+    #[test]
+    fn test_saved_cursor() {
+        let mut session = Session {
+            title: "test".to_string(),
+            root: Grammar::default(),
+            meta: Grammar::default(),
+            grammars: hashmap! {
+                coord!("root-A1") => Grammar::default(),
+                coord!("root-B2") => Grammar::default(),
+            },
+            auto_complete: true,
+            column_labels: HashMap::new(),
+            version: SESSION_VERSION,
+            frozen_rows: 0,
+            frozen_cols: 0,
+            default_col_width: DEFAULT_COL_WIDTH,
+            default_row_height: DEFAULT_ROW_HEIGHT,
+            notes: HashMap::new(),
+            conditional_formats: vec![],
+            active_cell: Some(coord!("root-B2")),
+            first_select_cell: Some(coord!("root-A1")),
+            last_select_cell: Some(coord!("root-B2")),
+            collapsed: HashSet::new(),
+            show_gridlines: true,
+            gridline_color: DEFAULT_GRIDLINE_COLOR.to_string(),
+            dark_mode: false,
+            editor_outputs: HashMap::new(),
+            autosave_key: None,
+        };
+        assert_eq!(
+            saved_cursor(&session),
+            (coord!("root-B2"), Some(coord!("root-A1")), Some(coord!("root-B2")))
+        );
+        // cells that are gone from the map aren't restored
+        session.active_cell = Some(coord!("root-C3"));
+        session.last_select_cell = Some(coord!("root-C3"));
+        assert_eq!(saved_cursor(&session), (coord!("root-A1"), None, None));
+        session.active_cell = None;
+        assert_eq!(saved_cursor(&session).0, coord!("root-A1"));
+    }
+
+    #[test]
+    fn test_move_cell_state() {
+        let mut session = SessionBuilder::new().grid("root", 3, 1).build();
+        session.notes = hashmap! {
+            coord!("root-A1") => "stays".to_string(),
+            coord!("root-A2") => "deleted".to_string(),
+            coord!("root-A3") => "moves up".to_string(),
+        };
+        session.collapsed = hashset! { coord!("root-A2"), coord!("root-A3") };
+        move_cell_state(&mut session, |c| shifted_coord(c, &coord!("root"), 2, -1, true));
+        assert_eq!(
+            session.notes,
+            hashmap! {
+                coord!("root-A1") => "stays".to_string(),
+                coord!("root-A2") => "moves up".to_string(),
+            }
+        );
+        assert_eq!(session.collapsed, hashset! { coord!("root-A2") });
+    }
+
+    #[test]
+    fn test_load_session_bytes() {
+        let mut sessions = vec![SessionBuilder::new().grid("root", 2, 2).build()];
+        let before = sessions[0].to_ise_bytes();
+        // a file that can't be read changes nothing, whether it was opening a new tab or not
+        for &new_tab in &[true, false] {
+            assert!(load_session_bytes(&mut sessions, 0, b"not json at all", new_tab).is_err());
+            assert_eq!(sessions.len(), 1);
+            assert_eq!(sessions[0].to_ise_bytes(), before);
+        }
+
+        let loaded = SessionBuilder::new().grid("root", 3, 1).build().to_ise_bytes();
+        assert_eq!(load_session_bytes(&mut sessions, 0, &loaded, true), Ok(1));
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions[1].grammars.contains_key(&coord!("root-A3")));
+        assert_eq!(load_session_bytes(&mut sessions, 0, &loaded, false), Ok(0));
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions[0].grammars.contains_key(&coord!("root-A3")));
+        assert!(!sessions[0].grammars.contains_key(&coord!("root-B2")));
+    }
+
+    #[test]
+    fn test_sheet_session() {
+        let mut current = SessionBuilder::new().grid("root", 1, 1).build();
+        current.title = "current".to_string();
+        current.notes.insert(coord!("root-A1"), "not carried over".to_string());
+        current.grammars.insert(coord!("meta-A1"), Grammar::input("", "carried over"));
+        let sheets = parse_xlsx(include_bytes!("../test/XLSX_MERGED.xlsx")).unwrap();
+        let sessions: Vec<Session> =
+            sheets.into_iter().map(|sheet| sheet_session(&current, sheet)).collect();
+        assert_eq!(sessions.len(), 2);
+
+        let people = &sessions[0];
+        assert_eq!(people.title, "People");
+        assert!(people.notes.is_empty());
+        assert_eq!(grid_size(&people.grammars, &coord!("root")), Some((3, 2)));
+        assert_eq!(people.grammars[&coord!("root-B3")].kind, Kind::Input("41".to_string()));
+        // the merged header shows its value in the bottom-right cell, like the Merge action
+        let (a1, b1) = (&people.grammars[&coord!("root-A1")], &people.grammars[&coord!("root-B1")]);
+        assert_eq!((a1.style.col_span, a1.style.display), ((1, 2), false));
+        assert_eq!((b1.style.col_span, b1.style.display), ((1, 2), true));
+        assert_eq!(b1.kind, Kind::Input("name".to_string()));
+
+        let totals = &sessions[1];
+        assert_eq!(totals.title, "Totals");
+        assert_eq!(grid_size(&totals.grammars, &coord!("root")), Some((2, 2)));
+        assert_eq!(totals.grammars[&coord!("root-B2")].kind, Kind::Input("77".to_string()));
+        // the meta grammars of the current session come along
+        assert!(totals.grammars.contains_key(&coord!("meta-A1")));
+    }
+
+    #[test]
+    fn test_moved_index() {
+        // [a, b, c, d] with b moved to the end is [a, c, d, b]
+        assert_eq!(moved_index(1, 1, 3), 3);
+        assert_eq!(moved_index(0, 1, 3), 0);
+        assert_eq!(moved_index(2, 1, 3), 1);
+        assert_eq!(moved_index(3, 1, 3), 2);
+        // [a, b, c, d] with d moved to the front is [d, a, b, c]
+        assert_eq!(moved_index(3, 3, 0), 0);
+        assert_eq!(moved_index(0, 3, 0), 1);
+        assert_eq!(moved_index(2, 3, 0), 3);
+    }
+
+    #[test]
+    fn test_toggled_lookup_kind() {
+        assert_eq!(
+            toggled_lookup_kind(&Kind::Input("$".to_string())),
+            Some(Kind::Lookup("".to_string(), None))
+        );
+        assert_eq!(
+            toggled_lookup_kind(&Kind::Input("root-A1".to_string())),
+            Some(Kind::Lookup("root-A1".to_string(), None))
+        );
+        assert_eq!(
+            toggled_lookup_kind(&Kind::Lookup("root-A1".to_string(), None)),
+            Some(Kind::Input("root-A1".to_string()))
+        );
+        assert_eq!(toggled_lookup_kind(&Kind::Text("a".to_string())), None);
+    }
+
+    #[test]
+    fn test_set_line_hidden() {
+        let mut merged = Grammar::input("", "");
+        merged.style.col_span = (1, 2);
+        merged.style.row_span = (1, 1);
+        let mut covered = merged.clone();
+        covered.style.display = false;
+        let mut grammars = hashmap! {
+            coord!("root") => Grammar::as_grid(NonZeroU32::new(2).unwrap(), NonZeroU32::new(2).unwrap()),
+            coord!("root-A1") => covered,
+            coord!("root-B1") => merged,
+            coord!("root-A2") => Grammar::input("", "a"),
+            coord!("root-B2") => Grammar::input("", "b"),
+        };
+        let col_a = cells_in_col(&grammars, &coord_col!("root", "A"));
+        set_line_hidden(&mut grammars, &coord!("root"), &col_a, 1, false, true);
+        assert_eq!(grammars[&coord!("root")].style.hidden_cols, vec![1]);
+        assert!(!grammars[&coord!("root-A2")].style.display);
+        // hiding keeps the data
+        assert_eq!(grammars[&coord!("root-A2")].kind, Kind::Input("a".to_string()));
+
+        let row_2 = cells_in_row(&grammars, &coord_row!("root", "2"));
+        set_line_hidden(&mut grammars, &coord!("root"), &row_2, 2, true, true);
+        set_line_hidden(&mut grammars, &coord!("root"), &col_a, 1, false, false);
+        assert!(grammars[&coord!("root")].style.hidden_cols.is_empty());
+        // A1 is still covered by the merge, and A2 is still in a hidden row
+        assert!(!grammars[&coord!("root-A1")].style.display);
+        assert!(!grammars[&coord!("root-A2")].style.display);
+
+        set_line_hidden(&mut grammars, &coord!("root"), &row_2, 2, true, false);
+        assert!(grammars[&coord!("root-A2")].style.display);
+        assert!(grammars[&coord!("root-B2")].style.display);
+    }
+
+    #[test]
+    fn test_longest_common_grid_shape() {
+        // 1x1 into 2x2 gets tiled across the selection
+        assert_eq!(Model::longest_common_grid_shape((1, 1), (2, 2)), (2, 2));
+        // 2x2 into 3x3 gets pasted once
+        assert_eq!(Model::longest_common_grid_shape((2, 2), (3, 3)), (2, 2));
+        assert_eq!(Model::longest_common_grid_shape((1, 2), (3, 4)), (3, 4));
+    }
+
+    #[test]
+    fn test_query_row_col_sorted() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [
+                    g!(Grammar::input("", "A1")),
+                    g!(Grammar::input("", "B1")),
+                    g!(Grammar::input("", "C1"))
+                ],
+                [
+                    g!(Grammar::input("", "A2")),
+                    g!(Grammar::text("", "B2")),
+                    g!(Grammar::input("", "C2"))
+                ],
+                [
+                    g!(Grammar::input("", "A3")),
+                    g!(Grammar::input("", "B3")),
+                    g!(Grammar::input("", "C3"))
+                ]
+            ],
+        );
+        let row = cells_in_row(&grammars, &coord_row!("root", "2"));
+        assert_eq!(row, vec![coord!("root-A2"), coord!("root-B2"), coord!("root-C2")]);
+        let col = cells_in_col(&grammars, &coord_col!("root", "C"));
+        assert_eq!(col, vec![coord!("root-C1"), coord!("root-C2"), coord!("root-C3")]);
+
+        // only input values get collected
+        assert_eq!(join_input_values(&grammars, &row), "A2, C2");
+        assert_eq!(join_input_values(&grammars, &col), "C1, C2, C3");
+
+        let range = Lookup::parse_range("B2:C3").unwrap();
+        assert_eq!(
+            lookup_sources(&grammars, &range),
+            vec![coord!("root-B2"), coord!("root-C2"), coord!("root-B3"), coord!("root-C3")]
+        );
+        assert_eq!(evaluate_lookup(&grammars, &range), "C2, B3, C3");
+    }
+
+    #[test]
+    fn test_propagate_change() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![[
+                g!(Grammar::input("", "1")),
+                g!(Grammar::input("", "")),
+                g!(Grammar::input("", ""))
+            ]],
+        );
+        // B1 and C1 both look up A1
+        for dependent in vec![coord!("root-B1"), coord!("root-C1")] {
+            grammars.get_mut(&dependent).unwrap().kind =
+                Kind::Lookup("1".to_string(), Some(Lookup::Cell(coord!("root-A1"))));
+        }
+        let observers = build_observers(&grammars);
+        assert_eq!(observers[&coord!("root-A1")].len(), 2);
+
+        grammars.get_mut(&coord!("root-A1")).unwrap().kind = Kind::Input("42".to_string());
+        let mut updated = propagate_change(&mut grammars, &observers, &coord!("root-A1"));
+        updated.sort_by_key(|c| c.col());
+        assert_eq!(updated, vec![coord!("root-B1"), coord!("root-C1")]);
+        for dependent in vec![coord!("root-B1"), coord!("root-C1")] {
+            assert_eq!(
+                grammars[&dependent].kind,
+                Kind::Lookup("42".to_string(), Some(Lookup::Cell(coord!("root-A1"))))
+            );
+        }
+    }
+
+    #[test]
+    fn test_interactive_lookup() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![[
+                g!(Grammar::default_slider()),
+                g!(Grammar::default_toggle()),
+                g!(Grammar::input("", ""))
+            ]],
+        );
+        grammars.get_mut(&coord!("root-C1")).unwrap().kind =
+            Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-A1"))));
+        let observers = build_observers(&grammars);
+
+        let slider = Interactive::Slider(0.0, 0.0, 100.0);
+        assert_eq!(with_interactive_value(&slider, 42.5), Interactive::Slider(42.5, 0.0, 100.0));
+        assert_eq!(with_interactive_value(&slider, 250.0), Interactive::Slider(100.0, 0.0, 100.0));
+        assert_eq!(with_interactive_value(&slider, -3.0), Interactive::Slider(0.0, 0.0, 100.0));
+        assert_eq!(
+            with_interactive_value(&Interactive::Toggle(false), 1.0),
+            Interactive::Toggle(true)
+        );
+        assert_eq!(
+            with_interactive_value(&Interactive::Toggle(true), 0.0),
+            Interactive::Toggle(false)
+        );
+
+        grammars.get_mut(&coord!("root-A1")).unwrap().kind =
+            Kind::Interactive("".to_string(), with_interactive_value(&slider, 42.5));
+        assert_eq!(
+            propagate_change(&mut grammars, &observers, &coord!("root-A1")),
+            vec![coord!("root-C1")]
+        );
+        assert_eq!(
+            grammars[&coord!("root-C1")].kind,
+            Kind::Lookup("42.5".to_string(), Some(Lookup::Cell(coord!("root-A1"))))
+        );
+        assert_eq!(
+            evaluate_lookup(&grammars, &Lookup::Cell(coord!("root-B1"))),
+            "false".to_string()
+        );
+    }
+
+    #[test]
+    fn test_number_lookup() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![[
+                g!(Grammar::input("", "")),
+                g!(Grammar::input("", "3")),
+                g!(Grammar::input("", ""))
+            ]],
+        );
+        grammars.get_mut(&coord!("root-A1")).unwrap().kind =
+            Kind::Number(1250.5, NumberFormat::Currency);
+        grammars.get_mut(&coord!("root-C1")).unwrap().kind =
+            Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-A1"))));
+        let observers = build_observers(&grammars);
+
+        assert_eq!(evaluate_lookup(&grammars, &Lookup::Cell(coord!("root-A1"))), "1250.5");
+        let row = Lookup::Range {
+            parent: coord!("root"),
+            start: non_zero_u32_tuple((1, 1)),
+            end: non_zero_u32_tuple((1, 2)),
+        };
+        assert_eq!(evaluate_lookup(&grammars, &row), "1250.5, 3");
+
+        grammars.get_mut(&coord!("root-A1")).unwrap().kind =
+            Kind::Number(7.0, NumberFormat::Percent);
+        propagate_change(&mut grammars, &observers, &coord!("root-A1"));
+        assert_eq!(
+            grammars[&coord!("root-C1")].kind,
+            Kind::Lookup("7".to_string(), Some(Lookup::Cell(coord!("root-A1"))))
+        );
+    }
+
+    #[test]
+    fn test_lookup_colors() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![[
+                g!(Grammar::input("", "1")),
+                g!(Grammar::input("", "2")),
+                g!(Grammar::input("", "")),
+                g!(Grammar::input("", ""))
+            ]],
+        );
+        // D1 and C1 both look up A1, so they share its color
+        for dependent in vec![coord!("root-D1"), coord!("root-C1")] {
+            grammars.get_mut(&dependent).unwrap().kind =
+                Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-A1"))));
+        }
+        let mut lookups = build_lookups(&grammars);
+        assert_eq!(lookups, vec![coord!("root-A1")]);
+        assert_eq!(lookup_color(&lookups, &coord!("root-A1")), Some(LOOKUP_COLORS[0]));
+        assert_eq!(lookup_color(&lookups, &coord!("root-B1")), None);
+
+        // new sources go at the end, so the existing colors stay put
+        register_lookup_sources(&mut lookups, &[coord!("root-B1"), coord!("root-A1")]);
+        assert_eq!(lookups, vec![coord!("root-A1"), coord!("root-B1")]);
+        assert_eq!(lookup_color(&lookups, &coord!("root-B1")), Some(LOOKUP_COLORS[1]));
+
+        let many: Vec<Coordinate> = (1..=9)
+            .map(|row| Coordinate::child_of(&coord!("root"), non_zero_u32_tuple((row, 1))))
+            .collect();
+        assert_eq!(lookup_color(&many, &many[8]), Some(LOOKUP_COLORS[0]));
+    }
+
+    #[test]
+    fn test_propagate_change_cycle() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![[g!(Grammar::input("", "")), g!(Grammar::input("", ""))]],
+        );
+        // A1 and B1 look each other up
+        grammars.get_mut(&coord!("root-A1")).unwrap().kind =
+            Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-B1"))));
+        grammars.get_mut(&coord!("root-B1")).unwrap().kind =
+            Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-A1"))));
+        let observers = build_observers(&grammars);
+        let updated = propagate_change(&mut grammars, &observers, &coord!("root-A1"));
+        assert_eq!(updated, vec![coord!("root-B1")]);
+    }
+
+    #[test]
+    fn test_propagate_change_formula() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![[
+                g!(Grammar::input("", "1")),
+                g!(Grammar::input("", "=A1*2")),
+                g!(Grammar::input("", ""))
+            ]],
+        );
+        // C1 looks up B1, which is a formula on A1
+        grammars.get_mut(&coord!("root-C1")).unwrap().kind =
+            Kind::Lookup("=A1*2".to_string(), Some(Lookup::Cell(coord!("root-B1"))));
+        let observers = build_observers(&grammars);
+        assert_eq!(observers[&coord!("root-A1")], vec![coord!("root-B1")]);
+
+        grammars.get_mut(&coord!("root-A1")).unwrap().kind = Kind::Input("5".to_string());
+        let updated = propagate_change(&mut grammars, &observers, &coord!("root-A1"));
+        assert_eq!(updated, vec![coord!("root-B1"), coord!("root-C1")]);
+        // C1 shows B1's result, not its formula
+        assert_eq!(
+            grammars[&coord!("root-C1")].kind,
+            Kind::Lookup("10".to_string(), Some(Lookup::Cell(coord!("root-B1"))))
+        );
+        let row = Lookup::Range {
+            parent: coord!("root"),
+            start: non_zero_u32_tuple((1, 1)),
+            end: non_zero_u32_tuple((1, 2)),
+        };
+        assert_eq!(evaluate_lookup(&grammars, &row), "5, 10");
+    }
+
+    #[test]
+    fn test_definition_dependents() {
+        let grammars = hashmap! {
+            coord!("meta-A1") => Grammar::input("total", ""),
+            coord!("meta-A1-A1") => Grammar::input("", "1"),
+            coord!("meta-A2") => Grammar::input("other", ""),
+            coord!("root-A1") => Grammar::input("", "=meta-A1-A1*2"),
+            coord!("root-B1") => Grammar {
+                kind: Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("meta-A1")))),
+                ..Grammar::default()
+            },
+            coord!("root-C1") => Grammar {
+                kind: Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("meta-A2")))),
+                ..Grammar::default()
+            },
+            // references from inside the definition itself don't count
+            coord!("meta-A1-B1") => Grammar::input("", "=A1+1"),
+        };
+        assert_eq!(
+            definition_dependents(&grammars, &coord!("meta-A1")),
+            vec![coord!("root-A1"), coord!("root-B1")]
+        );
+        assert_eq!(definition_dependents(&grammars, &coord!("meta-A2")), vec![coord!("root-C1")]);
+        assert!(definition_dependents(&grammars, &coord!("meta-A3")).is_empty());
+    }
+
+    #[test]
+    fn test_merge_selections() {
+        let range = vec![coord!("root-A1"), coord!("root-B1")];
+        let secondary: HashSet<Coordinate> =
+            vec![coord!("root-C3"), coord!("root-B1"), coord!("root-A2")].into_iter().collect();
+        assert_eq!(
+            merge_selections(range.clone(), &secondary),
+            vec![coord!("root-A1"), coord!("root-B1"), coord!("root-A2"), coord!("root-C3")]
+        );
+        assert_eq!(merge_selections(range.clone(), &HashSet::new()), range);
+        assert_eq!(
+            merge_selections(vec![], &secondary),
+            vec![coord!("root-A2"), coord!("root-B1"), coord!("root-C3")]
+        );
+    }
+
+    #[test]
+    fn test_navigation_target() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid_entry(10, 3, |_, _| g!(Grammar::input("", ""))),
+        );
+        let target = |coord, navigation| navigation_target(&grammars, &coord, navigation, 4);
+        assert_eq!(target(coord!("root-B5"), Navigation::RowStart), Some(coord!("root-A5")));
+        assert_eq!(target(coord!("root-B5"), Navigation::RowEnd), Some(coord!("root-C5")));
+        assert_eq!(target(coord!("root-B5"), Navigation::GridStart), Some(coord!("root-A1")));
+        assert_eq!(target(coord!("root-B5"), Navigation::GridEnd), Some(coord!("root-C10")));
+        assert_eq!(target(coord!("root-B5"), Navigation::PageDown), Some(coord!("root-B9")));
+        assert_eq!(target(coord!("root-B5"), Navigation::PageUp), Some(coord!("root-B1")));
+        // pages stop at the edges of the grid
+        assert_eq!(target(coord!("root-B9"), Navigation::PageDown), Some(coord!("root-B10")));
+        assert_eq!(target(coord!("root-B2"), Navigation::PageUp), Some(coord!("root-B1")));
+        // root has no grid around it to move in
+        assert_eq!(target(coord!("root"), Navigation::RowEnd), None);
+
+        // a missing bottom-right cell falls back to the end of the last row
+        grammars.remove(&coord!("root-C10"));
+        assert_eq!(
+            navigation_target(&grammars, &coord!("root-A1"), Navigation::GridEnd, 4),
+            Some(coord!("root-B10"))
+        );
+    }
+
+    #[test]
+    fn test_select_all_bounds() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid_entry(3, 4, |_, _| g!(Grammar::input("", ""))),
+        );
+        build_grammar_map(
+            &mut grammars,
+            coord!("root-B2"),
+            grid_entry(2, 2, |_, _| g!(Grammar::input("", ""))),
+        );
+        let root_grid = (coord!("root-A1"), coord!("root-D3"));
+        let nested_grid = (coord!("root-B2-A1"), coord!("root-B2-B2"));
+        assert_eq!(select_all_bounds(&grammars, &coord!("root-C2"), None), Some(root_grid.clone()));
+        assert_eq!(
+            select_all_bounds(&grammars, &coord!("root-B2-A2"), None),
+            Some(nested_grid.clone())
+        );
+        // pressing it again selects the grid around the nested one...
+        assert_eq!(
+            select_all_bounds(&grammars, &coord!("root-B2-A2"), Some(nested_grid)),
+            Some(root_grid.clone())
+        );
+        // ...and the one after that doesn't go back in to the nested grid
+        assert_eq!(
+            select_all_bounds(&grammars, &coord!("root-B2-A2"), Some(root_grid.clone())),
+            Some(root_grid.clone())
+        );
+        // there's nothing around the top-level grid
+        assert_eq!(
+            select_all_bounds(&grammars, &coord!("root-C2"), Some(root_grid.clone())),
+            Some(root_grid)
+        );
+    }
+
+    #[test]
+    fn test_paste_special() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "bold")), g!(Grammar::input("", "plain"))],
+                [g!(Grammar::input("", "A2")), g!(Grammar::input("", "B2"))]
+            ],
+        );
+        let bold = coord!("root-A1");
+        grammars.get_mut(&bold).unwrap().style.font_weight = 700;
+        grammars.get_mut(&coord!("root-B2")).unwrap().style.width = 200.0;
+        let clipboard = Clipboard {
+            rows: 1,
+            cols: 1,
+            grammars: copy_region(&grammars, &coord!("root"), (1, 1), (1, 1)),
+            results: HashMap::new(),
+        };
+
+        // values keep the destination's style
+        paste_special(&mut grammars, &clipboard, &coord!("root"), (1, 2), (1, 1), true);
+        assert_eq!(grammars[&coord!("root-B1")].kind, Kind::Input("bold".to_string()));
+        assert_eq!(grammars[&coord!("root-B1")].style.font_weight, 400);
+
+        // formats keep the destination's value (and its size)
+        paste_special(&mut grammars, &clipboard, &coord!("root"), (2, 2), (1, 1), false);
+        assert_eq!(grammars[&coord!("root-B2")].kind, Kind::Input("B2".to_string()));
+        assert_eq!(grammars[&coord!("root-B2")].style.font_weight, 700);
+        assert_eq!(grammars[&coord!("root-B2")].style.width, 200.0);
+        // pasting formats doesn't take a cell's lock off
+        grammars.get_mut(&coord!("root-B2")).unwrap().style.locked = true;
+        paste_special(&mut grammars, &clipboard, &coord!("root"), (2, 2), (1, 1), false);
+        assert!(grammars[&coord!("root-B2")].style.locked);
+
+        // values never get pasted over a grid
+        let one = NonZeroU32::new(1).unwrap();
+        grammars.insert(coord!("root-A2"), Grammar::as_grid(one, one));
+        paste_special(&mut grammars, &clipboard, &coord!("root"), (2, 1), (1, 1), true);
+        assert_eq!(grammars[&coord!("root-A2")].kind, Kind::Grid(row_col_vec![(1, 1)]));
+
+        // lookups and formulas are pasted as the value they showed
+        let lookup = Grammar {
+            kind: Kind::Lookup("bold".to_string(), Some(Lookup::Cell(bold.clone()))),
+            ..Grammar::default()
+        };
+        let clipboard = Clipboard {
+            rows: 1,
+            cols: 2,
+            grammars: vec![
+                (vec![non_zero_u32_tuple((1, 1))], lookup),
+                (vec![non_zero_u32_tuple((1, 2))], Grammar::input("", "=1+2")),
+            ],
+            results: hashmap! { non_zero_u32_tuple((1, 2)) => "3".to_string() },
+        };
+        paste_special(&mut grammars, &clipboard, &coord!("root"), (1, 1), (1, 2), true);
+        assert_eq!(grammars[&coord!("root-A1")].kind, Kind::Input("bold".to_string()));
+        assert_eq!(grammars[&coord!("root-B1")].kind, Kind::Input("3".to_string()));
+    }
+
+    #[test]
+    fn test_toggle_style() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![[g!(Grammar::input("", "A1")), g!(Grammar::input("", "B1"))]],
+        );
+        let cells = vec![coord!("root-A1"), coord!("root-B1")];
+        let is_italic = |style: &Style| style.font_style == "italic";
+        let set_italic = |style: &mut Style, italic: bool| {
+            style.font_style = if italic { "italic" } else { "normal" }.to_string()
+        };
+        grammars.get_mut(&coord!("root-A1")).unwrap().style.font_style = "italic".to_string();
+
+        // a mixed selection all gets the style...
+        toggle_style(&mut grammars, &cells, is_italic, set_italic);
+        assert!(cells.iter().all(|c| is_italic(&grammars[c].style)));
+        // ... and then it all gets taken off again
+        toggle_style(&mut grammars, &cells, is_italic, set_italic);
+        assert!(cells.iter().all(|c| !is_italic(&grammars[c].style)));
+    }
+
+    #[test]
+    fn test_python_values() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "Alice")), g!(Grammar::text("", "Name"))],
+                [g!(Grammar::default()), g!(Grammar::default())]
+            ],
+        );
+        grammars.get_mut(&coord!("root-A2")).unwrap().kind =
+            Kind::Number(12.5, NumberFormat::Currency);
+        grammars.insert(coord!("meta-A1"), Grammar::input("", "hidden"));
+        let one = NonZeroU32::new(1).unwrap();
+        grammars.insert(coord!("root-B2"), Grammar::as_grid(one, one));
+        grammars.insert(coord!("root-B2-A1"), Grammar::input("", "nested"));
+
+        let values = python_values(&grammars);
+        assert_eq!(values["A1"], "Alice");
+        assert_eq!(values["B1"], "Name");
+        assert_eq!(values["A2"], 12.5);
+        assert_eq!(values["B2-A1"], "nested");
+        // grids and cells outside of root aren't values
+        assert!(!values.contains_key("B2"));
+        assert!(!values.contains_key("root"));
+        assert_eq!(values.len(), 4);
+    }
+
+    #[test]
+    fn test_autosave_key() {
+        assert_eq!(autosave_key(3), "ise-autosave:3");
+        assert!(is_autosave_key(&autosave_key(1)));
+        assert!(!is_autosave_key("Untitled"));
+
+        assert_eq!(unused_autosave_key(&[]), "ise-autosave:1");
+        // keys from older versions were titles, which aren't numbers
+        let keys = vec![autosave_key(2), "ise-autosave:budget".to_string(), "other".to_string()];
+        assert_eq!(unused_autosave_key(&keys), "ise-autosave:3");
+    }
+
+    #[test]
+    fn test_grid_wrapper_class() {
+        assert_eq!(grid_wrapper_class(false, true), "grid-wrapper");
+        assert_eq!(grid_wrapper_class(true, true), "grid-wrapper lite");
+        assert_eq!(grid_wrapper_class(false, false), "grid-wrapper no-gridlines");
+        assert_eq!(grid_wrapper_class(true, false), "grid-wrapper lite no-gridlines");
+    }
+
+    #[test]
+    fn test_find_shortcut() {
+        assert_eq!(find_shortcut("Ctrl-g"), Some("Ctrl-g"));
+        assert_eq!(find_shortcut("Ctrl-Shift-m"), Some("Ctrl-Shift-M"));
+        assert_eq!(find_shortcut("Ctrl-/"), Some("Ctrl-/"));
+        assert_eq!(find_shortcut("g"), None);
+        assert_eq!(find_shortcut("Ctrl-Alt-g"), None);
+        // every shortcut is listed once
+        for (i, (shortcut, _)) in SHORTCUTS.iter().enumerate() {
+            assert_eq!(find_shortcut(shortcut), Some(*shortcut));
+            assert!(SHORTCUTS[i + 1..]
+                .iter()
+                .all(|(other, _)| !other.eq_ignore_ascii_case(shortcut)));
+        }
+    }
+
+    #[test]
+    fn test_cell_history() {
+        let mut history = HashMap::new();
+        let a1 = coord!("root-A1");
+        for i in 0..HISTORY_LENGTH + 5 {
+            push_history(&mut history, &a1, i as f64);
+        }
+        // only the latest values are kept
+        assert_eq!(history[&a1].len(), HISTORY_LENGTH);
+        assert_eq!(history[&a1][0], 5.0);
+        assert_eq!(*history[&a1].last().unwrap(), (HISTORY_LENGTH + 4) as f64);
+        // committing the same value again doesn't add to the history
+        push_history(&mut history, &a1, (HISTORY_LENGTH + 4) as f64);
+        assert_eq!(history[&a1].len(), HISTORY_LENGTH);
+        assert_eq!(history[&a1][0], 5.0);
+
+        assert_eq!(numeric_value(&Kind::Input(" 2.5 ".to_string())), Some(2.5));
+        assert_eq!(numeric_value(&Kind::Input("abc".to_string())), None);
+        assert_eq!(numeric_value(&Kind::Number(3.0, NumberFormat::Currency)), Some(3.0));
+        assert_eq!(numeric_value(&Kind::Text("1".to_string())), None);
+
+        // once the cell isn't an input anymore, its history goes
+        push_history(&mut history, &coord!("root-B1"), 1.0);
+        let mut grammars = HashMap::new();
+        grammars.insert(a1.clone(), Grammar::input("", "7"));
+        grammars.insert(coord!("root-B1"), Grammar::text("", "7"));
+        retain_history(&mut history, &grammars);
+        assert!(history.contains_key(&a1));
+        assert!(!history.contains_key(&coord!("root-B1")));
+    }
+
+    #[test]
+    fn test_push_log() {
+        let mut log = vec![];
+        for i in 0..ACTION_LOG_LENGTH + 3 {
+            push_log(&mut log, i as f64, format! {"{:?}", Action::SetInsertCount(i as u32)});
+        }
+        assert_eq!(log.len(), ACTION_LOG_LENGTH);
+        assert_eq!(log[0], (3.0, "SetInsertCount(3)".to_string()));
+
+        push_log(&mut log, 0.0, "x".repeat(MAX_LOGGED_ACTION_LENGTH + 50));
+        let (_, long) = log.last().unwrap();
+        assert_eq!(long.len(), MAX_LOGGED_ACTION_LENGTH + 3);
+        assert!(long.ends_with("..."));
+    }
+
+    #[test]
+    fn test_drag_resize() {
+        let (mut cell, mut line, mut preview) = (None, None, None);
+        let (mut row_heights, mut col_widths) = (HashMap::new(), HashMap::new());
+        let row = Row(coord!("root-A1"), NonZeroU32::new(2).unwrap());
+        let col = Col(coord!("root"), NonZeroU32::new(3).unwrap());
+        // the grid that `row` is in already has its own height
+        row_heights.insert(coord!("root-A1").full_row(), 100.0);
+        let mut drag = |msg: ResizeMsg| {
+            drag_resize(
+                &mut cell,
+                &mut line,
+                &mut preview,
+                &mut row_heights,
+                &mut col_widths,
+                (90.0, 30.0),
+                &msg,
+            )
+        };
+
+        // dragging a row header's border only changes the row's height
+        drag(ResizeMsg::StartRow(row.clone()));
+        assert!(drag(ResizeMsg::Y(10.0, (0.0, 0.0))));
+        assert!(!drag(ResizeMsg::X(5.0, (0.0, 0.0))));
+        drag(ResizeMsg::End);
+        // and a column header's border only its width
+        drag(ResizeMsg::StartCol(col.clone()));
+        assert!(drag(ResizeMsg::X(-20.0, (0.0, 0.0))));
+        assert!(!drag(ResizeMsg::Y(5.0, (0.0, 0.0))));
+        drag(ResizeMsg::End);
+
+        assert_eq!(row_heights[&row], 40.0);
+        assert_eq!(col_widths[&col], 70.0);
+        // the grid the row is in grows along with it (plus its borders)
+        assert_eq!(row_heights[&coord!("root-A1").full_row()], 112.0);
+        assert!(preview.is_none() && line.is_none() && cell.is_none());
+    }
+
+    #[test]
+    fn test_keeps_session() {
+        assert!(keeps_session(&Action::SetActiveCell(coord!("root-A1"))));
+        assert!(keeps_session(&Action::Resize(ResizeMsg::X(1.0, (0.0, 0.0)))));
+        assert!(!keeps_session(&Action::Resize(ResizeMsg::End)));
+        assert!(keeps_session(&Action::ToggleCoordinateOverlay));
+        // typing changes the session, but only restyles the cell being typed in
+        let typing = Action::ChangeInput(coord!("root-A1"), "1".to_string());
+        assert!(!keeps_session(&typing));
+        assert!(keeps_cell_styles(&typing));
+        // leaving a cell only adds to its history, which isn't saved with the session
+        assert!(keeps_session(&Action::CommitInput(coord!("root-A1"))));
+        assert!(!keeps_cell_styles(&Action::InsertRow));
+    }
+
+    #[test]
+    fn test_use_lite_mode() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "A1")), g!(Grammar::input("", "B1"))],
+                [g!(Grammar::input("", "A2")), g!(Grammar::input("", "B2"))]
+            ],
+        );
+        let mut collapsed = HashSet::new();
+        let small_cost = render_cost(&grammars, &collapsed, &coord!("root"));
+        assert_eq!(small_cost, 5);
+        assert!(!use_lite_mode(small_cost));
+
+        // hidden rows and columns, and cells that aren't displayed, aren't rendered
+        grammars.get_mut(&coord!("root")).unwrap().style.hidden_rows = vec![2];
+        assert_eq!(render_cost(&grammars, &collapsed, &coord!("root")), 3);
+        grammars.get_mut(&coord!("root")).unwrap().style.hidden_cols = vec![2];
+        assert_eq!(render_cost(&grammars, &collapsed, &coord!("root")), 2);
+        grammars.get_mut(&coord!("root-A1")).unwrap().style.display = false;
+        assert_eq!(render_cost(&grammars, &collapsed, &coord!("root")), 1);
+        grammars.get_mut(&coord!("root-A1")).unwrap().style.display = true;
+        grammars.get_mut(&coord!("root")).unwrap().style.hidden_rows = vec![];
+        grammars.get_mut(&coord!("root")).unwrap().style.hidden_cols = vec![];
+        // a collapsed grid is rendered, but not the cells inside it
+        collapsed.insert(coord!("root"));
+        assert_eq!(render_cost(&grammars, &collapsed, &coord!("root")), 1);
+        collapsed.clear();
+
+        for row in 1..=60 {
+            for col in 1..=60 {
+                grammars.insert(
+                    Coordinate::child_of(&coord!("root"), non_zero_u32_tuple((row, col))),
+                    Grammar::default(),
+                );
+            }
+        }
+        assert!(use_lite_mode(render_cost(&grammars, &collapsed, &coord!("root"))));
+        // cells outside of the rendered root don't count
+        assert_eq!(render_cost(&grammars, &collapsed, &coord!("meta")), 0);
+    }
+}
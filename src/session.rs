@@ -1,13 +1,21 @@
 use serde::{
+    de::{self, Visitor},
     ser::{SerializeSeq, SerializeStruct, SerializeStructVariant, Serializer},
-    Deserialize, Serialize,
+    Deserialize, Deserializer, Serialize,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::num::NonZeroU32;
 use std::option::Option;
 
-use crate::coordinate::Coordinate;
-use crate::grammar::{Grammar, Interactive, Kind};
-use crate::style::Style;
+use crate::coordinate::{col_to_letters, Col, Coordinate};
+use crate::g;
+use crate::grammar::{Grammar, Interactive, Kind, Lookup, NumberFormat};
+use crate::grammar_map::{build_grammar_map, grid_entry, MapEntry};
+use crate::style::{
+    ConditionalFormat, Style, DEFAULT_COL_WIDTH, DEFAULT_GRIDLINE_COLOR, DEFAULT_ROW_HEIGHT,
+};
+use crate::util::{grid_size, resize_grid};
 
 // Session encapsulates the serializable state of the application that gets stored to disk
 // in a .ise file (which is just a JSON file)
@@ -17,20 +25,426 @@ pub struct Session {
     pub root: Grammar,
     pub meta: Grammar,
     pub grammars: HashMap<Coordinate, Grammar>,
+    // whether typing in an input cell opens the suggestion dropdown, or if
+    // it only opens through the explicit shortcut (Ctrl-Space)
+    #[serde(default = "default_auto_complete")]
+    pub auto_complete: bool,
+    // custom labels shown in place of a column's letter (A, B, C...). Coordinates
+    // still refer to the column by its letter internally.
+    #[serde(default, deserialize_with = "deserialize_column_labels")]
+    pub column_labels: HashMap<Col, String>,
+    // version of the .ise format the session was saved with. Files from before versioning
+    // don't have one, which is treated as version 0.
+    #[serde(default)]
+    pub version: u32,
+    // number of rows and columns of the top-level grid that stay pinned while it scrolls
+    #[serde(default)]
+    pub frozen_rows: u32,
+    #[serde(default)]
+    pub frozen_cols: u32,
+    // size that new rows and columns get, until they're resized
+    #[serde(default = "default_col_width")]
+    pub default_col_width: f64,
+    #[serde(default = "default_row_height")]
+    pub default_row_height: f64,
+    // comments attached to cells, which show up when hovering over the cell
+    #[serde(default)]
+    pub notes: HashMap<Coordinate, String>,
+    // styles applied to the cells of a column whose value matches a predicate
+    #[serde(default)]
+    pub conditional_formats: Vec<ConditionalFormat>,
+    // the active cell and selection when the session was saved, so loading it picks up
+    // where it left off
+    #[serde(default)]
+    pub active_cell: Option<Coordinate>,
+    #[serde(default)]
+    pub first_select_cell: Option<Coordinate>,
+    #[serde(default)]
+    pub last_select_cell: Option<Coordinate>,
+    // nested grids that are folded up into a single summary cell
+    #[serde(default)]
+    pub collapsed: HashSet<Coordinate>,
+    // whether the lines between cells are drawn, and in what color
+    #[serde(default = "default_show_gridlines")]
+    pub show_gridlines: bool,
+    #[serde(default = "default_gridline_color")]
+    pub gridline_color: String,
+    // whether the app is shown with light text on a dark background (see `Theme`)
+    #[serde(default)]
+    pub dark_mode: bool,
+    // the cell that each code editor's RunPython output goes into, for editors that have one
+    // set (see `Model::editor_output`)
+    #[serde(default)]
+    pub editor_outputs: HashMap<Coordinate, Coordinate>,
+    // the localStorage key the session is auto-saved under, which is picked the first time it's
+    // auto-saved. It isn't part of the saved session, so every loaded copy gets its own
+    #[serde(skip)]
+    pub autosave_key: Option<String>,
 }
 js_serializable!(Session);
 js_deserializable!(Session);
 
+// version of the .ise format that this build of the app writes. Bump this whenever the format
+// changes, and handle older versions in `migrate`.
+pub const SESSION_VERSION: u32 = 1;
+
+fn default_auto_complete() -> bool {
+    true
+}
+
+fn default_show_gridlines() -> bool {
+    true
+}
+
+fn default_gridline_color() -> String {
+    DEFAULT_GRIDLINE_COLOR.to_string()
+}
+
+fn default_col_width() -> f64 {
+    DEFAULT_COL_WIDTH
+}
+
+fn default_row_height() -> f64 {
+    DEFAULT_ROW_HEIGHT
+}
+
+// since JSON object keys can only be strings, `column_labels` is stored as a list of pairs
+fn deserialize_column_labels<'de, D>(deserializer: D) -> Result<HashMap<Col, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pairs: Vec<(Col, String)> = Vec::deserialize(deserializer)?;
+    Ok(pairs.into_iter().collect())
+}
+
+impl Session {
+    // serializes the session into the contents of a .ise file
+    pub fn to_ise_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).expect("a session should always be serializable")
+    }
+
+    // reads a session from the contents of a .ise file, migrating files saved by older
+    // versions of the app. Files from newer versions are rejected rather than loaded partially.
+    pub fn from_ise_bytes(bytes: &[u8]) -> Result<Session, String> {
+        let session: Session =
+            serde_json::from_slice(bytes).map_err(|e| format! {"invalid .ise file: {}", e})?;
+        if session.version > SESSION_VERSION {
+            return Err(format! {
+                "this .ise file was saved with a newer version of the app (format version {}, \
+                 but only up to {} is supported)",
+                session.version, SESSION_VERSION
+            });
+        }
+        Ok(session.migrate())
+    }
+
+    // brings a session saved with an older format version up to SESSION_VERSION. Each
+    // version's changes get applied in order, e.g. `if self.version < 2 { ... }`.
+    fn migrate(mut self) -> Session {
+        // 0 -> 1: version 0 files predate the style spans/alignment, grammar validators,
+        // column labels and the auto-complete setting, which all get their defaults from
+        // serde while deserializing, so there's nothing else to fill in
+        self.version = SESSION_VERSION;
+        self
+    }
+
+    // label for a column, which is the custom label if one is set or the column letter otherwise
+    pub fn column_label(&self, col: &Col) -> String {
+        match self.column_labels.get(col) {
+            Some(label) => label.clone(),
+            None => col_to_letters(col.1.get()),
+        }
+    }
+}
+
+// version of the .isedef format, which gets checked like SESSION_VERSION
+pub const DEFINITIONS_VERSION: u32 = 1;
+
+// `Definitions` are the grammar definitions of a session, i.e. the cells of its meta grid and
+// everything nested in them. They get saved to a .isedef file (JSON, like .ise files) so that a
+// library of definitions can be reused in other sessions.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Definitions {
+    #[serde(default)]
+    pub version: u32,
+    pub grammars: HashMap<Coordinate, Grammar>,
+}
+
+impl Definitions {
+    pub fn of_session(session: &Session) -> Definitions {
+        let meta = parse_coord("meta");
+        Definitions {
+            version: DEFINITIONS_VERSION,
+            grammars: session
+                .grammars
+                .iter()
+                .filter(|(c, _)| c.row_cols.len() > 1 && c.row_cols.starts_with(&meta.row_cols))
+                .map(|(c, g)| (c.clone(), g.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn to_isedef_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).expect("definitions should always be serializable")
+    }
+
+    pub fn from_isedef_bytes(bytes: &[u8]) -> Result<Definitions, String> {
+        let definitions: Definitions =
+            serde_json::from_slice(bytes).map_err(|e| format! {"invalid .isedef file: {}", e})?;
+        if definitions.version > DEFINITIONS_VERSION {
+            return Err(format! {
+                "this .isedef file was saved with a newer version of the app (format version {}, \
+                 but only up to {} is supported)",
+                definitions.version, DEFINITIONS_VERSION
+            });
+        }
+        Ok(definitions)
+    }
+
+    // the definitions themselves (the cells of the meta grid), top to bottom
+    pub fn roots(&self) -> Vec<Coordinate> {
+        let mut roots: Vec<Coordinate> = self
+            .grammars
+            .keys()
+            .filter(|c| c.row_cols.len() == 2)
+            .cloned()
+            .collect();
+        roots.sort_by(|a, b| a.row_cols.cmp(&b.row_cols));
+        roots
+    }
+
+    // adds the definitions to the end of the first column of `session`'s meta grid, returning
+    // the names of the ones that were left out because the session has a definition with the
+    // same name already. Definition bindings (Kind::Defn) move along with their definitions.
+    pub fn merge_into(&self, session: &mut Session) -> Vec<String> {
+        let meta = parse_coord("meta");
+        let mut names: HashSet<String> = session
+            .grammars
+            .iter()
+            .filter(|(c, _)| c.parent().as_ref() == Some(&meta))
+            .map(|(_, g)| g.name.clone())
+            .filter(|name| !name.is_empty())
+            .collect();
+        let mut next_row = session
+            .grammars
+            .keys()
+            .filter(|c| c.parent().as_ref() == Some(&meta) && c.col().get() == 1)
+            .map(|c| c.row().get())
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let mut skipped = Vec::new();
+        for root in self.roots() {
+            let name = self.grammars[&root].name.clone();
+            if !name.is_empty() && !names.insert(name.clone()) {
+                skipped.push(name);
+                continue;
+            }
+            let row_col = (NonZeroU32::new(next_row).unwrap(), NonZeroU32::new(1).unwrap());
+            let dest = Coordinate::child_of(&meta, row_col);
+            for (c, grammar) in self.grammars.iter() {
+                if !c.row_cols.starts_with(&root.row_cols) {
+                    continue;
+                }
+                let mut grammar = grammar.clone();
+                if let Kind::Defn(_, defn_coord, rules) = &mut grammar.kind {
+                    *defn_coord = rebased(defn_coord, &root, &dest);
+                    for (_, rule_coord) in rules.iter_mut() {
+                        *rule_coord = rebased(rule_coord, &root, &dest);
+                    }
+                }
+                session.grammars.insert(rebased(c, &root, &dest), grammar);
+            }
+            // the meta grid can be in the grammar map too
+            for meta_grammar in vec![&mut session.meta]
+                .into_iter()
+                .chain(session.grammars.get_mut(&meta))
+            {
+                if let Kind::Grid(sub_coords) = &mut meta_grammar.kind {
+                    sub_coords.push(row_col);
+                }
+            }
+            next_row += 1;
+        }
+        skipped
+    }
+}
+
+impl Serialize for Definitions {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Definitions", 2)?;
+        state.serialize_field("version", &self.version)?;
+        state.serialize_field("grammars", &self.grammars)?;
+        state.end()
+    }
+}
+
+// `coord` moved from under `from` to under `to`, or `coord` itself if it isn't under `from`
+fn rebased(coord: &Coordinate, from: &Coordinate, to: &Coordinate) -> Coordinate {
+    if !coord.row_cols.starts_with(&from.row_cols) {
+        return coord.clone();
+    }
+    let mut row_cols = to.row_cols.clone();
+    row_cols.extend_from_slice(&coord.row_cols[from.row_cols.len()..]);
+    Coordinate { row_cols }
+}
+
+// `SessionBuilder` puts together a session in code, for tests or for generating sessions
+// outside of the app, e.g.
+//
+//     SessionBuilder::new()
+//         .title("budget")
+//         .grid("root", 2, 2)
+//         .cell("root-A1", Grammar::input("", "rent"))
+//         .build()
+//
+// Coordinates are written like `Coordinate::to_string` writes them, and a badly written one
+// panics the same way `coord!` does.
+pub struct SessionBuilder {
+    title: String,
+    grammars: HashMap<Coordinate, Grammar>,
+}
+
+impl SessionBuilder {
+    // starts off with a root grid of a single empty cell
+    pub fn new() -> SessionBuilder {
+        SessionBuilder {
+            title: "my session".to_string(),
+            grammars: HashMap::new(),
+        }
+        .grid("root", 1, 1)
+    }
+
+    pub fn title<S: Into<String>>(mut self, title: S) -> SessionBuilder {
+        self.title = title.into();
+        self
+    }
+
+    // puts `grammar` at `coord_str`, replacing whatever was there. If the cell is outside of
+    // its parent grid, the grid is extended to include it.
+    pub fn cell(mut self, coord_str: &str, grammar: Grammar) -> SessionBuilder {
+        let coord = parse_coord(coord_str);
+        self.remove_descendants(&coord);
+        self.add_to_parent(&coord);
+        self.grammars.insert(coord, grammar);
+        self
+    }
+
+    // puts a grid of `rows` x `cols` empty input cells at `coord_str`, replacing whatever
+    // was there (including any cells nested in it)
+    pub fn grid(mut self, coord_str: &str, rows: u32, cols: u32) -> SessionBuilder {
+        let coord = parse_coord(coord_str);
+        self.remove_descendants(&coord);
+        self.add_to_parent(&coord);
+        build_grammar_map(
+            &mut self.grammars,
+            coord,
+            grid_entry(rows, cols, |_, _| g!(Grammar::input("", ""))),
+        );
+        self
+    }
+
+    pub fn build(self) -> Session {
+        let root = Grammar {
+            name: "root".to_string(),
+            ..self.grammars[&parse_coord("root")].clone()
+        };
+        // definitions get added to the meta grid as rows, so it's a grid even when it's empty
+        let meta = Grammar {
+            name: "meta".to_string(),
+            ..self.grammars.get(&parse_coord("meta")).cloned().unwrap_or_else(|| Grammar {
+                kind: Kind::Grid(vec![]),
+                ..Grammar::default()
+            })
+        };
+        Session {
+            title: self.title,
+            root,
+            meta,
+            grammars: self.grammars,
+            auto_complete: default_auto_complete(),
+            column_labels: HashMap::new(),
+            version: SESSION_VERSION,
+            frozen_rows: 0,
+            frozen_cols: 0,
+            default_col_width: default_col_width(),
+            default_row_height: default_row_height(),
+            notes: HashMap::new(),
+            conditional_formats: vec![],
+            active_cell: None,
+            first_select_cell: None,
+            last_select_cell: None,
+            collapsed: HashSet::new(),
+            show_gridlines: default_show_gridlines(),
+            gridline_color: default_gridline_color(),
+            dark_mode: false,
+            editor_outputs: HashMap::new(),
+            autosave_key: None,
+        }
+    }
+
+    fn remove_descendants(&mut self, coord: &Coordinate) {
+        let depth = coord.row_cols.len();
+        self.grammars
+            .retain(|c, _| c.row_cols.len() <= depth || !c.row_cols.starts_with(&coord.row_cols));
+    }
+
+    // grows the parent grid (with empty cells, so it stays rectangular) if `coord` is outside it
+    fn add_to_parent(&mut self, coord: &Coordinate) {
+        let parent = match coord.parent() {
+            Some(parent) => parent,
+            None => return,
+        };
+        if let Some((rows, cols)) = grid_size(&self.grammars, &parent) {
+            let (row, col) = (coord.row().get(), coord.col().get());
+            if row > rows || col > cols {
+                resize_grid(&mut self.grammars, &parent, rows.max(row), cols.max(col));
+            }
+        }
+    }
+}
+
+impl Default for SessionBuilder {
+    fn default() -> Self {
+        SessionBuilder::new()
+    }
+}
+
+fn parse_coord(coord_str: &str) -> Coordinate {
+    coord_str.parse().unwrap_or_else(|e| panic!("{}", e))
+}
+
 impl Serialize for Session {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Session", 3)?;
+        let mut state = serializer.serialize_struct("Session", 21)?;
+        state.serialize_field("version", &self.version)?;
         state.serialize_field("title", &self.title)?;
         state.serialize_field("root", &self.root)?;
         state.serialize_field("meta", &self.meta)?;
         state.serialize_field("grammars", &self.grammars)?;
+        state.serialize_field("auto_complete", &self.auto_complete)?;
+        let column_labels: Vec<(&Col, &String)> = self.column_labels.iter().collect();
+        state.serialize_field("column_labels", &column_labels)?;
+        state.serialize_field("frozen_rows", &self.frozen_rows)?;
+        state.serialize_field("frozen_cols", &self.frozen_cols)?;
+        state.serialize_field("default_col_width", &self.default_col_width)?;
+        state.serialize_field("default_row_height", &self.default_row_height)?;
+        state.serialize_field("notes", &self.notes)?;
+        state.serialize_field("conditional_formats", &self.conditional_formats)?;
+        state.serialize_field("active_cell", &self.active_cell)?;
+        state.serialize_field("first_select_cell", &self.first_select_cell)?;
+        state.serialize_field("last_select_cell", &self.last_select_cell)?;
+        state.serialize_field("collapsed", &self.collapsed)?;
+        state.serialize_field("show_gridlines", &self.show_gridlines)?;
+        state.serialize_field("gridline_color", &self.gridline_color)?;
+        state.serialize_field("dark_mode", &self.dark_mode)?;
+        state.serialize_field("editor_outputs", &self.editor_outputs)?;
         state.end()
     }
 }
@@ -40,13 +454,29 @@ impl Serialize for Style {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Style", 6)?;
+        let mut state = serializer.serialize_struct("Style", 22)?;
         state.serialize_field("width", &self.width)?;
         state.serialize_field("height", &self.height)?;
         state.serialize_field("border_color", &self.border_color)?;
         state.serialize_field("border_collapse", &self.border_collapse)?;
         state.serialize_field("font_weight", &self.font_weight)?;
+        state.serialize_field("font_style", &self.font_style)?;
+        state.serialize_field("text_decoration", &self.text_decoration)?;
+        state.serialize_field("font_family", &self.font_family)?;
+        state.serialize_field("font_size", &self.font_size)?;
         state.serialize_field("font_color", &self.font_color)?;
+        state.serialize_field("background_color", &self.background_color)?;
+        state.serialize_field("col_span", &self.col_span)?;
+        state.serialize_field("row_span", &self.row_span)?;
+        state.serialize_field("display", &self.display)?;
+        state.serialize_field("locked", &self.locked)?;
+        state.serialize_field("text_align", &self.text_align)?;
+        state.serialize_field("vertical_align", &self.vertical_align)?;
+        state.serialize_field("hidden_rows", &self.hidden_rows)?;
+        state.serialize_field("hidden_cols", &self.hidden_cols)?;
+        state.serialize_field("number_format", &self.number_format)?;
+        state.serialize_field("wrap_text", &self.wrap_text)?;
+        state.serialize_field("display_precision", &self.display_precision)?;
         state.end()
     }
 }
@@ -56,10 +486,11 @@ impl Serialize for Grammar {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Grammar", 3)?;
+        let mut state = serializer.serialize_struct("Grammar", 4)?;
         state.serialize_field("name", &self.name)?;
         state.serialize_field("style", &self.style)?;
         state.serialize_field("kind", &self.kind)?;
+        state.serialize_field("validator", &self.validator)?;
         state.end()
     }
 }
@@ -138,6 +569,22 @@ impl Serialize for Kind {
                 sv.serialize_field("content", s)?;
                 sv.end()
             }
+            Kind::Number(n, format) => {
+                let mut sv = serializer.serialize_struct_variant("Kind", 5, "Number", 2)?;
+                sv.serialize_field("number", n)?;
+                sv.serialize_field("format", format)?;
+                sv.end()
+            }
+            Kind::Markdown(s) => {
+                let mut sv = serializer.serialize_struct_variant("Kind", 6, "Markdown", 1)?;
+                sv.serialize_field("markdown", s)?;
+                sv.end()
+            }
+            Kind::Image(url) => {
+                let mut sv = serializer.serialize_struct_variant("Kind", 7, "Image", 1)?;
+                sv.serialize_field("url", url)?;
+                sv.end()
+            }
         }
     }
 }
@@ -150,3 +597,455 @@ impl Serialize for Coordinate {
         serializer.serialize_str(&self.to_string())
     }
 }
+
+// The Deserialize implementations below read back what the Serialize implementations above
+// write, which is how .ise files are laid out
+
+impl<'de> Deserialize<'de> for Coordinate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CoordinateVisitor;
+
+        impl<'de> Visitor<'de> for CoordinateVisitor {
+            type Value = Coordinate;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a coordinate such as \"root-A1\"")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Coordinate, E>
+            where
+                E: de::Error,
+            {
+                value.parse::<Coordinate>().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(CoordinateVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+enum InteractiveRepr {
+    Button {
+        #[allow(dead_code)]
+        button: (),
+    },
+    Slider {
+        slider_value: f64,
+        slider_min: f64,
+        slider_max: f64,
+    },
+    Toggle {
+        toggle_state: bool,
+    },
+}
+
+impl<'de> Deserialize<'de> for Interactive {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match InteractiveRepr::deserialize(deserializer)? {
+            InteractiveRepr::Button { .. } => Interactive::Button(),
+            InteractiveRepr::Slider {
+                slider_value,
+                slider_min,
+                slider_max,
+            } => Interactive::Slider(slider_value, slider_min, slider_max),
+            InteractiveRepr::Toggle { toggle_state } => Interactive::Toggle(toggle_state),
+        })
+    }
+}
+
+// grids are written as a plain list of sub-coordinates, every other kind is tagged
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KindRepr {
+    Grid(Vec<(NonZeroU32, NonZeroU32)>),
+    Tagged(TaggedKindRepr),
+}
+
+#[derive(Deserialize)]
+enum TaggedKindRepr {
+    Text {
+        text: String,
+    },
+    Input {
+        input: String,
+    },
+    Interactive {
+        name: String,
+        interactive: Interactive,
+    },
+    Lookup {
+        raw_value: String,
+        lookup: Option<Lookup>,
+    },
+    Defn {
+        name: String,
+        coordinate: Coordinate,
+        rules: Vec<(String, Coordinate)>,
+    },
+    Editor {
+        content: String,
+    },
+    Number {
+        number: f64,
+        format: NumberFormat,
+    },
+    Markdown {
+        markdown: String,
+    },
+    Image {
+        url: String,
+    },
+}
+
+impl<'de> Deserialize<'de> for Kind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match KindRepr::deserialize(deserializer)? {
+            KindRepr::Grid(sub_coords) => Kind::Grid(sub_coords),
+            KindRepr::Tagged(TaggedKindRepr::Text { text }) => Kind::Text(text),
+            KindRepr::Tagged(TaggedKindRepr::Input { input }) => Kind::Input(input),
+            KindRepr::Tagged(TaggedKindRepr::Interactive { name, interactive }) => {
+                Kind::Interactive(name, interactive)
+            }
+            KindRepr::Tagged(TaggedKindRepr::Lookup { raw_value, lookup }) => {
+                Kind::Lookup(raw_value, lookup)
+            }
+            KindRepr::Tagged(TaggedKindRepr::Defn {
+                name,
+                coordinate,
+                rules,
+            }) => Kind::Defn(name, coordinate, rules),
+            KindRepr::Tagged(TaggedKindRepr::Editor { content }) => Kind::Editor(content),
+            KindRepr::Tagged(TaggedKindRepr::Number { number, format }) => {
+                Kind::Number(number, format)
+            }
+            KindRepr::Tagged(TaggedKindRepr::Markdown { markdown }) => Kind::Markdown(markdown),
+            KindRepr::Tagged(TaggedKindRepr::Image { url }) => Kind::Image(url),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pest::Parser;
+    use std::num::NonZeroU32;
+
+    use crate::coordinate::CoordinateParser;
+    use crate::coordinate::Rule;
+    use crate::style::Predicate;
+    use crate::util::non_zero_u32_tuple;
+    use crate::{coord, coord_col};
+
+    fn test_session() -> Session {
+        Session {
+            title: "test".to_string(),
+            root: Grammar::default(),
+            meta: Grammar::default(),
+            grammars: hashmap! {
+                coord!("root-A1") => Grammar::input("", "Alice"),
+                coord!("root-B1") => Grammar::input("", "2020-01-01"),
+            },
+            auto_complete: true,
+            column_labels: HashMap::new(),
+            version: SESSION_VERSION,
+            frozen_rows: 0,
+            frozen_cols: 0,
+            default_col_width: DEFAULT_COL_WIDTH,
+            default_row_height: DEFAULT_ROW_HEIGHT,
+            notes: HashMap::new(),
+            conditional_formats: vec![],
+            active_cell: None,
+            first_select_cell: None,
+            last_select_cell: None,
+            collapsed: HashSet::new(),
+            show_gridlines: true,
+            gridline_color: DEFAULT_GRIDLINE_COLOR.to_string(),
+            dark_mode: false,
+            editor_outputs: HashMap::new(),
+            autosave_key: None,
+        }
+    }
+
+    #[test]
+    fn test_ise_bytes_round_trip() {
+        let mut session = test_session();
+        session.grammars.insert(
+            coord!("root-C1"),
+            Grammar {
+                name: "".to_string(),
+                style: Style::default(),
+                kind: Kind::Interactive("".to_string(), Interactive::Slider(5.0, 0.0, 10.0)),
+                validator: None,
+            },
+        );
+        session.grammars.insert(
+            coord!("root-A2"),
+            Grammar::as_grid(NonZeroU32::new(1).unwrap(), NonZeroU32::new(2).unwrap()),
+        );
+        session.grammars.insert(
+            coord!("root-A10"),
+            Grammar {
+                name: "".to_string(),
+                style: Style::default(),
+                kind: Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-A1")))),
+                validator: Some("[0-9]+".to_string()),
+            },
+        );
+        // line breaks inside a cell (Alt+Enter) are kept
+        session
+            .grammars
+            .insert(coord!("root-C2"), Grammar::input("", "first line\nsecond line"));
+        session.grammars.insert(
+            coord!("root-B2"),
+            Grammar {
+                name: "".to_string(),
+                style: Style::default(),
+                kind: Kind::Number(1234.5, NumberFormat::Fixed(2)),
+                validator: None,
+            },
+        );
+        session.grammars.insert(
+            coord!("root-C1"),
+            Grammar {
+                name: "".to_string(),
+                style: Style::default(),
+                kind: Kind::Markdown("# Notes\n\n- **one**\n- two".to_string()),
+                validator: None,
+            },
+        );
+        session.grammars.insert(
+            coord!("root-D1"),
+            Grammar {
+                name: "".to_string(),
+                style: Style::default(),
+                kind: Kind::Image("data:image/png;base64,iVBORw0KGgo=".to_string()),
+                validator: None,
+            },
+        );
+        session.grammars.insert(
+            coord!("root-E1"),
+            Grammar {
+                name: "".to_string(),
+                style: Style::default(),
+                kind: Kind::Editor("total = 1 + 2\nprint(total)".to_string()),
+                validator: None,
+            },
+        );
+        session
+            .editor_outputs
+            .insert(coord!("root-E1"), coord!("root-A1"));
+        session
+            .column_labels
+            .insert(coord_col!("root", "A"), "Name".to_string());
+        session.frozen_rows = 1;
+        session.default_col_width = 120.0;
+        session
+            .notes
+            .insert(coord!("root-A1"), "checked with\nthe team".to_string());
+        session.active_cell = Some(coord!("root-B1"));
+        session.collapsed.insert(coord!("root-A2"));
+        session.show_gridlines = false;
+        session.gridline_color = "#336699".to_string();
+        session.dark_mode = true;
+        session.conditional_formats.push(ConditionalFormat {
+            column: coord_col!("root", "B"),
+            predicate: Predicate::Contains("2020".to_string()),
+            style: Style::default(),
+        });
+
+        let loaded = Session::from_ise_bytes(&session.to_ise_bytes()).unwrap();
+        assert_eq!(loaded.title, session.title);
+        assert_eq!(loaded.grammars.len(), session.grammars.len());
+        for (coord, grammar) in session.grammars.iter() {
+            assert_eq!(loaded.grammars[coord].kind, grammar.kind);
+            assert_eq!(loaded.grammars[coord].name, grammar.name);
+            assert_eq!(loaded.grammars[coord].validator, grammar.validator);
+            assert_eq!(
+                loaded.grammars[coord].style.to_string(),
+                grammar.style.to_string()
+            );
+        }
+        assert_eq!(loaded.column_label(&coord_col!("root", "A")), "Name");
+        assert_eq!(loaded.auto_complete, session.auto_complete);
+        assert_eq!(loaded.frozen_rows, 1);
+        assert_eq!(loaded.frozen_cols, 0);
+        assert_eq!(loaded.default_col_width, 120.0);
+        assert_eq!(loaded.default_row_height, DEFAULT_ROW_HEIGHT);
+        assert_eq!(loaded.notes, session.notes);
+        assert_eq!(loaded.active_cell, Some(coord!("root-B1")));
+        assert_eq!(loaded.first_select_cell, None);
+        assert_eq!(loaded.collapsed, session.collapsed);
+        assert!(!loaded.show_gridlines);
+        assert_eq!(loaded.gridline_color, "#336699");
+        assert!(loaded.dark_mode);
+        assert_eq!(loaded.editor_outputs, session.editor_outputs);
+        assert_eq!(loaded.conditional_formats.len(), 1);
+        assert_eq!(loaded.conditional_formats[0].column, coord_col!("root", "B"));
+        assert_eq!(
+            loaded.conditional_formats[0].predicate,
+            Predicate::Contains("2020".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ise_version() {
+        let bytes = test_session().to_ise_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["version"], SESSION_VERSION);
+
+        // files from before versioning load as version 0 and get migrated
+        let mut old_json = json.clone();
+        old_json.as_object_mut().unwrap().remove("version");
+        let old_bytes = serde_json::to_vec(&old_json).unwrap();
+        let migrated = Session::from_ise_bytes(&old_bytes).unwrap();
+        assert_eq!(migrated.version, SESSION_VERSION);
+        assert_eq!(migrated.grammars.len(), 2);
+
+        // files from a newer version of the app are rejected
+        let mut new_json = json.clone();
+        new_json["version"] = serde_json::Value::from(SESSION_VERSION + 1);
+        let new_bytes = serde_json::to_vec(&new_json).unwrap();
+        assert!(Session::from_ise_bytes(&new_bytes)
+            .unwrap_err()
+            .contains("newer version"));
+    }
+
+    #[test]
+    fn test_from_ise_bytes_error() {
+        assert!(Session::from_ise_bytes(b"").is_err());
+        assert!(Session::from_ise_bytes(b"{\"title\": \"missing fields\"}").is_err());
+        // grammars have to be keyed by valid coordinates
+        let bad_coordinate = String::from_utf8(test_session().to_ise_bytes())
+            .unwrap()
+            .replace("root-A1", "not-a-coordinate");
+        assert!(Session::from_ise_bytes(bad_coordinate.as_bytes()).is_err());
+        // garbage (truncated files, the wrong types, not even text) is an error, not a panic
+        let bytes = test_session().to_ise_bytes();
+        assert!(Session::from_ise_bytes(&bytes[..bytes.len() / 2]).is_err());
+        assert!(Session::from_ise_bytes(b"{\"title\": 42, \"grammars\": []}").is_err());
+        assert!(Session::from_ise_bytes(b"[1, 2, 3]").is_err());
+        assert!(Session::from_ise_bytes(&[0xff, 0xfe, 0x00, 0x7b]).is_err());
+        assert!(Session::from_ise_bytes(b"not json at all")
+            .unwrap_err()
+            .starts_with("invalid .ise file"));
+    }
+
+    #[test]
+    fn test_column_label() {
+        let mut session = test_session();
+        assert_eq!(session.column_label(&coord_col!("root", "A")), "A");
+
+        session
+            .column_labels
+            .insert(coord_col!("root", "A"), "Name".to_string());
+        assert_eq!(session.column_label(&coord_col!("root", "A")), "Name");
+        assert_eq!(session.column_label(&coord_col!("root", "B")), "B");
+        // the coordinate still resolves by its letter
+        assert_eq!(coord!("root-A1").full_col(), coord_col!("root", "A"));
+        assert!(session.grammars.contains_key(&coord!("root-A1")));
+    }
+
+    #[test]
+    fn test_session_builder() {
+        let session = SessionBuilder::new()
+            .title("budget")
+            .grid("root", 2, 2)
+            .cell("root-A1", Grammar::input("", "rent"))
+            .cell("root-B1", Grammar::input("", "1200"))
+            .build();
+        assert_eq!(session.title, "budget");
+        assert_eq!(session.grammars.len(), 5);
+        match &session.root.kind {
+            Kind::Grid(sub_coords) => assert_eq!(sub_coords.len(), 4),
+            kind => panic!("expected a grid, got {:?}", kind),
+        }
+        assert_eq!(session.grammars[&coord!("root-A1")].kind, Kind::Input("rent".to_string()));
+        assert_eq!(session.grammars[&coord!("root-B1")].kind, Kind::Input("1200".to_string()));
+        assert_eq!(session.grammars[&coord!("root-B2")].kind, Kind::Input("".to_string()));
+        // built sessions survive a save & load
+        let loaded = Session::from_ise_bytes(&session.to_ise_bytes()).unwrap();
+        assert_eq!(loaded.grammars.len(), 5);
+
+        // replacing a nested grid drops its old cells, and cells outside a grid extend it
+        let session = SessionBuilder::new()
+            .grid("root", 2, 2)
+            .grid("root-A1", 2, 2)
+            .cell("root-A1", Grammar::input("", "flat"))
+            .cell("root-C1", Grammar::input("", "extra"))
+            .build();
+        assert!(!session.grammars.contains_key(&coord!("root-A1-A1")));
+        match &session.grammars[&coord!("root")].kind {
+            Kind::Grid(sub_coords) => {
+                assert_eq!(sub_coords.len(), 6);
+                assert!(sub_coords.contains(&non_zero_u32_tuple((1, 3))));
+            }
+            kind => panic!("expected a grid, got {:?}", kind),
+        }
+        // the rest of the new column is filled with empty cells
+        assert_eq!(session.grammars[&coord!("root-C2")].kind, Kind::Input("".to_string()));
+        assert_eq!(session.meta.kind, Kind::Grid(vec![]));
+    }
+
+    #[test]
+    fn test_definitions() {
+        let total = Grammar {
+            name: "total".to_string(),
+            style: Style::default(),
+            kind: Kind::Defn(
+                "".to_string(),
+                coord!("meta-A1-B1"),
+                vec![("amount".to_string(), coord!("meta-A1-B1-B1"))],
+            ),
+            validator: None,
+        };
+        let source = SessionBuilder::new()
+            .grid("meta", 2, 1)
+            .cell("meta-A1", total)
+            .cell("meta-A1-B1-B1", Grammar::input("rule_grammar", "number"))
+            .cell("meta-A2", Grammar::input("list", ""))
+            .build();
+        let definitions = Definitions::of_session(&source);
+        assert_eq!(definitions.grammars.len(), 3);
+        assert_eq!(definitions.roots(), vec![coord!("meta-A1"), coord!("meta-A2")]);
+        let loaded = Definitions::from_isedef_bytes(&definitions.to_isedef_bytes()).unwrap();
+        assert_eq!(loaded.version, DEFINITIONS_VERSION);
+        assert_eq!(loaded.grammars.len(), 3);
+
+        // "list" is defined already, so only "total" gets added, below the existing definition
+        let mut session = SessionBuilder::new()
+            .grid("meta", 1, 1)
+            .cell("meta-A1", Grammar::input("list", ""))
+            .build();
+        assert_eq!(loaded.merge_into(&mut session), vec!["list".to_string()]);
+        assert_eq!(session.grammars[&coord!("meta-A2")].name, "total");
+        assert_eq!(
+            session.grammars[&coord!("meta-A2")].kind,
+            Kind::Defn(
+                "".to_string(),
+                coord!("meta-A2-B1"),
+                vec![("amount".to_string(), coord!("meta-A2-B1-B1"))],
+            )
+        );
+        assert_eq!(
+            session.grammars[&coord!("meta-A2-B1-B1")].kind,
+            Kind::Input("number".to_string())
+        );
+        match &session.grammars[&coord!("meta")].kind {
+            Kind::Grid(sub_coords) => assert_eq!(sub_coords.len(), 2),
+            kind => panic!("expected a grid, got {:?}", kind),
+        }
+
+        assert!(Definitions::from_isedef_bytes(b"{\"version\": 99, \"grammars\": {}}").is_err());
+        assert!(Definitions::from_isedef_bytes(b"not json").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use pest::Parser;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::num::NonZeroU32;
 use std::option::Option;
@@ -9,7 +10,7 @@ use std::vec::Vec;
 use crate::coordinate;
 use crate::coordinate::*;
 use crate::grammar;
-use crate::grammar::{Grammar, Interactive, Kind, Lookup};
+use crate::grammar::{Grammar, Interactive, Kind, Lookup, NumberFormat};
 use crate::model::Model;
 use crate::util::non_zero_u32_tuple;
 use crate::{coord, coord_col, coord_row, row_col_vec};
@@ -28,26 +29,192 @@ pub struct Style {
     pub border_color: String,  // CSS: border-color
     pub border_collapse: bool, // CSS: border-collapse
     pub font_weight: i32,      // CSS: font-weight
+    #[serde(default = "default_font_style")]
+    pub font_style: String,    // CSS: font-style (normal or italic)
+    #[serde(default = "default_text_decoration")]
+    pub text_decoration: String, // CSS: text-decoration (none or underline)
+    #[serde(default = "default_font_family")]
+    pub font_family: String,   // CSS: font-family
+    #[serde(default = "default_font_size")]
+    pub font_size: f64,        // CSS: font-size (px)
     pub font_color: String,    // CSS: font-color
+    #[serde(default = "default_background_color")]
+    pub background_color: String, // CSS: background
+    #[serde(default)]
     pub col_span: (u32, u32),
+    #[serde(default)]
     pub row_span: (u32, u32),
+    #[serde(default = "default_display")]
     pub display: bool,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default = "default_text_align")]
+    pub text_align: String,     // CSS: text-align (left, center or right)
+    #[serde(default = "default_vertical_align")]
+    pub vertical_align: String, // CSS: align-items (top, middle or bottom)
+    // rows & columns of a grid that are hidden, which get left out of its grid-template-areas
+    #[serde(default)]
+    pub hidden_rows: Vec<u32>,
+    #[serde(default)]
+    pub hidden_cols: Vec<u32>,
+    // numbers typed into an input cell with a number format become a Kind::Number
+    #[serde(default)]
+    pub number_format: Option<NumberFormat>,
+    // long text wraps onto more lines (and the row grows to fit) instead of staying on one line
+    #[serde(default)]
+    pub wrap_text: bool,
+    // number of decimal places that numeric-looking values are shown with. It only changes how
+    // the value looks, the cell keeps the value as it was typed
+    #[serde(default)]
+    pub display_precision: Option<u8>,
 }
 js_serializable!(Style);
 js_deserializable!(Style);
 
+// size of a cell before it's been resized. Sessions can override these, see
+// `Session::default_col_width` & `Session::default_row_height`
+pub const DEFAULT_COL_WIDTH: f64 = 90.0;
+pub const DEFAULT_ROW_HEIGHT: f64 = 30.0;
+
+// color of the lines between cells, unless the session sets its own
+pub const DEFAULT_GRIDLINE_COLOR: &str = "#e6e6e6";
+
+// the colors that the default ones (black on white, with light gridlines) become in dark mode
+pub const DARK_FONT_COLOR: &str = "#e0e0e0";
+pub const DARK_BACKGROUND_COLOR: &str = "#1e1e1e";
+pub const DARK_GRIDLINE_COLOR: &str = "#3c3c3c";
+
+// the color theme of the app (see `Session::dark_mode`). Cells keep the colors they've been
+// given in either theme, only the default colors change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    // CSS swapping the default colors of a cell's style for the theme's
+    pub fn overrides(&self, style: &Style) -> String {
+        if *self == Theme::Light {
+            return String::new();
+        }
+        let default = Style::default();
+        let mut css = String::new();
+        if style.font_color == default.font_color {
+            css += &format! {"color: {};\n", DARK_FONT_COLOR};
+        }
+        if style.background_color == default.background_color {
+            css += &format! {"background: {};\n", DARK_BACKGROUND_COLOR};
+        }
+        css
+    }
+
+    // the color gridlines are drawn in, where the default color is the theme's own
+    pub fn gridline_color(&self, color: &str) -> String {
+        match self {
+            Theme::Dark if color == DEFAULT_GRIDLINE_COLOR => DARK_GRIDLINE_COLOR.to_string(),
+            _ => color.to_string(),
+        }
+    }
+}
+
+fn default_font_family() -> String {
+    "sans-serif".to_string()
+}
+
+fn default_font_style() -> String {
+    "normal".to_string()
+}
+
+fn default_text_decoration() -> String {
+    "none".to_string()
+}
+
+fn default_font_size() -> f64 {
+    14.0
+}
+
+fn default_background_color() -> String {
+    "white".to_string()
+}
+
+fn default_display() -> bool {
+    true
+}
+
+fn default_text_align() -> String {
+    "left".to_string()
+}
+
+fn default_vertical_align() -> String {
+    "middle".to_string()
+}
+
+// cells are flex containers, so vertical alignment maps onto `align-items`
+fn align_items(vertical_align: &str) -> &str {
+    match vertical_align {
+        "top" => "flex-start",
+        "bottom" => "flex-end",
+        _ => "center",
+    }
+}
+
+// wrapped text keeps its line breaks and breaks long words, unwrapped text only breaks where
+// there's a line break (from Alt-Enter). The cell-data inside the cell inherits this
+fn white_space(wrap_text: bool) -> &'static str {
+    if wrap_text {
+        "white-space: pre-wrap;\noverflow-wrap: break-word;"
+    } else {
+        "white-space: pre;\noverflow: hidden;"
+    }
+}
+
 impl Style {
     pub fn default() -> Style {
         Style {
-            width: 90.00,
-            height: 30.00,
+            width: DEFAULT_COL_WIDTH,
+            height: DEFAULT_ROW_HEIGHT,
             border_color: "grey".to_string(),
             border_collapse: false,
             font_weight: 400,
+            font_style: default_font_style(),
+            text_decoration: default_text_decoration(),
+            font_family: default_font_family(),
+            font_size: default_font_size(),
             font_color: "black".to_string(),
+            background_color: default_background_color(),
             col_span: (0, 0),
             row_span: (0, 0),
-            display: true,
+            display: default_display(),
+            locked: false,
+            text_align: default_text_align(),
+            vertical_align: default_vertical_align(),
+            hidden_rows: vec![],
+            hidden_cols: vec![],
+            number_format: None,
+            wrap_text: false,
+            display_precision: None,
+        }
+    }
+
+    // the default style, except for what's part of the grid's structure rather than formatting:
+    // sizes, merges (spans & display), locking and hidden rows/cols are kept
+    pub fn cleared(&self) -> Style {
+        Style::default().with_structure_of(self)
+    }
+
+    // this style's formatting, with the structural parts (see `cleared`) taken from `other`
+    pub fn with_structure_of(&self, other: &Style) -> Style {
+        Style {
+            width: other.width,
+            height: other.height,
+            col_span: other.col_span,
+            row_span: other.row_span,
+            display: other.display,
+            locked: other.locked,
+            hidden_rows: other.hidden_rows.clone(),
+            hidden_cols: other.hidden_cols.clone(),
+            ..self.clone()
         }
     }
 
@@ -56,20 +223,205 @@ impl Style {
         "/* border: 1px; NOTE: ignoring Style::border_* for now */
 border-collapse: {};
 font-weight: {};
+font-style: {};
+text-decoration: {};
+font-family: {};
+font-size: {}px;
 color: {};
+background: {};
+text-align: {};
+display: flex;
+align-items: {};
+{}
 \n",
         // self.border_color,
         if self.border_collapse { "collapse" } else { "inherit" },
         self.font_weight,
+        self.font_style,
+        self.text_decoration,
+        self.font_family,
+        self.font_size,
         self.font_color,
+        self.background_color,
+        self.text_align,
+        align_items(&self.vertical_align),
+        white_space(self.wrap_text),
+        }
+    }
+}
+
+// predicates that a conditional format checks a cell's value against. The numeric ones never
+// match values that aren't numbers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Predicate {
+    GreaterThan(f64),
+    LessThan(f64),
+    Equals(String),
+    Contains(String),
+}
+
+// names of the predicates, as they're listed in the rule builder
+pub const PREDICATE_NAMES: [&str; 4] = ["greater than", "less than", "equals", "contains"];
+
+impl Predicate {
+    // builds a predicate from one of PREDICATE_NAMES and the value it compares against
+    pub fn from_parts(name: &str, operand: &str) -> Option<Predicate> {
+        match name {
+            "greater than" => operand.trim().parse().ok().map(Predicate::GreaterThan),
+            "less than" => operand.trim().parse().ok().map(Predicate::LessThan),
+            "equals" => Some(Predicate::Equals(operand.to_string())),
+            "contains" => Some(Predicate::Contains(operand.to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        let number = value.trim().parse::<f64>().ok();
+        match self {
+            Predicate::GreaterThan(x) => number.map_or(false, |n| n > *x),
+            Predicate::LessThan(x) => number.map_or(false, |n| n < *x),
+            Predicate::Equals(s) => value == s,
+            Predicate::Contains(s) => value.contains(s.as_str()),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            Predicate::GreaterThan(x) => format! {"> {}", x},
+            Predicate::LessThan(x) => format! {"< {}", x},
+            Predicate::Equals(s) => format! {"= \"{}\"", s},
+            Predicate::Contains(s) => format! {"contains \"{}\"", s},
         }
     }
 }
 
+// a style for the cells of `column` whose value satisfies `predicate`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConditionalFormat {
+    pub column: Col,
+    pub predicate: Predicate,
+    pub style: Style,
+}
+
+impl ConditionalFormat {
+    // CSS for the parts of the rule's style that differ from the default style, so the rest
+    // of the cell's own style (and its size) is kept
+    pub fn overrides(&self) -> String {
+        let default = Style::default();
+        let mut css = String::new();
+        if self.style.background_color != default.background_color {
+            css += &format! {"background: {};\n", self.style.background_color};
+        }
+        if self.style.font_color != default.font_color {
+            css += &format! {"color: {};\n", self.style.font_color};
+        }
+        if self.style.font_weight != default.font_weight {
+            css += &format! {"font-weight: {};\n", self.style.font_weight};
+        }
+        if self.style.font_style != default.font_style {
+            css += &format! {"font-style: {};\n", self.style.font_style};
+        }
+        if self.style.text_decoration != default.text_decoration {
+            css += &format! {"text-decoration: {};\n", self.style.text_decoration};
+        }
+        css
+    }
+}
+
+// CSS of the conditional formats that apply to the cell at `coord`, which is checked by the
+// value of an input (or number) cell. Later rules win over earlier ones.
+fn conditional_style(
+    formats: &[ConditionalFormat],
+    grammar: &Grammar,
+    coord: &Coordinate,
+) -> String {
+    let value = match &grammar.kind {
+        Kind::Input(value) => value.clone(),
+        Kind::Number(number, _) => number.to_string(),
+        _ => return String::new(),
+    };
+    formats
+        .iter()
+        .filter(|f| f.column == coord.full_col() && f.predicate.matches(&value))
+        .map(|f| f.overrides())
+        .collect()
+}
+
+// `default_size` is the (width, height) of columns and rows that haven't been resized
 pub fn get_style(
     model_grammar: &Grammar,
     model_col_widths: &HashMap<coordinate::Col, f64>,
     model_row_heights: &HashMap<coordinate::Row, f64>,
+    default_size: (f64, f64),
+    conditional_formats: &[ConditionalFormat],
+    theme: Theme,
+    coord: &Coordinate,
+) -> String {
+    // grids only get their layout from their style, so there's no colors to swap
+    let theme_style = match model_grammar.kind {
+        Kind::Grid(_) => String::new(),
+        _ => theme.overrides(&model_grammar.style),
+    };
+    format! {
+        "{}{}{}",
+        base_style(model_grammar, model_col_widths, model_row_heights, default_size, coord),
+        theme_style,
+        conditional_style(conditional_formats, model_grammar, coord),
+    }
+}
+
+// `StyleCache` keeps the CSS computed for each cell between renders, so a re-render only has
+// to recompute the styles of the cells that changed since the last one. `Model::update`
+// decides what gets thrown away: typing into a cell only invalidates that cell.
+#[derive(Debug, Default)]
+pub struct StyleCache {
+    styles: RefCell<HashMap<Coordinate, String>>,
+    // how many times a style actually had to be computed (handy for profiling)
+    pub misses: Cell<usize>,
+}
+
+impl StyleCache {
+    pub fn get(
+        &self,
+        grammar: &Grammar,
+        col_widths: &HashMap<coordinate::Col, f64>,
+        row_heights: &HashMap<coordinate::Row, f64>,
+        default_size: (f64, f64),
+        conditional_formats: &[ConditionalFormat],
+        theme: Theme,
+        coord: &Coordinate,
+    ) -> String {
+        if let Some(style) = self.styles.borrow().get(coord) {
+            return style.clone();
+        }
+        let style = get_style(
+            grammar,
+            col_widths,
+            row_heights,
+            default_size,
+            conditional_formats,
+            theme,
+            coord,
+        );
+        self.misses.set(self.misses.get() + 1);
+        self.styles.borrow_mut().insert(coord.clone(), style.clone());
+        style
+    }
+
+    pub fn invalidate(&self, coord: &Coordinate) {
+        self.styles.borrow_mut().remove(coord);
+    }
+
+    pub fn clear(&self) {
+        self.styles.borrow_mut().clear();
+    }
+}
+
+fn base_style(
+    model_grammar: &Grammar,
+    model_col_widths: &HashMap<coordinate::Col, f64>,
+    model_row_heights: &HashMap<coordinate::Row, f64>,
+    (default_col_width, default_row_height): (f64, f64),
     coord: &Coordinate,
 ) -> String {
     let grammar = model_grammar;
@@ -115,11 +467,13 @@ pub fn get_style(
             grammar.style(coord),
         };
     }
-    let col_width = model_col_widths.get(&coord.full_col()).unwrap_or(&90.0);
-    let row_height = model_row_heights.get(&coord.full_row()).unwrap_or(&30.0);
+    let col_width = model_col_widths.get(&coord.full_col()).unwrap_or(&default_col_width);
+    let row_height = model_row_heights.get(&coord.full_row()).unwrap_or(&default_row_height);
+    // the row height is only a minimum for wrapped text, so the row grows to fit all its lines
+    let height = if grammar.style.wrap_text { "min-height" } else { "height" };
     format! {
-        "{}\nwidth: {}px;\nheight: {}px;\n",
-        grammar.style(coord), col_width, row_height,
+        "{}\nwidth: {}px;\n{}: {}px;\n",
+        grammar.style(coord), col_width, height, row_height,
     }
 }
 
@@ -150,33 +504,200 @@ mod tests {
 
     #[test]
     fn test_style_to_string() {
-        assert_eq!(Style::default().to_string(),  String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\n"));
+        assert_eq!(Style::default().to_string(),  String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\nfont-style: normal;\ntext-decoration: none;\nfont-family: sans-serif;\nfont-size: 14px;\ncolor: black;\nbackground: white;\ntext-align: left;\ndisplay: flex;\nalign-items: center;\nwhite-space: pre;\noverflow: hidden;\n\n"));
+        let mut aligned = Style::default();
+        aligned.text_align = "right".to_string();
+        aligned.vertical_align = "top".to_string();
+        assert!(aligned.to_string().contains("text-align: right;\ndisplay: flex;\nalign-items: flex-start;\n"));
+        let mut wrapped = Style::default();
+        wrapped.wrap_text = true;
+        assert!(wrapped.to_string().contains("white-space: pre-wrap;\noverflow-wrap: break-word;\n"));
+        assert!(!wrapped.to_string().contains("white-space: pre;"));
+        assert!(!wrapped.to_string().contains("overflow: hidden;"));
         // assert_ne!(Style::default().to_string(),  String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\n    border-collapse: inherit;\n    font-weight: 400;\n    color: black;\n" ));
     }
 
     #[test]
     fn test_get_style() {
         //Test type Grid
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root-A1") ),
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]), validator: None,}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT), &[], Theme::Light, &coord!("root-A1") ),
         String::from("display: grid;\ngrid-area: cell-root-A1;\nheight: fit-content;\nwidth: fit-content !important;\ngrid-template-areas: \n\"cell-root-A1-A1 cell-root-A1-B1\"\n\"cell-root-A1-A2 cell-root-A1-B2\"\n\"cell-root-A1-A3 cell-root-A1-B3\";\n\nwidth: fit-content;\nheight: fit-content;\n"));
-        assert_ne!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root-A1") ),
+        assert_ne!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]), validator: None,}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT), &[], Theme::Light, &coord!("root-A1") ),
         String::from("display: grid;\ngrid-area: cell-root-B1;\nheight: fit-content;\nwidth: fit-content !important;\ngrid-template-areas: \n\"cell-root-A1-A1 cell-root-A1-C1\"\n\"cell-root-A1-A2 cell-root-A1-B2\"\n\"cell-root-A1-A3 cell-root-A1-B3\";\n\nwidth: fit-content;\nheight: fit-content;\n"));
 
         //Test Row_cols length == 1
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root") ),
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]), validator: None,}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT), &[], Theme::Light, &coord!("root") ),
         String::from("display: grid;\ngrid-area: cell-root;\nheight: fit-content;\nwidth: fit-content !important;\ngrid-template-areas: \n\"cell-root-A1 cell-root-B1\"\n\"cell-root-A2 cell-root-B2\"\n\"cell-root-A3 cell-root-B3\";\n"));
 
         //Test Kind input
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Input(String::default())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root") ),
-        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root;\n"));
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Input(String::default()), validator: None}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT), &[], Theme::Light, &coord!("root") ),
+        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\nfont-style: normal;\ntext-decoration: none;\nfont-family: sans-serif;\nfont-size: 14px;\ncolor: black;\nbackground: white;\ntext-align: left;\ndisplay: flex;\nalign-items: center;\nwhite-space: pre;\noverflow: hidden;\n\ngrid-area: cell-root;\n"));
 
         //Test Type interractive =>  Button as exemple
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Interactive(String::from("Test"), Interactive::Button())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root") ),
-        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root;\n"));
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Interactive(String::from("Test"), Interactive::Button()), validator: None}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT), &[], Theme::Light, &coord!("root") ),
+        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\nfont-style: normal;\ntext-decoration: none;\nfont-family: sans-serif;\nfont-size: 14px;\ncolor: black;\nbackground: white;\ntext-align: left;\ndisplay: flex;\nalign-items: center;\nwhite-space: pre;\noverflow: hidden;\n\ngrid-area: cell-root;\n"));
+
+        // wrapped text only sets a minimum height, so the row can grow
+        let mut wrapped = grammar::Grammar::input("", "some long text");
+        wrapped.style.wrap_text = true;
+        let (col_widths, row_heights) = (HashMap::new(), HashMap::new());
+        let default_size = (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT);
+        let wrapped_style = get_style(
+            &wrapped,
+            &col_widths,
+            &row_heights,
+            default_size,
+            &[],
+            Theme::Light,
+            &coord!("root-A1"),
+        );
+        assert!(wrapped_style.ends_with("width: 90px;\nmin-height: 30px;\n"));
+        // rows and columns that haven't been resized are the session's default size
+        let sized_style = get_style(
+            &wrapped,
+            &col_widths,
+            &row_heights,
+            (120.0, 40.0),
+            &[],
+            Theme::Light,
+            &coord!("root-A1"),
+        );
+        assert!(sized_style.ends_with("width: 120px;\nmin-height: 40px;\n"));
 
         // Test Type Lookup // Have to figureout the arguments
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Lookup(String::default(), std::option::Option::default())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root") ),
-        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ndisplay: inline-flex; grid-area: cell-root; background: white;\n"));
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Lookup(String::default(), std::option::Option::default()), validator: None}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT), &[], Theme::Light, &coord!("root") ),
+        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\nfont-style: normal;\ntext-decoration: none;\nfont-family: sans-serif;\nfont-size: 14px;\ncolor: black;\nbackground: white;\ntext-align: left;\ndisplay: flex;\nalign-items: center;\nwhite-space: pre;\noverflow: hidden;\n\ndisplay: inline-flex; grid-area: cell-root;\n"));
+    }
+
+    #[test]
+    fn test_style_cleared() {
+        let mut style = Style::default();
+        style.font_weight = 700;
+        style.font_style = "italic".to_string();
+        style.background_color = "#ffcc00".to_string();
+        style.text_align = "center".to_string();
+        style.width = 180.0;
+        style.col_span = (1, 2);
+        style.display = false;
+        style.locked = true;
+        style.hidden_rows = vec![2];
+
+        let cleared = style.cleared();
+        assert_eq!(cleared.to_string(), Style::default().to_string());
+        assert_eq!(cleared.width, 180.0);
+        assert_eq!(cleared.height, DEFAULT_ROW_HEIGHT);
+        assert_eq!(cleared.col_span, (1, 2));
+        assert_eq!(cleared.display, false);
+        assert!(cleared.locked);
+        assert_eq!(cleared.hidden_rows, vec![2]);
+    }
+
+    #[test]
+    fn test_predicate_matches() {
+        // numeric predicates
+        assert!(Predicate::GreaterThan(10.0).matches("12.5"));
+        assert!(!Predicate::GreaterThan(10.0).matches("10"));
+        assert!(Predicate::LessThan(0.0).matches(" -3 "));
+        assert!(!Predicate::LessThan(0.0).matches("abc"));
+        // string predicates
+        assert!(Predicate::Equals("done".to_string()).matches("done"));
+        assert!(!Predicate::Equals("done".to_string()).matches("not done yet"));
+        assert!(Predicate::Contains("done".to_string()).matches("not done yet"));
+        assert!(!Predicate::Contains("Done".to_string()).matches("done"));
+
+        assert_eq!(Predicate::from_parts("greater than", "5"), Some(Predicate::GreaterThan(5.0)));
+        assert_eq!(Predicate::from_parts("less than", "five"), None);
+        assert_eq!(
+            Predicate::from_parts("contains", "x"),
+            Some(Predicate::Contains("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_conditional_style() {
+        let mut highlight = Style::default();
+        highlight.background_color = "red".to_string();
+        let formats = vec![ConditionalFormat {
+            column: coord_col!("root", "A"),
+            predicate: Predicate::GreaterThan(100.0),
+            style: highlight,
+        }];
+        let grammar = Grammar::input("", "150");
+        assert_eq!(
+            conditional_style(&formats, &grammar, &coord!("root-A2")),
+            "background: red;\n"
+        );
+        // other columns and values that don't match keep their own style
+        assert_eq!(conditional_style(&formats, &grammar, &coord!("root-B2")), "");
+        let grammar = Grammar::input("", "50");
+        assert_eq!(conditional_style(&formats, &grammar, &coord!("root-A2")), "");
+        // the overrides go after the cell's own style, so they win
+        let grammar = Grammar::input("", "150");
+        let (col_widths, row_heights) = (HashMap::new(), HashMap::new());
+        let default_size = (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT);
+        let coord = coord!("root-A2");
+        let style = get_style(
+            &grammar,
+            &col_widths,
+            &row_heights,
+            default_size,
+            &formats,
+            Theme::Light,
+            &coord,
+        );
+        assert!(style.ends_with("background: red;\n"));
+    }
+
+    #[test]
+    fn test_theme() {
+        let grammar = Grammar::input("", "");
+        let (col_widths, row_heights) = (HashMap::new(), HashMap::new());
+        let default_size = (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT);
+        let coord = coord!("root-A1");
+        let dark =
+            get_style(&grammar, &col_widths, &row_heights, default_size, &[], Theme::Dark, &coord);
+        let colors = format! {
+            "color: {};\nbackground: {};\n",
+            DARK_FONT_COLOR, DARK_BACKGROUND_COLOR
+        };
+        assert!(dark.ends_with(&colors));
+        // colors that were picked for the cell stay as they are
+        let mut red = Style::default();
+        red.background_color = "red".to_string();
+        assert_eq!(Theme::Dark.overrides(&red), format! {"color: {};\n", DARK_FONT_COLOR});
+        assert_eq!(Theme::Light.overrides(&Style::default()), "");
+        assert_eq!(Theme::Dark.gridline_color(DEFAULT_GRIDLINE_COLOR), DARK_GRIDLINE_COLOR);
+        assert_eq!(Theme::Dark.gridline_color("#336699"), "#336699");
+    }
+
+    #[test]
+    fn test_style_cache() {
+        // a 20x20 grid, styled once
+        let coords: Vec<Coordinate> = (1..=20)
+            .flat_map(|row| (1..=20).map(move |col| (row, col)))
+            .map(|row_col| Coordinate::child_of(&coord!("root"), non_zero_u32_tuple(row_col)))
+            .collect();
+        let grammar = Grammar::input("", "");
+        let (col_widths, row_heights) = (HashMap::new(), HashMap::new());
+        let default_size = (DEFAULT_COL_WIDTH, DEFAULT_ROW_HEIGHT);
+        let cache = StyleCache::default();
+        let render = |cache: &StyleCache| {
+            for coord in &coords {
+                let theme = Theme::Light;
+                cache.get(&grammar, &col_widths, &row_heights, default_size, &[], theme, coord);
+            }
+        };
+        render(&cache);
+        assert_eq!(cache.misses.get(), 400);
+
+        // editing one cell only restyles that cell on the next render
+        cache.invalidate(&coord!("root-C7"));
+        render(&cache);
+        assert_eq!(cache.misses.get(), 401);
+
+        cache.clear();
+        render(&cache);
+        assert_eq!(cache.misses.get(), 801);
     }
 
     #[test]
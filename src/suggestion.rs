@@ -0,0 +1,92 @@
+// Suggestions for input cells are ranked by fuzzy matching what's typed in the cell against the
+// names of the grammars in meta, so "jsfn" finds "js::function".
+
+// at most this many suggestions are shown under a cell
+pub const MAX_SUGGESTIONS: usize = 8;
+
+// `fuzzy_score` scores how well `query` matches `name` as a (case-insensitive) subsequence,
+// or None if it doesn't match at all. Matches at the start of the name, right after a
+// separator, or right after the previous matched character score higher, and every character
+// skipped in between costs a point, so an exact prefix beats a scattered match.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut matched = 0;
+    let mut prev_match: Option<usize> = None;
+    for (i, c) in name.iter().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        if *c != query[matched] {
+            continue;
+        }
+        score += 1;
+        if i == 0 {
+            score += 10;
+        } else if is_separator(name[i - 1]) {
+            score += 3;
+        }
+        match prev_match {
+            Some(prev) if prev + 1 == i => score += 5,
+            Some(prev) => score -= (i - prev - 1) as i32,
+            None => (),
+        }
+        prev_match = Some(i);
+        matched += 1;
+    }
+    if matched == query.len() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c == ':' || c == '_' || c == '-' || c == ' ' || c == '.'
+}
+
+// `rank_suggestions` keeps the `candidates` whose name fuzzy-matches `query`, best match
+// first (ties keep their original order), up to MAX_SUGGESTIONS of them
+pub fn rank_suggestions<T: Clone>(query: &str, candidates: &[(String, T)]) -> Vec<T> {
+    let mut scored: Vec<(i32, T)> = candidates
+        .iter()
+        .filter_map(|(name, c)| fuzzy_score(query, name).map(|score| (score, c.clone())))
+        .collect();
+    scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "js::function"), None);
+        assert!(fuzzy_score("jsfn", "js::function").is_some());
+        assert!(fuzzy_score("JS", "js_grammar").is_some());
+        // an exact prefix ranks above a scattered subsequence
+        assert!(fuzzy_score("js", "js_grammar") > fuzzy_score("js", "jquery_script"));
+        assert!(fuzzy_score("java", "java_grammar") > fuzzy_score("java", "js_and_lava"));
+    }
+
+    #[test]
+    fn test_rank_suggestions() {
+        let candidates: Vec<(String, u32)> = vec![
+            ("jquery_script".to_string(), 1),
+            ("python".to_string(), 2),
+            ("js_grammar".to_string(), 3),
+        ];
+        assert_eq!(rank_suggestions("js", &candidates), vec![3, 1]);
+        assert_eq!(rank_suggestions("", &candidates), vec![1, 2, 3]);
+
+        let many: Vec<(String, u32)> = (0..20).map(|i| (format! {"grammar_{}", i}, i)).collect();
+        assert_eq!(rank_suggestions("g", &many).len(), MAX_SUGGESTIONS);
+    }
+}
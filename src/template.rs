@@ -0,0 +1,127 @@
+// Templates are ready-made structures (a table, a list of key-value pairs, a form) that can be
+// stamped into a cell from the Home menu, instead of building them up cell by cell.
+
+use std::collections::HashMap;
+
+use crate::coordinate::Coordinate;
+use crate::grammar::{Grammar, Interactive, Kind};
+use crate::grammar_map::*;
+use crate::{g, grid};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemplateId {
+    Table,
+    KeyValue,
+    Form,
+}
+
+// the templates in the order the gallery lists them
+pub const TEMPLATES: [TemplateId; 3] = [TemplateId::Table, TemplateId::KeyValue, TemplateId::Form];
+
+impl TemplateId {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemplateId::Table => "3-Column Table",
+            TemplateId::KeyValue => "Key-Value Pairs",
+            TemplateId::Form => "Form",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            TemplateId::Table => "a header row and three empty rows",
+            TemplateId::KeyValue => "keys on the left, their values on the right",
+            TemplateId::Form => "labelled fields and a submit button",
+        }
+    }
+
+    // the template's grid. It's built from scratch every time, so every insertion of a
+    // template is independent of the others
+    pub fn entry(&self) -> MapEntry {
+        match self {
+            TemplateId::Table => grid![
+                [g!(header("Column 1")), g!(header("Column 2")), g!(header("Column 3"))],
+                [g!(empty()), g!(empty()), g!(empty())],
+                [g!(empty()), g!(empty()), g!(empty())],
+                [g!(empty()), g!(empty()), g!(empty())]
+            ],
+            TemplateId::KeyValue => grid![
+                [g!(header("Key")), g!(header("Value"))],
+                [g!(empty()), g!(empty())],
+                [g!(empty()), g!(empty())]
+            ],
+            TemplateId::Form => grid![
+                [g!(Grammar::text("", "Name")), g!(empty())],
+                [g!(Grammar::text("", "Email")), g!(empty())],
+                [g!(Grammar::text("", "Notes")), g!(empty())],
+                [g!(empty()), g!(submit_button())]
+            ],
+        }
+    }
+
+    // the number of (rows, cols) in the template's grid
+    pub fn size(&self) -> (u32, u32) {
+        match self.entry() {
+            MapEntry::Grid(rows) => {
+                (rows.len() as u32, rows.iter().map(|row| row.len()).max().unwrap_or(0) as u32)
+            }
+            MapEntry::G(_) => (1, 1),
+        }
+    }
+}
+
+fn header(title: &str) -> Grammar {
+    let mut grammar = Grammar::input("", title);
+    grammar.style.font_weight = 700;
+    grammar
+}
+
+fn empty() -> Grammar {
+    Grammar::input("", "")
+}
+
+fn submit_button() -> Grammar {
+    Grammar {
+        kind: Kind::Interactive("Submit".to_string(), Interactive::Button()),
+        ..Grammar::default_button()
+    }
+}
+
+// `template_grammars` builds the grammars of `template` nested in the cell at `coord`,
+// including the grid at `coord` itself
+pub fn template_grammars(template: TemplateId, coord: &Coordinate) -> HashMap<Coordinate, Grammar> {
+    let mut grammars = HashMap::new();
+    build_grammar_map(&mut grammars, coord.clone(), template.entry());
+    grammars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord;
+
+    #[test]
+    fn test_template_grammars() {
+        for template in TEMPLATES.iter() {
+            let (rows, cols) = template.size();
+            let grammars = template_grammars(*template, &coord!("root-B2"));
+            assert_eq!(grammars.len() as u32, rows * cols + 1);
+            match &grammars[&coord!("root-B2")].kind {
+                Kind::Grid(sub_coords) => assert_eq!(sub_coords.len() as u32, rows * cols),
+                kind => panic!("expected a grid, got {:?}", kind),
+            }
+        }
+        assert_eq!(TemplateId::Table.size(), (4, 3));
+        let table = template_grammars(TemplateId::Table, &coord!("root-A1"));
+        assert_eq!(table[&coord!("root-A1-C1")].kind, Kind::Input("Column 3".to_string()));
+        assert_eq!(table[&coord!("root-A1-C1")].style.font_weight, 700);
+    }
+
+    #[test]
+    fn test_template_insertions_are_independent() {
+        let mut first = template_grammars(TemplateId::KeyValue, &coord!("root-A1"));
+        let second = template_grammars(TemplateId::KeyValue, &coord!("root-A1"));
+        first.get_mut(&coord!("root-A1-A1")).unwrap().kind = Kind::Input("changed".to_string());
+        assert_eq!(second[&coord!("root-A1-A1")].kind, Kind::Input("Key".to_string()));
+    }
+}
@@ -1,50 +1,1183 @@
 #![feature(core_intrinsics)]
-use std::char::from_u32;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+use std::io::Cursor;
 use std::num::NonZeroU32;
 use std::ops::Deref;
 use std::option::Option;
-use stdweb::unstable::TryFrom;
-use stdweb::web::{document, HtmlElement, IHtmlElement, INonElementParentNode};
+use calamine::{Reader, Xlsx};
+use pulldown_cmark::html::push_html;
+use pulldown_cmark::{CowStr, Event, Parser as MarkdownParser, Tag};
+use regex::Regex;
+use stdweb::unstable::{TryFrom, TryInto};
+use stdweb::web::{
+    document, set_timeout, EventTarget, HtmlElement, IHtmlElement, INonElementParentNode,
+    IParentNode,
+};
 use stdweb::Value;
+use yew::NodeRef;
 
-use crate::coordinate::{Col, Coordinate, Row};
-use crate::grammar::{Grammar, Kind};
+use crate::coordinate::{col_to_letters, Col, Coordinate, Row};
+use crate::formula::rebase_formula;
+use crate::grammar::{Grammar, Kind, Lookup};
 use crate::grammar_map::*;
-use crate::model::Model;
-use crate::style::Style;
+use crate::model::{CsvOrientation, Model};
+use crate::style::{ConditionalFormat, Style};
 use crate::{g, grid, row_col_vec};
 
 // `move_grammar` function does all the necessary operations when copying nested grammars from one
 // coordinate in the grid to another including:
 // - copying each nested grammar all the way to the innermost cell
 // - adjusting the sizes of the grammars in row_heights and col_widths
+// It returns an error (and leaves `dest` untouched) if there's no grammar at `source`.
 //
 // TODO:
-// - add error return value that can be checked to see if grammar move was successful
-// - add UNIT TEST to ensure that destination coord is only manipulated if source coord exists
 // - (maybe) incorporate dom_resize to get correct values
-pub fn move_grammar(m: &mut Model, source: Coordinate, dest: Coordinate) {
-    if let Some(source_grammar) = m.get_session_mut().grammars.clone().get(&source) {
-        // copy source grammar from map and insert into destination coordinate
-        m.get_session_mut()
-            .grammars
-            .insert(dest.clone(), source_grammar.clone());
-        // resizes new grammar
-        let row_height = m.row_heights.get(&source.full_row()).unwrap_or(&30.0);
-        let col_width = m.col_widths.get(&source.full_col()).unwrap_or(&90.0);
-        resize(m, dest.clone(), *row_height, *col_width);
-        // copying over child grammar values
-        if let Kind::Grid(sub_coords) = source_grammar.clone().kind {
-            for sub_coord in sub_coords {
-                move_grammar(
-                    m,
-                    Coordinate::child_of(&source, sub_coord),
-                    Coordinate::child_of(&dest, sub_coord),
-                );
+pub fn move_grammar(m: &mut Model, source: Coordinate, dest: Coordinate) -> Result<(), String> {
+    let source_grammar = copy_grammar(&mut m.get_session_mut().grammars, &source, &dest)?;
+    // resizes new grammar
+    let row_height = m.row_height(&source.full_row());
+    let col_width = m.col_width(&source.full_col());
+    resize(m, dest.clone(), row_height, col_width);
+    // copying over child grammar values
+    if let Kind::Grid(sub_coords) = source_grammar.kind {
+        for sub_coord in sub_coords {
+            let sub_source = Coordinate::child_of(&source, sub_coord);
+            // grids can list sub-coordinates that were never filled in, which are skipped
+            if m.get_session().grammars.contains_key(&sub_source) {
+                move_grammar(m, sub_source, Coordinate::child_of(&dest, sub_coord))?;
             }
         }
     }
+    Ok(())
+}
+
+// copies the grammar at `source` (not including its nested grammars) to `dest`, returning it
+pub fn copy_grammar(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    source: &Coordinate,
+    dest: &Coordinate,
+) -> Result<Grammar, String> {
+    let source_grammar = grammars
+        .get(source)
+        .cloned()
+        .ok_or_else(|| format! {"there's no grammar at {} to copy", source.to_string()})?;
+    grammars.insert(dest.clone(), source_grammar.clone());
+    Ok(source_grammar)
+}
+
+// `fill_targets` lists the cells that dragging the fill handle from `source` to `target` copies
+// into: every sibling of `source` in the rectangle between the two, except `source` itself
+pub fn fill_targets(source: &Coordinate, target: &Coordinate) -> Vec<Coordinate> {
+    let parent = match (source.parent(), target.parent()) {
+        (Some(source_parent), Some(target_parent)) if source_parent == target_parent => {
+            source_parent
+        }
+        _ => return vec![],
+    };
+    let (source_row, source_col) = source.row_col();
+    let (target_row, target_col) = target.row_col();
+    let rows = source_row.get().min(target_row.get())..=source_row.get().max(target_row.get());
+    let mut targets = Vec::new();
+    for row in rows {
+        let cols = source_col.get().min(target_col.get())..=source_col.get().max(target_col.get());
+        for col in cols {
+            let coord = Coordinate::child_of(&parent, non_zero_u32_tuple((row, col)));
+            if coord != *source {
+                targets.push(coord);
+            }
+        }
+    }
+    targets
+}
+
+// `add_defn_rule` appends an empty rule to the Kind::Defn grammar at `coord`, creating the rule's
+// name and grammar cells in the next row under the definition's coordinate. Returns false if
+// `coord` isn't a definition.
+pub fn add_defn_rule(grammars: &mut HashMap<Coordinate, Grammar>, coord: &Coordinate) -> bool {
+    let (defn_coord, index) = match grammars.get_mut(coord) {
+        Some(Grammar {
+            kind: Kind::Defn(_, defn_coord, rules),
+            ..
+        }) => {
+            let index = rules.len() as u32 + 1;
+            let rule_coord = Coordinate::child_of(defn_coord, non_zero_u32_tuple((index, 2)));
+            rules.push((String::new(), rule_coord));
+            (defn_coord.clone(), index)
+        }
+        _ => return false,
+    };
+    grammars.insert(
+        Coordinate::child_of(&defn_coord, non_zero_u32_tuple((index, 1))),
+        Grammar::input("rule_name", ""),
+    );
+    grammars.insert(
+        Coordinate::child_of(&defn_coord, non_zero_u32_tuple((index, 2))),
+        Grammar::input("rule_grammar", ""),
+    );
+    if let Some(Grammar {
+        kind: Kind::Grid(sub_coords),
+        ..
+    }) = grammars.get_mut(&defn_coord)
+    {
+        sub_coords.push(non_zero_u32_tuple((index, 1)));
+        sub_coords.push(non_zero_u32_tuple((index, 2)));
+    }
+    true
+}
+
+// `remove_defn_rule` removes the rule at `index` (starting from 0) of the Kind::Defn grammar at
+// `coord` along with its cells, moving the rules after it up a row. Returns false if `coord`
+// isn't a definition or doesn't have that many rules.
+pub fn remove_defn_rule(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    index: usize,
+) -> bool {
+    let defn_coord = match grammars.get_mut(coord) {
+        Some(Grammar {
+            kind: Kind::Defn(_, defn_coord, rules),
+            ..
+        }) if index < rules.len() => {
+            rules.remove(index);
+            // the rules after the removed one move up a row
+            for (i, (_, rule_coord)) in rules.iter_mut().enumerate().skip(index) {
+                *rule_coord =
+                    Coordinate::child_of(defn_coord, non_zero_u32_tuple((i as u32 + 1, 2)));
+            }
+            defn_coord.clone()
+        }
+        _ => return false,
+    };
+    let removed_row = index as u32 + 1;
+    let depth = defn_coord.row_cols.len();
+    grammars.retain(|c, _| {
+        !(c.row_cols.len() > depth
+            && c.row_cols.starts_with(&defn_coord.row_cols)
+            && c.row_cols[depth].0.get() == removed_row)
+    });
+    let moved: Vec<Coordinate> = grammars
+        .keys()
+        .filter(|c| {
+            c.row_cols.len() > depth
+                && c.row_cols.starts_with(&defn_coord.row_cols)
+                && c.row_cols[depth].0.get() > removed_row
+        })
+        .cloned()
+        .collect();
+    for c in moved {
+        if let Some(grammar) = grammars.remove(&c) {
+            let mut shifted = c.clone();
+            let (row, col) = c.row_cols[depth];
+            shifted.row_cols[depth] = non_zero_u32_tuple((row.get() - 1, col.get()));
+            grammars.insert(shifted, grammar);
+        }
+    }
+    if let Some(Grammar {
+        kind: Kind::Grid(sub_coords),
+        ..
+    }) = grammars.get_mut(&defn_coord)
+    {
+        sub_coords.retain(|(row, _)| row.get() != removed_row);
+        for (row, _) in sub_coords.iter_mut() {
+            if row.get() > removed_row {
+                *row = NonZeroU32::new(row.get() - 1).unwrap();
+            }
+        }
+    }
+    true
+}
+
+// `remove_definition` removes the definition at `defn`, a cell of the meta grid, along with its
+// nested cells. The definitions below it move up a row, and the bindings (Kind::Defn), lookups
+// and formulas that point into them move along. `meta` is the meta grid's own grammar, which the
+// session keeps apart from the grammar map. Returns false if there's no definition at `defn`.
+pub fn remove_definition(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    meta: &mut Grammar,
+    defn: &Coordinate,
+) -> bool {
+    let meta_coord = Coordinate {
+        row_cols: row_col_vec![(1, 2)],
+    };
+    if defn.parent() != Some(meta_coord.clone()) || !grammars.contains_key(defn) {
+        return false;
+    }
+    let (removed_row, col) = (defn.row().get(), defn.col());
+    remove_subtree(grammars, defn);
+    // where a coordinate inside one of the definitions below the removed one ends up
+    let shifted = |c: &Coordinate| match c.row_cols.get(1) {
+        Some(&(row, c_col))
+            if c.row_cols[0] == meta_coord.row_cols[0] && c_col == col && row.get() > removed_row =>
+        {
+            let mut shifted = c.clone();
+            shifted.row_cols[1] = non_zero_u32_tuple((row.get() - 1, col.get()));
+            shifted
+        }
+        _ => c.clone(),
+    };
+    rebase_references(grammars, &shifted);
+    let moved: Vec<(Coordinate, Grammar)> = grammars
+        .keys()
+        .filter(|c| shifted(c) != **c)
+        .cloned()
+        .collect::<Vec<Coordinate>>()
+        .into_iter()
+        .filter_map(|c| grammars.remove(&c).map(|g| (shifted(&c), g)))
+        .collect();
+    grammars.extend(moved);
+    for grammar in grammars.values_mut() {
+        if let Kind::Defn(_, defn_coord, rules) = &mut grammar.kind {
+            *defn_coord = shifted(defn_coord);
+            for (_, rule_coord) in rules.iter_mut() {
+                *rule_coord = shifted(rule_coord);
+            }
+        }
+    }
+    let unlist = |grammar: &mut Grammar| {
+        if let Kind::Grid(sub_coords) = &mut grammar.kind {
+            sub_coords.retain(|&(row, c)| !(row.get() == removed_row && c == col));
+            for (row, c) in sub_coords.iter_mut() {
+                if *c == col && row.get() > removed_row {
+                    *row = NonZeroU32::new(row.get() - 1).unwrap();
+                }
+            }
+        }
+    };
+    unlist(meta);
+    // the meta grid can be in the grammar map too
+    if let Some(meta_grammar) = grammars.get_mut(&meta_coord) {
+        unlist(meta_grammar);
+    }
+    true
+}
+
+// `rebase_references` points the lookups and formulas in `grammars` at where `moved` says the
+// cells they refer to end up, for when cells get moved around. It's called before the cells
+// themselves are moved, since formulas write references to their own grid differently.
+pub fn rebase_references<F>(grammars: &mut HashMap<Coordinate, Grammar>, moved: F)
+where
+    F: Fn(&Coordinate) -> Coordinate,
+{
+    for (coord, grammar) in grammars.iter_mut() {
+        match &mut grammar.kind {
+            Kind::Input(value) if is_formula(value) => {
+                *value = rebase_formula(value, coord, &moved(coord), &moved);
+            }
+            Kind::Lookup(_, Some(lookup)) => *lookup = rebase_lookup(lookup, &moved),
+            _ => (),
+        }
+    }
+}
+
+// rows and columns go along with their first cell, and ranges with their corners (as long as
+// those still end up in the same grid)
+fn rebase_lookup<F>(lookup: &Lookup, moved: F) -> Lookup
+where
+    F: Fn(&Coordinate) -> Coordinate,
+{
+    let first = NonZeroU32::new(1).unwrap();
+    match lookup {
+        Lookup::Cell(source) => Lookup::Cell(moved(source)),
+        Lookup::Row(Row(parent, row)) => {
+            let cell = moved(&Coordinate::child_of(parent, (*row, first)));
+            match cell.parent() {
+                Some(parent) => Lookup::Row(Row(parent, cell.row())),
+                None => lookup.clone(),
+            }
+        }
+        Lookup::Col(Col(parent, col)) => {
+            let cell = moved(&Coordinate::child_of(parent, (first, *col)));
+            match cell.parent() {
+                Some(parent) => Lookup::Col(Col(parent, cell.col())),
+                None => lookup.clone(),
+            }
+        }
+        Lookup::Range { parent, start, end } => {
+            let start = moved(&Coordinate::child_of(parent, *start));
+            let end = moved(&Coordinate::child_of(parent, *end));
+            match (start.parent(), end.parent()) {
+                (Some(parent), Some(end_parent)) if parent == end_parent => {
+                    let ((start_row, start_col), (end_row, end_col)) =
+                        (start.row_col(), end.row_col());
+                    Lookup::Range {
+                        parent,
+                        start: (start_row.min(end_row), start_col.min(end_col)),
+                        end: (start_row.max(end_row), start_col.max(end_col)),
+                    }
+                }
+                _ => lookup.clone(),
+            }
+        }
+    }
+}
+
+// the hidden row (or column) in `hidden` that's closest to `index`, which is the one "Unhide"
+// brings back
+pub fn nearest_hidden(hidden: &[u32], index: u32) -> Option<u32> {
+    hidden
+        .iter()
+        .cloned()
+        .min_by_key(|line| (*line as i64 - index as i64).abs())
+}
+
+// `frozen_css` pins the first rows and columns of the grid at `grid` with `position: sticky`,
+// given the heights of its frozen rows and the widths of its frozen columns. Each one is offset
+// by the size of the ones before it so they stack instead of overlapping.
+pub fn frozen_css(grid: &Coordinate, row_heights: &[f64], col_widths: &[f64]) -> String {
+    let mut css = String::new();
+    let mut top = 0.0;
+    for (i, height) in row_heights.iter().enumerate() {
+        let row = Coordinate::child_of(grid, non_zero_u32_tuple((i as u32 + 1, 1)));
+        css += format! {
+            "#cell-{} > .row-{} {{ position: sticky; top: {}px; z-index: 2; }}\n",
+            grid.to_string(), row.row_to_string(), top
+        }
+        .deref();
+        top += height;
+    }
+    let mut left = 0.0;
+    for (i, width) in col_widths.iter().enumerate() {
+        let col = Coordinate::child_of(grid, non_zero_u32_tuple((1, i as u32 + 1)));
+        css += format! {
+            "#cell-{} > .col-{} {{ position: sticky; left: {}px; z-index: 2; }}\n",
+            grid.to_string(), col.col_to_string(), left
+        }
+        .deref();
+        left += width;
+    }
+    css
+}
+
+// `tab_step` finds the cell that Tab (or Shift-Tab if `reverse`) moves to from `coord`
+pub fn tab_step(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    reverse: bool,
+) -> Option<Coordinate> {
+    // check if grammar corresponding to a coord exists, and if it's a grid, select its first cell
+    // (or its bottom-right cell when going backwards)
+    let step_into = |c: Coordinate| {
+        grammars.get(&c).map(|g| match &g.kind {
+            Kind::Grid(sub_coords) if reverse => {
+                let last = sub_coords.iter().max().cloned();
+                Coordinate::child_of(&c, last.unwrap_or(non_zero_u32_tuple((1, 1))))
+            }
+            Kind::Grid(_) => Coordinate::child_of(&c, non_zero_u32_tuple((1, 1))),
+            _ => c.clone(),
+        })
+    };
+    let neighbor = if reverse {
+        coord.neighbor_left()
+    } else {
+        coord.neighbor_right()
+    };
+    neighbor
+        .and_then(step_into)
+        .or_else(|| {
+            if reverse {
+                // last column of the previous row
+                coord.neighbor_above().and_then(|above| {
+                    let row = above.full_row();
+                    grammars
+                        .keys()
+                        .filter(|k| k.row_cols.len() != 1 /* ignore root & meta */)
+                        .filter(|k| k.full_row() == row)
+                        .max_by_key(|k| k.col())
+                        .cloned()
+                        .and_then(step_into)
+                })
+            } else {
+                // first column of the next row
+                coord.neighbor_below().and_then(|mut c| {
+                    *c.col_mut() = NonZeroU32::new(1).unwrap();
+                    if grammars.contains_key(&c) {
+                        Some(c)
+                    } else {
+                        None
+                    }
+                })
+            }
+        })
+        .or_else(|| {
+            coord.parent().and_then(|c| {
+                if reverse {
+                    c.neighbor_left()
+                } else {
+                    c.neighbor_right()
+                }
+            })
+        })
+}
+
+// `tab_next` is like `tab_step`, but keeps stepping past cells for which `skip` is true
+pub fn tab_next<F>(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    reverse: bool,
+    skip: F,
+) -> Option<Coordinate>
+where
+    F: Fn(&Coordinate) -> bool,
+{
+    let mut next = tab_step(grammars, coord, reverse);
+    let mut steps = 0;
+    loop {
+        match next {
+            Some(c) if skip(&c) => {
+                // bail out if every cell is skipped
+                if steps >= grammars.len() {
+                    return None;
+                }
+                next = tab_step(grammars, &c, reverse);
+                steps += 1;
+            }
+            other => return other,
+        }
+    }
+}
+
+// finds the first case-insensitive match of `query` in `value`,
+// returning the (start, end) byte offsets of the match
+pub fn find_case_insensitive(value: &str, query: &str) -> Option<(usize, usize)> {
+    if query == "" {
+        return None;
+    }
+    for (start, _) in value.char_indices() {
+        let mut value_chars = value[start..].char_indices();
+        let mut end = start;
+        let mut matched = true;
+        for q in query.chars() {
+            match value_chars.next() {
+                Some((offset, v)) if v.to_lowercase().eq(q.to_lowercase()) => {
+                    end = start + offset + v.len_utf8();
+                }
+                _ => {
+                    matched = false;
+                    break;
+                }
+            }
+        }
+        if matched {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+// replaces every case-insensitive match of `query` in `value`,
+// returning None if there were no matches
+pub fn replace_case_insensitive(value: &str, query: &str, replacement: &str) -> Option<String> {
+    let mut output = String::new();
+    let mut rest = value;
+    let mut replaced = false;
+    while let Some((start, end)) = find_case_insensitive(rest, query) {
+        output.push_str(&rest[..start]);
+        output.push_str(replacement);
+        rest = &rest[end..];
+        replaced = true;
+    }
+    if replaced {
+        output.push_str(rest);
+        Some(output)
+    } else {
+        None
+    }
+}
+
+// inserts `text` into `value` at the `caret`, which is counted in characters (as the browser
+// reports it) rather than bytes. A caret past the end of `value` appends `text`.
+pub fn insert_at_caret(value: &str, caret: usize, text: &str) -> String {
+    let offset = value
+        .char_indices()
+        .nth(caret)
+        .map(|(offset, _)| offset)
+        .unwrap_or(value.len());
+    let mut output = value[..offset].to_string();
+    output.push_str(text);
+    output.push_str(&value[offset..]);
+    output
+}
+
+// formulas are input cells that start with "="
+pub fn is_formula(value: &str) -> bool {
+    value.starts_with('=')
+}
+
+// reference to `target` as written inside a formula in the cell at `from`. Cells in the same
+// grid are referenced by their short A1 name, anything else by its full coordinate.
+pub fn formula_reference(from: &Coordinate, target: &Coordinate) -> String {
+    if from.parent().is_some() && from.parent() == target.parent() {
+        let (row, col) = target.row_col();
+        row_col_to_string((row.get(), col.get()))
+    } else {
+        target.to_string()
+    }
+}
+
+// whether `value` matches a grammar's validator pattern (the whole value has to match).
+// Grammars without a validator, or with a pattern that doesn't compile, accept anything.
+// Each pattern is only compiled once, into `regexes`.
+pub fn matches_validator(
+    regexes: &mut HashMap<String, Option<Regex>>,
+    validator: &Option<String>,
+    value: &str,
+) -> bool {
+    match validator {
+        Some(pattern) => regexes
+            .entry(pattern.clone())
+            .or_insert_with(|| Regex::new(&format! {"^(?:{})$", pattern}).ok())
+            .as_ref()
+            .map_or(true, |re| re.is_match(value)),
+        None => true,
+    }
+}
+
+// `with_precision` is how a cell's `value` is shown with the display precision of its style:
+// numbers are rounded to `precision` decimal places, and anything else is shown as it is
+pub fn with_precision(value: &str, precision: Option<u8>) -> String {
+    match (precision, value.trim().parse::<f64>()) {
+        (Some(precision), Ok(number)) if number.is_finite() => {
+            format! {"{:.*}", precision as usize, number}
+        }
+        _ => value.to_string(),
+    }
+}
+
+// count and sum of the values that are numbers, ignoring everything else (like empty cells)
+pub fn numeric_summary<'a, I>(values: I) -> (usize, f64)
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    values
+        .into_iter()
+        .filter_map(|value| value.trim().parse::<f64>().ok())
+        .filter(|n| n.is_finite())
+        .fold((0, 0.0), |(count, sum), n| (count + 1, sum + n))
+}
+
+// `clock_time` formats a timestamp in ms (like Date.now() returns) as the UTC time of day it's
+// at, e.g. "13:05:09.250"
+pub fn clock_time(timestamp: f64) -> String {
+    let ms = (timestamp.max(0.0) as u64) % (24 * 60 * 60 * 1000);
+    format! {
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000
+    }
+}
+
+// `sparkline_svg` draws `values` as a line chart that fills a `width` by `height` box, oldest
+// value on the left. It's an SVG string, since html! doesn't create SVG elements
+pub fn sparkline_svg(values: &[f64], width: f64, height: f64) -> String {
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1.0 };
+    let step = if values.len() > 1 { width / (values.len() - 1) as f64 } else { 0.0 };
+    let points: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            // flat lines are drawn through the middle
+            let y = if max > min { height - (value - min) / range * height } else { height / 2.0 };
+            format! {"{:.1},{:.1}", i as f64 * step, y}
+        })
+        .collect();
+    format! {
+        "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\
+         <polyline fill=\"none\" stroke=\"currentColor\" stroke-width=\"1\" points=\"{}\"/></svg>",
+        width, height, width, height, points.join(" ")
+    }
+}
+
+// `coordinate_color` is a color picked from a hash of `coord`, so a cell gets the same color on
+// every render (and in every session) without having to store it anywhere
+pub fn coordinate_color(coord: &Coordinate) -> String {
+    // FNV-1a, which unlike the std hashers is guaranteed not to change between releases
+    let hash = coord.to_string().bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format! {"hsl({}, 65%, 45%)", hash % 360}
+}
+
+// a cell is locked if it, or any of the grids it's nested in, has been locked
+pub fn is_locked(grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate) -> bool {
+    let mut current = Some(coord.clone());
+    while let Some(c) = current {
+        if grammars.get(&c).map_or(false, |g| g.style.locked) {
+            return true;
+        }
+        current = c.parent();
+    }
+    false
+}
+
+// whether deleting `coord` would take a locked cell with it (itself or anything nested inside it)
+pub fn contains_locked(grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate) -> bool {
+    is_locked(grammars, coord)
+        || grammars.iter().any(|(c, g)| {
+            g.style.locked
+                && c.row_cols.len() > coord.row_cols.len()
+                && c.row_cols.starts_with(&coord.row_cols)
+        })
+}
+
+// `copy_region` copies the grammars in a rectangle of cells under `parent`, including all their
+// nested grammars. Each grammar is keyed by its path relative to the rectangle, where the first
+// (row, col) is the position inside the rectangle (starting at (1, 1)).
+pub fn copy_region(
+    grammars: &HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+) -> Vec<(Vec<(NonZeroU32, NonZeroU32)>, Grammar)> {
+    let depth = parent.row_cols.len() + 1;
+    let mut region = Vec::new();
+    for (coord, grammar) in grammars.iter() {
+        if coord.row_cols.len() < depth || !coord.row_cols.starts_with(&parent.row_cols) {
+            continue;
+        }
+        let (row, col) = coord.row_cols[depth - 1];
+        if (top..=bottom).contains(&row.get()) && (left..=right).contains(&col.get()) {
+            let mut path = vec![non_zero_u32_tuple((row.get() - top + 1, col.get() - left + 1))];
+            path.extend_from_slice(&coord.row_cols[depth..]);
+            region.push((path, grammar.clone()));
+        }
+    }
+    region
+}
+
+// removes the grammar at `coord` along with all of it's nested grammars
+pub fn remove_subtree(grammars: &mut HashMap<Coordinate, Grammar>, coord: &Coordinate) {
+    grammars.retain(|c, _| !c.row_cols.starts_with(&coord.row_cols));
+}
+
+// `transpose_region` swaps the rows and columns of a rectangle of cells under `parent` in
+// place, anchored at the rectangle's top-left cell. Nested grammars move along with their cells,
+// and the parent grid is grown if the transposed rectangle no longer fits in it.
+pub fn transpose_region(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+) {
+    let (rows, cols) = (bottom - top + 1, right - left + 1);
+    let region = copy_region(grammars, parent, (top, left), (bottom, right));
+
+    // clear the original cells, and the cells the transposed region will cover
+    for row in top..=bottom {
+        for col in left..=right {
+            let cell = Coordinate::child_of(parent, non_zero_u32_tuple((row, col)));
+            remove_subtree(grammars, &cell);
+            grammars.insert(cell, Grammar::default());
+        }
+    }
+    for row in top..(top + cols) {
+        for col in left..(left + rows) {
+            let cell = Coordinate::child_of(parent, non_zero_u32_tuple((row, col)));
+            remove_subtree(grammars, &cell);
+        }
+    }
+
+    for (path, grammar) in region {
+        let (row, col) = path[0];
+        let mut dest = Coordinate::child_of(
+            parent,
+            non_zero_u32_tuple((top + col.get() - 1, left + row.get() - 1)),
+        );
+        dest.row_cols.extend_from_slice(&path[1..]);
+        grammars.insert(dest, grammar);
+    }
+
+    // grow the parent grid so it stays rectangular
+    if let Some(Kind::Grid(sub_coords)) = grammars.get(parent).map(|g| g.kind.clone()) {
+        let max_row = sub_coords
+            .iter()
+            .map(|(r, _)| r.get())
+            .chain(std::iter::once(top + cols - 1))
+            .max()
+            .unwrap();
+        let max_col = sub_coords
+            .iter()
+            .map(|(_, c)| c.get())
+            .chain(std::iter::once(left + rows - 1))
+            .max()
+            .unwrap();
+        let mut new_sub_coords = sub_coords.clone();
+        for row in 1..=max_row {
+            for col in 1..=max_col {
+                let sub_coord = non_zero_u32_tuple((row, col));
+                if !new_sub_coords.contains(&sub_coord) {
+                    new_sub_coords.push(sub_coord);
+                }
+                grammars
+                    .entry(Coordinate::child_of(parent, sub_coord))
+                    .or_insert(Grammar::default());
+            }
+        }
+        if let Some(parent_grammar) = grammars.get_mut(parent) {
+            parent_grammar.kind = Kind::Grid(new_sub_coords);
+        }
+    }
+}
+
+// `transposed_coord` is where `coord` ends up after `transpose_region` swaps the rows and
+// columns of the rectangle under `parent`. Returns None if `coord` is one of the cells the
+// transposed rectangle is written over.
+pub fn transposed_coord(
+    coord: &Coordinate,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+) -> Option<Coordinate> {
+    let depth = parent.row_cols.len();
+    if coord.row_cols.len() <= depth || !coord.row_cols.starts_with(&parent.row_cols) {
+        return Some(coord.clone());
+    }
+    let (row, col) = coord.row_cols[depth];
+    let (row, col) = (row.get(), col.get());
+    let mut transposed = coord.clone();
+    if (top..=bottom).contains(&row) && (left..=right).contains(&col) {
+        transposed.row_cols[depth] = non_zero_u32_tuple((top + col - left, left + row - top));
+        Some(transposed)
+    } else if (top..=top + right - left).contains(&row)
+        && (left..=left + bottom - top).contains(&col)
+    {
+        None
+    } else {
+        Some(transposed)
+    }
+}
+
+// where line `index` ends up after `offset` lines are inserted at line `at`, or, if `offset` is
+// negative, after that many lines from `at` on are deleted and the ones after them move back.
+// None if it was one of the deleted lines
+fn shifted_index(index: NonZeroU32, at: u32, offset: i32) -> Option<NonZeroU32> {
+    let (index, at, offset) = (index.get() as i64, at as i64, offset as i64);
+    if index < at {
+        NonZeroU32::new(index as u32)
+    } else if index < at - offset {
+        None
+    } else {
+        NonZeroU32::new((index + offset) as u32)
+    }
+}
+
+// `shifted_coord` is where `coord` ends up after `offset` rows (or columns if `!rows`) are
+// inserted at line `at` of the grid at `parent`, or deleted from there if `offset` is negative.
+// Nested grammars move along with the cell they're in. Returns None if `coord` was deleted.
+pub fn shifted_coord(
+    coord: &Coordinate,
+    parent: &Coordinate,
+    at: u32,
+    offset: i32,
+    rows: bool,
+) -> Option<Coordinate> {
+    let depth = parent.row_cols.len();
+    if coord.row_cols.len() <= depth || !coord.row_cols.starts_with(&parent.row_cols) {
+        return Some(coord.clone());
+    }
+    let (row, col) = coord.row_cols[depth];
+    let mut shifted = coord.clone();
+    shifted.row_cols[depth] = if rows {
+        (shifted_index(row, at, offset)?, col)
+    } else {
+        (row, shifted_index(col, at, offset)?)
+    };
+    Some(shifted)
+}
+
+// where the size of `row` goes when lines are shifted like `shifted_coord` does. The rows of
+// `parent` itself are renumbered, and the rows of nested grids go along with their cell
+pub fn shifted_row(
+    row: &Row,
+    parent: &Coordinate,
+    at: u32,
+    offset: i32,
+    rows: bool,
+) -> Option<Row> {
+    if row.0 != *parent {
+        shifted_coord(&row.0, parent, at, offset, rows).map(|p| Row(p, row.1))
+    } else if rows {
+        shifted_index(row.1, at, offset).map(|index| Row(row.0.clone(), index))
+    } else {
+        Some(row.clone())
+    }
+}
+
+// like `shifted_row`, for the size of `col`
+pub fn shifted_col(
+    col: &Col,
+    parent: &Coordinate,
+    at: u32,
+    offset: i32,
+    rows: bool,
+) -> Option<Col> {
+    if col.0 != *parent {
+        shifted_coord(&col.0, parent, at, offset, rows).map(|p| Col(p, col.1))
+    } else if !rows {
+        shifted_index(col.1, at, offset).map(|index| Col(col.0.clone(), index))
+    } else {
+        Some(col.clone())
+    }
+}
+
+// `rekey` moves each value of `map` to where `moved` says its key ends up, and drops the ones it
+// returns None for. State kept per cell, row or column follows its cells around with it.
+pub fn rekey<K, V, F>(map: &mut HashMap<K, V>, moved: F)
+where
+    K: Eq + Hash,
+    F: Fn(&K) -> Option<K>,
+{
+    let entries: Vec<(K, V)> = map.drain().collect();
+    map.extend(entries.into_iter().filter_map(|(key, value)| moved(&key).map(|key| (key, value))));
+}
+
+// `shift_cells` moves the cells under `parent` from row (or column if `!rows`) `from` onwards one
+// step down (or right), leaving an empty line at `from` for a new row (or column) to go in
+pub fn shift_cells(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    from: u32,
+    rows: bool,
+) {
+    rekey(grammars, |c| shifted_coord(c, parent, from, 1, rows));
+    if let Some(Grammar {
+        kind: Kind::Grid(sub_coords),
+        ..
+    }) = grammars.get_mut(parent)
+    {
+        for (row, col) in sub_coords.iter_mut() {
+            let line = if rows { row } else { col };
+            *line = shifted_index(*line, from, 1).unwrap();
+        }
+    }
+}
+
+// renumbers `row_heights` and `col_widths` after `offset` rows (or columns if `!rows`) are
+// inserted at line `at` of `parent`, or deleted from there if `offset` is negative. The sizes
+// of deleted lines go, and the ones after them move along with their cells
+pub fn shift_line_sizes(
+    row_heights: &mut HashMap<Row, f64>,
+    col_widths: &mut HashMap<Col, f64>,
+    parent: &Coordinate,
+    at: u32,
+    offset: i32,
+    rows: bool,
+) {
+    rekey(row_heights, |r| shifted_row(r, parent, at, offset, rows));
+    rekey(col_widths, |c| shifted_col(c, parent, at, offset, rows));
+}
+
+// moves conditional formats along with their column when lines are shifted like
+// `shift_line_sizes` does. The formats of deleted columns go.
+pub fn shift_conditional_formats(
+    formats: &mut Vec<ConditionalFormat>,
+    parent: &Coordinate,
+    at: u32,
+    offset: i32,
+    rows: bool,
+) {
+    *formats = formats
+        .drain(..)
+        .filter_map(|format| {
+            shifted_col(&format.column, parent, at, offset, rows)
+                .map(|column| ConditionalFormat { column, ..format })
+        })
+        .collect();
+}
+
+// the text a cell gets sorted by, or "" if it doesn't show any
+fn sort_value(grammar: Option<&Grammar>) -> String {
+    match grammar.map(|g| &g.kind) {
+        Some(Kind::Input(value)) | Some(Kind::Lookup(value, _)) | Some(Kind::Markdown(value)) => {
+            value.trim().to_string()
+        }
+        Some(Kind::Number(number, _)) => number.to_string(),
+        _ => String::new(),
+    }
+}
+
+// `compare_sort_values` orders two cell values numerically if they're both numbers and
+// alphabetically otherwise, with numbers before text
+pub fn compare_sort_values(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+// `sorted_coord` is where `coord` ends up after the rows of the columns `left` to `right` under
+// `parent` are put in `order` (the original row of each row, from row `top` down). Nested
+// grammars move along with the cell they're in. Returns None if `coord` doesn't move.
+pub fn sorted_coord(
+    coord: &Coordinate,
+    parent: &Coordinate,
+    (left, right): (u32, u32),
+    top: u32,
+    order: &[u32],
+) -> Option<Coordinate> {
+    let depth = parent.row_cols.len();
+    if coord.row_cols.len() <= depth || !coord.row_cols.starts_with(&parent.row_cols) {
+        return None;
+    }
+    let (row, col) = coord.row_cols[depth];
+    if !(left..=right).contains(&col.get()) {
+        return None;
+    }
+    let new_row = top + order.iter().position(|r| *r == row.get())? as u32;
+    if new_row == row.get() {
+        return None;
+    }
+    let mut sorted = coord.clone();
+    sorted.row_cols[depth].0 = NonZeroU32::new(new_row).unwrap();
+    Some(sorted)
+}
+
+// `sort_region` sorts the rows of a rectangle of cells under `parent` by the values in its first
+// column. Nested grammars move along with their row, and empty cells always go last. Returns
+// the original row of each row in the rectangle, top to bottom, for `sort_line_sizes`.
+pub fn sort_region(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+    ascending: bool,
+) -> Vec<u32> {
+    let mut order: Vec<u32> = (top..=bottom).collect();
+    let key = |row: u32| {
+        sort_value(grammars.get(&Coordinate::child_of(parent, non_zero_u32_tuple((row, left)))))
+    };
+    // sort_by is stable, so rows with the same value keep their order
+    order.sort_by(|a, b| {
+        let (a, b) = (key(*a), key(*b));
+        match (a.is_empty(), b.is_empty()) {
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            _ if ascending => compare_sort_values(&a, &b),
+            _ => compare_sort_values(&b, &a),
+        }
+    });
+
+    let sorted: Vec<(Coordinate, Coordinate)> = grammars
+        .keys()
+        .filter_map(|c| sorted_coord(c, parent, (left, right), top, &order).map(|s| (c.clone(), s)))
+        .collect();
+    let moved: Vec<(Coordinate, Grammar)> = sorted
+        .into_iter()
+        .filter_map(|(c, s)| grammars.remove(&c).map(|g| (s, g)))
+        .collect();
+    grammars.extend(moved);
+    order
+}
+
+// moves `row_heights` and `col_widths` along with the rows sorted by `sort_region`. The heights
+// of `parent`'s own rows only follow the sort if `whole_rows` were sorted, since otherwise the
+// cells outside the sorted columns stay in their rows.
+pub fn sort_line_sizes(
+    row_heights: &mut HashMap<Row, f64>,
+    col_widths: &mut HashMap<Col, f64>,
+    parent: &Coordinate,
+    (left, right): (u32, u32),
+    top: u32,
+    order: &[u32],
+    whole_rows: bool,
+) {
+    let sorted = |c: &Coordinate| {
+        sorted_coord(c, parent, (left, right), top, order).unwrap_or_else(|| c.clone())
+    };
+    rekey(row_heights, |r| {
+        if r.0 != *parent {
+            return Some(Row(sorted(&r.0), r.1));
+        }
+        match order.iter().position(|row| *row == r.1.get()) {
+            Some(i) if whole_rows => {
+                Some(Row(r.0.clone(), NonZeroU32::new(top + i as u32).unwrap()))
+            }
+            _ => Some(r.clone()),
+        }
+    });
+    rekey(col_widths, |c| Some(Col(sorted(&c.0), c.1)));
+}
+
+// `grouped_coord` is where `coord` ends up after the rectangle of cells under `parent` is
+// grouped into a grid at its top-left cell by `group_region`. Returns None if `coord` isn't in
+// (or nested in a cell of) the rectangle.
+pub fn grouped_coord(
+    coord: &Coordinate,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+) -> Option<Coordinate> {
+    let depth = parent.row_cols.len();
+    if coord.row_cols.len() <= depth || !coord.row_cols.starts_with(&parent.row_cols) {
+        return None;
+    }
+    let (row, col) = coord.row_cols[depth];
+    if !(top..=bottom).contains(&row.get()) || !(left..=right).contains(&col.get()) {
+        return None;
+    }
+    let mut grouped = Coordinate::child_of(parent, non_zero_u32_tuple((top, left)));
+    grouped.row_cols.push(non_zero_u32_tuple((row.get() - top + 1, col.get() - left + 1)));
+    grouped.row_cols.extend_from_slice(&coord.row_cols[depth + 1..]);
+    Some(grouped)
+}
+
+// `group_region` turns a rectangle of cells under `parent` into a grid nested in its top-left
+// cell, with the cells (and anything nested in them) moved into the grid in the same layout.
+// The rest of the rectangle is left with empty cells, and the lookups and formulas that point
+// into the rectangle move along. It's the inverse of flattening a grid.
+pub fn group_region(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+) {
+    rebase_references(grammars, |c| {
+        grouped_coord(c, parent, (top, left), (bottom, right)).unwrap_or_else(|| c.clone())
+    });
+    let grouped: Vec<(Coordinate, Coordinate)> = grammars
+        .keys()
+        .filter_map(|c| {
+            grouped_coord(c, parent, (top, left), (bottom, right)).map(|g| (c.clone(), g))
+        })
+        .collect();
+    let moved: Vec<(Coordinate, Grammar)> = grouped
+        .into_iter()
+        .filter_map(|(c, g)| grammars.remove(&c).map(|grammar| (g, grammar)))
+        .collect();
+    for row in top..=bottom {
+        for col in left..=right {
+            let cell = Coordinate::child_of(parent, non_zero_u32_tuple((row, col)));
+            grammars.insert(cell, Grammar::default());
+        }
+    }
+    grammars.extend(moved);
+    let (rows, cols) = non_zero_u32_tuple((bottom - top + 1, right - left + 1));
+    grammars.insert(
+        Coordinate::child_of(parent, non_zero_u32_tuple((top, left))),
+        Grammar::as_grid(rows, cols),
+    );
+}
+
+// moves the `row_heights` and `col_widths` of the grids nested in the cells grouped by
+// `group_region` along with them
+pub fn group_line_sizes(
+    row_heights: &mut HashMap<Row, f64>,
+    col_widths: &mut HashMap<Col, f64>,
+    parent: &Coordinate,
+    top_left: (u32, u32),
+    bottom_right: (u32, u32),
+) {
+    rekey(row_heights, |r| {
+        Some(grouped_coord(&r.0, parent, top_left, bottom_right).map_or(r.clone(), |p| Row(p, r.1)))
+    });
+    rekey(col_widths, |c| {
+        Some(grouped_coord(&c.0, parent, top_left, bottom_right).map_or(c.clone(), |p| Col(p, c.1)))
+    });
+}
+
+// the number of (rows, cols) of the grid at `coord`, or None if it isn't a grid
+pub fn grid_size(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+) -> Option<(u32, u32)> {
+    match &grammars.get(coord)?.kind {
+        Kind::Grid(sub_coords) => Some((
+            sub_coords.iter().map(|(row, _)| row.get()).max().unwrap_or(0),
+            sub_coords.iter().map(|(_, col)| col.get()).max().unwrap_or(0),
+        )),
+        _ => None,
+    }
+}
+
+// `resize_grid` gives the grid at `coord` `rows` x `cols` cells, adding empty cells to the end
+// of its rows & columns, or removing the trailing ones along with whatever's nested in them.
+// Cells that are kept are left as they are.
+pub fn resize_grid(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    rows: u32,
+    cols: u32,
+) {
+    let depth = coord.row_cols.len();
+    grammars.retain(|c, _| {
+        if c.row_cols.len() <= depth || !c.row_cols.starts_with(&coord.row_cols) {
+            return true;
+        }
+        let (row, col) = c.row_cols[depth];
+        row.get() <= rows && col.get() <= cols
+    });
+    let mut sub_coords = vec![];
+    for row in 1..=rows {
+        for col in 1..=cols {
+            let row_col = non_zero_u32_tuple((row, col));
+            let cell = Coordinate::child_of(coord, row_col);
+            grammars.entry(cell).or_insert_with(Grammar::default);
+            sub_coords.push(row_col);
+        }
+    }
+    if let Some(grammar) = grammars.get_mut(coord) {
+        grammar.kind = Kind::Grid(sub_coords);
+    }
+}
+
+// merged cells are the ones with a non-zero col_span or row_span
+pub fn is_merged(grammar: &Grammar) -> bool {
+    grammar.style.col_span != (0, 0) || grammar.style.row_span != (0, 0)
+}
+
+// `unmerge_region` is the inverse of merging cells: given any cell of a merged region, it resets
+// the span of every cell in the region and shows the cells hidden by the merge again.
+// Returns the coordinates of the unmerged cells, which is empty if `coord` wasn't merged.
+pub fn unmerge_region(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+) -> Vec<Coordinate> {
+    let (col_span, row_span) = match grammars.get(coord) {
+        Some(g) if is_merged(g) => (g.style.col_span, g.style.row_span),
+        _ => return vec![],
+    };
+    let parent = match coord.parent() {
+        Some(parent) => parent,
+        None => return vec![],
+    };
+    // a span of (0, 0) means the region doesn't extend in that direction
+    let (first_row, last_row) = if row_span == (0, 0) {
+        (coord.row().get(), coord.row().get())
+    } else {
+        row_span
+    };
+    let (first_col, last_col) = if col_span == (0, 0) {
+        (coord.col().get(), coord.col().get())
+    } else {
+        col_span
+    };
+    // cells in hidden rows or columns stay hidden
+    let (hidden_rows, hidden_cols) = grammars.get(&parent).map_or((vec![], vec![]), |g| {
+        (g.style.hidden_rows.clone(), g.style.hidden_cols.clone())
+    });
+    let mut cells = Vec::new();
+    for row in first_row..=last_row {
+        for col in first_col..=last_col {
+            let cell = Coordinate::child_of(&parent, non_zero_u32_tuple((row, col)));
+            if let Some(g) = grammars.get_mut(&cell) {
+                g.style.col_span = (0, 0);
+                g.style.row_span = (0, 0);
+                g.style.display = !hidden_rows.contains(&row) && !hidden_cols.contains(&col);
+                cells.push(cell);
+            }
+        }
+    }
+    cells
+}
+
+// `nested_cell_size` is the (width, height) of each cell of a `rows` x `cols` grid nested
+// inside a cell of `parent_size`, so that the nested cells exactly tile the parent cell
+pub fn nested_cell_size((width, height): (f64, f64), (rows, cols): (u32, u32)) -> (f64, f64) {
+    (width / (cols.max(1) as f64), height / (rows.max(1) as f64))
 }
 
 pub fn non_zero_u32_tuple(val: (u32, u32)) -> (NonZeroU32, NonZeroU32) {
@@ -53,9 +1186,7 @@ pub fn non_zero_u32_tuple(val: (u32, u32)) -> (NonZeroU32, NonZeroU32) {
 }
 
 pub fn row_col_to_string((row, col): (u32, u32)) -> String {
-    let row_str = row.to_string();
-    let col_str = from_u32(col + 64).unwrap();
-    format! {"{}{}", col_str, row_str}
+    format! {"{}{}", col_to_letters(col), row}
 }
 
 pub fn coord_show(row_cols: Vec<(u32, u32)>) -> Option<String> {
@@ -80,6 +1211,466 @@ pub fn coord_show(row_cols: Vec<(u32, u32)>) -> Option<String> {
     }
 }
 
+// `session_file_names` picks out the .ise files from a directory listing, sorted alphabetically
+pub fn session_file_names(entries: Vec<String>) -> Vec<String> {
+    let mut names: Vec<String> = entries
+        .into_iter()
+        .filter(|name| name.to_lowercase().ends_with(".ise"))
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    names
+}
+
+// `delimiter_for_file_name` guesses the field delimiter of a dropped file from its extension,
+// so tab-separated exports (.tsv/.tab) don't all end up in a single column
+pub fn delimiter_for_file_name(name: &str) -> u8 {
+    let name = name.to_lowercase();
+    if name.ends_with(".tsv") || name.ends_with(".tab") {
+        b'\t'
+    } else {
+        b','
+    }
+}
+
+// `is_image_mime_type` is whether a dropped file (with that MIME type) should be embedded as an
+// image rather than imported as data
+pub fn is_image_mime_type(mime: &str) -> bool {
+    mime.starts_with("image/")
+}
+
+// `data_uri` encodes the contents of a file as a base64 data URI, so images can be embedded in
+// a session without pointing to a file on disk
+pub fn data_uri(mime: &str, bytes: &[u8]) -> String {
+    format! {"data:{};base64,{}", mime, base64::encode(bytes)}
+}
+
+// `parse_csv_grid` reads the contents of a CSV file into a 2D grid of cell values, where the
+// first line of the file (the headers) is kept as the first row. For `CsvOrientation::ColumnMajor`
+// the grid is transposed, so each line of the file becomes a column instead. Files that can't be
+// read, including ones whose lines don't all have the same number of fields, give back an error.
+pub fn parse_csv_grid(csv: &str, orientation: CsvOrientation) -> Result<Vec<Vec<String>>, String> {
+    parse_delimited_grid(csv, b',', orientation)
+}
+
+// same as `parse_csv_grid`, but with fields separated by `delimiter` instead of commas
+pub fn parse_delimited_grid(
+    csv: &str,
+    delimiter: u8,
+    orientation: CsvOrientation,
+) -> Result<Vec<Vec<String>>, String> {
+    // the reader isn't flexible, so a ragged line is an error rather than a ragged grid
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(csv.as_bytes());
+    let mut grid: Vec<Vec<String>> = Vec::new();
+    let header_row: Vec<String> = reader
+        .headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|header| header.to_string())
+        .collect();
+    grid.push(header_row);
+
+    for row in reader.records() {
+        let row = row.map_err(|e| e.to_string())?;
+        grid.push(row.iter().map(|cell| cell.to_string()).collect());
+    }
+
+    Ok(match orientation {
+        CsvOrientation::RowMajor => grid,
+        CsvOrientation::ColumnMajor => {
+            let num_cols = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+            (0..num_cols)
+                .map(|col| {
+                    grid.iter()
+                        .map(|row| row.get(col).cloned().unwrap_or_default())
+                        .collect()
+                })
+                .collect()
+        }
+    })
+}
+
+// `delimited_file_grid` reads the contents of the delimited (CSV, TSV...) file `name` into a grid
+// of cell values, or gives back why the file can't be imported
+pub fn delimited_file_grid(
+    name: &str,
+    content: &[u8],
+    delimiter: u8,
+    orientation: CsvOrientation,
+) -> Result<Vec<Vec<String>>, String> {
+    let csv = std::str::from_utf8(content)
+        .map_err(|e| format! {"could not import {}: {}", name, e})?;
+    if csv.trim().is_empty() {
+        return Err(format! {"could not import {}: the file is empty", name});
+    }
+    parse_delimited_grid(csv, delimiter, orientation)
+        .map_err(|e| format! {"could not import {}: {}", name, e})
+}
+
+// `append_grid_rows` adds `rows` of values below the last row of the grid at `coord`, which
+// needs to have as many columns as the rows. A first row that's the same as the grid's own first
+// row (the file's headers) is left out, so appending files with the same headers keeps them once.
+// Returns the number of rows that were added.
+pub fn append_grid_rows(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    rows: &[Vec<String>],
+) -> Result<u32, String> {
+    let (grid_rows, grid_cols) = grid_size(grammars, coord)
+        .ok_or_else(|| format! {"{} isn't a grid", coord.to_string()})?;
+    if let Some(row) = rows.iter().find(|row| row.len() as u32 != grid_cols) {
+        return Err(format! {
+            "the rows have {} columns, but the grid at {} has {}",
+            row.len(), coord.to_string(), grid_cols
+        });
+    }
+    let header: Vec<String> = (1..=grid_cols)
+        .map(|col| {
+            let cell = Coordinate::child_of(coord, non_zero_u32_tuple((1, col)));
+            match grammars.get(&cell).map(|g| &g.kind) {
+                Some(Kind::Input(value)) => value.clone(),
+                _ => String::new(),
+            }
+        })
+        .collect();
+    let rows = match rows.split_first() {
+        Some((first, rest)) if *first == header => rest,
+        _ => rows,
+    };
+    resize_grid(grammars, coord, grid_rows + rows.len() as u32, grid_cols);
+    for (i, row) in rows.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            let row_col = non_zero_u32_tuple((grid_rows + 1 + i as u32, 1 + j as u32));
+            grammars.insert(Coordinate::child_of(coord, row_col), Grammar::input("", value));
+        }
+    }
+    Ok(rows.len() as u32)
+}
+
+// `parse_json_grid` reads a JSON array of flat objects into a 2D grid of cell values, like
+// `parse_csv_grid`. The first row holds the headers, which are the union of keys across all the
+// objects (sorted so the column order is stable), and keys missing from an object are left empty.
+pub fn parse_json_grid(json: &str) -> Result<Vec<Vec<String>>, String> {
+    let objects: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_str(json)
+        .map_err(|e| format! {"expected a JSON array of objects: {}", e})?;
+    let headers: Vec<String> = objects
+        .iter()
+        .flat_map(|object| object.keys().cloned())
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect();
+    if headers.is_empty() {
+        return Err("JSON file has no data to import".to_string());
+    }
+
+    let mut grid: Vec<Vec<String>> = vec![headers.clone()];
+    for object in objects.iter() {
+        grid.push(
+            headers
+                .iter()
+                .map(|key| match object.get(key) {
+                    None | Some(serde_json::Value::Null) => String::new(),
+                    Some(serde_json::Value::String(s)) => s.clone(),
+                    Some(value) => value.to_string(),
+                })
+                .collect(),
+        );
+    }
+    Ok(grid)
+}
+
+// a worksheet read from an .xlsx file. `merges` holds the (top, left) and (bottom, right) cell of
+// each merged region, counting from (1, 1) like coordinates do
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sheet {
+    pub name: String,
+    pub cells: Vec<Vec<String>>,
+    pub merges: Vec<((u32, u32), (u32, u32))>,
+}
+
+// `parse_xlsx` reads every worksheet of an .xlsx file that has any cells into a 2D grid of cell
+// values, like `parse_csv_grid`. The grid always starts at A1, even if the sheet's first
+// non-empty cell is further in, and is padded out to cover all of the sheet's merged regions.
+pub fn parse_xlsx(bytes: &[u8]) -> Result<Vec<Sheet>, String> {
+    let mut workbook = Xlsx::new(Cursor::new(bytes.to_vec()))
+        .map_err(|e| format! {"could not read xlsx file: {}", e})?;
+    workbook
+        .load_merged_regions()
+        .map_err(|e| format! {"could not read merged cells of xlsx file: {}", e})?;
+    let mut sheets = Vec::new();
+    for name in workbook.sheet_names().to_owned() {
+        let range = match workbook.worksheet_range(&name) {
+            Some(Ok(range)) => range,
+            Some(Err(e)) => return Err(format! {"could not read sheet \"{}\": {}", name, e}),
+            None => continue,
+        };
+        let merges: Vec<((u32, u32), (u32, u32))> = workbook
+            .merged_regions_by_sheet(&name)
+            .iter()
+            .map(|(_, _, region)| {
+                let (top, left) = region.start;
+                let (bottom, right) = region.end;
+                ((top + 1, left + 1), (bottom + 1, right + 1))
+            })
+            .collect();
+        let (top, left) = match range.start() {
+            Some((top, left)) => (top as usize, left as usize),
+            None => continue,
+        };
+        let rows = merges
+            .iter()
+            .map(|(_, (bottom, _))| *bottom as usize)
+            .fold(top + range.height(), usize::max);
+        let cols = merges
+            .iter()
+            .map(|(_, (_, right))| *right as usize)
+            .fold(left + range.width(), usize::max);
+        let mut cells = vec![vec![String::new(); cols]; rows];
+        for (row, col, value) in range.cells() {
+            cells[top + row][left + col] = value.to_string();
+        }
+        sheets.push(Sheet { name, cells, merges });
+    }
+    if sheets.is_empty() {
+        return Err("the xlsx file doesn't have any sheets with cells in them".to_string());
+    }
+    Ok(sheets)
+}
+
+// `merge_region` merges a rectangle of cells under `parent` the same way the Merge action does:
+// every cell gets the region's span, and only the bottom-right cell stays displayed, sized to
+// cover the region. It shows the value of the top-left cell, and the other cells are cleared.
+pub fn merge_region(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+) {
+    let cell = |row, col| Coordinate::child_of(parent, non_zero_u32_tuple((row, col)));
+    let value = grammars.get(&cell(top, left)).map(|g| g.kind.clone());
+    let width: f64 = (left..=right)
+        .filter_map(|col| grammars.get(&cell(bottom, col)))
+        .map(|g| g.style.width)
+        .sum();
+    let height: f64 = (top..=bottom)
+        .filter_map(|row| grammars.get(&cell(row, right)))
+        .map(|g| g.style.height)
+        .sum();
+    for row in top..=bottom {
+        for col in left..=right {
+            if let Some(g) = grammars.get_mut(&cell(row, col)) {
+                g.kind = Kind::Input("".to_string());
+                g.style.col_span = (left, right);
+                g.style.row_span = (top, bottom);
+                g.style.display = false;
+            }
+        }
+    }
+    if let Some(g) = grammars.get_mut(&cell(bottom, right)) {
+        g.kind = value.unwrap_or_else(|| Kind::Input("".to_string()));
+        g.style.width = width;
+        g.style.height = height;
+        g.style.display = true;
+    }
+}
+
+// `merge_lines` merges each row (or each column if `!across`) of a rectangle of cells under
+// `parent` into a cell of its own, so they can also be unmerged one at a time. Rows or columns
+// that are a single cell wide are left alone.
+pub fn merge_lines(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+    across: bool,
+) {
+    if across && left < right {
+        for row in top..=bottom {
+            merge_region(grammars, parent, (row, left), (row, right));
+        }
+    } else if !across && top < bottom {
+        for col in left..=right {
+            merge_region(grammars, parent, (top, col), (bottom, col));
+        }
+    }
+}
+
+// `grid_to_html` turns the grid at `coord` into a standalone <table> with each cell's style
+// inlined. A merged region becomes one cell with rowspan/colspan (leaving out the cells hidden by
+// the merge), hidden rows & columns are skipped, and nested grids become nested tables. If there
+// are `headers` (one per column) they go in a header row above the cells.
+pub fn grid_to_html(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    headers: &[String],
+) -> String {
+    let grammar = match grammars.get(coord) {
+        Some(grammar) => grammar,
+        None => return String::new(),
+    };
+    let mut sub_coords = match &grammar.kind {
+        Kind::Grid(sub_coords) => sub_coords.clone(),
+        _ => return String::new(),
+    };
+    sub_coords.sort();
+    let (hidden_rows, hidden_cols) = (&grammar.style.hidden_rows, &grammar.style.hidden_cols);
+    // a span of (0, 0) means the region doesn't extend in that direction
+    let span_or = |span: (u32, u32), index: u32| {
+        if span == (0, 0) {
+            (index, index)
+        } else {
+            span
+        }
+    };
+
+    let mut html = "<table style=\"border-collapse: collapse;\">\n".to_string();
+    if !headers.is_empty() {
+        html += "<tr>";
+        for (col, header) in headers.iter().enumerate() {
+            if !hidden_cols.contains(&(col as u32 + 1)) {
+                html += &format! {"<th>{}</th>", escape_html(header)};
+            }
+        }
+        html += "</tr>\n";
+    }
+    let mut current_row = None;
+    for (row, col) in sub_coords {
+        if hidden_rows.contains(&row.get()) || hidden_cols.contains(&col.get()) {
+            continue;
+        }
+        if current_row != Some(row) {
+            if current_row.is_some() {
+                html += "</tr>\n";
+            }
+            html += "<tr>";
+            current_row = Some(row);
+        }
+        let cell = Coordinate::child_of(coord, (row, col));
+        let cell_grammar = match grammars.get(&cell) {
+            Some(grammar) => grammar,
+            None => continue,
+        };
+        if is_merged(cell_grammar) {
+            // the region is written out where it starts, as the one cell of it that's displayed
+            let (first_row, last_row) = span_or(cell_grammar.style.row_span, row.get());
+            let (first_col, last_col) = span_or(cell_grammar.style.col_span, col.get());
+            if (row.get(), col.get()) != (first_row, first_col) {
+                continue;
+            }
+            let anchor = (first_row..=last_row)
+                .flat_map(|r| (first_col..=last_col).map(move |c| (r, c)))
+                .map(|row_col| Coordinate::child_of(coord, non_zero_u32_tuple(row_col)))
+                .find(|c| grammars.get(c).map_or(false, |g| g.style.display));
+            if let Some(anchor) = anchor {
+                let rowspan = (first_row..=last_row).filter(|r| !hidden_rows.contains(r)).count();
+                let colspan = (first_col..=last_col).filter(|c| !hidden_cols.contains(c)).count();
+                html += &cell_to_html(grammars, &anchor, rowspan, colspan);
+            }
+        } else if cell_grammar.style.display {
+            html += &cell_to_html(grammars, &cell, 1, 1);
+        }
+    }
+    if current_row.is_some() {
+        html += "</tr>\n";
+    }
+    html + "</table>"
+}
+
+fn cell_to_html(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    rowspan: usize,
+    colspan: usize,
+) -> String {
+    let grammar = &grammars[coord];
+    let mut attributes = String::new();
+    if rowspan > 1 {
+        attributes += &format! {" rowspan=\"{}\"", rowspan};
+    }
+    if colspan > 1 {
+        attributes += &format! {" colspan=\"{}\"", colspan};
+    }
+    // cells are flex containers in the app, but have to stay table cells here
+    let style: Vec<&str> = grammar
+        .style
+        .to_string()
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| {
+            !line.is_empty()
+                && !line.starts_with("/*")
+                && !line.starts_with("display:")
+                && !line.starts_with("align-items:")
+        })
+        .collect();
+    let content = match &grammar.kind {
+        Kind::Grid(_) => grid_to_html(grammars, coord, &[]),
+        Kind::Text(value) | Kind::Input(value) | Kind::Lookup(value, _) => escape_html(value),
+        Kind::Number(number, format) => escape_html(&format.format(*number)),
+        Kind::Markdown(source) => render_markdown(source),
+        Kind::Image(url) => format! {"<img src=\"{}\">", escape_html(url)},
+        _ => escape_html(&grammar.name),
+    };
+    format! {
+        "<td{} style=\"{}\">{}</td>",
+        attributes,
+        escape_html(&style.join(" ")),
+        content,
+    }
+}
+
+// `render_markdown` turns the source of a markdown cell into HTML. Any HTML written in the
+// source is escaped rather than passed through, so it can't break out of the cell, and links
+// or images pointing anywhere other than `is_safe_url` allows lose their destination.
+pub fn render_markdown(source: &str) -> String {
+    let events = MarkdownParser::new(source).map(|event| match event {
+        Event::Html(html) => Event::Text(html),
+        Event::Start(tag) => Event::Start(safe_tag(tag)),
+        Event::End(tag) => Event::End(safe_tag(tag)),
+        event => event,
+    });
+    let mut html = String::new();
+    push_html(&mut html, events);
+    html
+}
+
+fn safe_tag(tag: Tag) -> Tag {
+    match tag {
+        Tag::Link(link_type, url, title) if !is_safe_url(&url, false) => {
+            Tag::Link(link_type, CowStr::Borrowed(""), title)
+        }
+        Tag::Image(link_type, url, title) if !is_safe_url(&url, true) => {
+            Tag::Image(link_type, CowStr::Borrowed(""), title)
+        }
+        tag => tag,
+    }
+}
+
+// relative urls and http(s)/mailto ones are fine, and images can also be inline data. Anything
+// else (javascript:, vbscript:, data:text/html, ...) could run code when clicked or loaded
+fn is_safe_url(url: &str, image: bool) -> bool {
+    let url = url.trim().to_lowercase();
+    let scheme_end = url.find(|c: char| c == ':' || c == '/' || c == '?' || c == '#');
+    match scheme_end {
+        Some(i) if url[i..].starts_with(':') => {
+            let scheme = &url[..i];
+            scheme == "http"
+                || scheme == "https"
+                || scheme == "mailto"
+                || (image && url.starts_with("data:image/"))
+        }
+        _ => true,
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 pub fn apply_definition_grammar(m: &mut Model, root_coord: Coordinate) {
     // definition grammar contains the name of the grammar and then the list of
     // different parts of the grammar
@@ -109,11 +1700,13 @@ pub fn apply_definition_grammar(m: &mut Model, root_coord: Coordinate) {
                     name: "defn_label".to_string(),
                     style: defn_label_style,
                     kind: Kind::Text("Define Grammar".to_string()),
+                    validator: None,
                 }),
                 g!(Grammar {
                     name: "defn_name".to_string(),
                     style: Style::default(),
                     kind: Kind::Input(String::new()),
+                    validator: None,
                 })
             ],
             [grid![
@@ -176,21 +1769,197 @@ pub fn resize(m: &mut Model, coord: Coordinate, row_height: f64, col_width: f64)
     }
 }
 
-pub fn resize_diff(m: &mut Model, coord: Coordinate, row_height_diff: f64, col_width_diff: f64) {
-    let additional_offset = if m.resizing.is_none() {
-        2.0 /* if not resizing, account for internal borders width */
-    } else {
-        0.0
-    };
-    if let Some(parent_coord) = coord.parent() {
-        if let Some(row_height) = m.row_heights.get_mut(&coord.full_row()) {
-            *row_height += row_height_diff + additional_offset;
+pub fn resize_diff(m: &mut Model, coord: Coordinate, row_height_diff: f64, col_width_diff: f64) {
+    let is_resizing = m.resizing.is_some();
+    let (row_heights, col_widths) = (&mut m.row_heights, &mut m.col_widths);
+    grow_line_sizes(row_heights, col_widths, coord, row_height_diff, col_width_diff, is_resizing);
+}
+
+// `grow_line_sizes` is `resize_diff` on just the row heights & column widths: the row and column
+// of `coord`, and of every grid it's nested in, grow by the given amounts
+pub fn grow_line_sizes(
+    row_heights: &mut HashMap<Row, f64>,
+    col_widths: &mut HashMap<Col, f64>,
+    coord: Coordinate,
+    row_height_diff: f64,
+    col_width_diff: f64,
+    is_resizing: bool,
+) {
+    let additional_offset = if !is_resizing {
+        2.0 /* if not resizing, account for internal borders width */
+    } else {
+        0.0
+    };
+    if let Some(parent_coord) = coord.parent() {
+        if let Some(row_height) = row_heights.get_mut(&coord.full_row()) {
+            *row_height += row_height_diff + additional_offset;
+        }
+        if let Some(col_width) = col_widths.get_mut(&coord.full_col()) {
+            *col_width += col_width_diff + additional_offset;
+        }
+        grow_line_sizes(
+            row_heights,
+            col_widths,
+            parent_coord,
+            row_height_diff,
+            col_width_diff,
+            is_resizing,
+        );
+    }
+}
+
+// bounds on how far the sheet can be zoomed out or in
+pub const MIN_ZOOM: f32 = 0.2;
+pub const MAX_ZOOM: f32 = 3.0;
+
+pub fn clamp_zoom(zoom: f32) -> f32 {
+    zoom.max(MIN_ZOOM).min(MAX_ZOOM)
+}
+
+// ZoomIn/ZoomOut step by tenths. Rounding keeps repeated steps from drifting (e.g. to
+// 0.70000005) and clamping keeps the sheet from disappearing at zero or negative zoom.
+pub const ZOOM_STEP: f32 = 0.1;
+
+pub fn step_zoom(zoom: f32, step: f32) -> f32 {
+    clamp_zoom(((zoom + step) * 10.0).round() / 10.0)
+}
+
+// zoom at which content of size `content` (width, height) fits in `viewport`, where the
+// content's size is measured unzoomed
+pub fn zoom_to_fit(content: (f64, f64), viewport: (f64, f64)) -> f32 {
+    let (width, height) = content;
+    let (viewport_width, viewport_height) = viewport;
+    if width <= 0.0 || height <= 0.0 {
+        return 1.0;
+    }
+    clamp_zoom((viewport_width / width).min(viewport_height / height) as f32)
+}
+
+// (left, top, right, bottom) of the rendered cell at `coord`, if it's on the page
+pub fn dom_rect(coord: &Coordinate) -> Option<(f64, f64, f64, f64)> {
+    let element = document().get_element_by_id(format! {"cell-{}", coord.to_string()}.deref())?;
+    let rect = HtmlElement::try_from(element).ok()?.get_bounding_client_rect();
+    Some((rect.get_left(), rect.get_top(), rect.get_right(), rect.get_bottom()))
+}
+
+// `scroll_into_view` is the scroll offset of a container that brings `cell` (left, top, right,
+// bottom) into the container's `view` (in the same coordinates), starting from `scroll`. It only
+// scrolls as far as needed, and lines up the top-left of cells bigger than the view.
+pub fn scroll_into_view(
+    cell: (f64, f64, f64, f64),
+    view: (f64, f64, f64, f64),
+    (scroll_x, scroll_y): (f64, f64),
+) -> (f64, f64) {
+    let (cell_left, cell_top, cell_right, cell_bottom) = cell;
+    let (view_left, view_top, view_right, view_bottom) = view;
+    let x = if cell_left < view_left || cell_right - cell_left > view_right - view_left {
+        scroll_x - (view_left - cell_left)
+    } else if cell_right > view_right {
+        scroll_x + (cell_right - view_right)
+    } else {
+        scroll_x
+    };
+    let y = if cell_top < view_top || cell_bottom - cell_top > view_bottom - view_top {
+        scroll_y - (view_top - cell_top)
+    } else if cell_bottom > view_bottom {
+        scroll_y + (cell_bottom - view_bottom)
+    } else {
+        scroll_y
+    };
+    (x.max(0.0), y.max(0.0))
+}
+
+// the bounds of `.main`, which the sheet scrolls inside of
+pub fn dom_scroll_container() -> Option<(f64, f64, f64, f64)> {
+    let element = document().query_selector(".main").ok()??;
+    let rect = HtmlElement::try_from(element).ok()?.get_bounding_client_rect();
+    Some((rect.get_left(), rect.get_top(), rect.get_right(), rect.get_bottom()))
+}
+
+// how far (x, y) `.main` is scrolled
+pub fn dom_scroll_offset() -> (f64, f64) {
+    let x: f64 = js! {
+        let main = document.querySelector(".main");
+        return main ? main.scrollLeft : 0;
+    }
+    .try_into()
+    .unwrap_or(0.0);
+    let y: f64 = js! {
+        let main = document.querySelector(".main");
+        return main ? main.scrollTop : 0;
+    }
+    .try_into()
+    .unwrap_or(0.0);
+    (x, y)
+}
+
+pub fn dom_scroll_to((x, y): (f64, f64)) {
+    js! {
+        let main = document.querySelector(".main");
+        if (main) {
+            main.scrollLeft = @{x};
+            main.scrollTop = @{y};
+        }
+    };
+}
+
+// saves `contents` as a file called `file_name`, through the hidden #downloadAnchorElem link
+pub fn dom_download(file_name: &str, contents: &str, mime_type: &str) {
+    js! {
+        let anchor = document.getElementById("downloadAnchorElem");
+        let blob = new Blob([@{contents}], { type: @{mime_type} });
+        anchor.setAttribute("href", URL.createObjectURL(blob));
+        anchor.setAttribute("download", @{file_name});
+        anchor.click();
+    };
+}
+
+// focuses the element behind `node_ref` once yew has rendered it, since an element that was just
+// added by an update doesn't exist yet when the update runs (and `autofocus` only fires on load)
+pub fn dom_focus_later(node_ref: &NodeRef) {
+    let node_ref = node_ref.clone();
+    set_timeout(
+        move || {
+            if let Some(element) = node_ref.try_into::<HtmlElement>() {
+                element.focus();
+            }
+        },
+        0,
+    );
+}
+
+// whether `target` is somewhere text is being typed, where shortcuts like Ctrl-a should keep
+// doing what they do in text: inputs, code editors, and cells that have text in them
+pub fn dom_is_text_entry(target: &EventTarget) -> bool {
+    js! {
+        let target = @{target};
+        if (!(target instanceof Element)) {
+            return false;
         }
-        if let Some(col_width) = m.col_widths.get_mut(&coord.full_col()) {
-            *col_width += col_width_diff + additional_offset;
+        if (target.matches("input, textarea, select") || target.closest(".CodeMirror")) {
+            return true;
         }
-        resize_diff(m, parent_coord, row_height_diff, col_width_diff);
+        return target.isContentEditable && target.textContent.length > 0;
+    }
+    .try_into()
+    .unwrap_or(false)
+}
+
+// space left in the window for the sheet, from the top-left of the `.main` section
+pub fn dom_viewport() -> (f64, f64) {
+    let width: f64 = js! {
+        let main = document.querySelector(".main");
+        return window.innerWidth - (main ? main.getBoundingClientRect().left : 0);
     }
+    .try_into()
+    .unwrap_or(0.0);
+    let height: f64 = js! {
+        let main = document.querySelector(".main");
+        return window.innerHeight - (main ? main.getBoundingClientRect().top : 0);
+    }
+    .try_into()
+    .unwrap_or(0.0);
+    (width, height)
 }
 
 // Use width and height values from DOM to resize element
@@ -230,6 +1999,56 @@ pub fn dom_resize(m: &mut Model, on: Coordinate) {
     */
 }
 
+// natural (width, height) of the content of the cell at `coord`, measured by letting its element
+// size itself to what's in it for a moment. `None` if the cell isn't on the page.
+pub fn dom_content_size(coord: &Coordinate) -> Option<(f64, f64)> {
+    let id = format! {"cell-{}", coord.to_string()};
+    let size: Vec<f64> = js! {
+        let element = document.getElementById(@{id});
+        if (element === null) {
+            return null;
+        }
+        let style = element.style.cssText;
+        element.style.width = "max-content";
+        let width = element.getBoundingClientRect().width;
+        element.style.cssText = style;
+        element.style.height = "auto";
+        element.style.minHeight = "0px";
+        let height = element.getBoundingClientRect().height;
+        element.style.cssText = style;
+        return [width, height];
+    }
+    .try_into()
+    .ok()?;
+    match size.as_slice() {
+        [width, height] => Some((*width, *height)),
+        _ => None,
+    }
+}
+
+// size of a row or column that fits the biggest content out of its `cells`, as measured by
+// `measure`. Empty cells don't count, and a line with nothing to fit snaps back to `default`.
+pub fn fit_line_size<F>(
+    grammars: &HashMap<Coordinate, Grammar>,
+    cells: &[Coordinate],
+    measure: F,
+    default: f64,
+) -> f64
+where
+    F: Fn(&Coordinate) -> Option<f64>,
+{
+    cells
+        .iter()
+        .filter(|coord| match grammars.get(coord) {
+            Some(Grammar { kind: Kind::Input(value), .. }) => !value.is_empty(),
+            Some(_) => true,
+            None => false,
+        })
+        .filter_map(|coord| measure(coord))
+        .fold(None, |fit: Option<f64>, size| Some(fit.map_or(size, |fit| fit.max(size))))
+        .map_or(default, f64::ceil)
+}
+
 // macro for easily defining a vector of non-zero tuples
 // used in Coordinate::root() below
 #[macro_export]
@@ -245,9 +2064,13 @@ macro_rules! row_col_vec {
     };
 }
 
-/* TODO: get this working so w can color code lookups */
 mod tests {
     use super::*;
+    use pest::Parser;
+
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::style::Predicate;
+    use crate::{coord, coord_col, coord_row};
 
     #[test]
     fn test_non_zero_u32_tuple() {
@@ -272,4 +2095,905 @@ mod tests {
         assert_eq!(coord_show(vec![(1, 1), (1, 1)]).unwrap(), "root-A1");
         assert_ne!(coord_show(vec![(1, 1), (1, 1)]).unwrap(), "root")
     }
+
+    #[test]
+    fn test_is_locked_nested() {
+        let mut grammars = HashMap::new();
+        grammars.insert(coord!("root"), Grammar::default());
+        grammars.insert(coord!("root-A1"), Grammar::default());
+        grammars.insert(coord!("root-A1-B2"), Grammar::default());
+        grammars.insert(coord!("root-A2"), Grammar::default());
+        grammars.get_mut(&coord!("root-A1")).unwrap().style.locked = true;
+
+        assert!(is_locked(&grammars, &coord!("root-A1")));
+        // children of a locked grid are protected too
+        assert!(is_locked(&grammars, &coord!("root-A1-B2")));
+        assert!(!is_locked(&grammars, &coord!("root-A2")));
+        assert!(!is_locked(&grammars, &coord!("root")));
+
+        // deleting the root would take the locked grid with it
+        assert!(contains_locked(&grammars, &coord!("root")));
+        assert!(!contains_locked(&grammars, &coord!("root-A2")));
+    }
+
+    #[test]
+    fn test_tab_step_reverse() {
+        let mut grammars = hashmap! {
+            coord!("root-A1") => Grammar::default(),
+            coord!("root-B1") => Grammar::default(),
+            coord!("root-C1") => Grammar::default(),
+            coord!("root-A2") => Grammar::as_grid(NonZeroU32::new(2).unwrap(), NonZeroU32::new(3).unwrap()),
+            coord!("root-B2") => Grammar::default(),
+        };
+        for row_col in vec![(1, 1), (1, 2), (1, 3), (2, 1), (2, 2), (2, 3)] {
+            let nested = Coordinate::child_of(&coord!("root-A2"), non_zero_u32_tuple(row_col));
+            grammars.insert(nested, Grammar::default());
+        }
+        // the start of a row goes back to the last column of the row above
+        assert_eq!(tab_step(&grammars, &coord!("root-A2"), true), Some(coord!("root-C1")));
+        // stepping back into a grid lands on its bottom-right cell...
+        assert_eq!(tab_step(&grammars, &coord!("root-B2"), true), Some(coord!("root-A2-C2")));
+        assert_eq!(tab_step(&grammars, &coord!("root-A2-B1"), true), Some(coord!("root-A2-A1")));
+        assert_eq!(tab_step(&grammars, &coord!("root-A2-A2"), true), Some(coord!("root-A2-C1")));
+        assert_eq!(tab_step(&grammars, &coord!("root-A1"), false), Some(coord!("root-B1")));
+    }
+
+    #[test]
+    fn test_tab_next_skips_locked() {
+        let mut grammars = hashmap! {
+            coord!("root-A1") => Grammar::default(),
+            coord!("root-B1") => Grammar::default(),
+            coord!("root-C1") => Grammar::default(),
+        };
+        grammars.get_mut(&coord!("root-B1")).unwrap().style.locked = true;
+
+        assert_eq!(
+            tab_next(&grammars, &coord!("root-A1"), false, |_| false),
+            Some(coord!("root-B1"))
+        );
+        assert_eq!(
+            tab_next(&grammars, &coord!("root-A1"), false, |c| is_locked(&grammars, c)),
+            Some(coord!("root-C1"))
+        );
+        assert_eq!(
+            tab_next(&grammars, &coord!("root-C1"), true, |c| is_locked(&grammars, c)),
+            Some(coord!("root-A1"))
+        );
+    }
+
+    #[test]
+    fn test_copy_grammar() {
+        let mut grammars = hashmap! {
+            coord!("root-A1") => Grammar::input("", "a"),
+            coord!("root-B1") => Grammar::input("", "b"),
+        };
+        assert!(copy_grammar(&mut grammars, &coord!("root-A1"), &coord!("root-B1")).is_ok());
+        assert_eq!(grammars[&coord!("root-B1")].kind, Kind::Input("a".to_string()));
+
+        // copying from an empty coordinate leaves the destination untouched
+        grammars.insert(coord!("root-B1"), Grammar::input("", "b"));
+        assert!(copy_grammar(&mut grammars, &coord!("root-C1"), &coord!("root-B1")).is_err());
+        assert_eq!(grammars[&coord!("root-B1")].kind, Kind::Input("b".to_string()));
+    }
+
+    #[test]
+    fn test_add_and_remove_defn_rule() {
+        let defn = Grammar {
+            name: "defn".to_string(),
+            style: Style::default(),
+            kind: Kind::Defn("".to_string(), coord!("meta-A3-B1"), vec![]),
+            validator: None,
+        };
+        let mut grammars = hashmap! { coord!("meta-A3") => defn };
+        assert!(add_defn_rule(&mut grammars, &coord!("meta-A3")));
+        assert!(add_defn_rule(&mut grammars, &coord!("meta-A3")));
+        assert!(grammars.contains_key(&coord!("meta-A3-B1-A2")));
+        assert!(grammars.contains_key(&coord!("meta-A3-B1-B2")));
+        grammars.insert(coord!("meta-A3-B1-B2"), Grammar::input("rule_grammar", "second"));
+
+        // removing the first rule moves the second one up
+        assert!(remove_defn_rule(&mut grammars, &coord!("meta-A3"), 0));
+        assert_eq!(
+            grammars[&coord!("meta-A3-B1-B1")].kind,
+            Kind::Input("second".to_string())
+        );
+        assert!(!grammars.contains_key(&coord!("meta-A3-B1-B2")));
+        if let Kind::Defn(_, _, rules) = &grammars[&coord!("meta-A3")].kind {
+            assert_eq!(rules, &vec![("".to_string(), coord!("meta-A3-B1-B1"))]);
+        } else {
+            panic!("meta-A3 should still be a definition");
+        }
+
+        assert!(!remove_defn_rule(&mut grammars, &coord!("meta-A3"), 1));
+        assert!(!add_defn_rule(&mut grammars, &coord!("meta-A3-B1-B1")));
+    }
+
+    #[test]
+    fn test_remove_definition() {
+        let mut meta = Grammar {
+            name: "meta".to_string(),
+            style: Style::default(),
+            kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1)]),
+            validator: None,
+        };
+        let mut grammars = hashmap! {
+            coord!("meta-A1") => Grammar::input("first", ""),
+            coord!("meta-A2") => Grammar::input("second", ""),
+            coord!("meta-A3") => Grammar {
+                name: "third".to_string(),
+                style: Style::default(),
+                kind: Kind::Defn(
+                    "".to_string(),
+                    coord!("meta-A3-B1"),
+                    vec![("rule".to_string(), coord!("meta-A3-B1-B1"))],
+                ),
+                validator: None,
+            },
+            coord!("meta-A3-B1-B1") => Grammar::input("rule_grammar", "x"),
+            coord!("root-A1") => Grammar {
+                kind: Kind::Lookup("x".to_string(), Some(Lookup::Cell(coord!("meta-A3-B1-B1")))),
+                ..Grammar::default()
+            },
+            coord!("root-B1") => Grammar::input("", "=meta-A3-B1-B1*2+meta-A1"),
+        };
+        assert!(remove_definition(&mut grammars, &mut meta, &coord!("meta-A2")));
+        assert_eq!(grammars[&coord!("meta-A1")].name, "first");
+        // the definition below moves up, along with its nested cells and its binding
+        assert_eq!(grammars[&coord!("meta-A2")].name, "third");
+        assert_eq!(
+            grammars[&coord!("meta-A2")].kind,
+            Kind::Defn(
+                "".to_string(),
+                coord!("meta-A2-B1"),
+                vec![("rule".to_string(), coord!("meta-A2-B1-B1"))],
+            )
+        );
+        assert_eq!(grammars[&coord!("meta-A2-B1-B1")].kind, Kind::Input("x".to_string()));
+        // and so do lookups and formulas referring to it
+        assert_eq!(
+            grammars[&coord!("root-A1")].kind,
+            Kind::Lookup("x".to_string(), Some(Lookup::Cell(coord!("meta-A2-B1-B1"))))
+        );
+        assert_eq!(
+            grammars[&coord!("root-B1")].kind,
+            Kind::Input("=meta-A2-B1-B1*2+meta-A1".to_string())
+        );
+        assert_eq!(grammars.len(), 5);
+        assert_eq!(meta.kind, Kind::Grid(row_col_vec![(1, 1), (2, 1)]));
+
+        // only cells of the meta grid are definitions
+        assert!(!remove_definition(&mut grammars, &mut meta, &coord!("meta-A2-B1-B1")));
+        assert!(!remove_definition(&mut grammars, &mut meta, &coord!("meta-A5")));
+        assert!(!remove_definition(&mut grammars, &mut meta, &coord!("root-A1")));
+    }
+
+    #[test]
+    fn test_nearest_hidden() {
+        assert_eq!(nearest_hidden(&[], 3), None);
+        assert_eq!(nearest_hidden(&[1, 5], 3), Some(1));
+        assert_eq!(nearest_hidden(&[1, 5], 4), Some(5));
+    }
+
+    #[test]
+    fn test_frozen_css() {
+        assert_eq!(frozen_css(&coord!("root"), &[], &[]), "");
+        assert_eq!(
+            frozen_css(&coord!("root"), &[30.0, 40.0], &[90.0]),
+            "#cell-root > .row-root-1 { position: sticky; top: 0px; z-index: 2; }\n\
+             #cell-root > .row-root-2 { position: sticky; top: 30px; z-index: 2; }\n\
+             #cell-root > .col-root-A { position: sticky; left: 0px; z-index: 2; }\n"
+        );
+    }
+
+    #[test]
+    fn test_fill_targets() {
+        assert_eq!(
+            fill_targets(&coord!("root-A1"), &coord!("root-A3")),
+            vec![coord!("root-A2"), coord!("root-A3")]
+        );
+        // dragging up and to the left fills the same rectangle
+        assert_eq!(
+            fill_targets(&coord!("root-B2"), &coord!("root-A1")),
+            vec![coord!("root-A1"), coord!("root-B1"), coord!("root-A2")]
+        );
+        assert_eq!(fill_targets(&coord!("root-A1"), &coord!("root-A1")), vec![]);
+        // cells in a different grid are never filled
+        assert_eq!(fill_targets(&coord!("root-A1"), &coord!("root-B1-A1")), vec![]);
+    }
+
+    #[test]
+    fn test_transpose_region() {
+        let mut grammars = hashmap! {
+            coord!("root") => Grammar::as_grid(NonZeroU32::new(1).unwrap(), NonZeroU32::new(2).unwrap()),
+            coord!("root-A1") => Grammar::input("", "a"),
+            coord!("root-B1") => Grammar::input("", "b"),
+        };
+        transpose_region(&mut grammars, &coord!("root"), (1, 1), (1, 2));
+        assert_eq!(grammars[&coord!("root-A1")].kind, Kind::Input("a".to_string()));
+        assert_eq!(grammars[&coord!("root-A2")].kind, Kind::Input("b".to_string()));
+        assert_eq!(grammars[&coord!("root-B1")].kind, Kind::Input("".to_string()));
+        if let Kind::Grid(sub_coords) = &grammars[&coord!("root")].kind {
+            assert_eq!(sub_coords.len(), 4);
+        } else {
+            panic!("root should still be a grid");
+        }
+    }
+
+    #[test]
+    fn test_transposed_coord() {
+        let transposed = |c: Coordinate| transposed_coord(&c, &coord!("root"), (1, 1), (1, 2));
+        assert_eq!(transposed(coord!("root-A1")), Some(coord!("root-A1")));
+        assert_eq!(transposed(coord!("root-B1-A1")), Some(coord!("root-A2-A1")));
+        // written over by the transposed rectangle
+        assert_eq!(transposed(coord!("root-A2")), None);
+        assert_eq!(transposed(coord!("root-B2")), Some(coord!("root-B2")));
+        assert_eq!(transposed(coord!("meta-A1")), Some(coord!("meta-A1")));
+    }
+
+    #[test]
+    fn test_group_region() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [
+                    g!(Grammar::input("", "A1")),
+                    g!(Grammar::input("", "B1")),
+                    g!(Grammar::input("", "C1"))
+                ],
+                [
+                    g!(Grammar::input("", "A2")),
+                    g!(Grammar::input("", "B2")),
+                    grid![[g!(Grammar::input("", "nested"))]]
+                ]
+            ],
+        );
+        grammars.insert(coord!("root-A1"), Grammar::input("", "=C1+A2"));
+        grammars.get_mut(&coord!("root-A2")).unwrap().kind =
+            Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-C2-A1"))));
+        let mut row_heights = hashmap! { coord_row!("root-C2", "1") => 50.0 };
+        let mut col_widths = HashMap::new();
+        group_region(&mut grammars, &coord!("root"), (1, 2), (2, 3));
+        group_line_sizes(&mut row_heights, &mut col_widths, &coord!("root"), (1, 2), (2, 3));
+
+        // the grid has the shape of the selection
+        assert_eq!(
+            grammars[&coord!("root-B1")].kind,
+            Kind::Grid(row_col_vec![(1, 1), (1, 2), (2, 1), (2, 2)])
+        );
+        assert_eq!(grammars[&coord!("root-B1-A1")].kind, Kind::Input("B1".to_string()));
+        assert_eq!(grammars[&coord!("root-B1-B1")].kind, Kind::Input("C1".to_string()));
+        assert_eq!(grammars[&coord!("root-B1-A2")].kind, Kind::Input("B2".to_string()));
+        assert_eq!(grammars[&coord!("root-B1-B2-A1")].kind, Kind::Input("nested".to_string()));
+        assert_eq!(row_heights[&coord_row!("root-B1-B2", "1")], 50.0);
+        // the rest of the selection is emptied, and cells outside it are left alone
+        assert_eq!(grammars[&coord!("root-C2")].kind, Kind::Input("".to_string()));
+        assert!(!grammars.contains_key(&coord!("root-C2-A1")));
+        // references into the selection follow the cells they point at
+        assert_eq!(grammars[&coord!("root-A1")].kind, Kind::Input("=root-B1-B1+A2".to_string()));
+        assert_eq!(
+            grammars[&coord!("root-A2")].kind,
+            Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-B1-B2-A1"))))
+        );
+    }
+
+    #[test]
+    fn test_resize_grid() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "A1")), g!(Grammar::input("", "B1"))],
+                [g!(Grammar::input("", "A2")), g!(Grammar::input("", "B2"))]
+            ],
+        );
+        assert_eq!(grid_size(&grammars, &coord!("root")), Some((2, 2)));
+        assert_eq!(grid_size(&grammars, &coord!("root-A1")), None);
+
+        let before = grammars.len();
+        resize_grid(&mut grammars, &coord!("root"), 3, 3);
+        assert_eq!(grammars.len() - before, 5);
+        assert_eq!(grid_size(&grammars, &coord!("root")), Some((3, 3)));
+        assert_eq!(grammars[&coord!("root-B2")].kind, Kind::Input("B2".to_string()));
+        assert_eq!(grammars[&coord!("root-C3")].kind, Kind::Input("".to_string()));
+
+        // shrinking drops the trailing cells, and anything nested in them
+        grammars.insert(coord!("root-B1-A1"), Grammar::input("", "nested"));
+        resize_grid(&mut grammars, &coord!("root"), 3, 1);
+        assert_eq!(grid_size(&grammars, &coord!("root")), Some((3, 1)));
+        assert_eq!(grammars.len(), 1 + 3);
+        assert!(!grammars.contains_key(&coord!("root-B1-A1")));
+        assert_eq!(grammars[&coord!("root-A2")].kind, Kind::Input("A2".to_string()));
+    }
+
+    #[test]
+    fn test_compare_sort_values() {
+        assert_eq!(compare_sort_values("9", "10"), Ordering::Less);
+        assert_eq!(compare_sort_values("-2.5", "-3"), Ordering::Greater);
+        assert_eq!(compare_sort_values("10", "apple"), Ordering::Less);
+        assert_eq!(compare_sort_values("banana", "apple"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_sort_region() {
+        let mut grammars = hashmap! {
+            coord!("root") => Grammar::as_grid(NonZeroU32::new(4).unwrap(), NonZeroU32::new(2).unwrap()),
+            coord!("root-A1") => Grammar::input("", "10"),
+            coord!("root-B1") => Grammar::input("", "ten"),
+            coord!("root-A2") => Grammar::input("", ""),
+            coord!("root-B2") => Grammar::input("", "empty"),
+            coord!("root-A3") => Grammar::input("", "9"),
+            coord!("root-B3") => Grammar::as_grid(NonZeroU32::new(1).unwrap(), NonZeroU32::new(1).unwrap()),
+            coord!("root-B3-A1") => Grammar::input("", "nine"),
+            coord!("root-A4") => Grammar::input("", "-1.5"),
+            coord!("root-B4") => Grammar::input("", "minus"),
+        };
+        let mut row_heights = hashmap! {
+            coord_row!("root", "3") => 60.0,
+            coord_row!("root-B3", "1") => 50.0,
+        };
+        let mut col_widths = hashmap! {
+            coord_col!("root-B3", "A") => 80.0,
+        };
+        let order = sort_region(&mut grammars, &coord!("root"), (1, 1), (4, 2), true);
+        assert_eq!(order, vec![4, 3, 1, 2]);
+        let root = coord!("root");
+        sort_line_sizes(&mut row_heights, &mut col_widths, &root, (1, 2), 1, &order, true);
+
+        let first_column = |grammars: &HashMap<Coordinate, Grammar>| -> Vec<Kind> {
+            (1..=4)
+                .map(|row| {
+                    let cell = Coordinate::child_of(&coord!("root"), non_zero_u32_tuple((row, 1)));
+                    grammars[&cell].kind.clone()
+                })
+                .collect()
+        };
+        let inputs = |values: Vec<&str>| -> Vec<Kind> {
+            values.into_iter().map(|v| Kind::Input(v.to_string())).collect()
+        };
+        assert_eq!(first_column(&grammars), inputs(vec!["-1.5", "9", "10", ""]));
+        // the nested grid moves with its row
+        assert_eq!(grammars[&coord!("root-B2-A1")].kind, Kind::Input("nine".to_string()));
+        assert!(!grammars.contains_key(&coord!("root-B3-A1")));
+        assert_eq!(grammars[&coord!("root-B4")].kind, Kind::Input("empty".to_string()));
+        assert_eq!(row_heights[&coord_row!("root", "2")], 60.0);
+        assert_eq!(row_heights[&coord_row!("root-B2", "1")], 50.0);
+        assert_eq!(col_widths[&coord_col!("root-B2", "A")], 80.0);
+
+        // descending still leaves the empty cell at the bottom
+        let order = sort_region(&mut grammars, &coord!("root"), (1, 1), (4, 2), false);
+        assert_eq!(order, vec![3, 2, 1, 4]);
+        assert_eq!(first_column(&grammars), inputs(vec!["10", "9", "-1.5", ""]));
+
+        // only sorting some of the columns leaves the heights of the grid's rows alone
+        let mut row_heights = hashmap! { coord_row!("root", "1") => 30.0 };
+        sort_line_sizes(&mut row_heights, &mut HashMap::new(), &root, (1, 1), 1, &[2, 1], false);
+        assert_eq!(row_heights[&coord_row!("root", "1")], 30.0);
+    }
+
+    #[test]
+    fn test_shift_cells() {
+        let mut grammars = hashmap! {
+            coord!("root") => Grammar::as_grid(NonZeroU32::new(2).unwrap(), NonZeroU32::new(1).unwrap()),
+            coord!("root-A1") => Grammar::input("", "a"),
+            coord!("root-A2") => Grammar::as_grid(NonZeroU32::new(1).unwrap(), NonZeroU32::new(1).unwrap()),
+            coord!("root-A2-A1") => Grammar::input("", "nested"),
+        };
+        let mut row_heights = hashmap! {
+            coord_row!("root", "1") => 30.0,
+            coord_row!("root", "2") => 60.0,
+            coord_row!("root-A2", "1") => 50.0,
+        };
+        shift_cells(&mut grammars, &coord!("root"), 2, true);
+        shift_line_sizes(&mut row_heights, &mut HashMap::new(), &coord!("root"), 2, 1, true);
+
+        assert_eq!(grammars[&coord!("root-A1")].kind, Kind::Input("a".to_string()));
+        assert!(!grammars.contains_key(&coord!("root-A2")));
+        assert_eq!(grammars[&coord!("root-A3-A1")].kind, Kind::Input("nested".to_string()));
+        assert_eq!(
+            grammars[&coord!("root")].kind,
+            Kind::Grid(vec![non_zero_u32_tuple((1, 1)), non_zero_u32_tuple((3, 1))])
+        );
+        assert_eq!(row_heights[&coord_row!("root", "1")], 30.0);
+        assert_eq!(row_heights[&coord_row!("root", "3")], 60.0);
+        assert_eq!(row_heights[&coord_row!("root-A3", "1")], 50.0);
+        assert!(!row_heights.contains_key(&coord_row!("root", "2")));
+    }
+
+    #[test]
+    fn test_shift_line_sizes() {
+        let mut row_heights = hashmap! {
+            coord_row!("root", "1") => 30.0,
+            coord_row!("root", "2") => 60.0,
+            coord_row!("root", "3") => 45.0,
+            coord_row!("root-A2", "1") => 20.0,
+            coord_row!("root-A3", "1") => 50.0,
+        };
+        shift_line_sizes(&mut row_heights, &mut HashMap::new(), &coord!("root"), 2, -1, true);
+
+        let root_rows = row_heights.keys().filter(|r| r.0 == coord!("root")).count();
+        assert_eq!(root_rows, 2);
+        assert_eq!(row_heights[&coord_row!("root", "1")], 30.0);
+        assert_eq!(row_heights[&coord_row!("root", "2")], 45.0);
+        assert!(!row_heights.contains_key(&coord_row!("root", "3")));
+        // nested sizes go with their cell
+        assert_eq!(row_heights[&coord_row!("root-A2", "1")], 50.0);
+        assert!(!row_heights.contains_key(&coord_row!("root-A3", "1")));
+
+        let mut col_widths = hashmap! {
+            coord_col!("root", "A") => 90.0,
+            coord_col!("root", "B") => 120.0,
+            coord_col!("root-B1", "A") => 40.0,
+        };
+        shift_line_sizes(&mut HashMap::new(), &mut col_widths, &coord!("root"), 1, -1, false);
+        assert_eq!(col_widths.len(), 2);
+        assert_eq!(col_widths[&coord_col!("root", "A")], 120.0);
+        assert_eq!(col_widths[&coord_col!("root-A1", "A")], 40.0);
+    }
+
+    #[test]
+    fn test_shift_conditional_formats() {
+        let format = |column: Col| ConditionalFormat {
+            column,
+            predicate: Predicate::Contains("".to_string()),
+            style: Style::default(),
+        };
+        let mut formats = vec![
+            format(coord_col!("root", "A")),
+            format(coord_col!("root", "B")),
+            format(coord_col!("root", "C")),
+        ];
+        shift_conditional_formats(&mut formats, &coord!("root"), 2, -1, false);
+        let columns: Vec<Col> = formats.into_iter().map(|f| f.column).collect();
+        assert_eq!(columns, vec![coord_col!("root", "A"), coord_col!("root", "B")]);
+    }
+
+    #[test]
+    fn test_nested_cell_size() {
+        let (width, height) = nested_cell_size((90.0, 30.0), (3, 3));
+        assert_eq!(width * 3.0, 90.0);
+        assert_eq!(height * 3.0, 30.0);
+
+        // smaller than default parents aren't clamped
+        let (width, height) = nested_cell_size((40.0, 20.0), (2, 4));
+        assert_eq!(width * 4.0, 40.0);
+        assert_eq!(height * 2.0, 20.0);
+    }
+
+    #[test]
+    fn test_unmerge_region() {
+        let mut grammars = hashmap! {
+            coord!("root-A1") => Grammar::default(),
+            coord!("root-B1") => Grammar::default(),
+            coord!("root-C1") => Grammar::default(),
+        };
+        for c in vec![coord!("root-A1"), coord!("root-B1")] {
+            let g = grammars.get_mut(&c).unwrap();
+            g.style.col_span = (1, 2);
+            g.style.row_span = (1, 1);
+        }
+        grammars.get_mut(&coord!("root-A1")).unwrap().style.display = false;
+
+        // cells that aren't merged are left alone
+        assert!(unmerge_region(&mut grammars, &coord!("root-C1")).is_empty());
+
+        let cells = unmerge_region(&mut grammars, &coord!("root-B1"));
+        assert_eq!(cells, vec![coord!("root-A1"), coord!("root-B1")]);
+        assert!(grammars[&coord!("root-A1")].style.display);
+        assert!(!is_merged(&grammars[&coord!("root-A1")]));
+        assert!(!is_merged(&grammars[&coord!("root-B1")]));
+    }
+
+    #[test]
+    fn test_merge_region() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "title")), g!(Grammar::input("", "B1"))],
+                [g!(Grammar::input("", "A2")), g!(Grammar::input("", "B2"))]
+            ],
+        );
+        let width = grammars[&coord!("root-A1")].style.width;
+        merge_region(&mut grammars, &coord!("root"), (1, 1), (1, 2));
+        assert!(!grammars[&coord!("root-A1")].style.display);
+        assert_eq!(grammars[&coord!("root-A1")].kind, Kind::Input("".to_string()));
+        assert_eq!(grammars[&coord!("root-B1")].kind, Kind::Input("title".to_string()));
+        assert_eq!(grammars[&coord!("root-B1")].style.width, 2.0 * width);
+        assert!(is_merged(&grammars[&coord!("root-A1")]));
+        // the merge can be undone like any other
+        unmerge_region(&mut grammars, &coord!("root-A1"));
+        assert!(grammars[&coord!("root-A1")].style.display);
+        assert_eq!(grammars[&coord!("root-A2")].kind, Kind::Input("A2".to_string()));
+    }
+
+    #[test]
+    fn test_merge_lines() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "A1")), g!(Grammar::input("", "B1"))],
+                [g!(Grammar::input("", "A2")), g!(Grammar::input("", "B2"))]
+            ],
+        );
+        merge_lines(&mut grammars, &coord!("root"), (1, 1), (2, 2), true);
+        // each row is its own merged cell, showing its first value
+        assert_eq!(grammars[&coord!("root-B1")].kind, Kind::Input("A1".to_string()));
+        assert_eq!(grammars[&coord!("root-B2")].kind, Kind::Input("A2".to_string()));
+        assert_eq!(grammars[&coord!("root-A2")].style.row_span, (2, 2));
+
+        // so unmerging one row leaves the other merged
+        assert_eq!(
+            unmerge_region(&mut grammars, &coord!("root-A1")),
+            vec![coord!("root-A1"), coord!("root-B1")]
+        );
+        assert!(is_merged(&grammars[&coord!("root-B2")]));
+        assert!(!grammars[&coord!("root-A2")].style.display);
+
+        merge_lines(&mut grammars, &coord!("root"), (1, 1), (2, 1), false);
+        assert_eq!(grammars[&coord!("root-A1")].style.row_span, (1, 2));
+        assert_eq!(grammars[&coord!("root-A1")].style.col_span, (1, 1));
+    }
+
+    #[test]
+    fn test_parse_xlsx() {
+        assert!(parse_xlsx(b"").is_err());
+        assert!(parse_xlsx(b"name,age\nAlice,30").is_err());
+
+        // "People" has a merged header, "Totals" only has B2 filled in and "Empty" has nothing
+        let sheets = parse_xlsx(include_bytes!("../test/XLSX_MERGED.xlsx")).unwrap();
+        assert_eq!(
+            sheets,
+            vec![
+                Sheet {
+                    name: "People".to_string(),
+                    cells: vec![
+                        vec!["name".to_string(), "".to_string()],
+                        vec!["ada".to_string(), "36".to_string()],
+                        vec!["alan".to_string(), "41".to_string()],
+                    ],
+                    merges: vec![((1, 1), (1, 2))],
+                },
+                // the grid still starts at A1, and empty sheets don't get a tab
+                Sheet {
+                    name: "Totals".to_string(),
+                    cells: vec![
+                        vec!["".to_string(), "".to_string()],
+                        vec!["".to_string(), "77".to_string()],
+                    ],
+                    merges: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_case_insensitive() {
+        assert_eq!(find_case_insensitive("Hello World", "world"), Some((6, 11)));
+        assert_eq!(find_case_insensitive("Hello World", "planet"), None);
+        assert_eq!(
+            replace_case_insensitive("Foo bar FOO", "foo", "baz"),
+            Some("baz bar baz".to_string())
+        );
+        assert_eq!(replace_case_insensitive("Foo bar", "qux", "baz"), None);
+    }
+
+    #[test]
+    fn test_insert_at_caret() {
+        assert_eq!(insert_at_caret("=+B1", 1, "A1"), "=A1+B1");
+        assert_eq!(insert_at_caret("=A1+", 4, "B1"), "=A1+B1");
+        // caret past the end appends
+        assert_eq!(insert_at_caret("=", 10, "A1"), "=A1");
+        // caret is counted in characters, not bytes
+        assert_eq!(insert_at_caret("=é+", 2, "A1"), "=éA1+");
+
+        assert_eq!(
+            formula_reference(&coord!("root-A1"), &coord!("root-B2")),
+            "B2"
+        );
+        assert_eq!(
+            formula_reference(&coord!("root-A1"), &coord!("root-C3-A1")),
+            "root-C3-A1"
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_grid() {
+        let csv = "a,b,c\n1,2,3\n";
+        let row_major = parse_csv_grid(csv, CsvOrientation::RowMajor).unwrap();
+        assert_eq!(row_major.len(), 2);
+        assert_eq!(row_major[0].len(), 3);
+        assert_eq!(row_major[1], vec!["1", "2", "3"]);
+
+        let col_major = parse_csv_grid(csv, CsvOrientation::ColumnMajor).unwrap();
+        assert_eq!(col_major.len(), 3);
+        assert_eq!(col_major[0].len(), 2);
+        assert_eq!(col_major[0], vec!["a", "1"]);
+        assert_eq!(col_major[2], vec!["c", "3"]);
+
+        // a line with more (or fewer) fields than the others is an error, not a panic
+        assert!(parse_csv_grid("a,b\n1,2,3\n", CsvOrientation::RowMajor).is_err());
+        assert!(parse_csv_grid("a,b,c\n1,2\n", CsvOrientation::ColumnMajor).is_err());
+    }
+
+    #[test]
+    fn test_parse_delimited_grid() {
+        let tsv = "name\tage\nada\t36\n";
+        let grid = parse_delimited_grid(tsv, b'\t', CsvOrientation::RowMajor).unwrap();
+        assert_eq!(grid, vec![vec!["name", "age"], vec!["ada", "36"]]);
+
+        // read as plain CSV, each line is a single column
+        assert_eq!(parse_csv_grid(tsv, CsvOrientation::RowMajor).unwrap()[0].len(), 1);
+
+        assert_eq!(delimiter_for_file_name("export.TSV"), b'\t');
+        assert_eq!(delimiter_for_file_name("data.csv"), b',');
+
+        assert_eq!(
+            delimited_file_grid("a.tsv", tsv.as_bytes(), b'\t', CsvOrientation::RowMajor),
+            Ok(grid)
+        );
+        assert!(delimited_file_grid("a.csv", &[0xff, 0xfe], b',', CsvOrientation::RowMajor)
+            .unwrap_err()
+            .starts_with("could not import a.csv"));
+        assert_eq!(
+            delimited_file_grid("a.csv", b" \n", b',', CsvOrientation::RowMajor),
+            Err("could not import a.csv: the file is empty".to_string())
+        );
+        assert!(delimited_file_grid("a.csv", b"a,b\n1\n", b',', CsvOrientation::RowMajor)
+            .unwrap_err()
+            .starts_with("could not import a.csv: "));
+    }
+
+    #[test]
+    fn test_append_grid_rows() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "name")), g!(Grammar::input("", "age"))],
+                [g!(Grammar::input("", "ada")), g!(Grammar::input("", "36"))]
+            ],
+        );
+        let csv = "name,age\nalan,41\ngrace,85\n";
+        let file = parse_delimited_grid(csv, b',', CsvOrientation::RowMajor).unwrap();
+        // the matching header row isn't added again
+        assert_eq!(append_grid_rows(&mut grammars, &coord!("root"), &file), Ok(2));
+        assert_eq!(grid_size(&grammars, &coord!("root")), Some((4, 2)));
+        assert_eq!(grammars[&coord!("root-A2")].kind, Kind::Input("ada".to_string()));
+        assert_eq!(grammars[&coord!("root-A3")].kind, Kind::Input("alan".to_string()));
+        assert_eq!(grammars[&coord!("root-B4")].kind, Kind::Input("85".to_string()));
+
+        let wide = vec![vec!["a".to_string(), "b".to_string(), "c".to_string()]];
+        assert!(append_grid_rows(&mut grammars, &coord!("root"), &wide).is_err());
+        assert_eq!(grid_size(&grammars, &coord!("root")), Some((4, 2)));
+    }
+
+    #[test]
+    fn test_data_uri() {
+        assert_eq!(data_uri("image/png", b""), "data:image/png;base64,");
+        assert_eq!(data_uri("image/png", b"f"), "data:image/png;base64,Zg==");
+        assert_eq!(data_uri("image/png", b"fo"), "data:image/png;base64,Zm8=");
+        assert_eq!(data_uri("image/gif", b"foobar"), "data:image/gif;base64,Zm9vYmFy");
+        assert_eq!(data_uri("image/png", &[0xff, 0xfe]), "data:image/png;base64,//4=");
+
+        assert!(is_image_mime_type("image/svg+xml"));
+        assert!(!is_image_mime_type("text/csv"));
+    }
+
+    #[test]
+    fn test_with_precision() {
+        assert_eq!(with_precision("3.14159", Some(2)), "3.14");
+        assert_eq!(with_precision("3.14159", Some(0)), "3");
+        assert_eq!(with_precision(" 2.5", Some(3)), "2.500");
+        assert_eq!(with_precision("-0.125", Some(1)), "-0.1");
+        // without a precision, or if it isn't a number, the value is shown unchanged
+        assert_eq!(with_precision("3.14159", None), "3.14159");
+        assert_eq!(with_precision("pi", Some(2)), "pi");
+        assert_eq!(with_precision("", Some(2)), "");
+        assert_eq!(with_precision("inf", Some(2)), "inf");
+    }
+
+    #[test]
+    fn test_fit_line_size() {
+        let grammars = hashmap! {
+            coord!("root-A1") => Grammar::input("", "a long value"),
+            coord!("root-A2") => Grammar::input("", ""),
+            coord!("root-A3") => Grammar::input("", "short"),
+        };
+        let measure = |coord: &Coordinate| match coord.to_string().as_str() {
+            "root-A1" => Some(120.4),
+            "root-A2" => Some(500.0),
+            "root-A3" => Some(40.0),
+            _ => None,
+        };
+        let cells = vec![coord!("root-A1"), coord!("root-A2"), coord!("root-A3")];
+        // the empty cell is left out, even if its element happens to be wider
+        assert_eq!(fit_line_size(&grammars, &cells, measure, 90.0), 121.0);
+        assert_eq!(fit_line_size(&grammars, &cells[2..], measure, 90.0), 40.0);
+        // nothing to fit
+        assert_eq!(fit_line_size(&grammars, &cells[1..2], measure, 90.0), 90.0);
+        assert_eq!(fit_line_size(&grammars, &[], measure, 90.0), 90.0);
+        assert_eq!(fit_line_size(&grammars, &[coord!("root-B1")], measure, 90.0), 90.0);
+    }
+
+    #[test]
+    fn test_numeric_summary() {
+        assert_eq!(numeric_summary(vec!["1", " 2.5", "", "abc", "-0.5"]), (3, 3.0));
+        assert_eq!(numeric_summary(vec!["NaN", "inf"]), (0, 0.0));
+        assert_eq!(numeric_summary(Vec::<&str>::new()), (0, 0.0));
+    }
+
+    #[test]
+    fn test_clock_time() {
+        assert_eq!(clock_time(0.0), "00:00:00.000");
+        // 2020-06-01T13:05:09.250Z
+        assert_eq!(clock_time(1_591_016_709_250.0), "13:05:09.250");
+    }
+
+    #[test]
+    fn test_sparkline_svg() {
+        let svg = sparkline_svg(&[1.0, 3.0, 2.0], 40.0, 10.0);
+        assert!(svg.starts_with("<svg width=\"40\" height=\"10\" viewBox=\"0 0 40 10\">"));
+        // the lowest value is at the bottom and the highest at the top
+        assert!(svg.contains("points=\"0.0,10.0 20.0,0.0 40.0,5.0\""));
+        // a single value or a flat line sits in the middle
+        assert!(sparkline_svg(&[4.0], 40.0, 10.0).contains("points=\"0.0,5.0\""));
+        assert!(sparkline_svg(&[2.0, 2.0], 40.0, 10.0).contains("points=\"0.0,5.0 40.0,5.0\""));
+    }
+
+    #[test]
+    fn test_coordinate_color() {
+        assert_eq!(coordinate_color(&coord!("root-A1")), coordinate_color(&coord!("root-A1")));
+        assert_eq!(coordinate_color(&coord!("root-A1")), "hsl(278, 65%, 45%)");
+        assert_ne!(coordinate_color(&coord!("root-A1")), coordinate_color(&coord!("root-B1")));
+    }
+
+    #[test]
+    fn test_matches_validator() {
+        let mut regexes = HashMap::new();
+        let digits = Some("[0-9]+".to_string());
+        assert!(matches_validator(&mut regexes, &digits, "2020"));
+        assert!(!matches_validator(&mut regexes, &digits, "20x0"));
+        // the pattern has to match the whole value
+        assert!(!matches_validator(&mut regexes, &Some("[a-z]".to_string()), "abc"));
+        assert!(matches_validator(&mut regexes, &None, "anything"));
+        // invalid patterns don't flag anything
+        assert!(matches_validator(&mut regexes, &Some("(".to_string()), "anything"));
+        // each pattern was compiled once, even the one that didn't compile
+        assert_eq!(regexes.len(), 3);
+        assert!(regexes[&"(".to_string()].is_none());
+    }
+
+    #[test]
+    fn test_step_zoom() {
+        let mut zoom = 1.0;
+        for _ in 0..100 {
+            zoom = step_zoom(zoom, -ZOOM_STEP);
+            assert!(zoom >= MIN_ZOOM);
+        }
+        assert_eq!(zoom, MIN_ZOOM);
+        for _ in 0..100 {
+            zoom = step_zoom(zoom, ZOOM_STEP);
+            assert!(zoom <= MAX_ZOOM);
+        }
+        assert_eq!(zoom, MAX_ZOOM);
+        // stepping in and back out lands exactly where it started
+        assert_eq!(step_zoom(step_zoom(1.0, ZOOM_STEP), -ZOOM_STEP), 1.0);
+    }
+
+    #[test]
+    fn test_scroll_into_view() {
+        let view = (0.0, 0.0, 100.0, 100.0);
+        // already visible
+        assert_eq!(scroll_into_view((10.0, 10.0, 50.0, 50.0), view, (5.0, 5.0)), (5.0, 5.0));
+        // below & to the right of the view
+        assert_eq!(scroll_into_view((90.0, 120.0, 130.0, 150.0), view, (0.0, 0.0)), (30.0, 50.0));
+        // above & to the left of the view, which can't scroll past 0
+        assert_eq!(scroll_into_view((-20.0, -10.0, 10.0, 20.0), view, (50.0, 5.0)), (30.0, 0.0));
+        // bigger than the view, so its top-left lines up with the view's
+        assert_eq!(scroll_into_view((20.0, 0.0, 220.0, 50.0), view, (0.0, 0.0)), (20.0, 0.0));
+    }
+
+    #[test]
+    fn test_zoom_to_fit() {
+        // content twice as wide as the viewport gets zoomed out by half
+        assert_eq!(zoom_to_fit((2000.0, 500.0), (1000.0, 1000.0)), 0.5);
+        assert_eq!(zoom_to_fit((500.0, 250.0), (1000.0, 1000.0)), 2.0);
+        // zoom stays within bounds
+        assert_eq!(zoom_to_fit((100000.0, 10.0), (1000.0, 1000.0)), MIN_ZOOM);
+        assert_eq!(zoom_to_fit((10.0, 10.0), (1000.0, 1000.0)), MAX_ZOOM);
+        assert_eq!(zoom_to_fit((0.0, 0.0), (1000.0, 1000.0)), 1.0);
+        assert_eq!(clamp_zoom(-0.1), MIN_ZOOM);
+    }
+
+    #[test]
+    fn test_session_file_names() {
+        let entries = vec![
+            "notes.txt".to_string(),
+            "budget.ise".to_string(),
+            "Accounts.ise".to_string(),
+            "drivers".to_string(),
+        ];
+        assert_eq!(
+            session_file_names(entries),
+            vec!["Accounts.ise".to_string(), "budget.ise".to_string()]
+        );
+        assert!(session_file_names(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_json_grid() {
+        let json = r#"[{"name": "Alice", "age": 30}, {"name": "Bob", "city": "Lagos"}]"#;
+        let grid = parse_json_grid(json).unwrap();
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid[0], vec!["age", "city", "name"]);
+        assert_eq!(grid[1], vec!["30", "", "Alice"]);
+        assert_eq!(grid[2], vec!["", "Lagos", "Bob"]);
+
+        assert!(parse_json_grid("[]").is_err());
+        assert!(parse_json_grid(r#"{"name": "Alice"}"#).is_err());
+        assert!(parse_json_grid("not json").is_err());
+    }
+
+    #[test]
+    fn test_grid_to_html() {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "hidden")), g!(Grammar::input("", "B1"))],
+                [g!(Grammar::input("", "merged")), g!(Grammar::input("", "a < b"))]
+            ],
+        );
+        // A1 & A2 are merged into a 2x1 region, which shows A2
+        for coord in vec![coord!("root-A1"), coord!("root-A2")] {
+            let style = &mut grammars.get_mut(&coord).unwrap().style;
+            style.row_span = (1, 2);
+            style.col_span = (1, 1);
+        }
+        grammars.get_mut(&coord!("root-A1")).unwrap().style.display = false;
+
+        let html = grid_to_html(&grammars, &coord!("root"), &[]);
+        assert!(html.starts_with("<table"));
+        assert_eq!(html.matches("<tr>").count(), 2);
+        assert_eq!(html.matches("<td").count(), 3);
+        assert!(html.contains(">merged</td>"));
+        assert!(!html.contains("hidden"));
+        let anchor = html.split("<td").nth(1).unwrap();
+        assert!(anchor.starts_with(" rowspan=\"2\" style="));
+        assert!(anchor.contains(">merged</td>"));
+        assert!(html.contains(">a &lt; b</td>"));
+
+        // column labels head the table
+        let headers = vec!["Name".to_string(), "B".to_string()];
+        let html = grid_to_html(&grammars, &coord!("root"), &headers);
+        assert_eq!(html.matches("<tr>").count(), 3);
+        assert!(html.contains("<tr><th>Name</th><th>B</th></tr>"));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        assert_eq!(render_markdown("**bold** text"), "<p><strong>bold</strong> text</p>\n");
+        assert_eq!(render_markdown("- one\n- two"), "<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n");
+        // html in the source is shown as text
+        assert_eq!(render_markdown("a <b>tag</b>"), "<p>a &lt;b&gt;tag&lt;/b&gt;</p>\n");
+        assert!(!render_markdown("<div style=\"position: fixed\">x</div>").contains("<div"));
+        // only safe link and image destinations are kept
+        assert_eq!(
+            render_markdown("[x](https://example.com)"),
+            "<p><a href=\"https://example.com\">x</a></p>\n"
+        );
+        assert_eq!(render_markdown("[x](javascript:alert(1))"), "<p><a href=\"\">x</a></p>\n");
+        assert!(!render_markdown("![](JavaScript:alert(1))").contains("alert"));
+        assert!(!render_markdown("[x](data:text/html,hi)").contains("data:"));
+        assert!(render_markdown("![](data:image/png;base64,AAAA)").contains("data:image/png"));
+        assert!(render_markdown("[x](other-sheet.ise)").contains("other-sheet.ise"));
+    }
 }
@@ -1,30 +1,58 @@
 #![recursion_limit = "1024"]
 use pest::Parser;
+use std::collections::HashSet;
 use std::num::NonZeroU32;
 use std::ops::Deref;
 use stdweb::traits::IEvent;
 use stdweb::unstable::TryFrom;
-use stdweb::unstable::TryInto;
 use stdweb::web::event::IDragEvent;
-use stdweb::web::{html_element::InputElement, HtmlElement, IHtmlElement};
+use stdweb::web::{
+    document, html_element::InputElement, HtmlElement, IBlob, IElement, IHtmlElement,
+};
 use yew::events::{ClickEvent, IKeyboardEvent, IMouseEvent, KeyPressEvent};
 use yew::prelude::*;
 use yew::services::reader::File;
 use yew::virtual_dom::vlist::VList;
+use yew::virtual_dom::VNode;
 use yew::{html, ChangeData, Html, InputData};
 
 use crate::codemirror::CodeMirror;
-use crate::coordinate::Coordinate;
-use crate::grammar::{Grammar, Interactive, Kind, Lookup};
-use crate::model::{Action, CursorType, Model, ResizeMsg, SelectMsg, SideMenu};
-use crate::style::get_style;
-use crate::util::non_zero_u32_tuple;
+use crate::coordinate::{parse_cell_name, Col, Coordinate, Row};
+use crate::formula::formula_result;
+use crate::grammar::{Grammar, Interactive, Kind, Lookup, NumberFormat};
+use crate::model::{
+    Action, CsvOrientation, CursorType, GridLine, Model, Navigation, ResizeMsg, SelectMsg,
+    SideMenu, ACTION_LOG_LENGTH, CELL_SHORTCUTS, SESSIONS_DIRECTORY, SHORTCUTS,
+};
+use crate::style::{ConditionalFormat, Predicate, Style, PREDICATE_NAMES};
+use crate::suggestion::rank_suggestions;
+use crate::template::TEMPLATES;
+use crate::util::{
+    clock_time, delimiter_for_file_name, fill_targets, frozen_css, grid_size, is_formula,
+    is_image_mime_type, is_locked, is_merged, nearest_hidden, non_zero_u32_tuple, numeric_summary,
+    render_markdown, sparkline_svg, tab_next, with_precision,
+};
 use crate::{coord};
 
 #[derive(Parser)]
 #[grammar = "coordinate.pest"]
 pub struct CoordinateParser;
 
+// font families that can be picked for a cell from the Settings menu
+const FONT_FAMILIES: [&str; 5] = ["sans-serif", "serif", "monospace", "cursive", "fantasy"];
+
+// number formats that can be picked for a cell from the Settings menu
+const NUMBER_FORMATS: [(&str, Option<NumberFormat>); 5] = [
+    ("none", None),
+    ("plain", Some(NumberFormat::Plain)),
+    ("currency", Some(NumberFormat::Currency)),
+    ("percent", Some(NumberFormat::Percent)),
+    ("fixed (2 places)", Some(NumberFormat::Fixed(2))),
+];
+
+// the most decimal places that can be picked as a cell's display precision in the Settings menu
+const MAX_DISPLAY_PRECISION: u8 = 6;
+
 pub fn view_side_nav(m: &Model) -> Html {
     let mut side_menu_nodes = VList::new();
     let mut side_menu_section = html! { <></> };
@@ -63,13 +91,55 @@ pub fn view_side_nav(m: &Model) -> Html {
 pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
     match side_menu.name.deref() {
         "Home" => {
+            let mut templates = VList::new();
+            for template in TEMPLATES.iter() {
+                let template = *template;
+                let target = m.active_cell.clone();
+                templates.add_child(html! {
+                    <div class="template" onclick=m.link.callback(move |_| {
+                        target.clone().map_or(
+                            Action::Alert("select a cell to insert the template into".to_string()),
+                            |c| Action::InsertTemplate(c, template),
+                        )
+                    })>
+                        <b>{ template.name() }</b>
+                        <div>{ template.description() }</div>
+                    </div>
+                });
+            }
             html! {
                 <div class="side-menu-section">
-                    {"THIS IS Home MENU"}
+                    <h1>
+                        {"Home"}
+                    </h1>
+
+                    <h3>{"templates"}</h3>
+                    <br></br>
+                    {"click a template to insert it into the active cell"}
+                    { templates }
                 </div>
             }
         }
         "File Explorer" => {
+            let is_column_major = m.csv_orientation == CsvOrientation::ColumnMajor;
+            let append_grid = m.active_grid();
+            let session_index = m.current_session_index;
+            let mut saved_sessions = VList::new();
+            for file_name in m.saved_sessions.iter() {
+                let to_open = file_name.clone();
+                saved_sessions.add_child(html! {
+                    <div class="saved-session" onclick=m.link.callback(move |_| {
+                        Action::OpenSavedSession(to_open.clone())
+                    })>
+                        { file_name }
+                    </div>
+                });
+            }
+            if m.saved_sessions.is_empty() {
+                saved_sessions.add_child(html! {
+                    <div>{ format!{"no sessions saved in {}/", SESSIONS_DIRECTORY} }</div>
+                });
+            }
             html! {
                 <div class="side-menu-section">
                     <h1>
@@ -82,7 +152,7 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
                         if let ChangeData::Files(files) = value {
                             if files.len() >= 1 {
                                 if let Some(file) = files.iter().nth(0) {
-                                    return Action::ReadSession(file);
+                                    return Action::ReadSession(file, false);
                                 }
                             } else {
                                 return Action::Alert("Could not load file".to_string());
@@ -91,11 +161,16 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
                         Action::Noop
                     })>
                     </input>
+                    <h3>{"saved sessions"}</h3>
+                    <br></br>
+                    { saved_sessions }
+                    <input type="button" value="Refresh" onclick=m.link.callback(|_| Action::ListSavedSessions)>
+                    </input>
                     <h3>{"save session"}</h3>
                     <br></br>
-                    <input type="text" value=m.get_session().title onchange=m.link.callback(|v| {
+                    <input type="text" value=m.get_session().title onchange=m.link.callback(move |v| {
                         if let ChangeData::Value(s) = v {
-                            return Action::SetSessionTitle(s);
+                            return Action::SetSessionTitle(session_index, s);
                         }
                         Action::Noop
                     })>
@@ -103,10 +178,122 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
                     </input>
                     <input type="button" value="Save" onclick=m.link.callback(|_| Action::SaveSession())>
                     </input>
+
+                    <h3>{"definitions"}</h3>
+                    <br></br>
+                    <input type="button" value="Export" onclick=m.link.callback(|_| Action::ExportDefinitions())>
+                    </input>
+                    <input type="file" accept=".isedef" onchange=m.link.callback(|value| {
+                        if let ChangeData::Files(files) = value {
+                            if let Some(file) = files.iter().next() {
+                                return Action::ReadDefinitions(file);
+                            }
+                        }
+                        Action::Noop
+                    })>
+                    </input>
+                    {"import definitions from another session"}
+
+                    <h3>{"csv import"}</h3>
+                    <br></br>
+                    <input
+                        type="checkbox"
+                        checked={m.csv_orientation == CsvOrientation::ColumnMajor}
+                        onchange=m.link.callback(move |_| Action::SetCsvOrientation(
+                            if is_column_major { CsvOrientation::RowMajor } else { CsvOrientation::ColumnMajor }
+                        ))>
+                    </input>
+                    {"import csv lines as columns"}
+                    <br></br>
+                    <input type="file" accept=".csv,.tsv,.tab" onchange=m.link.callback(move |value| {
+                        if let ChangeData::Files(files) = value {
+                            if let (Some(file), Some(grid)) = (files.iter().next(), append_grid.clone()) {
+                                return Action::AppendCSVRows(file, grid);
+                            }
+                        }
+                        Action::Noop
+                    })>
+                    </input>
+                    {"append rows to the grid of the active cell"}
+
+                    <h3>{"xlsx import"}</h3>
+                    <br></br>
+                    <input type="file" accept=".xlsx" onchange=m.link.callback(|value| {
+                        if let ChangeData::Files(files) = value {
+                            if let Some(file) = files.iter().next() {
+                                return Action::ReadXLSXFile(file);
+                            }
+                        }
+                        Action::Noop
+                    })>
+                    </input>
+                    {"each sheet opens in a new tab"}
                 </div>
             }
         }
         "Settings" => {
+            let active_style = m
+                .active_cell
+                .clone()
+                .and_then(|c| m.get_session().grammars.get(&c))
+                .map(|g| g.style.clone())
+                .unwrap_or(Style::default());
+            let font_family_cell = m.active_cell.clone();
+            let font_size_cell = m.active_cell.clone();
+            let background_cell = m.active_cell.clone();
+            let validator_cell = m.active_cell.clone();
+            let number_format_cell = m.active_cell.clone();
+            let precision_cell = m.active_cell.clone();
+            let active_validator = m
+                .active_cell
+                .clone()
+                .and_then(|c| m.get_session().grammars.get(&c))
+                .and_then(|g| g.validator.clone())
+                .unwrap_or_default();
+            let font_family_options = {
+                let mut options = VList::new();
+                for font_family in FONT_FAMILIES.iter() {
+                    options.add_child(html! {
+                        <option
+                            value={font_family}
+                            selected={*font_family == active_style.font_family}>
+                            {font_family}
+                        </option>
+                    });
+                }
+                options
+            };
+            let number_format_options = {
+                let mut options = VList::new();
+                for (label, number_format) in NUMBER_FORMATS.iter() {
+                    options.add_child(html! {
+                        <option
+                            value={label}
+                            selected={*number_format == active_style.number_format}>
+                            {label}
+                        </option>
+                    });
+                }
+                options
+            };
+            let precision_options = {
+                let mut options = VList::new();
+                options.add_child(html! {
+                    <option value="" selected={active_style.display_precision.is_none()}>
+                        {"as typed"}
+                    </option>
+                });
+                for precision in 0..=MAX_DISPLAY_PRECISION {
+                    options.add_child(html! {
+                        <option
+                            value={precision.to_string()}
+                            selected={active_style.display_precision == Some(precision)}>
+                            {format! {"{} decimal places", precision}}
+                        </option>
+                    });
+                }
+                options
+            };
             html! {
                 <div class="side-menu-section">
                     <h1>
@@ -138,13 +325,259 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
                         Action::Noop
                     })>
                     </input>
+
+                    <h3>{"font"}</h3>
+                    <br></br>
+                    <select onchange=m.link.callback(move |e: ChangeData| {
+                        if let (ChangeData::Select(select), Some(cell)) = (e, font_family_cell.clone()) {
+                            if let Some(font_family) = select.value() {
+                                return Action::SetFontFamily(cell, font_family);
+                            }
+                        }
+                        Action::Noop
+                    })>
+                        { font_family_options }
+                    </select>
+                    <input
+                        type="number"
+                        min="1"
+                        size="3"
+                        value={active_style.font_size}
+                        onchange=m.link.callback(move |e: ChangeData| {
+                            if let (ChangeData::Value(value), Some(cell)) = (e, font_size_cell.clone()) {
+                                if let Ok(font_size) = value.parse::<f64>() {
+                                    return Action::SetFontSize(cell, font_size);
+                                }
+                            }
+                            Action::Noop
+                        })>
+                    </input>
+                    {"px"}
+
+                    <h3>{"default cell size"}</h3>
+                    <br></br>
+                    <input
+                        type="number"
+                        min="1"
+                        size="4"
+                        value={m.default_col_width}
+                        onchange=m.link.callback(|e: ChangeData| {
+                            if let ChangeData::Value(value) = e {
+                                if let Ok(width) = value.parse::<f64>() {
+                                    return Action::SetDefaultColWidth(width);
+                                }
+                            }
+                            Action::Noop
+                        })>
+                    </input>
+                    {"px wide, "}
+                    <input
+                        type="number"
+                        min="1"
+                        size="4"
+                        value={m.default_row_height}
+                        onchange=m.link.callback(|e: ChangeData| {
+                            if let ChangeData::Value(value) = e {
+                                if let Ok(height) = value.parse::<f64>() {
+                                    return Action::SetDefaultRowHeight(height);
+                                }
+                            }
+                            Action::Noop
+                        })>
+                    </input>
+                    {"px tall"}
+
+                    <h3>{"background color"}</h3>
+                    <br></br>
+                    <input
+                        type="color"
+                        value={active_style.background_color.clone()}
+                        onchange=m.link.callback(move |e: ChangeData| {
+                            if let (ChangeData::Value(color), Some(cell)) = (e, background_cell.clone()) {
+                                return Action::SetBackgroundColor(cell, color);
+                            }
+                            Action::Noop
+                        })>
+                    </input>
+
+                    <h3>{"validation"}</h3>
+                    <br></br>
+                    <input
+                        type="text"
+                        placeholder="pattern (regex)"
+                        value={active_validator}
+                        onchange=m.link.callback(move |e: ChangeData| {
+                            if let (ChangeData::Value(pattern), Some(cell)) = (e, validator_cell.clone()) {
+                                let validator = if pattern.trim() == "" { None } else { Some(pattern) };
+                                return Action::SetValidator(cell, validator);
+                            }
+                            Action::Noop
+                        })>
+                    </input>
+
+                    <h3>{"number format"}</h3>
+                    <br></br>
+                    <select onchange=m.link.callback(move |e: ChangeData| {
+                        if let (ChangeData::Select(select), Some(cell)) = (e, number_format_cell.clone()) {
+                            let selected = select.value().and_then(|value| {
+                                NUMBER_FORMATS.iter().find(|(label, _)| *label == value)
+                            });
+                            if let Some((_, number_format)) = selected {
+                                return Action::SetNumberFormat(cell, *number_format);
+                            }
+                        }
+                        Action::Noop
+                    })>
+                        { number_format_options }
+                    </select>
+                    <select onchange=m.link.callback(move |e: ChangeData| {
+                        if let (ChangeData::Select(select), Some(cell)) = (e, precision_cell.clone()) {
+                            let precision = select.value().and_then(|value| value.parse::<u8>().ok());
+                            return Action::SetPrecision(cell, precision);
+                        }
+                        Action::Noop
+                    })>
+                        { precision_options }
+                    </select>
+
+                    <h3>{"conditional formatting"}</h3>
+                    <br></br>
+                    { view_conditional_formats(m) }
+
+                    <h3>{"navigation"}</h3>
+                    <br></br>
+                    <input
+                        type="checkbox"
+                        checked={m.tab_skips_locked}
+                        onchange=m.link.callback(|_| Action::ToggleCellLockNavigation)>
+                    </input>
+                    {"Tab skips locked cells"}
+
+                    <h3>{"auto-complete"}</h3>
+                    <br></br>
+                    <input
+                        type="checkbox"
+                        checked={m.get_session().auto_complete}
+                        onchange=m.link.callback(|_| Action::ToggleAutoComplete)>
+                    </input>
+                    {"show suggestions while typing (Ctrl-Space always shows them)"}
+
+                    <h3>{"gridlines"}</h3>
+                    <br></br>
+                    <input
+                        type="checkbox"
+                        checked={m.get_session().show_gridlines}
+                        onchange=m.link.callback(|_| Action::ToggleGridlines)>
+                    </input>
+                    {"show gridlines "}
+                    <input
+                        type="color"
+                        value={m.get_session().gridline_color.clone()}
+                        onchange=m.link.callback(|e: ChangeData| {
+                            if let ChangeData::Value(color) = e {
+                                return Action::SetGridlineColor(color);
+                            }
+                            Action::Noop
+                        })>
+                    </input>
+
+                    <h3>{"theme"}</h3>
+                    <br></br>
+                    <input
+                        type="checkbox"
+                        checked={m.get_session().dark_mode}
+                        onchange=m.link.callback(|_| Action::ToggleDarkMode)>
+                    </input>
+                    {"dark mode"}
+
+                    <h3>{"auto-save"}</h3>
+                    <br></br>
+                    {"save a recovery copy every "}
+                    <input
+                        type="number"
+                        min="0"
+                        size="3"
+                        value={m.autosave_interval}
+                        onchange=m.link.callback(|e: ChangeData| {
+                            if let ChangeData::Value(value) = e {
+                                if let Ok(interval) = value.parse::<usize>() {
+                                    return Action::SetAutosaveInterval(interval);
+                                }
+                            }
+                            Action::Noop
+                        })>
+                    </input>
+                    {" changes (0 turns it off)"}
+                </div>
+            }
+        }
+        "Search" => {
+            let query = m.search_query.clone();
+            let replacement = m.search_replacement.clone();
+            html! {
+                <div class="side-menu-section">
+                    <h1>
+                        {"Search"}
+                    </h1>
+
+                    <h3>{"find"}</h3>
+                    <br></br>
+                    <input type="text" value=query oninput=m.link.callback(|e: InputData| {
+                        Action::SetSearchQuery(e.value)
+                    })>
+                    </input>
+                    <h3>{"replace with"}</h3>
+                    <br></br>
+                    <input type="text" value=replacement oninput=m.link.callback(|e: InputData| {
+                        Action::SetSearchReplacement(e.value)
+                    })>
+                    </input>
+                    <br></br>
+                    <input type="button" value="Find Next" onclick=m.link.callback(|_| {
+                        Action::FindReplace { replace_all: false }
+                    })>
+                    </input>
+                    <input type="button" value="Replace All" onclick=m.link.callback(|_| {
+                        Action::FindReplace { replace_all: true }
+                    })>
+                    </input>
                 </div>
             }
         }
         "Info" => {
+            // newest first
+            let mut entries = VList::new();
+            for (timestamp, action) in m.action_log.iter().rev() {
+                entries.add_child(html! {
+                    <div class="action-log-entry">
+                        <span class="action-log-time">{ clock_time(*timestamp) }</span>
+                        { action }
+                    </div>
+                });
+            }
             html! {
                 <div class="side-menu-section">
-                    {"THIS IS info MENU"}
+                    <h1>
+                        {"Info"}
+                    </h1>
+
+                    <h3>{"action log"}</h3>
+                    <br></br>
+                    { format!{"the last {} actions are logged while this menu is open", ACTION_LOG_LENGTH} }
+                    <input type="button" value="Clear" onclick=m.link.callback(|_| Action::ClearActionLog)>
+                    </input>
+                    <div class="action-log">
+                        { entries }
+                    </div>
+
+                    <h3>{"debugging"}</h3>
+                    <br></br>
+                    <input
+                        type="checkbox"
+                        checked={m.show_coords}
+                        onchange=m.link.callback(|_| Action::ToggleCoordinateOverlay)>
+                    </input>
+                    {"show the coordinate of every cell"}
                 </div>
             }
         }
@@ -153,8 +586,101 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
     }
 }
 
+// the conditional formats of the session, followed by a builder for a new rule on the active
+// cell's column
+pub fn view_conditional_formats(m: &Model) -> Html {
+    let mut rules = VList::new();
+    for (index, format) in m.get_session().conditional_formats.iter().enumerate() {
+        rules.add_child(html! {
+            <div class="conditional-format">
+                <span style=format!{"background: {};", format.style.background_color}>
+                    { format!{
+                        "{} {}",
+                        m.get_session().column_label(&format.column),
+                        format.predicate.to_string(),
+                    } }
+                </span>
+                <button onclick=m.link.callback(move |_| Action::RemoveConditionalFormat(index))>
+                    {"-"}
+                </button>
+            </div>
+        });
+    }
+
+    let (predicate_name, operand, color) = m.format_rule_draft.clone();
+    let predicate_options = {
+        let mut options = VList::new();
+        for name in PREDICATE_NAMES.iter() {
+            options.add_child(html! {
+                <option value={name} selected={*name == predicate_name}>{name}</option>
+            });
+        }
+        options
+    };
+    let (operand_1, color_1) = (operand.clone(), color.clone());
+    let (predicate_name_2, color_2) = (predicate_name.clone(), color.clone());
+    let (predicate_name_3, operand_3) = (predicate_name.clone(), operand.clone());
+    let active_col = m.active_cell.as_ref().map(|c| c.full_col());
+    html! {
+        <>
+            { rules }
+            <select onchange=m.link.callback(move |e: ChangeData| {
+                if let ChangeData::Select(select) = e {
+                    if let Some(name) = select.value() {
+                        return Action::ChangeFormatRuleDraft((name, operand_1.clone(), color_1.clone()));
+                    }
+                }
+                Action::Noop
+            })>
+                { predicate_options }
+            </select>
+            <input
+                type="text"
+                size="6"
+                value={operand.clone()}
+                onchange=m.link.callback(move |e: ChangeData| {
+                    if let ChangeData::Value(value) = e {
+                        return Action::ChangeFormatRuleDraft((predicate_name_2.clone(), value, color_2.clone()));
+                    }
+                    Action::Noop
+                })>
+            </input>
+            <input
+                type="color"
+                value={color.clone()}
+                onchange=m.link.callback(move |e: ChangeData| {
+                    if let ChangeData::Value(value) = e {
+                        return Action::ChangeFormatRuleDraft((predicate_name_3.clone(), operand_3.clone(), value));
+                    }
+                    Action::Noop
+                })>
+            </input>
+            <button onclick=m.link.callback(move |_| {
+                // the rule goes on the column of the active cell
+                match (active_col.clone(), Predicate::from_parts(&predicate_name, &operand)) {
+                    (Some(column), Some(predicate)) => {
+                        let mut style = Style::default();
+                        style.background_color = color.clone();
+                        Action::AddConditionalFormat(ConditionalFormat { column, predicate, style })
+                    }
+                    (None, _) => Action::Alert("Select a cell in the column to format".to_string()),
+                    (_, None) => Action::Alert(format! {"\"{}\" is not a number", operand}),
+                }
+            })>
+                {"Add Rule"}
+            </button>
+        </>
+    }
+}
+
 pub fn view_menu_bar(m: &Model) -> Html {
     let active_cell = m.active_cell.clone();
+    // RunPython runs the code of the active cell's editor, into that editor's output cell
+    let python_output = match &active_cell {
+        Some(active) => m.editor_output(active),
+        None => coord!("root-A1"),
+    };
+    let insert_count = m.insert_count;
     let (default_row, default_col) = {
         let (r, c) = m.default_nested_row_cols.clone();
         (r.get(), c.get())
@@ -204,6 +730,53 @@ pub fn view_menu_bar(m: &Model) -> Html {
         </button>
     };
 
+    let (recreate_row, recreate_col) = {
+        let (r, c) = m.recreate_row_cols.clone();
+        (r.get(), c.get())
+    };
+    let recreate_button = html! {
+        /* like "Nest Grid", the size of the fresh sheet is typed into the button */
+        <button class="menu-bar-button" id="RecreateWithSize" onclick=m.link.callback(move |_| {
+            Action::RecreateWithSize(recreate_row, recreate_col)
+        })>
+            { "Reset to  " }
+            <input
+                class="active-cell-indicator"
+                placeholder="Rows"
+                size="3"
+                onchange=m.link.callback(move |e: ChangeData| {
+                    if let ChangeData::Value(value) = e {
+                        if let Ok(row) = value.parse::<u32>() {
+                            if row > 0 {
+                                return Action::ChangeRecreateSize(non_zero_u32_tuple((row, recreate_col)));
+                            }
+                        }
+                    }
+                    Action::Noop
+                })
+                onclick=m.link.callback(|e: ClickEvent| { e.prevent_default(); Action::Noop })
+                value={recreate_row}>
+            </input>
+            <input
+                class="active-cell-indicator"
+                placeholder="Cols"
+                size="3"
+                onchange=m.link.callback(move |e: ChangeData| {
+                    if let ChangeData::Value(value) = e {
+                        if let Ok(col) = value.parse::<u32>() {
+                            if col > 0 {
+                                return Action::ChangeRecreateSize(non_zero_u32_tuple((recreate_row, col)));
+                            }
+                        }
+                    }
+                    Action::Noop
+                })
+                onclick=m.link.callback(|e: ClickEvent| { e.prevent_default(); Action::Noop })
+                value={recreate_col}>
+            </input>
+        </button>
+    };
+
     let add_definition_button = {
         let (can_add_definition, default_name, callback) = match (
             m.first_select_cell.clone(),
@@ -300,6 +873,63 @@ pub fn view_menu_bar(m: &Model) -> Html {
             </button>
         }
     };
+    // alignment applies to the active cell, or the whole selection when there is one
+    let align_cell = m.first_select_cell.clone().or(m.active_cell.clone());
+    let align_button = |label: &str, text_align: &'static str| {
+        let align_cell = align_cell.clone();
+        html! {
+            <button class="menu-bar-button" onclick=m.link.callback(move |_| {
+                if let Some(cell) = &align_cell {
+                    Action::SetTextAlign(cell.clone(), text_align.to_string())
+                } else { Action::Noop }
+            })>
+                { label }
+            </button>
+        }
+    };
+
+    let wrap_text_button = {
+        let wrap_cell = align_cell.clone();
+        html! {
+            <button class="menu-bar-button" onclick=m.link.callback(move |_| {
+                wrap_cell.clone().map_or(Action::Noop, Action::ToggleWrapText)
+            })>
+                { "Wrap Text" }
+            </button>
+        }
+    };
+
+    // freezing pins everything up to the row/column of the active cell's top-level cell
+    let freeze_cell = m.active_cell.clone().map(|c| c.truncate(2).unwrap_or(c));
+    let freeze_rows_button = {
+        let (label, rows) = match (m.frozen_rows, &freeze_cell) {
+            (0, Some(cell)) => ("Freeze Rows", Some(cell.row().get())),
+            (0, None) => ("Freeze Rows", None),
+            _ => ("Unfreeze Rows", Some(0)),
+        };
+        html! {
+            <button id="FreezeRows" class="menu-bar-button" onclick=m.link.callback(move |_| {
+                rows.map_or(Action::Noop, |rows| Action::SetFrozenRows(rows))
+            })>
+                { label }
+            </button>
+        }
+    };
+    let freeze_cols_button = {
+        let (label, cols) = match (m.frozen_cols, &freeze_cell) {
+            (0, Some(cell)) => ("Freeze Columns", Some(cell.col().get())),
+            (0, None) => ("Freeze Columns", None),
+            _ => ("Unfreeze Columns", Some(0)),
+        };
+        html! {
+            <button id="FreezeCols" class="menu-bar-button" onclick=m.link.callback(move |_| {
+                cols.map_or(Action::Noop, |cols| Action::SetFrozenCols(cols))
+            })>
+                { label }
+            </button>
+        }
+    };
+
     // ALL MENU BAR ITEMS
     html! {
         <div class="menu-bar horizontal-bar">
@@ -311,8 +941,8 @@ pub fn view_menu_bar(m: &Model) -> Html {
                 value={
                     match (m.active_cell.clone(), m.first_select_cell.clone(), m.last_select_cell.clone()) {
                         (_, Some(first_cell), Some(last_cell)) =>
-                            format!{"{}:{}", first_cell.to_string(), last_cell.to_string()},
-                        (Some(cell), _, _) => cell.to_string(),
+                            format!{"{}:{}", first_cell.to_a1_string(), last_cell.to_a1_string()},
+                        (Some(cell), _, _) => cell.to_a1_string(),
                         _ => "".to_string(),
                     }
                 }>
@@ -332,31 +962,81 @@ pub fn view_menu_bar(m: &Model) -> Html {
             <button id="ZoomOut" class="menu-bar-button" onclick=m.link.callback(|_| Action::ZoomOut)>
                 { "Zoom Out (-)" }
             </button>
+            <button id="ZoomToFit" class="menu-bar-button" onclick=m.link.callback(|_| Action::ZoomToFit)>
+                { "Zoom to Fit" }
+            </button>
+            <button id="ZoomToSelection" class="menu-bar-button" onclick=m.link.callback(|_| Action::ZoomToSelection)>
+                { "Zoom to Selection" }
+            </button>
+            { freeze_rows_button }
+            { freeze_cols_button }
             <button id="Reset" class="menu-bar-button" onclick=m.link.callback(|_| Action::Recreate)>
                 { "Reset" }
             </button>
+            { recreate_button }
             //<>
                 { nest_grid_button }
             //</>
-            <button id="InsertRow" class="menu-bar-button" onclick=m.link.callback(|_| Action::InsertRow)>
-                { "Insert Row" }
+            <input
+                class="insert-count"
+                type="number"
+                min="1"
+                size="3"
+                title="Number of rows/columns to insert"
+                value={m.insert_count}
+                onchange=m.link.callback(|e: ChangeData| {
+                    if let ChangeData::Value(value) = e {
+                        if let Ok(count) = value.parse::<u32>() {
+                            return Action::SetInsertCount(count);
+                        }
+                    }
+                    Action::Noop
+                })>
+            </input>
+            <button id="InsertRow" class="menu-bar-button" onclick=m.link.callback(move |_| Action::InsertRows(insert_count))>
+                { if insert_count == 1 { "Insert Row".to_string() } else { format!{"Insert {} Rows", insert_count} } }
             </button>
-            <button id="InsertCol" class="menu-bar-button" onclick=m.link.callback(|_| Action::InsertCol)>
-                { "Insert Column" }
+            <button id="InsertCol" class="menu-bar-button" onclick=m.link.callback(move |_| Action::InsertCols(insert_count))>
+                { if insert_count == 1 { "Insert Column".to_string() } else { format!{"Insert {} Columns", insert_count} } }
             </button>
             <button id="Merge" class="menu-bar-button" onclick=m.link.callback(move |_ : ClickEvent| Action::MergeCells())>
                 { "Merge" }
             </button>
-            <button id="DeleteRow" class="menu-bar-button" onclick=m.link.callback(|_| Action::DeleteRow)>
-                { "Delete Row" }
+            <button id="Transpose" class="menu-bar-button" onclick=m.link.callback(|_| Action::TransposeRange())>
+                { "Transpose" }
             </button>
-            <button id="DeleteCol" class="menu-bar-button" onclick=m.link.callback(|_| Action::DeleteCol)>
+            { align_button("Align Left", "left") }
+            { align_button("Align Center", "center") }
+            { align_button("Align Right", "right") }
+            <button class="menu-bar-button" onclick=m.link.callback(|_| Action::ToggleBold)>
+                <b>{ "B" }</b>
+            </button>
+            <button class="menu-bar-button" onclick=m.link.callback(|_| Action::ToggleItalic)>
+                <i>{ "I" }</i>
+            </button>
+            <button class="menu-bar-button" onclick=m.link.callback(|_| Action::ToggleUnderline)>
+                <u>{ "U" }</u>
+            </button>
+            { wrap_text_button }
+            <button id="DeleteRow" class="menu-bar-button" onclick=m.link.callback(|_| Action::DeleteRow)>
+                { "Delete Row" }
+            </button>
+            <button id="DeleteCol" class="menu-bar-button" onclick=m.link.callback(|_| Action::DeleteCol)>
                 { "Delete Column" }
             </button>
+            <button id="ExportHTML" class="menu-bar-button" onclick=m.link.callback(|_| Action::ExportHTML(coord!("root")))>
+                { "Export HTML" }
+            </button>
+            <button id="CheckSheet" class="menu-bar-button" onclick=m.link.callback(|_| Action::ValidateGrammarMap(false))>
+                { "Check Sheet" }
+            </button>
+            <button id="RepairSheet" class="menu-bar-button" onclick=m.link.callback(|_| Action::ValidateGrammarMap(true))>
+                { "Repair Sheet" }
+            </button>
             <button id="NewEditor" class="menu-bar-button" onclick=m.link.callback(|_| Action::NewEditor)>
                 { "New Editor" }
             </button>
-            <button id="RunPython" class="menu-bar-button" onclick=m.link.callback(|_| Action::RunPython("import sys\nsys.version\nprint(1+2)".to_string(), coord!("root-A1")))>
+            <button id="RunPython" class="menu-bar-button" onclick=m.link.callback(move |_| Action::RunPython("import sys\nsys.version\nprint(1+2)".to_string(), python_output.clone()))>
                 { "Run Python" }
             </button>
             //<>
@@ -366,18 +1046,81 @@ pub fn view_menu_bar(m: &Model) -> Html {
     }
 }
 
+// Excel-style summary of the selection: its size and range, and the count, sum and average
+// of the numbers in it. Without a selection it just shows the active cell.
+pub fn view_status_bar(m: &Model) -> Html {
+    let summary = match (&m.first_select_cell, &m.last_select_cell) {
+        (Some(first), Some(last)) => {
+            let cells = m.selected_cells();
+            let grammars = &m.get_session().grammars;
+            let values: Vec<String> = cells
+                .iter()
+                .filter_map(|c| match grammars.get(c).map(|g| &g.kind) {
+                    Some(Kind::Input(value)) | Some(Kind::Lookup(value, _)) => Some(value.clone()),
+                    Some(Kind::Number(n, _)) => Some(n.to_string()),
+                    _ => None,
+                })
+                .collect();
+            let (count, sum) = numeric_summary(values.iter().map(String::as_str));
+            let mut summary = format! {
+                "{}:{}    {} cells",
+                first.to_a1_string(),
+                last.to_a1_string(),
+                cells.len()
+            };
+            if count > 0 {
+                summary += &format! {
+                    "    Count: {}    Sum: {}    Average: {}",
+                    count, sum, sum / count as f64
+                };
+            }
+            summary
+        }
+        _ => m
+            .active_cell
+            .as_ref()
+            .map_or("".to_string(), |c| c.to_a1_string()),
+    };
+    html! {
+        <div class="status-bar horizontal-bar">
+            { summary }
+        </div>
+    }
+}
+
 pub fn view_tab_bar(m: &Model) -> Html {
     let mut tabs = VList::new();
     for (index, tab) in m.sessions.clone().iter().enumerate() {
-        if (index as usize) == m.current_session_index {
-            tabs.add_child(html! {
-                <button class="tab active-tab">{ tab.title.clone() }</button>
-            });
+        let class = if (index as usize) == m.current_session_index {
+            "tab active-tab"
         } else {
-            tabs.add_child(html! {
-                <button class="tab">{ tab.title.clone() }</button>
-            });
-        }
+            "tab"
+        };
+        // tabs are reordered by dragging them onto another tab, and the dragged tab's
+        // index is carried along in the drag's data
+        tabs.add_child(html! {
+            <button class=class draggable="true"
+                ondragstart=m.link.callback(move |e: DragStartEvent| {
+                    if let Some(data_transfer) = e.data_transfer() {
+                        data_transfer.set_data("text/plain", &index.to_string());
+                    }
+                    Action::Noop
+                })
+                ondragover=m.link.callback(|e: DragOverEvent| {
+                    // needed for the tab to accept drops
+                    e.prevent_default();
+                    Action::Noop
+                })
+                ondrop=m.link.callback(move |e: DragDropEvent| {
+                    e.prevent_default();
+                    let from = e
+                        .data_transfer()
+                        .and_then(|data_transfer| data_transfer.get_data("text/plain").parse().ok());
+                    from.map_or(Action::Noop, |from| Action::ReorderSession(from, index))
+                })>
+                { tab.title.clone() }
+            </button>
+        });
     }
     html! {
         <div class="tab-bar horizontal-bar">
@@ -396,24 +1139,20 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
         if grammar.clone().style.display == false {
             return html! {<> </>};
         }
-        match grammar.kind.clone() {
+        let cell = match grammar.kind.clone() {
             Kind::Text(value) => view_text_grammar(m, &coord, value, is_active),
+            Kind::Input(value) if m.lite_mode => {
+                // skip computing suggestions on sheets that are too large
+                view_input_grammar(m, coord.clone(), vec![], value, is_active)
+            }
             Kind::Input(value) => {
-                let suggestions = m
-                    .meta_suggestions
-                    .iter()
-                    .filter_map(|(name, suggestion_coord)| {
-                        if let Some(suggestion_grammar) =
-                            m.get_session().grammars.get(&suggestion_coord)
-                        {
-                            if name.contains(value.deref()) {
-                                Some((suggestion_coord.clone(), suggestion_grammar.clone()))
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
+                let suggestions = rank_suggestions(value.deref(), &m.meta_suggestions)
+                    .into_iter()
+                    .filter_map(|suggestion_coord| {
+                        m.get_session()
+                            .grammars
+                            .get(&suggestion_coord)
+                            .map(|suggestion_grammar| (suggestion_coord, suggestion_grammar.clone()))
                     })
                     .collect();
                 view_input_grammar(m, coord.clone(), suggestions, value, is_active)
@@ -423,7 +1162,7 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
                     <div
                         class=format!{"cell interactive row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
                         id=format!{"cell-{}", coord.to_string()}
-                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+                        style={ m.cell_style(&coord) }>
                         <button>
                             { name }
                         </button>
@@ -431,33 +1170,47 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
                 }
             }
             Kind::Interactive(name, Interactive::Slider(value, min, max)) => {
+                let slider_coord = coord.clone();
                 html! {
                     <div
                         onclick=m.link.callback(|_| Action::HideContextMenu)
                         class=format!{"cell interactive row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
                         id=format!{"cell-{}", coord.to_string()}
                         // style={ get_style(&m, &coord) }>
-                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
-                        <input type="range" min={min} max={max} value={value}>
+                        style={ m.cell_style(&coord) }>
+                        <input type="range" min={min} max={max} value={value}
+                            oninput=m.link.callback(move |e: InputData| {
+                                e.value.parse::<f64>().map_or(Action::Noop, |value| {
+                                    Action::SetInteractiveValue(slider_coord.clone(), value)
+                                })
+                            })>
                             { name }
                         </input>
                     </div>
                 }
             }
             Kind::Interactive(name, Interactive::Toggle(checked)) => {
+                let toggle_coord = coord.clone();
+                let new_value = if checked { 0.0 } else { 1.0 };
                 html! {
                     <div
                         onclick=m.link.callback(|_| Action::HideContextMenu)
                         class=format!{"cell interactive row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
                         id=format!{"cell-{}", coord.to_string()}
                         // style={ get_style(&m, &coord) }>
-                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
-                        <input type="checkbox" checked={checked}>
+                        style={ m.cell_style(&coord) }>
+                        <input type="checkbox" checked={checked}
+                            onchange=m.link.callback(move |_| {
+                                Action::SetInteractiveValue(toggle_coord.clone(), new_value)
+                            })>
                             { name }
                         </input>
                     </div>
                 }
             }
+            Kind::Grid(sub_coords) if m.get_session().collapsed.contains(&coord) => {
+                view_collapsed_grid(m, &coord, &sub_coords)
+            }
             Kind::Grid(sub_coords) => view_grid_grammar(
                 m,
                 &coord,
@@ -466,25 +1219,35 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
                     .map(|c| Coordinate::child_of(&coord, *c))
                     .collect(),
             ),
+            Kind::Lookup(value, lookup_type) if m.lite_mode => {
+                view_lookup_grammar(m, &coord, vec![], value, lookup_type, is_active)
+            }
             Kind::Lookup(value, lookup_type) => {
-                let suggestions: Vec<Coordinate> = m
-                    .get_session()
-                    .grammars
-                    .keys()
-                    .filter_map(|lookup_c| {
-                        if lookup_c.to_string().contains(value.deref()) {
-                            Some(lookup_c.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
+                let suggestions = lookup_suggestions(m, value.deref());
                 view_lookup_grammar(m, &coord, suggestions, value, lookup_type, is_active)
             }
             Kind::Defn(name, defn_coord, sub_grammars) => {
                 view_defn_grammar(m, &coord, &defn_coord, name, sub_grammars)
             }
             Kind::Editor(content) => view_editor_grammar(m, &coord, content),
+            Kind::Number(value, number_format) => {
+                view_number_grammar(m, &coord, value, number_format, is_active)
+            }
+            Kind::Markdown(source) => view_markdown_grammar(m, &coord, source),
+            Kind::Image(url) => view_image_grammar(m, &coord, url),
+        };
+        // the label goes in the same grid area as the cell, on top of it
+        if m.show_coords && coord.parent().is_some() {
+            html! {
+                <>
+                    { cell }
+                    <div class="coord-overlay" style=format!{"grid-area: cell-{};", coord.to_string()}>
+                        { coord.to_string() }
+                    </div>
+                </>
+            }
+        } else {
+            cell
         }
     } else {
         html! { <></> }
@@ -492,9 +1255,25 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
 }
 
 pub fn view_editor_grammar(m: &Model, coord: &Coordinate, content: String) -> Html {
+    let output = m.editor_output(coord).to_string();
+    let editor = coord.clone();
     html! {
-        <CodeMirror content={content} coordinate={coord.clone()}>
-        </CodeMirror>
+        <div class="editor">
+            <CodeMirror content={content} coordinate={coord.clone()}>
+            </CodeMirror>
+            <input
+                class="editor-output"
+                title="cell that Run Python writes the output into"
+                value={output}
+                onchange=m.link.callback(move |e: ChangeData| {
+                    if let ChangeData::Value(output) = e {
+                        Action::SetEditorOutput(editor.clone(), output)
+                    } else {
+                        Action::Noop
+                    }
+                })>
+            </input>
+        </div>
     }
 }
 
@@ -512,27 +1291,58 @@ pub fn view_defn_grammar(
         let name_coord = Coordinate::child_of(defn_coord, non_zero_u32_tuple((index.clone(), 1)));
         let grammar_coord =
             Coordinate::child_of(defn_coord, non_zero_u32_tuple((index.clone(), 2)));
+        // sub-rules can carry a validator pattern, which cells completed from them keep
+        let validator = m
+            .get_session()
+            .grammars
+            .get(&grammar_coord)
+            .and_then(|g| g.validator.clone())
+            .unwrap_or_default();
+        let validator_coord = grammar_coord.clone();
+        let remove_coord = coord.clone();
+        let rule_index = index as usize - 1;
         nodes.add_child(html! {
-            <div>
+            <div class="defn-rule">
                 // { view_text_grammar(m, &name_coord, name) } // changes to the sub-rule name requires re-bindings
                 { view_grammar(m, grammar_coord) }  // any change to the grammar, reflects in the grammar map
+                <input
+                    class="defn-rule-validator"
+                    placeholder="pattern"
+                    value={validator}
+                    onchange=m.link.callback(move |e: ChangeData| {
+                        if let ChangeData::Value(pattern) = e {
+                            let validator = if pattern.trim() == "" { None } else { Some(pattern) };
+                            return Action::SetValidator(validator_coord.clone(), validator);
+                        }
+                        Action::Noop
+                    })>
+                </input>
+                <button class="defn-rule-remove" onclick=m.link.callback(move |_| {
+                    Action::RemoveDefnRule(remove_coord.clone(), rule_index)
+                })>
+                    {"-"}
+                </button>
             </div>
         });
         index += 1;
     }
     let c = coord.clone();
+    let add_coord = coord.clone();
     html! {
         <div
             onclick=m.link.callback(|_| Action::HideContextMenu)
             class=format!{"cell grid row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
             id=format!{"cell-{}", coord.to_string()}
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ m.cell_style(&coord) }>
             <input
                 class="cell"
                 value={name}>
                 // oninput=m.link.callback(move |e : InputData| Action::DefnUpdateName(c.clone(), e.value))>
             </input>
             { nodes }
+            <button class="defn-rule-add" onclick=m.link.callback(move |_| Action::AddDefnRule(add_coord.clone()))>
+                {"+"}
+            </button>
         </div>
     }
 }
@@ -554,32 +1364,104 @@ pub fn view_defn_variant_grammar(
             class=format!{"cell variant row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
             id=format!{"cell-{}", coord.to_string()}
             // style={ get_style(&m, &coord) }>
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ m.cell_style(&coord) }>
             { nodes }
-            <button onclick=m.link.callback(|_| Action::InsertCol)>
-                {"+"}
-            </button>
         </div>
     }
 }
 
+// suggestions for a lookup cell as (label, lookup) pairs. Typing "row:" or "col:" suggests
+// whole rows or columns, "start:end" suggests ranges (see `range_suggestions`), and anything
+// else suggests cells whose coordinate contains the value.
+pub fn lookup_suggestions(m: &Model, value: &str) -> Vec<(String, Lookup)> {
+    let cells = m.get_session().grammars.keys().filter(|c| c.row_cols.len() > 1);
+    // rows & columns are labelled like `Coordinate::row_to_string`/`col_to_string`, using
+    // any cell that's in them
+    let mut suggestions: Vec<(String, Lookup)> = if value.starts_with("row:") {
+        let query = &value["row:".len()..];
+        let rows: HashSet<Row> = cells.map(|c| c.full_row()).collect();
+        rows.into_iter()
+            .map(|row| (Coordinate::child_of(&row.0, (row.1, row.1)).row_to_string(), row))
+            .filter(|(label, _)| label.contains(query))
+            .map(|(label, row)| (label, Lookup::Row(row)))
+            .collect()
+    } else if value.starts_with("col:") {
+        let query = &value["col:".len()..];
+        let cols: HashSet<Col> = cells.map(|c| c.full_col()).collect();
+        cols.into_iter()
+            .map(|col| (Coordinate::child_of(&col.0, (col.1, col.1)).col_to_string(), col))
+            .filter(|(label, _)| label.contains(query))
+            .map(|(label, col)| (label, Lookup::Col(col)))
+            .collect()
+    } else if value.contains(':') {
+        range_suggestions(m, value)
+    } else {
+        m.get_session()
+            .grammars
+            .keys()
+            .filter(|c| c.to_string().contains(value))
+            .map(|c| (c.to_string(), Lookup::Cell(c.clone())))
+            .collect()
+    };
+    suggestions.sort_by(|(a, _), (b, _)| a.cmp(b));
+    suggestions
+}
+
+// for "start:end", the ranges from `start` to each cell in its grid whose coordinate matches
+// `end`. Nothing is suggested until `start` is a cell.
+fn range_suggestions(m: &Model, value: &str) -> Vec<(String, Lookup)> {
+    let mut ends = value.splitn(2, ':');
+    let start = match ends.next().map(parse_cell_name) {
+        Some(Ok(start)) => start,
+        _ => return vec![],
+    };
+    let end_query = ends.next().unwrap_or("").trim().to_uppercase();
+    let parent = start.parent();
+    m.get_session()
+        .grammars
+        .keys()
+        .filter(|c| c.parent() == parent && c.to_string().to_uppercase().contains(&end_query))
+        .filter_map(|end| {
+            let label = format! {"{}:{}", start.to_string(), end.to_string()};
+            Lookup::parse_range(&label).ok().map(|range| (label, range))
+        })
+        .collect()
+}
+
 pub fn view_lookup_grammar(
     m: &Model,
     coord: &Coordinate,
-    suggestions: Vec<Coordinate>,
+    suggestions: Vec<(String, Lookup)>,
     value: String,
     _lookup_type: Option<Lookup>,
     is_active: bool,
 ) -> Html {
+    // a range that can't be looked up gets explained under the cell
+    let range_error = match Lookup::parse_range(&value) {
+        Err(message) if value.contains(':') && suggestions.is_empty() => {
+            html! { <div class="lookup-error">{ message }</div> }
+        }
+        _ => html! { <></> },
+    };
     let suggestions_div = if is_active {
         let mut suggestions_nodes = VList::new();
-        for lookup_coord in suggestions {
+        for (label, lookup) in suggestions {
             let dest = coord.clone();
-            let source = lookup_coord.clone();
+            // cells with a value are referenced (and kept up to date), anything else gets copied in
+            let is_reference = match &lookup {
+                Lookup::Cell(source) => match m.get_session().grammars.get(source).map(|g| &g.kind) {
+                    Some(Kind::Input(_)) | Some(Kind::Lookup(_, _)) => true,
+                    _ => false,
+                },
+                _ => true,
+            };
             suggestions_nodes.add_child(html!{
                 <a tabindex=2
-                    onclick=m.link.callback(move |_ : ClickEvent| Action::DoCompletion(source.clone(), dest.clone()))>
-                    { lookup_coord.to_string() }
+                    onclick=m.link.callback(move |_ : ClickEvent| match lookup.clone() {
+                        Lookup::Cell(source) if !is_reference => Action::DoCompletion(source, dest.clone()),
+                        lookup => Action::Lookup(dest.clone(), lookup),
+                    })>
+                    { label }
                 </a>
             })
         }
@@ -601,8 +1483,8 @@ pub fn view_lookup_grammar(
             onclick=m.link.callback(|_| Action::HideContextMenu)
             class=format!{"cell suggestion lookup row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
             id=format!{"cell-{}", coord.to_string()}
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
-            <b style=format!{"font-size: 20px; color: {};", random_color()}>{ "$" }</b>
+            style={ m.cell_style(&coord) }>
+            <b style=format!{"font-size: 20px; color: {};", m.lookup_marker_color(&coord)}>{ "$" }</b>
             <div contenteditable=true
                 class=format!{
                         "cell-data {}",
@@ -624,6 +1506,7 @@ pub fn view_lookup_grammar(
                 >
             </div>
             { value }
+            { range_error }
             { suggestions_div }
         </div>
     }
@@ -643,16 +1526,24 @@ pub fn view_input_grammar(
     }
     // load the suggestion values, including the completion callbacks
     // and parse them into DOM nodes
-    let suggestions_len = if value.clone() != "" && is_active {
+    let show_suggestions = m.dismissed_suggestions.as_ref() != Some(&coord)
+        && suggestions_visible(
+            value.deref(),
+            is_active,
+            m.get_session().auto_complete,
+            m.forced_suggestions == Some(coord.clone()),
+        );
+    let suggestions_len = if show_suggestions {
         suggestions.len()
     } else {
         0
     };
-    let suggestions = if value.clone() != "" && is_active {
+    let suggestions = if show_suggestions {
         let mut suggestion_nodes = VList::new();
         let mut suggestion_index = 1;
         for (s_coord, s_grammar) in suggestions {
             let s_coord_2 = s_coord.clone();
+            let remove_coord = s_coord.clone();
             let c = coord.clone();
             let dest_coord = coord.clone();
             suggestion_nodes.add_child(html! {
@@ -661,16 +1552,32 @@ pub fn view_input_grammar(
                         tabindex=2
                         onkeydown=m.link.callback(move |e : KeyDownEvent| {
                             Action::HideContextMenu;
-                            if e.code() == "Tab" {
+                            let step = match e.code().as_str() {
+                                "Tab" if e.shift_key() => -1,
+                                "Tab" | "ArrowDown" => 1,
+                                "ArrowUp" => -1,
+                                _ => 0,
+                            };
+                            if step != 0 {
                                 e.prevent_default();
-                                return Action::NextSuggestion(c.clone(), if e.shift_key() { suggestion_index-1 } else { suggestion_index+1 });
+                                let next_index = suggestion_step(suggestion_index, step, suggestions_len);
+                                return Action::NextSuggestion(c.clone(), next_index);
                             } else if e.code() == "Enter" || e.code() == "Space" {
                                 return Action::DoCompletion(s_coord_2.clone(), c.clone());
+                            } else if e.code() == "Escape" {
+                                return Action::DismissSuggestions(c.clone());
                             }
                             Action::Noop
                         })
                         onclick=m.link.callback(move |_ : ClickEvent| Action::DoCompletion(s_coord.clone(), dest_coord.clone()))>
                         { &s_grammar.name }
+                        <span class="suggestion-remove" title="Remove this definition"
+                            onclick=m.link.callback(move |e : ClickEvent| {
+                                e.stop_propagation();
+                                Action::RemoveDefinition(remove_coord.clone())
+                            })>
+                            { "×" }
+                        </span>
                     </a>
                 });
             suggestion_index += 1;
@@ -689,89 +1596,124 @@ pub fn view_input_grammar(
      * Calculate if a specific cell should be selected based on the top-rightmost
      * and bottom-leftmost cells
      */
-    let is_selected = cell_is_selected(&coord, &m.first_select_cell, &m.last_select_cell);
+    let is_selected = cell_is_selected(&coord, &m.first_select_cell, &m.last_select_cell)
+        || m.secondary_selections.contains(&coord);
     let has_lookup_prefix: bool = value.clone() == "$";
     let current_coord = coord.clone();
     let tab_coord = coord.clone();
     let focus_coord = coord.clone();
+    let commit_coord = coord.clone();
     let drag_coord = coord.clone();
     let is_hovered_on = coord.clone();
     let shift_key_pressed = m.shift_key_pressed;
     let new_selected_cell = coord.clone();
-    let cell_classes =
-        format! {"cell suggestion row-{} col-{}", coord.row_to_string(), coord.col_to_string()};
+    let reference_coord = coord.clone();
+    // while another cell is editing a formula, clicking this one points at it instead
+    let point_mode = m.active_cell.as_ref().map_or(false, |active| {
+        *active != coord
+            && match m.get_session().grammars.get(active).map(|g| &g.kind) {
+                Some(Kind::Input(active_value)) => is_formula(active_value),
+                _ => false,
+            }
+    });
+    // the cells that the fill handle (if it's being dragged) will copy into
+    let is_filling = m.filling.is_some();
+    let in_fill_range = m
+        .filling
+        .as_ref()
+        .map_or(false, |(source, target)| fill_targets(source, target).contains(&coord));
+    let fill_coord = coord.clone();
+    let fill_to_coord = coord.clone();
+    let merged = m.get_session().grammars.get(&coord).map_or(false, is_merged);
+    let cell_classes = format! {
+        "cell suggestion row-{} col-{} {} {} {} {}",
+        coord.row_to_string(),
+        coord.col_to_string(),
+        if merged { "merged" } else { "" },
+        if m.invalid_cells.contains(&coord) { "invalid" } else { "" },
+        if m.python_errors.contains(&coord) { "python-error" } else { "" },
+        if in_fill_range { "fill-target" } else { "" }
+    };
+    // text-align has to be set on the editable div itself for the text inside it to move
+    let text_align = m
+        .get_session()
+        .grammars
+        .get(&coord)
+        .map_or("left".to_string(), |g| g.style.text_align.clone());
     let cell_data_classes = format! {
         "cell-data {} {}",
         if is_active { "cell-active" } else { "cell-inactive" },
         if is_selected { "selection" } else { "" }
     };
     // relevant coordinates for navigation purposes
-    let neighbor_left = current_coord
-        .neighbor_left()
-        .and_then(|c| {
-            // check if grammar corresponding to left neighbor coord exists...
-            m.get_session().grammars.get(&c).map(|g| {
-                // ... and if it's a grid, select it's first cell
-                if let Kind::Grid(_) = g.kind {
-                    Coordinate::child_of(&c, non_zero_u32_tuple((1, 1)))
-                } else {
-                    c.clone()
-                }
-            })
-        })
-        .clone();
-    let neighbor_right = current_coord
-        .neighbor_right()
-        .and_then(|c| {
-            // check if grammar corresponding to right neighbor coord exists...
-            m.get_session().grammars.get(&c).map(|g| {
-                // ... and if it's a grid, select it's first cell
-                // TODO: make this select the last (bottom-rightmost) nested cell
-                if let Kind::Grid(_) = g.kind {
-                    Coordinate::child_of(&c, non_zero_u32_tuple((1, 1)))
-                } else {
-                    c.clone()
-                }
-            })
-        })
-        .clone();
-    let first_col_next_row = {
-        let temp = &mut current_coord.neighbor_below();
-        if let Some(t) = temp {
-            let col = t.col_mut();
-            *col = NonZeroU32::new(1).unwrap();
-            if m.get_session().grammars.contains_key(&t) {
-                Some(t.clone())
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    let skip_locked = m.tab_skips_locked;
+    let grammars = &m.get_session().grammars;
+    let next_cell = tab_next(grammars, &current_coord, false, |c| {
+        skip_locked && is_locked(grammars, c)
+    });
+    let prev_cell = tab_next(grammars, &current_coord, true, |c| {
+        skip_locked && is_locked(grammars, c)
+    });
+    let below_cell = current_coord
+        .neighbor_below()
+        .filter(|c| grammars.contains_key(c));
+    let newline_coord = coord.clone();
+    // locked cells (or cells inside a locked grid) can't be edited
+    let locked = is_locked(grammars, &coord);
+    let lock_indicator = if locked {
+        html! { <div class="lock-indicator" title="Locked">{ "🔒" }</div> }
+    } else {
+        html! { <></> }
     };
-    let last_col_prev_row = /* TODO: get the correct value of this */ current_coord.neighbor_above();
 
     let keydownhandler = m.link.callback(move |e: KeyDownEvent| {
         info! {"suggestion len {}", suggestions_len}
+        if e.ctrl_key() && e.code() == "Space" {
+            // explicitly open suggestions, regardless of the auto-complete setting
+            e.prevent_default();
+            return Action::ForceSuggestions(tab_coord.clone());
+        }
+        if suggestions_len > 0 && e.code() == "ArrowDown" {
+            // arrows move from the cell into the dropdown, like Tab does
+            e.prevent_default();
+            return Action::NextSuggestion(tab_coord.clone(), 1);
+        }
+        if suggestions_len > 0 && e.code() == "Escape" {
+            return Action::DismissSuggestions(tab_coord.clone());
+        }
         if e.code() == "Tab" {
             e.prevent_default();
             if suggestions_len > 0 {
                 return Action::NextSuggestion(tab_coord.clone(), 1);
             }
             let next_active_cell = if e.shift_key() {
-                neighbor_left
-                    .clone()
-                    .or(last_col_prev_row.clone())
-                    .or(tab_coord.parent().and_then(|c| c.neighbor_left()))
+                prev_cell.clone()
             } else {
-                neighbor_right
-                    .clone()
-                    .or(first_col_next_row.clone())
-                    .or(tab_coord.parent().and_then(|c| c.neighbor_right()))
+                next_cell.clone()
             };
-            info! {"next_active_cell {}", next_active_cell.clone().unwrap().to_string()};
             return next_active_cell.map_or(Action::Noop, |c| Action::SetActiveCell(c));
         } 
+        let navigation = match e.code().as_str() {
+            "Home" if e.ctrl_key() => Some(Navigation::GridStart),
+            "End" if e.ctrl_key() => Some(Navigation::GridEnd),
+            "Home" => Some(Navigation::RowStart),
+            "End" => Some(Navigation::RowEnd),
+            "PageUp" => Some(Navigation::PageUp),
+            "PageDown" => Some(Navigation::PageDown),
+            _ => None,
+        };
+        if let Some(navigation) = navigation {
+            e.prevent_default();
+            return Action::Navigate(tab_coord.clone(), navigation);
+        }
+        if e.code() == "Enter" {
+            // Alt+Enter adds a line to the cell, plain Enter confirms it and moves down
+            e.prevent_default();
+            if e.alt_key() {
+                return Action::InsertNewline(newline_coord.clone());
+            }
+            return below_cell.clone().map_or(Action::Noop, |c| Action::SetActiveCell(c));
+        }
         if is_selected && (e.code() == "Backspace" || e.code() == "Delete") {       
             return Action::RangeDelete();
         }
@@ -780,16 +1722,93 @@ pub fn view_input_grammar(
     let drophandler = m.link.callback(move |e: DragDropEvent| {
         let file = e.data_transfer().unwrap().files().iter().next().unwrap();
         // info!{"this is csv {:?}", file}
-        Action::ReadCSVFile(file, is_hovered_on.clone())
+        let mime = file.mime().unwrap_or_default();
+        if is_image_mime_type(&mime) {
+            Action::ReadImageFile(file, is_hovered_on.clone(), mime)
+        } else if file.name().to_lowercase().ends_with(".json") {
+            Action::ReadJSONFile(file, is_hovered_on.clone())
+        } else if file.name().to_lowercase().ends_with(".xlsx") {
+            Action::ReadXLSXFile(file)
+        } else {
+            let delimiter = delimiter_for_file_name(&file.name());
+            Action::ReadDelimitedFile(file, is_hovered_on.clone(), delimiter)
+        }
     });
+    /*
+     * FILL HANDLE
+     * the handle is its own element over the bottom-right corner of the active cell, so pressing
+     * it never reaches the resize region of the editable div underneath
+     */
+    let fill_handle = if is_active {
+        html! {
+            <div class="fill-handle"
+                onmousedown=m.link.callback(move |e: MouseDownEvent| {
+                    e.prevent_default();
+                    e.stop_propagation();
+                    Action::FillStart(fill_coord.clone())
+                })>
+            </div>
+        }
+    } else {
+        html! { <></> }
+    };
+    /*
+     * NOTES
+     * cells with a note get a triangle in their top-right corner, which shows the note on hover
+     */
+    let sparkline = view_sparkline(m, &coord);
+    let note = m.get_session().notes.get(&coord).cloned();
+    let note_indicator = match note.clone() {
+        Some(text) => html! {
+            <div class="note">
+                <div class="note-indicator"></div>
+                <div class="note-text">{ text }</div>
+            </div>
+        },
+        None => html! { <></> },
+    };
+    let note_editor = if m.note_editor.as_ref() == Some(&coord) {
+        let note_coord = coord.clone();
+        html! {
+            <textarea
+                class="note-editor"
+                placeholder="Note"
+                value=note.unwrap_or_default()
+                onchange=m.link.callback(move |e: ChangeData| {
+                    if let ChangeData::Value(text) = e {
+                        return Action::SetNote(note_coord.clone(), text);
+                    }
+                    Action::Noop
+                })>
+            </textarea>
+        }
+    } else {
+        html! { <></> }
+    };
+    // numbers are shown with the cell's display precision, except while they're being edited
+    let precision = m.get_session().grammars.get(&coord).and_then(|g| g.style.display_precision);
+    // formulas show their result (see `formula_result`)
+    let shown_value = if is_active {
+        value.clone()
+    } else {
+        with_precision(&formula_result(&m.get_session().grammars, &coord, &value), precision)
+    };
     html! {
         <div
             onclick=m.link.callback(|_| Action::HideContextMenu)
+            onmouseenter=m.link.callback(move |_: MouseEnterEvent| {
+                if is_filling {
+                    Action::FillTo(fill_to_coord.clone())
+                } else {
+                    Action::Noop
+                }
+            })
             class=cell_classes
             id=format!{"cell-{}", coord.to_string()}
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
-            <div contenteditable=true
+            style={ m.cell_style(&coord) }>
+            <div contenteditable={!locked}
                 class=cell_data_classes
+                style=format!{"text-align: {};", text_align}
                 onkeydown=keydownhandler
                 onkeypress=m.link.callback(move |e : KeyPressEvent| {
                     if e.code() == "Space" && has_lookup_prefix {
@@ -800,8 +1819,11 @@ pub fn view_input_grammar(
                 oninput=m.link.callback(move |e : InputData| {
                     Action::ChangeInput(coord.clone(), e.value)
                 })
+                onblur=m.link.callback(move |_| Action::CommitInput(commit_coord.clone()))
                 onclick=m.link.callback(move |e : ClickEvent| {
-                    if e.shift_key() {
+                    if e.ctrl_key() {
+                        Action::ToggleSecondarySelection(new_selected_cell.clone())
+                    } else if e.shift_key() {
                         Action::Select(SelectMsg::End(new_selected_cell.clone()))
                     } else {
                         Action::Select(SelectMsg::Start(new_selected_cell.clone()))
@@ -836,6 +1858,11 @@ pub fn view_input_grammar(
                     }
                 })
                 onmousedown=m.link.callback(move |e: MouseDownEvent| {
+                    if point_mode {
+                        // keep the caret in the formula cell
+                        e.prevent_default();
+                        return Action::InsertReferenceToSelection(reference_coord.clone());
+                    }
                     let (offset_x, offset_y) = {
                         // compute the distance from the right & bottom borders that resizing is allowed
                         let target = HtmlElement::try_from(e.target().unwrap()).unwrap();
@@ -851,22 +1878,167 @@ pub fn view_input_grammar(
                     }
                 })
                 ondrop=drophandler >
-                { value }
+                { shown_value }
             </div>
+            { fill_handle }
+            { lock_indicator }
+            { note_indicator }
+            { sparkline }
+            { note_editor }
             { suggestions }
         </div>
     }
 }
 
+pub fn view_number_grammar(
+    m: &Model,
+    coord: &Coordinate,
+    value: f64,
+    number_format: NumberFormat,
+    is_active: bool,
+) -> Html {
+    let is_selected = cell_is_selected(coord, &m.first_select_cell, &m.last_select_cell)
+        || m.secondary_selections.contains(coord);
+    // the raw number is edited, and the formatted one is shown otherwise
+    let shown_value = if is_active {
+        value.to_string()
+    } else {
+        number_format.format(value)
+    };
+    let input_coord = coord.clone();
+    let focus_coord = coord.clone();
+    let commit_coord = coord.clone();
+    let select_coord = coord.clone();
+    let locked = is_locked(&m.get_session().grammars, coord);
+    html! {
+        <div
+            onclick=m.link.callback(|_| Action::HideContextMenu)
+            class=format!{"cell suggestion number row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
+            id=format!{"cell-{}", coord.to_string()}
+            style={ m.cell_style(&coord) }>
+            <div contenteditable={!locked}
+                class={
+                    format!{
+                        "cell-data {} {}",
+                        if is_active { "cell-active" } else { "cell-inactive" },
+                        if is_selected { "selection" } else { "" }
+                    }
+                }
+                oninput=m.link.callback(move |e : InputData| {
+                    Action::ChangeInput(input_coord.clone(), e.value)
+                })
+                onblur=m.link.callback(move |_| Action::CommitInput(commit_coord.clone()))
+                onclick=m.link.callback(move |e : ClickEvent| {
+                    if e.ctrl_key() {
+                        Action::ToggleSecondarySelection(select_coord.clone())
+                    } else if e.shift_key() {
+                        Action::Select(SelectMsg::End(select_coord.clone()))
+                    } else {
+                        Action::Select(SelectMsg::Start(select_coord.clone()))
+                    }
+                })
+                onfocus=m.link.callback(move |_ : FocusEvent| Action::SetActiveCell(focus_coord.clone()))>
+                { shown_value }
+            </div>
+            { view_sparkline(m, coord) }
+        </div>
+    }
+}
+
+// chart of the values a cell has had, drawn in its corner once it's been toggled on
+pub fn view_sparkline(m: &Model, coord: &Coordinate) -> Html {
+    match m.cell_history.get(coord) {
+        Some(values) if m.sparklines.contains(coord) && values.len() > 1 => {
+            // like markdown, the svg is set directly on an element since html! can't make it
+            let sparkline = document().create_element("div").unwrap();
+            sparkline.set_attribute("class", "sparkline").unwrap();
+            js! { @{&sparkline}.innerHTML = @{sparkline_svg(values, 40.0, 12.0)}; };
+            VNode::VRef(sparkline.into())
+        }
+        _ => html! { <></> },
+    }
+}
+
+// markdown cells show the rendered markdown, and double-clicking switches to editing the source
+pub fn view_markdown_grammar(m: &Model, coord: &Coordinate, source: String) -> Html {
+    let is_editing = m.markdown_editor.as_ref() == Some(coord);
+    let toggle_coord = coord.clone();
+    let content = if is_editing {
+        let input_coord = coord.clone();
+        let focus_coord = coord.clone();
+        html! {
+            <div contenteditable=true
+                class="cell-data cell-active"
+                oninput=m.link.callback(move |e : InputData| {
+                    Action::ChangeInput(input_coord.clone(), e.value)
+                })
+                onfocus=m.link.callback(move |_ : FocusEvent| Action::SetActiveCell(focus_coord.clone()))>
+                { source }
+            </div>
+        }
+    } else {
+        // the rendered html is set directly on an element, since html! only makes text nodes
+        let rendered = document().create_element("div").unwrap();
+        rendered.set_attribute("class", "cell-data markdown").unwrap();
+        js! { @{&rendered}.innerHTML = @{render_markdown(&source)}; };
+        VNode::VRef(rendered.into())
+    };
+    html! {
+        <div
+            onclick=m.link.callback(|_| Action::HideContextMenu)
+            ondoubleclick=m.link.callback(move |_ : DoubleClickEvent| Action::ToggleMarkdownEdit(toggle_coord.clone()))
+            class=format!{"cell suggestion row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
+            id=format!{"cell-{}", coord.to_string()}
+            style={ m.cell_style(&coord) }>
+            { content }
+        </div>
+    }
+}
+
+// the image is scaled to fit in the cell, which keeps the cell's size
+pub fn view_image_grammar(m: &Model, coord: &Coordinate, url: String) -> Html {
+    let focus_coord = coord.clone();
+    let drop_coord = coord.clone();
+    html! {
+        <div
+            onclick=m.link.callback(move |_| Action::SetActiveCell(focus_coord.clone()))
+            ondragover=m.link.callback(|e: DragOverEvent| {
+                // needed for the cell to accept drops
+                e.prevent_default();
+                Action::Noop
+            })
+            // dropping another image onto the cell replaces its image
+            ondrop=m.link.callback(move |e: DragDropEvent| {
+                e.prevent_default();
+                let file = e.data_transfer().and_then(|d| d.files().iter().next());
+                match file {
+                    Some(file) if is_image_mime_type(&file.mime().unwrap_or_default()) => {
+                        let mime = file.mime().unwrap_or_default();
+                        Action::ReadImageFile(file, drop_coord.clone(), mime)
+                    }
+                    _ => Action::Noop,
+                }
+            })
+            class=format!{"cell image row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
+            id=format!{"cell-{}", coord.to_string()}
+            style={ m.cell_style(&coord) }>
+            <img class="cell-image" src=url></img>
+        </div>
+    }
+}
+
 pub fn view_text_grammar(m: &Model, coord: &Coordinate, value: String, is_active: bool) -> Html {
-    let is_selected = cell_is_selected(coord, &m.first_select_cell, &m.last_select_cell);
+    let is_selected = cell_is_selected(coord, &m.first_select_cell, &m.last_select_cell)
+        || m.secondary_selections.contains(coord);
+    let precision = m.get_session().grammars.get(coord).and_then(|g| g.style.display_precision);
+    let value = if is_active { value } else { with_precision(&value, precision) };
     html! {
         <div
             onclick=m.link.callback(|_| Action::HideContextMenu)
             class=format!{"cell suggestion row-{} col-{}", coord.row_to_string(), coord.col_to_string(),}
             id=format!{"cell-{}", coord.to_string()}
             // style={ get_style(&m, &coord) }>
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ m.cell_style(&coord) }>
             <div
                 class={
                     format!{
@@ -886,23 +2058,355 @@ pub fn view_text_grammar(m: &Model, coord: &Coordinate, value: String, is_active
     }
 }
 
+// text shown in place of a collapsed grid, e.g. "▸ 3×3 grid"
+pub fn collapsed_grid_summary(sub_coords: &[(NonZeroU32, NonZeroU32)]) -> String {
+    let num_rows = sub_coords.iter().map(|(row, _)| row.get()).max().unwrap_or(0);
+    let num_cols = sub_coords.iter().map(|(_, col)| col.get()).max().unwrap_or(0);
+    format! {"▸ {}×{} grid", num_rows, num_cols}
+}
+
+// a collapsed grid is drawn as one cell that expands the grid again when clicked
+pub fn view_collapsed_grid(
+    m: &Model,
+    coord: &Coordinate,
+    sub_coords: &[(NonZeroU32, NonZeroU32)],
+) -> Html {
+    let expand_coord = coord.clone();
+    html! {
+        <div
+            onclick=m.link.callback(move |_| Action::ToggleCollapse(expand_coord.clone()))
+            class=format!{"cell collapsed-grid row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
+            id=format!{"cell-{}", coord.to_string()}
+            title="Click to expand"
+            style={ m.cell_style(&coord) }>
+            { collapsed_grid_summary(sub_coords) }
+        </div>
+    }
+}
+
 pub fn view_grid_grammar(m: &Model, coord: &Coordinate, sub_coords: Vec<Coordinate>) -> Html {
     let mut nodes = VList::new();
     for c in sub_coords {
         nodes.add_child(view_grammar(m, c.clone()));
     }
-    html! {
+    // frozen rows/cols of the top-level grid are pinned with a stylesheet, since the sticky
+    // offsets depend on the sizes of the rows/cols before them
+    if *coord == coord!("root") && (m.frozen_rows > 0 || m.frozen_cols > 0) {
+        let row_heights: Vec<f64> = (1..=m.frozen_rows)
+            .map(|r| m.row_height(&Row(coord.clone(), NonZeroU32::new(r).unwrap())))
+            .collect();
+        let col_widths: Vec<f64> = (1..=m.frozen_cols)
+            .map(|c| m.col_width(&Col(coord.clone(), NonZeroU32::new(c).unwrap())))
+            .collect();
+        nodes.add_child(html! {
+            <style>{ frozen_css(coord, &row_heights, &col_widths) }</style>
+        });
+    }
+    let grid = html! {
         <div
             onclick=m.link.callback(|_| Action::HideContextMenu)
             class=format!{"\ncell grid row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
             id=format!{"cell-{}", coord.to_string()}
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ m.cell_style(&coord) }>
             { nodes }
         </div>
+    };
+    if *coord == coord!("root") {
+        view_grid_headers(m, coord, grid)
+    } else {
+        grid
+    }
+}
+
+// puts row numbers to the left of the grid at `coord` and column labels above it. Clicking
+// a header selects the whole row or column, and double-clicking a column header renames it.
+pub fn view_grid_headers(m: &Model, coord: &Coordinate, grid: Html) -> Html {
+    let grammar = match m.get_session().grammars.get(coord) {
+        Some(grammar) => grammar,
+        None => return grid,
+    };
+    let (num_rows, num_cols) = match &grammar.kind {
+        Kind::Grid(sub_coords) => (
+            sub_coords.iter().map(|(row, _)| row.get()).max().unwrap_or(0),
+            sub_coords.iter().map(|(_, col)| col.get()).max().unwrap_or(0),
+        ),
+        _ => return grid,
+    };
+    let mut col_headers = VList::new();
+    for col in (1..=num_cols).filter(|c| !grammar.style.hidden_cols.contains(c)) {
+        let col = Col(coord.clone(), NonZeroU32::new(col).unwrap());
+        let width = m.col_width(&col);
+        let label = m.get_session().column_label(&col);
+        let label = if m.header_editor.as_ref() == Some(&col) {
+            let rename_col = col.clone();
+            html! {
+                <input class="col-header-input" value=label autofocus=true
+                    onclick=m.link.callback(|e: ClickEvent| { e.stop_propagation(); Action::Noop })
+                    onchange=m.link.callback(move |e: ChangeData| match e {
+                        ChangeData::Value(label) => {
+                            Action::RenameColumnHeader(rename_col.clone(), label)
+                        }
+                        _ => Action::Noop,
+                    })
+                    onkeydown=m.link.callback(|e: KeyDownEvent| {
+                        if e.key() == "Escape" {
+                            Action::EditColumnHeader(None)
+                        } else {
+                            Action::Noop
+                        }
+                    })
+                    onblur=m.link.callback(|_| Action::EditColumnHeader(None))>
+                </input>
+            }
+        } else {
+            html! { <>{ label }</> }
+        };
+        let resize_col = col.clone();
+        let fit_col = col.clone();
+        let edit_col = col.clone();
+        col_headers.add_child(html! {
+            <div class="header col-header" style=format!{"width: {}px;", width}
+                onclick=m.link.callback(move |_| Action::SelectColumn(col.clone()))
+                ondoubleclick=m.link.callback(move |_: DoubleClickEvent| {
+                    Action::EditColumnHeader(Some(edit_col.clone()))
+                })>
+                { label }
+                <div class="col-resize-handle"
+                    onclick=m.link.callback(|e: ClickEvent| { e.stop_propagation(); Action::Noop })
+                    ondoubleclick=m.link.callback(move |e: DoubleClickEvent| {
+                        e.stop_propagation();
+                        Action::AutoFitColumn(fit_col.clone())
+                    })
+                    onmousedown=m.link.callback(move |e: MouseDownEvent| {
+                        e.prevent_default();
+                        e.stop_propagation();
+                        Action::Resize(ResizeMsg::StartCol(resize_col.clone()))
+                    })>
+                </div>
+            </div>
+        });
+    }
+    let mut row_headers = VList::new();
+    for row in (1..=num_rows).filter(|r| !grammar.style.hidden_rows.contains(r)) {
+        let row = Row(coord.clone(), NonZeroU32::new(row).unwrap());
+        let height = m.row_height(&row);
+        let label = row.1.to_string();
+        let resize_row = row.clone();
+        let fit_row = row.clone();
+        row_headers.add_child(html! {
+            <div class="header row-header" style=format!{"height: {}px;", height}
+                onclick=m.link.callback(move |_| Action::SelectRow(row.clone()))>
+                { label }
+                <div class="row-resize-handle"
+                    onclick=m.link.callback(|e: ClickEvent| { e.stop_propagation(); Action::Noop })
+                    ondoubleclick=m.link.callback(move |e: DoubleClickEvent| {
+                        e.stop_propagation();
+                        Action::AutoFitRow(fit_row.clone())
+                    })
+                    onmousedown=m.link.callback(move |e: MouseDownEvent| {
+                        e.prevent_default();
+                        e.stop_propagation();
+                        Action::Resize(ResizeMsg::StartRow(resize_row.clone()))
+                    })>
+                </div>
+            </div>
+        });
+    }
+    html! {
+        <div class="grid-headers">
+            <div class="header-corner"></div>
+            <div class="col-headers">{ col_headers }</div>
+            <div class="row-headers">{ row_headers }</div>
+            { grid }
+        </div>
+    }
+}
+
+// "Go To" box (Ctrl-j) for jumping to a cell by typing its name, like "B3" or "root-A1-B2"
+pub fn view_goto(m: &Model) -> Html {
+    if !m.goto_open {
+        return html! { <></> };
+    }
+    html! {
+        <div class="goto">
+            <input
+                class="goto-input"
+                placeholder="Go to cell (e.g. B3 or root-A1-B2)"
+                ref=m.goto_node_ref.clone()
+                onchange=m.link.callback(|e: ChangeData| {
+                    if let ChangeData::Value(target) = e {
+                        return Action::GoTo(target);
+                    }
+                    Action::Noop
+                })
+                onkeydown=m.link.callback(|e: KeyDownEvent| {
+                    if e.key() == "Escape" {
+                        Action::ShowGoTo(false)
+                    } else {
+                        Action::Noop
+                    }
+                })
+                onblur=m.link.callback(|_| Action::ShowGoTo(false))>
+            </input>
+        </div>
+    }
+}
+
+// list of the keyboard shortcuts (Ctrl-/), which closes when clicked or on Escape
+pub fn view_help_overlay(m: &Model) -> Html {
+    if !m.help_open {
+        return html! { <></> };
+    }
+    let rows = |shortcuts: &[(&str, &str)]| {
+        let mut rows = VList::new();
+        for (keys, description) in shortcuts {
+            rows.add_child(html! {
+                <tr>
+                    <td class="help-keys">{ keys }</td>
+                    <td>{ description }</td>
+                </tr>
+            });
+        }
+        rows
+    };
+    html! {
+        <div class="help-overlay" onclick=m.link.callback(|_| Action::ToggleHelpOverlay)>
+            <div class="help-content">
+                <h3>{ "Keyboard shortcuts" }</h3>
+                <table>{ rows(SHORTCUTS) }</table>
+                <h3>{ "In a cell" }</h3>
+                <table>{ rows(CELL_SHORTCUTS) }</table>
+            </div>
+        </div>
+    }
+}
+
+// offers to restore a session that was auto-saved before the app last closed (or crashed)
+pub fn view_autosave_recovery(m: &Model) -> Html {
+    let title = match &m.recovered_session {
+        Some(session) => session.title.clone(),
+        None => return html! { <></> },
+    };
+    html! {
+        <div class="autosave-recovery">
+            { format! {"An auto-saved copy of \"{}\" was found.", title} }
+            <button onclick=m.link.callback(|_| Action::RestoreAutosave)>{ "Restore" }</button>
+            <button onclick=m.link.callback(|_| Action::DiscardAutosave)>{ "Discard" }</button>
+        </div>
+    }
+}
+
+// while files are being read in, a spinner shows that the sheet is busy and not frozen
+pub fn view_loading_indicator(m: &Model) -> Html {
+    if m.loading_tasks == 0 {
+        return html! { <></> };
+    }
+    html! {
+        <div class="loading-overlay">
+            <div class="spinner"></div>
+            <div class="loading-label">
+                { format!{"Loading {} file{}...", m.loading_tasks, if m.loading_tasks == 1 { "" } else { "s" }} }
+            </div>
+        </div>
+    }
+}
+
+// floating label next to the mouse with the size a cell will have once the resize drag ends
+pub fn view_resize_preview(m: &Model) -> Html {
+    match m.resize_preview {
+        Some((width, height)) => {
+            let (x, y) = m.resize_label_position;
+            html! {
+                <div class="resize-preview" style=format!{"left: {}px; top: {}px;", x + 12.0, y + 12.0}>
+                    { match m.resizing_line {
+                        Some(GridLine::Row(_)) => format!{"{:.0}px tall", height},
+                        Some(GridLine::Col(_)) => format!{"{:.0}px wide", width},
+                        None => format!{"{:.0} × {:.0}", width, height},
+                    } }
+                </div>
+            }
+        }
+        None => html! { <></> },
     }
 }
 
 pub fn view_context_menu(m: &Model) -> Html {
+    let unmerge_cell = m.active_cell.clone();
+    // rows/cols are hidden from the active cell, and "Unhide" brings back the closest hidden one
+    let line_cell = m.active_cell.clone().filter(|c| c.parent().is_some());
+    let (hidden_rows, hidden_cols) = line_cell
+        .as_ref()
+        .and_then(|c| m.get_session().grammars.get(&c.parent().unwrap()))
+        .map_or((vec![], vec![]), |g| {
+            (g.style.hidden_rows.clone(), g.style.hidden_cols.clone())
+        });
+    let hide_row = line_cell.as_ref().map(|c| c.full_row());
+    let hide_col = line_cell.as_ref().map(|c| c.full_col());
+    let unhide_row = line_cell.as_ref().and_then(|c| {
+        nearest_hidden(&hidden_rows, c.row().get())
+            .map(|row| Row(c.parent().unwrap(), NonZeroU32::new(row).unwrap()))
+    });
+    let unhide_col = line_cell.as_ref().and_then(|c| {
+        nearest_hidden(&hidden_cols, c.col().get())
+            .map(|col| Col(c.parent().unwrap(), NonZeroU32::new(col).unwrap()))
+    });
+    let (has_hidden_rows, has_hidden_cols) = (unhide_row.is_some(), unhide_col.is_some());
+    // input cells can be made into lookups, and lookups back into inputs
+    let active_kind = m
+        .active_cell
+        .as_ref()
+        .and_then(|c| m.get_session().grammars.get(c))
+        .map(|g| g.kind.clone());
+    let (is_input, is_lookup, is_number, is_image) = match active_kind {
+        Some(Kind::Input(_)) => (true, false, false, false),
+        Some(Kind::Lookup(_, _)) => (false, true, false, false),
+        Some(Kind::Number(_, _)) => (false, false, true, false),
+        Some(Kind::Image(_)) => (false, false, false, true),
+        _ => (false, false, false, false),
+    };
+    let toggle_lookup_cell = m.active_cell.clone();
+    let toggle_input_cell = m.active_cell.clone();
+    let markdown_cell = m.active_cell.clone();
+    let image_cell = m.active_cell.clone();
+    let lock_cell = m.active_cell.clone();
+    let sparkline_cell = m.active_cell.clone();
+    let has_sparkline = m
+        .active_cell
+        .as_ref()
+        .map_or(false, |c| m.sparklines.contains(c));
+    // the nested grid that the active cell is in, which "Collapse Grid" folds up
+    let collapse_grid = m
+        .active_cell
+        .as_ref()
+        .and_then(|c| c.parent())
+        .filter(|p| *p != coord!("root") && *p != coord!("meta"));
+    let can_collapse = collapse_grid.is_some();
+    // whether the selection has more than one column to merge across, and more than one row to
+    // merge down
+    let merge_lines = m
+        .last_select_cell
+        .as_ref()
+        .and_then(|_| m.selection_bounds())
+        .map(|(_, (top, left), (bottom, right))| (left < right, top < bottom));
+    // sorting only makes sense once more than one row is selected
+    let can_sort = m.last_select_cell.is_some()
+        && m.selection_bounds().map_or(false, |(_, (top, _), (bottom, _))| top < bottom);
+    let unlock_cell = m.active_cell.clone();
+    let is_cell_locked = m
+        .active_cell
+        .as_ref()
+        .and_then(|c| m.get_session().grammars.get(c))
+        .map_or(false, |g| g.style.locked);
+    let has_note = m
+        .active_cell
+        .as_ref()
+        .map_or(false, |c| m.get_session().notes.contains_key(c));
+    let add_note_cell = m.active_cell.clone();
+    let edit_note_cell = m.active_cell.clone();
+    let remove_note_cell = m.active_cell.clone();
+    let wrap_text_cell = m.active_cell.clone();
+    let paste_values_cell = m.active_cell.clone();
+    let paste_formats_cell = m.active_cell.clone();
     let default_options = vec![
         (
             "Insert Row",
@@ -916,6 +2420,18 @@ pub fn view_context_menu(m: &Model) -> Html {
             true,
             1,
         ),
+        (
+            "Duplicate Row",
+            m.link.callback(|_| Action::DuplicateRow),
+            true,
+            1,
+        ),
+        (
+            "Duplicate Col",
+            m.link.callback(|_| Action::DuplicateColumn),
+            true,
+            1,
+        ),
         (
             "Delete Row",
             m.link.callback(|_| Action::DeleteRow),
@@ -928,6 +2444,30 @@ pub fn view_context_menu(m: &Model) -> Html {
             true,
             1,
         ),
+        (
+            "Hide Row",
+            m.link.callback(move |_| hide_row.clone().map_or(Action::Noop, Action::HideRow)),
+            true,
+            1,
+        ),
+        (
+            "Hide Col",
+            m.link.callback(move |_| hide_col.clone().map_or(Action::Noop, Action::HideColumn)),
+            true,
+            1,
+        ),
+        (
+            "Unhide Row",
+            m.link.callback(move |_| unhide_row.clone().map_or(Action::Noop, Action::ShowRow)),
+            false,
+            1,
+        ),
+        (
+            "Unhide Col",
+            m.link.callback(move |_| unhide_col.clone().map_or(Action::Noop, Action::ShowColumn)),
+            false,
+            1,
+        ),
         (
             "----------",
             m.link.callback(|_| Action::HideContextMenu),
@@ -953,9 +2493,167 @@ pub fn view_context_menu(m: &Model) -> Html {
             true,
             0,
         ),
+        ("Copy", m.link.callback(|_| Action::CopySelection), true, 3),
+        ("Paste", m.link.callback(|_| Action::PasteSelection), false, 3),
+        (
+            "Paste Values",
+            m.link.callback(move |_| {
+                paste_values_cell
+                    .clone()
+                    .map_or(Action::Noop, Action::PasteValues)
+            }),
+            false,
+            3,
+        ),
+        (
+            "Paste Formats",
+            m.link.callback(move |_| {
+                paste_formats_cell
+                    .clone()
+                    .map_or(Action::Noop, Action::PasteFormats)
+            }),
+            false,
+            3,
+        ),
         ("Save", m.link.callback(|_| Action::SaveSession()), true, 3),
         ("Reset", m.link.callback(|_| Action::Recreate), true, 3),
         ("Merge", m.link.callback(|_| Action::MergeCells()), false, 3),
+        ("Merge Across", m.link.callback(|_| Action::MergeAcross), false, 3),
+        ("Merge Down", m.link.callback(|_| Action::MergeDown), false, 3),
+        (
+            "Make Lookup",
+            m.link.callback(move |_| {
+                toggle_lookup_cell
+                    .clone()
+                    .map_or(Action::Noop, Action::ToggleLookup)
+            }),
+            false,
+            3,
+        ),
+        (
+            "Make Input",
+            m.link.callback(move |_| {
+                toggle_input_cell
+                    .clone()
+                    .map_or(Action::Noop, Action::ToggleLookup)
+            }),
+            false,
+            3,
+        ),
+        (
+            "Make Markdown",
+            m.link.callback(move |_| {
+                markdown_cell
+                    .clone()
+                    .map_or(Action::Noop, Action::MakeMarkdown)
+            }),
+            false,
+            3,
+        ),
+        (
+            "Remove Image",
+            m.link.callback(move |_| image_cell.clone().map_or(Action::Noop, Action::RemoveImage)),
+            false,
+            3,
+        ),
+        (
+            "Lock Cell",
+            m.link.callback(move |_| lock_cell.clone().map_or(Action::Noop, Action::ToggleLock)),
+            false,
+            3,
+        ),
+        (
+            "Unlock Cell",
+            m.link.callback(move |_| unlock_cell.clone().map_or(Action::Noop, Action::ToggleLock)),
+            false,
+            3,
+        ),
+        (
+            if has_sparkline { "Hide Sparkline" } else { "Show Sparkline" },
+            m.link.callback(move |_| {
+                sparkline_cell
+                    .clone()
+                    .map_or(Action::Noop, Action::ToggleSparkline)
+            }),
+            false,
+            3,
+        ),
+        (
+            "Clear Formatting",
+            m.link.callback(|_| Action::ClearFormatting),
+            false,
+            3,
+        ),
+        (
+            "Wrap Text",
+            m.link.callback(move |_| {
+                wrap_text_cell
+                    .clone()
+                    .map_or(Action::Noop, Action::ToggleWrapText)
+            }),
+            false,
+            3,
+        ),
+        (
+            "Group Into Grid",
+            m.link.callback(|_| Action::GroupSelectionIntoGrid()),
+            false,
+            3,
+        ),
+        (
+            "Sort Ascending",
+            m.link.callback(|_| Action::SortRange { ascending: true }),
+            false,
+            3,
+        ),
+        (
+            "Sort Descending",
+            m.link.callback(|_| Action::SortRange { ascending: false }),
+            false,
+            3,
+        ),
+        (
+            "Collapse Grid",
+            m.link.callback(move |_| {
+                collapse_grid
+                    .clone()
+                    .map_or(Action::Noop, Action::ToggleCollapse)
+            }),
+            false,
+            3,
+        ),
+        (
+            "Add Note",
+            m.link.callback(move |_| add_note_cell.clone().map_or(Action::Noop, Action::EditNote)),
+            false,
+            3,
+        ),
+        (
+            "Edit Note",
+            m.link.callback(move |_| edit_note_cell.clone().map_or(Action::Noop, Action::EditNote)),
+            false,
+            3,
+        ),
+        (
+            "Remove Note",
+            m.link.callback(move |_| {
+                remove_note_cell
+                    .clone()
+                    .map_or(Action::Noop, Action::ClearNote)
+            }),
+            false,
+            3,
+        ),
+        (
+            "Unmerge",
+            m.link.callback(move |_| {
+                unmerge_cell
+                    .clone()
+                    .map_or(Action::Noop, |c| Action::UnmergeCells(c))
+            }),
+            false,
+            3,
+        ),
     ];
     /*option Name and action are what their name means
     option_param represents the default or conditionnal render of an option
@@ -978,6 +2676,42 @@ pub fn view_context_menu(m: &Model) -> Html {
                             should_render = true;
                         }
                     }
+                    "Merge Across" => should_render = merge_lines.map_or(false, |(rows, _)| rows),
+                    "Merge Down" => should_render = merge_lines.map_or(false, |(_, cols)| cols),
+                    "Paste" | "Paste Values" | "Paste Formats" => {
+                        if m.clipboard.is_some() {
+                            should_render = true;
+                        }
+                    }
+                    "Make Lookup" => should_render = is_input,
+                    "Make Input" => should_render = is_lookup,
+                    "Make Markdown" => should_render = is_input,
+                    "Remove Image" => should_render = is_image,
+                    "Lock Cell" => should_render = m.active_cell.is_some() && !is_cell_locked,
+                    "Unlock Cell" => should_render = is_cell_locked,
+                    "Collapse Grid" => should_render = can_collapse,
+                    "Clear Formatting" | "Wrap Text" => should_render = m.active_cell.is_some(),
+                    "Sort Ascending" | "Sort Descending" => should_render = can_sort,
+                    "Group Into Grid" => {
+                        should_render = merge_lines.map_or(false, |(cols, rows)| cols || rows)
+                    }
+                    "Show Sparkline" | "Hide Sparkline" => {
+                        should_render = is_input || is_number || has_sparkline
+                    }
+                    "Add Note" => should_render = m.active_cell.is_some() && !has_note,
+                    "Edit Note" | "Remove Note" => should_render = has_note,
+                    "Unhide Row" => should_render = has_hidden_rows,
+                    "Unhide Col" => should_render = has_hidden_cols,
+                    "Unmerge" => {
+                        if m
+                            .active_cell
+                            .as_ref()
+                            .and_then(|c| m.get_session().grammars.get(c))
+                            .map_or(false, is_merged)
+                        {
+                            should_render = true;
+                        }
+                    }
                     _ => info!("Parameter not managed {:?}", option_name),
                 }
             }
@@ -993,6 +2727,45 @@ pub fn view_context_menu(m: &Model) -> Html {
         v
     };
 
+    // the size of the grid that was right-clicked (or the grid the clicked cell is in), which
+    // can be changed right from the menu
+    let size_grid = m.active_grid();
+    let grid_size_option = match size_grid
+        .clone()
+        .and_then(|c| grid_size(&m.get_session().grammars, &c))
+    {
+        Some((rows, cols)) => {
+            let size_input = |value: u32, is_rows: bool| {
+                let grid = size_grid.clone().unwrap();
+                html! {
+                    <input type="number" min="1" value={value.to_string()}
+                        onclick=m.link.callback(|e: ClickEvent| { e.stop_propagation(); Action::Noop })
+                        onchange=m.link.callback(move |e: ChangeData| {
+                            let size = match e {
+                                ChangeData::Value(v) => v.trim().parse::<u32>().unwrap_or(0),
+                                _ => return Action::Noop,
+                            };
+                            if is_rows {
+                                Action::ResizeGrid(grid.clone(), size, cols)
+                            } else {
+                                Action::ResizeGrid(grid.clone(), rows, size)
+                            }
+                        })>
+                    </input>
+                }
+            };
+            html! {
+                <li class="context-menu-option grid-size">
+                    { "Grid " }
+                    { size_input(rows, true) }
+                    { " × " }
+                    { size_input(cols, false) }
+                </li>
+            }
+        }
+        None => html! { <></> },
+    };
+
     let position_style = if let Some((left, top)) = m.context_menu_position {
         format! {"display: block; top: {}px; left: {}px", top, left}
     } else {
@@ -1005,10 +2778,29 @@ pub fn view_context_menu(m: &Model) -> Html {
             class="context-menu" style=position_style>
             <ul class="context-menu-options">
                 {option_nodes}
+                {grid_size_option}
             </ul>
         </div>
     }
 }
+// util function for determining if the suggestion dropdown of an input cell should be shown.
+// When auto-complete is off, suggestions only show after being explicitly opened (Ctrl-Space).
+fn suggestions_visible(value: &str, is_active: bool, auto_complete: bool, forced: bool) -> bool {
+    if !is_active {
+        return false;
+    }
+    forced || (auto_complete && value != "")
+}
+
+// util function for the index of the suggestion `step` away from `index`, wrapping around at
+// either end of the `len` suggestions (which are numbered from 1)
+fn suggestion_step(index: i32, step: i32, len: usize) -> i32 {
+    if len == 0 {
+        return index;
+    }
+    (index - 1 + step).rem_euclid(len as i32) + 1
+}
+
 // util function for determining if one cell's coordinate is within the range of selected cells.
 fn cell_is_selected(
     coord: &Coordinate,
@@ -1054,14 +2846,46 @@ fn cell_is_selected(
     }
 }
 
-fn random_color() -> String {
-    js! (
-        var col = "";
-        for (var i=0; i<6; i++) {
-            col += (Math.random()*16|0).toString(16);
-        }
-        return "#"+col;
-    )
-    .try_into()
-    .unwrap()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggestions_visible() {
+        // auto-complete on: typing opens suggestions
+        assert!(suggestions_visible("js", true, true, false));
+        assert!(!suggestions_visible("", true, true, false));
+        // auto-complete off: typing doesn't open suggestions...
+        assert!(!suggestions_visible("js", true, false, false));
+        // ... but the explicit trigger does
+        assert!(suggestions_visible("js", true, false, true));
+        assert!(suggestions_visible("", true, false, true));
+        // inactive cells never show suggestions
+        assert!(!suggestions_visible("js", false, true, true));
+    }
+
+    #[test]
+    fn test_collapsed_grid_summary() {
+        let sub_coords = vec![
+            non_zero_u32_tuple((1, 1)),
+            non_zero_u32_tuple((1, 2)),
+            non_zero_u32_tuple((2, 1)),
+            non_zero_u32_tuple((2, 2)),
+            non_zero_u32_tuple((3, 1)),
+            non_zero_u32_tuple((3, 2)),
+        ];
+        assert_eq!(collapsed_grid_summary(&sub_coords), "▸ 3×2 grid");
+        assert_eq!(collapsed_grid_summary(&[]), "▸ 0×0 grid");
+    }
+
+    #[test]
+    fn test_suggestion_step() {
+        assert_eq!(suggestion_step(1, 1, 3), 2);
+        assert_eq!(suggestion_step(2, -1, 3), 1);
+        // wraps around at both ends
+        assert_eq!(suggestion_step(3, 1, 3), 1);
+        assert_eq!(suggestion_step(1, -1, 3), 3);
+        assert_eq!(suggestion_step(1, 1, 1), 1);
+        assert_eq!(suggestion_step(1, 1, 0), 1);
+    }
 }